@@ -0,0 +1,269 @@
+//! Authentication, and the `/auth` routes for logging in and out.
+
+use axum::{
+  async_trait,
+  extract::{FromRef, FromRequestParts, State},
+  http::{request::Parts, HeaderMap},
+  Json,
+};
+use prime_domain::{
+  models, LoginError, PrimeDomainService, Principal, TokenVerifyError,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+
+/// An extractor that requires valid credentials in an `Authorization: Bearer
+/// <id>:<secret>` header, belonging to either a token or a session.
+pub struct Authenticated(pub Principal);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Authenticated
+where
+  S: Send + Sync,
+  AppState: FromRef<S>,
+{
+  type Rejection = mollusk::ExternalApiError;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &S,
+  ) -> Result<Self, Self::Rejection> {
+    let app_state = AppState::from_ref(state);
+
+    let (id, secret) =
+      bearer_pair(&parts.headers).ok_or(mollusk::MissingTokenError)?;
+    let credentials = parse_credentials(id, secret).ok_or_else(|| {
+      mollusk::MalformedTokenSecretError {
+        token: id.to_string(),
+      }
+    })?;
+
+    let principal = app_state
+      .prime_domain_service
+      .verify_credentials(credentials)
+      .await
+      .map_err(|e| match e {
+        // don't tell the client which half was wrong
+        TokenVerifyError::IdNotFound | TokenVerifyError::SecretMismatch => {
+          mollusk::ExternalApiError::from(mollusk::NonExistentTokenError {
+            token: id.to_string(),
+          })
+        }
+        TokenVerifyError::Expired => mollusk::ExpiredTokenError {
+          token: id.to_string(),
+        }
+        .into(),
+        TokenVerifyError::Revoked => mollusk::RevokedTokenError {
+          token: id.to_string(),
+        }
+        .into(),
+        TokenVerifyError::FetchError(e) => {
+          mollusk::InternalError(format!("{e:?}")).into()
+        }
+      })?;
+
+    Ok(Self(principal))
+  }
+}
+
+/// An extractor that requires a session from logging in, rather than a
+/// token.
+pub struct AuthenticatedSession {
+  /// The verified session.
+  pub session: models::Session,
+  /// The user the session belongs to.
+  pub user:    models::User,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthenticatedSession
+where
+  S: Send + Sync,
+  AppState: FromRef<S>,
+{
+  type Rejection = mollusk::ExternalApiError;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &S,
+  ) -> Result<Self, Self::Rejection> {
+    match Authenticated::from_request_parts(parts, state).await?.0 {
+      Principal::Session { session, user } => Ok(Self { session, user }),
+      Principal::Token(token) => Err(
+        mollusk::NotASessionError {
+          token: token.id.to_string(),
+        }
+        .into(),
+      ),
+    }
+  }
+}
+
+/// Splits an `Authorization: Bearer <id>:<secret>` header into its ID and
+/// secret.
+fn bearer_pair(headers: &HeaderMap) -> Option<(&str, &str)> {
+  headers
+    .get("authorization")
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.strip_prefix("Bearer "))
+    .and_then(|pair| pair.trim().split_once(':'))
+}
+
+/// Parses an ID and secret into credentials.
+///
+/// A secret that isn't already a valid slug is rejected rather than rewritten,
+/// so that it can't be mistaken for the secret it would slugify to.
+fn parse_credentials(id: &str, secret: &str) -> Option<models::Credentials> {
+  Some(models::Credentials {
+    id:     models::Ulid::from_string(id).ok()?,
+    secret: models::TokenSecret::new(models::StrictSlug::try_exact(
+      secret.to_string(),
+    )?),
+  })
+}
+
+/// Reads credentials from an `Authorization: Bearer <id>:<secret>` header,
+/// leaving verification to the task.
+pub fn credentials_from_headers(
+  headers: &HeaderMap,
+) -> Option<models::Credentials> {
+  let (id, secret) = bearer_pair(headers)?;
+  parse_credentials(id, secret)
+}
+
+/// Reads credentials from an ID and secret passed along by the fetcher,
+/// leaving verification to the task.
+pub fn credentials_from_pair(
+  id: Option<String>,
+  secret: Option<String>,
+) -> Option<models::Credentials> {
+  parse_credentials(&id?, &secret?)
+}
+
+/// The body of a login request.
+#[derive(Deserialize)]
+pub struct LoginRequest {
+  /// The username to log in as.
+  pub username: String,
+  /// The user's password.
+  pub password: String,
+}
+
+/// A session started by logging in.
+#[derive(Serialize)]
+pub struct LoginResponse {
+  /// The session's ID.
+  pub session_id:     models::SessionRecordId,
+  /// The session's secret. It's only ever returned here.
+  pub session_secret: models::TokenSecret,
+  /// When the session stops being valid.
+  pub expires_at:     chrono::DateTime<chrono::Utc>,
+}
+
+/// Logs a user in with their username and password, starting a session.
+///
+/// The session is used like a token, as `Authorization: Bearer <id>:<secret>`.
+#[tracing::instrument(skip(app_state, request), fields(username = %request.username))]
+pub async fn login(
+  State(app_state): State<AppState>,
+  Json(request): Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, mollusk::ExternalApiError> {
+  // a password that isn't valid can't match any user's
+  let password = models::Password::try_new(request.password)
+    .map_err(|_| mollusk::InvalidCredentialsError)?;
+  let username =
+    models::EntityName::new(models::StrictSlug::new(request.username));
+
+  let (session, secret) = app_state
+    .prime_domain_service
+    .login(username, password)
+    .await
+    .map_err(|e| match e {
+      LoginError::InvalidCredentials => {
+        mollusk::ExternalApiError::from(mollusk::InvalidCredentialsError)
+      }
+      e => mollusk::InternalError(format!("{e:?}")).into(),
+    })?;
+
+  Ok(Json(LoginResponse {
+    session_id:     session.id,
+    session_secret: secret,
+    expires_at:     session.expires_at,
+  }))
+}
+
+/// Logs out of the session used to make the request.
+#[tracing::instrument(skip(app_state, session), fields(session = %session.id))]
+pub async fn logout(
+  State(app_state): State<AppState>,
+  AuthenticatedSession { session, .. }: AuthenticatedSession,
+) -> Result<(), mollusk::ExternalApiError> {
+  app_state
+    .prime_domain_service
+    .end_session(session)
+    .await
+    .map_err(|e| mollusk::InternalError(format!("{e:?}")))?;
+
+  Ok(())
+}
+
+/// How a request was authenticated.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+  /// With an API token.
+  Token,
+  /// With a session from logging in.
+  Session,
+}
+
+/// The user behind a request.
+#[derive(Serialize)]
+pub struct WhoAmIResponse {
+  /// The user's ID.
+  pub user_id:  models::UserRecordId,
+  /// The user's username.
+  pub username: models::EntityName,
+  /// The user's name.
+  pub name:     models::HumanName,
+  /// The user's org.
+  pub org:      models::OrgRecordId,
+  /// How the request was authenticated.
+  pub method:   AuthMethod,
+}
+
+/// Returns the user behind the request's token or session.
+///
+/// For a token, that's the user who owns it.
+#[tracing::instrument(skip(app_state, principal), fields(principal = %principal.id()))]
+pub async fn whoami(
+  State(app_state): State<AppState>,
+  Authenticated(principal): Authenticated,
+) -> Result<Json<WhoAmIResponse>, mollusk::ExternalApiError> {
+  let (user, method) = match principal {
+    Principal::Token(token) => {
+      let user = app_state
+        .prime_domain_service
+        .fetch_user_by_id(token.owner)
+        .await
+        .map_err(|e| mollusk::InternalError(format!("{e:?}")))?
+        .ok_or_else(|| {
+          mollusk::InternalError(format!(
+            "token {} belongs to non-existent user {}",
+            token.id, token.owner
+          ))
+        })?;
+      (user, AuthMethod::Token)
+    }
+    Principal::Session { user, .. } => (user, AuthMethod::Session),
+  };
+
+  Ok(Json(WhoAmIResponse {
+    user_id: user.id,
+    username: user.username,
+    name: user.name,
+    org: user.org,
+    method,
+  }))
+}
@@ -0,0 +1,77 @@
+//! Authorization of writes, for the routes that upload to a cache.
+
+use axum::{
+  async_trait,
+  extract::{FromRef, FromRequestParts, RawPathParams},
+  http::request::Parts,
+};
+use prime_domain::{models, PrimeDomainService, Principal};
+
+use crate::{auth::Authenticated, AppState};
+
+/// An extractor that requires an `Authorization: Bearer <id>:<secret>` token
+/// or session with write access to the cache named by the route's `:name`
+/// parameter.
+pub struct CacheWriteToken {
+  /// The cache being written to.
+  pub cache:     models::Cache,
+  /// The verified token or session.
+  pub principal: Principal,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CacheWriteToken
+where
+  S: Send + Sync,
+  AppState: FromRef<S>,
+{
+  type Rejection = mollusk::ExternalApiError;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &S,
+  ) -> Result<Self, Self::Rejection> {
+    let app_state = AppState::from_ref(state);
+    let prime_domain_service = app_state.prime_domain_service;
+
+    let params = RawPathParams::from_request_parts(parts, state)
+      .await
+      .map_err(|e| mollusk::InternalError(e.to_string()))?;
+    let cache_name = params
+      .iter()
+      .find_map(|(key, value)| (key == "name").then(|| value.to_string()))
+      .ok_or_else(|| {
+        mollusk::InternalError("route has no `:name` parameter".to_string())
+      })?;
+
+    let Authenticated(principal) =
+      Authenticated::from_request_parts(parts, state).await?;
+
+    // a name that isn't already a valid slug can't be any cache's
+    let Some(cache_slug) = models::StrictSlug::try_exact(cache_name.clone())
+    else {
+      return Err(mollusk::NonExistentCacheError(cache_name).into());
+    };
+    let cache = prime_domain_service
+      .find_cache_by_name(cache_slug)
+      .await
+      .map_err(|e| mollusk::InternalError(format!("{e:?}")))?
+      .ok_or(mollusk::NonExistentCacheError(cache_name.clone()))?;
+
+    let permission = models::CachePermissionType::Write;
+    let required = models::PermissionSet::from_iter(vec![
+      models::Permission::CachePermission {
+        cache_id:   cache.id,
+        permission: permission.clone(),
+      },
+    ]);
+    if !principal.authorized(&required) {
+      Err(mollusk::UnauthorizedCacheAccessError {
+        cache_name,
+        permission,
+      })?;
+    }
+
+    Ok(Self { cache, principal })
+  }
+}
@@ -0,0 +1,204 @@
+//! The `/caches` routes, for users to manage the caches in their org.
+
+use axum::{
+  extract::{Path, State},
+  Json,
+};
+use prime_domain::{
+  models, CacheStoreError, CreateCacheError, DeleteCacheError,
+  PrimeDomainService, UpdateCacheError,
+};
+use serde::Deserialize;
+
+use crate::{auth::AuthenticatedSession, AppState};
+
+/// The body of a cache create request.
+#[derive(Deserialize)]
+pub struct CreateCacheRequest {
+  /// The cache's name.
+  pub name:       String,
+  /// The cache's visibility.
+  pub visibility: models::Visibility,
+  /// The cache's backing store.
+  pub store:      models::StoreRecordId,
+}
+
+/// The body of a cache update request. Fields left out are kept as they are.
+#[derive(Deserialize)]
+pub struct UpdateCacheRequest {
+  /// The cache's new name.
+  #[serde(default)]
+  pub name:       Option<String>,
+  /// The cache's new visibility.
+  #[serde(default)]
+  pub visibility: Option<models::Visibility>,
+  /// The cache's new backing store.
+  #[serde(default)]
+  pub store:      Option<models::StoreRecordId>,
+}
+
+/// Parses a cache name, rejecting it rather than rewriting it into a slug.
+fn parse_cache_name(
+  name: String,
+) -> Result<models::EntityName, mollusk::InvalidCacheNameError> {
+  models::StrictSlug::try_exact(name.clone())
+    .map(models::EntityName::new)
+    .ok_or(mollusk::InvalidCacheNameError(name))
+}
+
+fn store_error(e: CacheStoreError) -> mollusk::ExternalApiError {
+  match e {
+    CacheStoreError::NotFound(id) => {
+      mollusk::NonExistentStoreError(id.to_string()).into()
+    }
+    CacheStoreError::ForeignOrg(id) => {
+      mollusk::ForeignStoreError(id.to_string()).into()
+    }
+    e => mollusk::InternalError(format!("{e:?}")).into(),
+  }
+}
+
+/// Finds a cache by name, if it's in the user's org.
+async fn find_org_cache(
+  app_state: &AppState,
+  user: &models::User,
+  name: String,
+) -> Result<models::Cache, mollusk::ExternalApiError> {
+  // names that aren't slugs can't belong to any cache, and caches in other
+  // orgs are reported as missing, so their names don't leak
+  let Some(slug) = models::StrictSlug::try_exact(name.clone()) else {
+    return Err(mollusk::NonExistentCacheError(name).into());
+  };
+  Ok(
+    app_state
+      .prime_domain_service
+      .find_cache_by_name(slug)
+      .await
+      .map_err(|e| mollusk::InternalError(format!("{e:?}")))?
+      .filter(|c| c.org == user.org)
+      .ok_or(mollusk::NonExistentCacheError(name))?,
+  )
+}
+
+/// Lists the caches in the session's user's org.
+#[tracing::instrument(skip(app_state, user), fields(user = %user.id))]
+pub async fn list_caches(
+  State(app_state): State<AppState>,
+  AuthenticatedSession { user, .. }: AuthenticatedSession,
+) -> Result<Json<Vec<models::Cache>>, mollusk::ExternalApiError> {
+  let caches = app_state
+    .prime_domain_service
+    .enumerate_caches_by_org(user.org)
+    .await
+    .map_err(|e| mollusk::InternalError(format!("{e:?}")))?;
+
+  Ok(Json(caches))
+}
+
+/// Creates a cache in the session's user's org, with its own signing key, and
+/// grants them read and write access to it.
+#[tracing::instrument(skip(app_state, user, request), fields(user = %user.id, name = %request.name))]
+pub async fn create_cache(
+  State(app_state): State<AppState>,
+  AuthenticatedSession { user, .. }: AuthenticatedSession,
+  Json(request): Json<CreateCacheRequest>,
+) -> Result<Json<models::Cache>, mollusk::ExternalApiError> {
+  let name = parse_cache_name(request.name)?;
+
+  let cache = app_state
+    .prime_domain_service
+    .create_cache(&user, name, request.visibility, request.store)
+    .await
+    .map_err(|e| match e {
+      CreateCacheError::NameTaken(name) => mollusk::ExternalApiError::from(
+        mollusk::DuplicateCacheNameError(name.to_string()),
+      ),
+      CreateCacheError::StoreError(e) => store_error(e),
+      e => mollusk::InternalError(format!("{e:?}")).into(),
+    })?;
+
+  Ok(Json(cache))
+}
+
+/// Shows a cache in the session's user's org.
+#[tracing::instrument(skip(app_state, user), fields(user = %user.id))]
+pub async fn show_cache(
+  State(app_state): State<AppState>,
+  Path(name): Path<String>,
+  AuthenticatedSession { user, .. }: AuthenticatedSession,
+) -> Result<Json<models::Cache>, mollusk::ExternalApiError> {
+  Ok(Json(find_org_cache(&app_state, &user, name).await?))
+}
+
+/// Changes a cache's name, visibility or backing store.
+#[tracing::instrument(skip(app_state, user, request), fields(user = %user.id))]
+pub async fn update_cache(
+  State(app_state): State<AppState>,
+  Path(name): Path<String>,
+  AuthenticatedSession { user, .. }: AuthenticatedSession,
+  Json(request): Json<UpdateCacheRequest>,
+) -> Result<Json<models::Cache>, mollusk::ExternalApiError> {
+  let cache = find_org_cache(&app_state, &user, name.clone()).await?;
+  let update = models::CacheUpdateRequest {
+    name:       request.name.map(parse_cache_name).transpose()?,
+    visibility: request.visibility,
+    store:      request.store,
+  };
+
+  let cache = app_state
+    .prime_domain_service
+    .update_cache(&user, cache.id, update)
+    .await
+    .map_err(|e| match e {
+      UpdateCacheError::NotFound(_) => {
+        mollusk::ExternalApiError::from(mollusk::NonExistentCacheError(name))
+      }
+      UpdateCacheError::Unauthorized(_) => {
+        mollusk::UnauthorizedCacheAccessError {
+          cache_name: name,
+          permission: models::CachePermissionType::Write,
+        }
+        .into()
+      }
+      UpdateCacheError::NotEmpty(_) => mollusk::CacheNotEmptyError(name).into(),
+      UpdateCacheError::NameTaken(name) => {
+        mollusk::DuplicateCacheNameError(name.to_string()).into()
+      }
+      UpdateCacheError::StoreError(e) => store_error(e),
+      e => mollusk::InternalError(format!("{e:?}")).into(),
+    })?;
+
+  Ok(Json(cache))
+}
+
+/// Deletes an empty cache, along with its signing key, realisations and debug
+/// info.
+#[tracing::instrument(skip(app_state, user), fields(user = %user.id))]
+pub async fn delete_cache(
+  State(app_state): State<AppState>,
+  Path(name): Path<String>,
+  AuthenticatedSession { user, .. }: AuthenticatedSession,
+) -> Result<Json<models::Cache>, mollusk::ExternalApiError> {
+  let cache = find_org_cache(&app_state, &user, name.clone()).await?;
+
+  let cache = app_state
+    .prime_domain_service
+    .delete_cache(&user, cache.id)
+    .await
+    .map_err(|e| match e {
+      DeleteCacheError::NotFound(_) => {
+        mollusk::ExternalApiError::from(mollusk::NonExistentCacheError(name))
+      }
+      DeleteCacheError::Unauthorized(_) => {
+        mollusk::UnauthorizedCacheAccessError {
+          cache_name: name,
+          permission: models::CachePermissionType::Write,
+        }
+        .into()
+      }
+      DeleteCacheError::NotEmpty(_) => mollusk::CacheNotEmptyError(name).into(),
+      e => mollusk::InternalError(format!("{e:?}")).into(),
+    })?;
+
+  Ok(Json(cache))
+}
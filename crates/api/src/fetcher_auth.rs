@@ -0,0 +1,68 @@
+//! Authentication of the fetcher, for the routes that only it may call.
+
+use std::sync::Arc;
+
+use axum::{
+  async_trait,
+  extract::{FromRef, FromRequestParts},
+  http::request::Parts,
+};
+use subtle::ConstantTimeEq;
+
+use crate::AppState;
+
+/// The secret shared between the API and the fetcher, from `FETCHER_SECRET`.
+#[derive(Clone)]
+pub struct FetcherSecret(Arc<str>);
+
+impl FetcherSecret {
+  /// Reads the secret from `FETCHER_SECRET`.
+  pub fn new_from_env() -> miette::Result<Self> {
+    std::env::var("FETCHER_SECRET")
+      .ok()
+      .filter(|s| !s.is_empty())
+      .map(|s| Self(s.into()))
+      .ok_or_else(|| {
+        miette::miette!("missing environment variable: `FETCHER_SECRET`")
+      })
+  }
+
+  /// Checks a secret against this one, in constant time.
+  fn verify(&self, secret: &str) -> bool {
+    self.0.as_bytes().ct_eq(secret.as_bytes()).into()
+  }
+}
+
+/// An extractor that requires the fetcher's secret in the
+/// [`FETCHER_SECRET_HEADER`](mollusk::FETCHER_SECRET_HEADER) header.
+///
+/// The headers are checked before the body is read, so nobody else can stream
+/// data into the routes that use this.
+pub struct FromFetcher;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for FromFetcher
+where
+  S: Send + Sync,
+  AppState: FromRef<S>,
+{
+  type Rejection = mollusk::ExternalApiError;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &S,
+  ) -> Result<Self, Self::Rejection> {
+    let app_state = AppState::from_ref(state);
+
+    let secret = parts
+      .headers
+      .get(mollusk::FETCHER_SECRET_HEADER)
+      .and_then(|value| value.to_str().ok())
+      .ok_or(mollusk::FetcherOnlyError)?;
+    if !app_state.fetcher_secret.verify(secret) {
+      Err(mollusk::FetcherOnlyError)?;
+    }
+
+    Ok(Self)
+  }
+}
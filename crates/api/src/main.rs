@@ -13,20 +13,28 @@
 //! See `api --help` for more information and other options.
 //!
 //! # Environment Variables
-//! It has no extra required environment variables, outside of those required by
-//! its services. If you're missing one, it will tell you. Your exact service
+//! It requires `FETCHER_SECRET`, which the fetcher also has to be given, to
+//! authenticate the routes that only the fetcher may call. Otherwise, it only
+//! needs those required by its services. If you're missing one, it will tell
+//! you. Your exact service
 //! configuration depends on a number of other crates, in addition to which
 //! things you're mocking.
 
+mod auth;
+mod cache_write_token;
+mod caches;
 mod cmd;
+mod fetcher_auth;
 mod temp_storage_payload;
+mod tokens;
 
 use std::{sync::Arc, time::Duration};
 
 use axum::{
   extract::{FromRef, Path, State},
+  http::HeaderMap,
   response::IntoResponse,
-  routing::{get, post},
+  routing::{get, post, put},
   Json, Router,
 };
 use clap::Parser;
@@ -44,7 +52,13 @@ use prime_domain::{
 use tasks::Task;
 use tracing_subscriber::prelude::*;
 
-use self::{cmd::RuntimeConfig, temp_storage_payload::TempStoragePayload};
+use self::{
+  auth::{credentials_from_headers, credentials_from_pair},
+  cache_write_token::CacheWriteToken,
+  cmd::RuntimeConfig,
+  fetcher_auth::{FetcherSecret, FromFetcher},
+  temp_storage_payload::TempStoragePayload,
+};
 
 #[tracing::instrument(skip(app_state))]
 async fn prepare_fetch_payload(
@@ -56,14 +70,15 @@ async fn prepare_fetch_payload(
     Option<String>,
   )>,
 ) -> Result<Json<models::StorageCredentials>, mollusk::InternalApiError> {
+  let path = models::StorePath::try_new(path.clone()).map_err(|_| {
+    mollusk::PrepareFetchPayloadError::from(mollusk::InvalidPathError { path })
+  })?;
+
   Ok(
     tasks::PrepareFetchPayloadTask {
-      cache_name:   models::StrictSlug::new(cache_name),
-      token_id:     token_id
-        .and_then(|s| models::TokenRecordId::try_from(s).ok()),
-      token_secret: token_secret
-        .map(|s| models::TokenSecret::new(models::StrictSlug::new(s))),
-      path:         models::LaxSlug::new(path),
+      cache_name: models::StrictSlug::new(cache_name),
+      credentials: credentials_from_pair(token_id, token_secret),
+      path,
     }
     .run(app_state.prime_domain_service.clone())
     .await
@@ -71,34 +86,214 @@ async fn prepare_fetch_payload(
   )
 }
 
-#[tracing::instrument(skip(app_state, payload))]
+#[tracing::instrument(skip(app_state))]
+async fn prepare_binary_cache_payload(
+  State(app_state): State<AppState>,
+  Json((cache_name, file, token_id, token_secret)): Json<(
+    String,
+    mollusk::BinaryCacheFile,
+    Option<String>,
+    Option<String>,
+  )>,
+) -> Result<Json<mollusk::BinaryCachePayload>, mollusk::InternalApiError> {
+  Ok(
+    tasks::PrepareBinaryCachePayloadTask {
+      cache_name: models::StrictSlug::new(cache_name),
+      credentials: credentials_from_pair(token_id, token_secret),
+      file,
+    }
+    .run(app_state.prime_domain_service.clone())
+    .await
+    .map(Json)?,
+  )
+}
+
+/// Uploads a NAR for a store path, with no narinfo.
+///
+/// Requires a token or session with write access to the cache.
+#[tracing::instrument(skip(app_state, cache, principal, payload), fields(principal = %principal.id()))]
 async fn naive_upload(
   State(app_state): State<AppState>,
-  Path((cache_name, original_path)): Path<(String, String)>,
+  Path((_, original_path)): Path<(String, String)>,
+  CacheWriteToken { cache, principal }: CacheWriteToken,
   payload: TempStoragePayload,
 ) -> Result<(), mollusk::ExternalApiError> {
-  let path = models::LaxSlug::new(original_path.clone());
-  if path.to_string() != original_path {
-    return Err(
+  let path =
+    models::StorePath::try_new(original_path.clone()).map_err(|_| {
       mollusk::InvalidPathError {
         path: original_path,
       }
-      .into(),
-    );
-  }
+    })?;
 
-  let payload_path = payload.upload().await.unwrap();
+  let temp_storage_path = payload
+    .upload()
+    .await
+    .map_err(|e| mollusk::InternalError(e.to_string()))?;
   tasks::NaiveUploadTask {
-    cache_name: models::StrictSlug::new(cache_name),
+    cache_id: cache.id,
     path,
-    temp_storage_path: payload_path,
+    temp_storage_path,
+  }
+  .run(app_state.prime_domain_service.clone())
+  .await?;
+  Ok(())
+}
+
+/// The maximum size of an uploaded `.narinfo` file.
+const MAX_NARINFO_SIZE: usize = 1024 * 1024;
+/// The maximum size of an uploaded `.doi` realisation.
+const MAX_REALISATION_SIZE: usize = 1024 * 1024;
+/// The maximum number of paths in a missing paths query.
+const MAX_MISSING_PATHS: usize = 1000;
+
+/// Accepts uploads from Nix's HTTP binary cache store, i.e. `nix copy --to
+/// http://...`.
+///
+/// Nix uploads the NAR first, which is kept in temp storage, and then its
+/// narinfo, which turns it into an entry. Realisations of content-addressed
+/// derivations are uploaded as `.doi` files.
+#[tracing::instrument(skip(app_state, headers, payload))]
+async fn binary_cache_upload(
+  State(app_state): State<AppState>,
+  Path((cache_name, path)): Path<(String, String)>,
+  headers: HeaderMap,
+  payload: TempStoragePayload,
+) -> Result<(), mollusk::ExternalApiError> {
+  let file = mollusk::BinaryCacheUpload::from_request_path(&path).ok_or(
+    mollusk::BinaryCacheUploadError::from(mollusk::InvalidPathError { path }),
+  )?;
+  let cache_name = models::StrictSlug::new(cache_name);
+  let credentials = credentials_from_headers(&headers);
+
+  match file {
+    mollusk::BinaryCacheUpload::Nar(file) => {
+      let temp_storage_path = tasks::PrepareNarUploadTask {
+        cache_name,
+        credentials,
+        file,
+      }
+      .run(app_state.prime_domain_service.clone())
+      .await?;
+      payload
+        .upload_to(temp_storage_path)
+        .await
+        .map_err(|e| mollusk::InternalError(e.to_string()))?;
+    }
+    mollusk::BinaryCacheUpload::NarInfo(hash) => {
+      let narinfo = payload.into_text(MAX_NARINFO_SIZE).await.map_err(|e| {
+        mollusk::MalformedNarInfoError {
+          reason: e.to_string(),
+        }
+      })?;
+      tasks::UploadNarInfoTask {
+        cache_name,
+        credentials,
+        hash,
+        narinfo,
+      }
+      .run(app_state.prime_domain_service.clone())
+      .await?;
+    }
+    mollusk::BinaryCacheUpload::Realisation(id) => {
+      let realisation =
+        payload.into_text(MAX_REALISATION_SIZE).await.map_err(|e| {
+          mollusk::MalformedRealisationError {
+            reason: e.to_string(),
+          }
+        })?;
+      tasks::UploadRealisationTask {
+        cache_name,
+        credentials,
+        id,
+        realisation,
+      }
+      .run(app_state.prime_domain_service.clone())
+      .await?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Stores a NAR that the fetcher pulled through from a proxy cache's upstream.
+///
+/// The body is the uncompressed NAR, and its metadata is taken from the
+/// upstream's narinfo. Only the fetcher may call this.
+#[tracing::instrument(skip(app_state, headers, payload))]
+async fn proxy_store(
+  State(app_state): State<AppState>,
+  Path((cache_name, hash)): Path<(String, String)>,
+  _: FromFetcher,
+  headers: HeaderMap,
+  payload: TempStoragePayload,
+) -> Result<(), mollusk::ExternalApiError> {
+  if !models::validate_store_path_hash(&hash) {
+    Err(mollusk::InvalidStorePathHashError { hash: hash.clone() })?;
+  }
+  let credentials = credentials_from_headers(&headers);
+
+  let temp_storage_path = payload
+    .upload()
+    .await
+    .map_err(|e| mollusk::InternalError(e.to_string()))?;
+  tasks::StoreProxiedNarTask {
+    cache_name: models::StrictSlug::new(cache_name),
+    credentials,
+    hash: models::LaxSlug::new(hash),
+    temp_storage_path,
   }
   .run(app_state.prime_domain_service.clone())
-  .await
-  .unwrap();
+  .await?;
+
   Ok(())
 }
 
+/// Returns which of the given store paths are missing from a cache.
+///
+/// Takes a JSON array of store paths in `<hash>-<name>` form, and returns the
+/// ones that aren't in the cache, in the same order. Those that the cache's
+/// upstream caches have are returned separately, as `available_upstream`.
+/// Clients use this to skip paths that are already there before pushing a
+/// closure. Queries are capped at [`MAX_MISSING_PATHS`] paths, so bigger
+/// closures have to be split up.
+#[tracing::instrument(skip(app_state, headers, paths))]
+async fn missing_paths(
+  State(app_state): State<AppState>,
+  Path(cache_name): Path<String>,
+  headers: HeaderMap,
+  Json(paths): Json<Vec<String>>,
+) -> Result<Json<tasks::MissingPaths>, mollusk::ExternalApiError> {
+  if paths.len() > MAX_MISSING_PATHS {
+    Err(mollusk::TooManyPathsError {
+      count: paths.len(),
+      max:   MAX_MISSING_PATHS,
+    })?;
+  }
+
+  let paths = paths
+    .into_iter()
+    .map(|path| {
+      models::StorePath::try_new(path.clone()).map_err(|_| {
+        mollusk::PrepareFetchPayloadError::from(mollusk::InvalidPathError {
+          path,
+        })
+      })
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+  let credentials = credentials_from_headers(&headers);
+
+  Ok(
+    tasks::QueryMissingPathsTask {
+      cache_name: models::StrictSlug::new(cache_name),
+      credentials,
+      paths,
+    }
+    .run(app_state.prime_domain_service.clone())
+    .await
+    .map(Json)?,
+  )
+}
+
 async fn dummy_root_handler() -> impl IntoResponse {
   "You've reached the root endpoint of the Rambit API binary.\nYou probably \
    meant to go somewhere else."
@@ -119,6 +314,7 @@ async fn health_handler(
 #[derive(Clone, FromRef)]
 struct AppState {
   prime_domain_service: DynPrimeDomainService,
+  fetcher_secret:       FetcherSecret,
 }
 
 impl AppState {
@@ -133,12 +329,28 @@ impl AppState {
     );
     let cache_repo =
       prime_domain::repos::CacheRepositoryCanonical::new(kv_db_adapter.clone());
+    let signing_key_repo =
+      prime_domain::repos::SigningKeyRepositoryCanonical::new(
+        kv_db_adapter.clone(),
+      );
     let store_repo =
       prime_domain::repos::StoreRepositoryCanonical::new(kv_db_adapter.clone());
     let token_repo =
       prime_domain::repos::TokenRepositoryCanonical::new(kv_db_adapter.clone());
+    let session_repo = prime_domain::repos::SessionRepositoryCanonical::new(
+      kv_db_adapter.clone(),
+    );
+    let user_repo =
+      prime_domain::repos::UserRepositoryCanonical::new(kv_db_adapter.clone());
+    let debuginfo_repo = prime_domain::repos::DebugInfoRepositoryCanonical::new(
+      kv_db_adapter.clone(),
+    );
     let entry_repo =
       prime_domain::repos::EntryRepositoryCanonical::new(kv_db_adapter.clone());
+    let realisation_repo =
+      prime_domain::repos::RealisationRepositoryCanonical::new(
+        kv_db_adapter.clone(),
+      );
     let temp_storage_repo: Box<dyn TempStorageRepository> = if config
       .mock_temp_storage
     {
@@ -156,18 +368,49 @@ impl AppState {
     };
     let user_storage_repo =
       prime_domain::repos::UserStorageRepositoryCanonical::new();
+    let token_hasher = prime_domain::TokenSecretHasher::new_from_env()?;
+    let fetcher_secret = FetcherSecret::new_from_env()?;
 
     let prime_domain_service = prime_domain::PrimeDomainServiceCanonical::new(
       cache_repo,
+      debuginfo_repo,
       entry_repo,
+      realisation_repo,
+      session_repo,
+      signing_key_repo,
       store_repo,
       token_repo,
       temp_storage_repo,
+      user_repo,
       user_storage_repo,
+      token_hasher,
     );
+    let prime_domain_service: DynPrimeDomainService =
+      Arc::new(Box::new(prime_domain_service));
+
+    // plaintext token secrets and legacy entries are migrated in the
+    // background, so that startup doesn't wait on the database
+    tokio::spawn({
+      let prime_domain_service = prime_domain_service.clone();
+      async move {
+        match prime_domain_service.migrate_legacy_token_secrets().await {
+          Ok(0) => {}
+          Ok(count) => tracing::info!("hashed {count} legacy token secrets"),
+          Err(e) => {
+            tracing::warn!("failed to hash legacy token secrets: {e:?}");
+          }
+        }
+        match prime_domain_service.migrate_legacy_entries().await {
+          Ok(0) => {}
+          Ok(count) => tracing::info!("migrated {count} legacy entries"),
+          Err(e) => tracing::warn!("failed to migrate legacy entries: {e:?}"),
+        }
+      }
+    });
 
     Ok(AppState {
-      prime_domain_service: Arc::new(Box::new(prime_domain_service)),
+      prime_domain_service,
+      fetcher_secret,
     })
   }
 }
@@ -249,7 +492,29 @@ async fn main() -> Result<()> {
   let app = Router::new()
     .route("/health", get(health_handler))
     .route("/naive-upload/:name/*path", post(naive_upload))
+    .route("/binary-cache-upload/:name/*path", put(binary_cache_upload))
+    .route("/proxy-store/:name/:hash", put(proxy_store))
+    .route("/missing-paths/:name", post(missing_paths))
+    .route("/auth/login", post(auth::login))
+    .route("/auth/logout", post(auth::logout))
+    .route("/auth/whoami", get(auth::whoami))
+    .route(
+      "/tokens",
+      get(tokens::list_tokens).post(tokens::create_token),
+    )
+    .route("/tokens/:id/revoke", post(tokens::revoke_token))
+    .route(
+      "/caches",
+      get(caches::list_caches).post(caches::create_cache),
+    )
+    .route(
+      "/caches/:name",
+      get(caches::show_cache)
+        .patch(caches::update_cache)
+        .delete(caches::delete_cache),
+    )
     .route("/fetch_payload", get(prepare_fetch_payload))
+    .route("/binary_cache_payload", get(prepare_binary_cache_payload))
     .route("/", get(dummy_root_handler))
     .with_state(state);
 
@@ -47,6 +47,12 @@ pub enum TempStoragePayloadError {
   /// Error writing to temp storage.
   #[error("Error writing to temp storage: {0}")]
   WriteError(prime_domain::StorageWriteError),
+  /// Error reading the request body.
+  #[error("Error reading the request body: {0}")]
+  ReadError(axum::Error),
+  /// The request body is not valid UTF-8.
+  #[error("The request body is not valid UTF-8: {0}")]
+  Utf8Error(std::string::FromUtf8Error),
 }
 
 impl TempStoragePayload {
@@ -55,17 +61,51 @@ impl TempStoragePayload {
   ) -> Result<TempStoragePath, TempStoragePayloadError> {
     let TempStoragePayload(body, temp_storage_service) = self;
 
-    let data = Belt::from_stream(
-      body.into_data_stream().map(|res| {
-        res.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
-      }),
-      Some(belt::DEFAULT_CHUNK_SIZE),
-    );
     let path = temp_storage_service
-      .write_to_temp_storage(data)
+      .write_to_temp_storage(body_to_belt(body))
       .await
       .map_err(TempStoragePayloadError::WriteError)?;
 
     Ok(path)
   }
+
+  /// Uploads the payload to a given path in temp storage, overwriting
+  /// whatever is there.
+  pub async fn upload_to(
+    self,
+    path: TempStoragePath,
+  ) -> Result<TempStoragePath, TempStoragePayloadError> {
+    let TempStoragePayload(body, temp_storage_service) = self;
+
+    let path = temp_storage_service
+      .write_to_temp_storage_at(path, body_to_belt(body))
+      .await
+      .map_err(TempStoragePayloadError::WriteError)?;
+
+    Ok(path)
+  }
+
+  /// Reads the payload into memory as text instead of uploading it, for
+  /// small payloads like `.narinfo` files. Fails if it's over `limit` bytes.
+  pub async fn into_text(
+    self,
+    limit: usize,
+  ) -> Result<String, TempStoragePayloadError> {
+    let TempStoragePayload(body, _) = self;
+
+    let bytes = axum::body::to_bytes(body, limit)
+      .await
+      .map_err(TempStoragePayloadError::ReadError)?;
+    String::from_utf8(bytes.to_vec())
+      .map_err(TempStoragePayloadError::Utf8Error)
+  }
+}
+
+fn body_to_belt(body: Body) -> Belt {
+  Belt::from_stream(
+    body.into_data_stream().map(|res| {
+      res.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+    }),
+    Some(belt::DEFAULT_CHUNK_SIZE),
+  )
 }
@@ -0,0 +1,156 @@
+//! The `/tokens` routes, for users to manage their own tokens.
+//!
+//! These only accept sessions, so that a leaked token can't be used to mint
+//! more tokens.
+
+use std::str::FromStr;
+
+use axum::{
+  extract::{Path, State},
+  Json,
+};
+use chrono::{DateTime, Utc};
+use prime_domain::{
+  models, IssueTokenError, PrimeDomainService, RevokeTokenError,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{auth::AuthenticatedSession, AppState};
+
+/// A token, without its secret hash.
+#[derive(Serialize)]
+pub struct TokenInfo {
+  /// The token's ID.
+  pub id:           models::TokenRecordId,
+  /// The token's nickname.
+  pub nickname:     models::EntityNickname,
+  /// The token's permissions.
+  pub perms:        models::PermissionSet,
+  /// The token's org.
+  pub org:          models::OrgRecordId,
+  /// When the token stops being valid, if ever.
+  pub expires_at:   Option<DateTime<Utc>>,
+  /// When the token was revoked, if it has been.
+  pub revoked_at:   Option<DateTime<Utc>>,
+  /// When the token was last used.
+  pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<models::Token> for TokenInfo {
+  fn from(token: models::Token) -> Self {
+    Self {
+      id:           token.id,
+      nickname:     token.nickname,
+      perms:        token.perms,
+      org:          token.org,
+      expires_at:   token.expires_at,
+      revoked_at:   token.revoked_at,
+      last_used_at: token.last_used_at,
+    }
+  }
+}
+
+/// The body of a token create request.
+#[derive(Deserialize)]
+pub struct CreateTokenRequest {
+  /// The token's nickname.
+  pub nickname:   String,
+  /// The token's permissions.
+  pub perms:      models::PermissionSet,
+  /// When the token stops being valid, if ever.
+  #[serde(default)]
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A newly created token.
+#[derive(Serialize)]
+pub struct CreateTokenResponse {
+  /// The token.
+  #[serde(flatten)]
+  pub token:  TokenInfo,
+  /// The token's secret. It's only ever returned here.
+  pub secret: models::TokenSecret,
+}
+
+/// Creates a token owned by the session's user.
+#[tracing::instrument(skip(app_state, user, request), fields(user = %user.id))]
+pub async fn create_token(
+  State(app_state): State<AppState>,
+  AuthenticatedSession { user, .. }: AuthenticatedSession,
+  Json(request): Json<CreateTokenRequest>,
+) -> Result<Json<CreateTokenResponse>, mollusk::ExternalApiError> {
+  let nickname = models::StrictSlug::try_exact(request.nickname.clone())
+    .map(models::EntityNickname::new)
+    .ok_or(mollusk::InvalidNicknameError {
+      nickname: request.nickname,
+    })?;
+  if let Some(expires_at) = request.expires_at.filter(|t| *t <= Utc::now()) {
+    Err(mollusk::PastTokenExpiryError {
+      expires_at: expires_at.to_rfc3339(),
+    })?;
+  }
+
+  let (token, secret) = app_state
+    .prime_domain_service
+    .issue_token(&user, nickname, request.perms, request.expires_at)
+    .await
+    .map_err(|e| match e {
+      IssueTokenError::ExcessPermissions(excess) => {
+        let mut permissions =
+          excess.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+        permissions.sort();
+        mollusk::ExternalApiError::from(mollusk::ExcessTokenPermissionsError {
+          permissions,
+        })
+      }
+      e => mollusk::InternalError(format!("{e:?}")).into(),
+    })?;
+
+  Ok(Json(CreateTokenResponse {
+    token: token.into(),
+    secret,
+  }))
+}
+
+/// Lists the tokens owned by the session's user, including revoked and
+/// expired ones.
+#[tracing::instrument(skip(app_state, user), fields(user = %user.id))]
+pub async fn list_tokens(
+  State(app_state): State<AppState>,
+  AuthenticatedSession { user, .. }: AuthenticatedSession,
+) -> Result<Json<Vec<TokenInfo>>, mollusk::ExternalApiError> {
+  let tokens = app_state
+    .prime_domain_service
+    .enumerate_tokens_by_owner(user.id)
+    .await
+    .map_err(|e| mollusk::InternalError(format!("{e:?}")))?;
+
+  Ok(Json(tokens.into_iter().map(TokenInfo::from).collect()))
+}
+
+/// Revokes a token owned by the session's user.
+#[tracing::instrument(skip(app_state, user), fields(user = %user.id))]
+pub async fn revoke_token(
+  State(app_state): State<AppState>,
+  Path(id): Path<String>,
+  AuthenticatedSession { user, .. }: AuthenticatedSession,
+) -> Result<Json<TokenInfo>, mollusk::ExternalApiError> {
+  let token_id = models::TokenRecordId::from_str(&id)
+    .map_err(|_| mollusk::NonExistentTokenError { token: id.clone() })?;
+
+  let token = app_state
+    .prime_domain_service
+    .revoke_token(&user, token_id)
+    .await
+    .map_err(|e| match e {
+      // other users' tokens are reported as missing, to not reveal them
+      RevokeTokenError::NotFound(_) => {
+        mollusk::ExternalApiError::from(mollusk::NonExistentTokenError {
+          token: id,
+        })
+      }
+      e => mollusk::InternalError(format!("{e:?}")).into(),
+    })?;
+
+  Ok(Json(token.into()))
+}
@@ -1,6 +1,8 @@
 use std::pin::Pin;
 
-use async_compression::tokio::bufread::{ZstdDecoder, ZstdEncoder};
+use async_compression::tokio::bufread::{
+  XzDecoder, XzEncoder, ZstdDecoder, ZstdEncoder,
+};
 use bytes::Bytes;
 use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader};
 use tokio_util::io::StreamReader;
@@ -14,11 +16,15 @@ type ArBelt = StreamReader<Belt, Bytes>;
 pub enum CompressionAlgorithm {
   /// The Zstandard compression algorithm.
   Zstd,
+  /// The XZ compression algorithm, which is Nix's default for binary caches.
+  Xz,
 }
 
 pub(crate) enum CompressionAdapter {
   FromZstd(BufReader<ZstdDecoder<ArBelt>>),
   ToZstd(BufReader<ZstdEncoder<ArBelt>>),
+  FromXz(BufReader<XzDecoder<ArBelt>>),
+  ToXz(BufReader<XzEncoder<ArBelt>>),
 }
 
 impl CompressionAdapter {
@@ -28,6 +34,12 @@ impl CompressionAdapter {
   pub(crate) fn to_zstd(belt: Belt) -> Self {
     Self::ToZstd(BufReader::new(ZstdEncoder::new(belt.to_async_buf_read())))
   }
+  pub(crate) fn from_xz(belt: Belt) -> Self {
+    Self::FromXz(BufReader::new(XzDecoder::new(belt.to_async_buf_read())))
+  }
+  pub(crate) fn to_xz(belt: Belt) -> Self {
+    Self::ToXz(BufReader::new(XzEncoder::new(belt.to_async_buf_read())))
+  }
 }
 
 impl AsyncRead for CompressionAdapter {
@@ -39,6 +51,8 @@ impl AsyncRead for CompressionAdapter {
     match self.get_mut() {
       Self::FromZstd(inner) => Pin::new(inner).poll_read(cx, buf),
       Self::ToZstd(inner) => Pin::new(inner).poll_read(cx, buf),
+      Self::FromXz(inner) => Pin::new(inner).poll_read(cx, buf),
+      Self::ToXz(inner) => Pin::new(inner).poll_read(cx, buf),
     }
   }
 }
@@ -51,6 +65,8 @@ impl AsyncBufRead for CompressionAdapter {
     match self.get_mut() {
       Self::FromZstd(inner) => Pin::new(inner).poll_fill_buf(cx),
       Self::ToZstd(inner) => Pin::new(inner).poll_fill_buf(cx),
+      Self::FromXz(inner) => Pin::new(inner).poll_fill_buf(cx),
+      Self::ToXz(inner) => Pin::new(inner).poll_fill_buf(cx),
     }
   }
 
@@ -58,6 +74,8 @@ impl AsyncBufRead for CompressionAdapter {
     match self.get_mut() {
       Self::FromZstd(inner) => inner.consume(amt),
       Self::ToZstd(inner) => inner.consume(amt),
+      Self::FromXz(inner) => inner.consume(amt),
+      Self::ToXz(inner) => inner.consume(amt),
     }
   }
 }
@@ -86,4 +104,22 @@ mod tests {
     let n = reader.read(&mut buf).await.unwrap();
     assert_eq!(&buf[..n], b"hello world");
   }
+
+  #[tokio::test]
+  async fn test_compression_adapter_roundtrip_xz() {
+    let stream = futures::stream::iter(vec![
+      Ok(Bytes::from("hello")),
+      Ok(Bytes::from(" world")),
+    ]);
+    let belt = Belt::from_stream(stream, None);
+
+    let belt = belt.adapt_to_comp(CompressionAlgorithm::Xz);
+    let belt = belt.adapt_to_no_comp();
+
+    let mut reader = BufReader::new(belt.to_async_buf_read());
+    let mut buf = vec![0; 1024];
+
+    let n = reader.read(&mut buf).await.unwrap();
+    assert_eq!(&buf[..n], b"hello world");
+  }
 }
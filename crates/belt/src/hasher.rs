@@ -0,0 +1,22 @@
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+
+/// A tracking sha256 hasher for the bytes read from a [`Belt`](crate::Belt).
+#[derive(Debug)]
+pub struct Hasher(Arc<Mutex<Sha256>>);
+
+impl Hasher {
+  /// Get the sha256 digest of the bytes read so far.
+  pub fn current(&self) -> [u8; 32] {
+    self
+      .0
+      .lock()
+      .expect("hasher mutex poisoned")
+      .clone()
+      .finalize()
+      .into()
+  }
+
+  pub(crate) fn new(state: Arc<Mutex<Sha256>>) -> Self { Self(state) }
+}
@@ -3,6 +3,8 @@
 mod bottleneck;
 mod comp;
 mod counter;
+mod hasher;
+mod observer;
 mod source;
 
 /// A good default chunk size for [`Belt`]s. Not used anywhere in the library,
@@ -16,18 +18,24 @@ use std::{
   pin::Pin,
   sync::{
     atomic::{AtomicU64, Ordering},
-    Arc,
+    Arc, Mutex,
   },
   task::{Context, Poll},
 };
 
 use bytes::Bytes;
 use futures::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
 use tokio::{io::AsyncBufRead, sync::mpsc};
 use tokio_util::io::ReaderStream;
 
 use self::{bottleneck::Bottleneck, source::BytesSource};
-pub use self::{comp::CompressionAlgorithm, counter::Counter};
+pub use self::{
+  comp::CompressionAlgorithm,
+  counter::Counter,
+  hasher::Hasher,
+  observer::{Observed, Observer},
+};
 
 #[derive(Debug)]
 enum MaybeBottleneckSource {
@@ -54,6 +62,8 @@ impl Stream for MaybeBottleneckSource {
 pub struct Belt {
   inner:         MaybeBottleneckSource,
   count:         Arc<AtomicU64>,
+  hash:          Option<Arc<Mutex<Sha256>>>,
+  observers:     Vec<Arc<Mutex<dyn Observer>>>,
   declared_comp: Option<CompressionAlgorithm>,
 }
 
@@ -73,6 +83,8 @@ impl Belt {
         }
       },
       count:         Arc::new(AtomicU64::new(0)),
+      hash:          None,
+      observers:     Vec::new(),
       declared_comp: None,
     }
   }
@@ -95,6 +107,8 @@ impl Belt {
         )),
       },
       count:         Arc::new(AtomicU64::new(0)),
+      hash:          None,
+      observers:     Vec::new(),
       declared_comp: None,
     }
   }
@@ -119,6 +133,8 @@ impl Belt {
         )),
       },
       count:         Arc::new(AtomicU64::new(0)),
+      hash:          None,
+      observers:     Vec::new(),
       declared_comp: None,
     }
   }
@@ -149,10 +165,13 @@ impl Belt {
             CompressionAlgorithm::Zstd => {
               comp::CompressionAdapter::to_zstd(self)
             }
+            CompressionAlgorithm::Xz => comp::CompressionAdapter::to_xz(self),
           },
         ))),
       ),
       count:         Arc::new(AtomicU64::new(0)),
+      hash:          None,
+      observers:     Vec::new(),
       declared_comp: Some(algo),
     }
   }
@@ -166,10 +185,13 @@ impl Belt {
             CompressionAlgorithm::Zstd => {
               comp::CompressionAdapter::from_zstd(self)
             }
+            CompressionAlgorithm::Xz => comp::CompressionAdapter::from_xz(self),
           },
         ))),
       ),
       count:         Arc::new(AtomicU64::new(0)),
+      hash:          None,
+      observers:     Vec::new(),
       declared_comp: None,
     }
   }
@@ -194,6 +216,63 @@ impl Belt {
   /// [`Belt`].
   pub fn counter(&self) -> Counter { Counter::new(self.count.clone()) }
 
+  /// Get a tracking hasher for the bytes read from this [`Belt`].
+  ///
+  /// Hashing is only done once a hasher has been requested, so this should be
+  /// called before any bytes are read.
+  pub fn hasher(&mut self) -> Hasher {
+    Hasher::new(
+      self
+        .hash
+        .get_or_insert_with(|| Arc::new(Mutex::new(Sha256::new())))
+        .clone(),
+    )
+  }
+
+  /// Attach an [`Observer`] to the bytes read from this [`Belt`].
+  ///
+  /// Like [`hasher`](Self::hasher), this should be called before any bytes
+  /// are read.
+  pub fn observe<O: Observer>(&mut self, observer: O) -> Observed<O> {
+    let observed = Observed::new(observer);
+    self.observers.push(observed.erased());
+    observed
+  }
+
+  /// Split this [`Belt`] into two that both yield its bytes.
+  ///
+  /// The bytes are pumped by a spawned task, so this must be called from
+  /// within a Tokio runtime. Both halves are fed at the pace of the slower one,
+  /// but dropping one half doesn't stop the other.
+  pub fn tee(mut self, buffer_size: usize) -> (Self, Self) {
+    let declared_comp = self.declared_comp;
+    let (tx_a, belt_a) = Self::new_channel(buffer_size, None);
+    let (tx_b, belt_b) = Self::new_channel(buffer_size, None);
+
+    tokio::spawn(async move {
+      while let Some(result) = self.next().await {
+        let is_err = result.is_err();
+        for tx in [&tx_a, &tx_b] {
+          // `io::Error` isn't `Clone`, so errors are recreated for each half
+          let item = match &result {
+            Ok(bytes) => Ok(bytes.clone()),
+            Err(e) => Err(std::io::Error::new(e.kind(), e.to_string())),
+          };
+          // a dropped half just stops receiving
+          let _ = tx.send(item).await;
+        }
+        if is_err || (tx_a.is_closed() && tx_b.is_closed()) {
+          break;
+        }
+      }
+    });
+
+    (
+      belt_a.set_declared_comp(declared_comp),
+      belt_b.set_declared_comp(declared_comp),
+    )
+  }
+
   /// Convert this Belt into an [`AsyncBufRead`] implementer.
   pub fn to_async_buf_read(self) -> tokio_util::io::StreamReader<Self, Bytes> {
     tokio_util::io::StreamReader::new(self)
@@ -211,6 +290,15 @@ impl Stream for Belt {
 
     if let Poll::Ready(Some(Ok(bytes))) = &poll_result {
       self.count.fetch_add(bytes.len() as u64, Ordering::Release);
+      if let Some(hash) = &self.hash {
+        hash.lock().expect("hasher mutex poisoned").update(bytes);
+      }
+      for observer in &self.observers {
+        observer
+          .lock()
+          .expect("observer mutex poisoned")
+          .observe(bytes);
+      }
     }
 
     poll_result
@@ -349,4 +437,88 @@ mod tests {
     // the bytes that weren't read from the StreamReader
     assert_eq!(buf, Some(Bytes::from_static(b" world")));
   }
+
+  #[tokio::test]
+  async fn test_belt_tee() {
+    let stream = futures::stream::iter(vec![
+      Ok(Bytes::from("hello")),
+      Ok(Bytes::from(" world")),
+    ]);
+    let belt = Belt::from_stream(stream, None)
+      .set_declared_comp(Some(CompressionAlgorithm::Zstd));
+    let (a, b) = belt.tee(1);
+    assert_eq!(a.declared_comp, Some(CompressionAlgorithm::Zstd));
+
+    // dropping one half doesn't starve the other
+    drop(a);
+    let mut buf = Vec::new();
+    b.to_async_buf_read().read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"hello world");
+  }
+
+  #[tokio::test]
+  async fn test_belt_tee_error() {
+    let (tx, belt) = Belt::new_channel(10, None);
+    tx.send(Ok(Bytes::from("hello"))).await.unwrap();
+    tx.send(Err(std::io::Error::other("oh no"))).await.unwrap();
+    drop(tx);
+
+    let (a, b) = belt.tee(10);
+    for half in [a, b] {
+      let mut buf = Vec::new();
+      let err = half.to_async_buf_read().read_to_end(&mut buf).await;
+      assert_eq!(buf, b"hello");
+      assert_eq!(err.unwrap_err().to_string(), "oh no");
+    }
+  }
+
+  #[tokio::test]
+  async fn test_belt_hasher() {
+    let stream = futures::stream::iter(vec![
+      Ok(Bytes::from("hello")),
+      Ok(Bytes::from(" world")),
+    ]);
+    let mut belt = Belt::from_stream(stream, None);
+    let uncompressed_hasher = belt.hasher();
+
+    let mut belt = belt.adapt_to_comp(CompressionAlgorithm::Zstd);
+    let compressed_hasher = belt.hasher();
+
+    let mut compressed = Vec::new();
+    while let Some(bytes) = belt.next().await.transpose().unwrap() {
+      compressed.extend_from_slice(&bytes);
+    }
+
+    let expected: [u8; 32] = Sha256::digest(b"hello world").into();
+    assert_eq!(uncompressed_hasher.current(), expected);
+    let expected: [u8; 32] = Sha256::digest(&compressed).into();
+    assert_eq!(compressed_hasher.current(), expected);
+  }
+
+  #[derive(Debug, Default)]
+  struct Collector(Vec<u8>);
+
+  impl Observer for Collector {
+    fn observe(&mut self, chunk: &[u8]) { self.0.extend_from_slice(chunk); }
+  }
+
+  #[tokio::test]
+  async fn test_belt_observer() {
+    let stream = futures::stream::iter(vec![
+      Ok(Bytes::from("hello")),
+      Ok(Bytes::from(" world")),
+    ]);
+    let mut belt = Belt::from_stream(stream, None);
+    let observed = belt.observe(Collector::default());
+
+    assert_eq!(
+      belt.next().await.transpose().unwrap(),
+      Some(Bytes::from("hello"))
+    );
+    assert_eq!(observed.with(|c| c.0.clone()), b"hello");
+
+    while belt.next().await.transpose().unwrap().is_some() {}
+    drop(belt);
+    assert_eq!(observed.into_inner().unwrap().0, b"hello world");
+  }
 }
@@ -0,0 +1,36 @@
+use std::{
+  fmt::Debug,
+  sync::{Arc, Mutex},
+};
+
+/// Something that watches the bytes read from a [`Belt`](crate::Belt).
+pub trait Observer: Debug + Send + 'static {
+  /// Observe the next chunk of bytes read.
+  fn observe(&mut self, chunk: &[u8]);
+}
+
+/// A handle to an [`Observer`] attached to a [`Belt`](crate::Belt).
+#[derive(Debug)]
+pub struct Observed<O>(Arc<Mutex<O>>);
+
+impl<O: Observer> Observed<O> {
+  /// Run a closure against the observer's current state.
+  pub fn with<R>(&self, f: impl FnOnce(&O) -> R) -> R {
+    f(&self.0.lock().expect("observer mutex poisoned"))
+  }
+
+  /// Take the observer back, once the [`Belt`](crate::Belt) it was attached
+  /// to has been dropped.
+  ///
+  /// Returns `None` if the belt is still alive.
+  pub fn into_inner(self) -> Option<O> {
+    Arc::into_inner(self.0)
+      .map(|m| m.into_inner().expect("observer mutex poisoned"))
+  }
+
+  pub(crate) fn new(observer: O) -> Self {
+    Self(Arc::new(Mutex::new(observer)))
+  }
+
+  pub(crate) fn erased(&self) -> Arc<Mutex<dyn Observer>> { self.0.clone() }
+}
@@ -12,6 +12,12 @@ fn Entry(#[prop(into)] entry: Signal<models::Entry>) -> impl IntoView {
 
   let entry_path = move || entry.with(|e| format!("{:?}", e.path.to_string()));
   let entry_c_status = move || entry.with(|e| format!("{:?}", e.c_status));
+  let entry_nar_hash = move || {
+    entry.with(|e| e.nar_hash.map_or("unknown".to_string(), |h| h.to_string()))
+  };
+  let entry_file_hash = move || {
+    entry.with(|e| e.file_hash.map_or("unknown".to_string(), |h| h.to_string()))
+  };
   let entry_cache = Signal::derive(move || entry.with(|e| e.cache));
 
   view! {
@@ -24,6 +30,8 @@ fn Entry(#[prop(into)] entry: Signal<models::Entry>) -> impl IntoView {
         <KeyValue key="ID:"><EntryIdLink id=entry_id /></KeyValue>
         <KeyValue key="Path:"> { entry_path } </KeyValue>
         <KeyValue key="C-Status:"> { entry_c_status } </KeyValue>
+        <KeyValue key="NAR Hash:"> { entry_nar_hash } </KeyValue>
+        <KeyValue key="File Hash:"> { entry_file_hash } </KeyValue>
         <KeyValue key="Cache:">
           <CacheIdLink id=entry_cache />
         </KeyValue>
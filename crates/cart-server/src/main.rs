@@ -30,24 +30,46 @@ async fn main() -> miette::Result<()> {
     Arc::new(prime_domain::repos::db::KvDatabaseAdapter::new(tikv_store));
   let cache_repo =
     prime_domain::repos::CacheRepositoryCanonical::new(kv_db_adapter.clone());
+  let debuginfo_repo = prime_domain::repos::DebugInfoRepositoryCanonical::new(
+    kv_db_adapter.clone(),
+  );
   let entry_repo =
     prime_domain::repos::EntryRepositoryCanonical::new(kv_db_adapter.clone());
+  let realisation_repo =
+    prime_domain::repos::RealisationRepositoryCanonical::new(
+      kv_db_adapter.clone(),
+    );
+  let signing_key_repo =
+    prime_domain::repos::SigningKeyRepositoryCanonical::new(
+      kv_db_adapter.clone(),
+    );
   let store_repo =
     prime_domain::repos::StoreRepositoryCanonical::new(kv_db_adapter.clone());
   let token_repo =
     prime_domain::repos::TokenRepositoryCanonical::new(kv_db_adapter.clone());
+  let session_repo =
+    prime_domain::repos::SessionRepositoryCanonical::new(kv_db_adapter.clone());
+  let user_repo =
+    prime_domain::repos::UserRepositoryCanonical::new(kv_db_adapter.clone());
   let temp_storage_repo = prime_domain::repos::TempStorageRepositoryMock::new(
     std::path::PathBuf::from("/tmp/rambit-temp-storage"),
   );
   let user_storage_repo =
     prime_domain::repos::UserStorageRepositoryCanonical::new();
+  let token_hasher = prime_domain::TokenSecretHasher::new_from_env()?;
   let prime_domain_service = prime_domain::PrimeDomainServiceCanonical::new(
     cache_repo,
+    debuginfo_repo,
     entry_repo,
+    realisation_repo,
+    session_repo,
+    signing_key_repo,
     store_repo,
     token_repo,
     temp_storage_repo,
+    user_repo,
     user_storage_repo,
+    token_hasher,
   );
 
   let app_state = AppState {
@@ -0,0 +1,87 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::config::{ApiConfig, ApiToken};
+
+/// A client for the API routes that require a session from `cli login`.
+pub(crate) struct SessionClient {
+  client:  reqwest::blocking::Client,
+  api_url: String,
+  session: ApiToken,
+}
+
+impl SessionClient {
+  /// Builds a client from the resolved config, which must have a session.
+  pub(crate) fn new(config: ApiConfig) -> miette::Result<Self> {
+    let Some(session) = config.session else {
+      tracing::error!("no session configured; run `cli login` first");
+      miette::bail!("not logged in");
+    };
+
+    Ok(Self {
+      client: reqwest::blocking::Client::new(),
+      api_url: config.api_url,
+      session,
+    })
+  }
+
+  /// Sends a `GET` request to the given route.
+  pub(crate) fn get<R: DeserializeOwned>(
+    &self,
+    route: &str,
+  ) -> miette::Result<R> {
+    self.send(self.client.get(self.url(route)))
+  }
+
+  /// Sends a `POST` request to the given route, with a JSON body.
+  pub(crate) fn post<B: Serialize, R: DeserializeOwned>(
+    &self,
+    route: &str,
+    body: &B,
+  ) -> miette::Result<R> {
+    self.send(self.client.post(self.url(route)).json(body))
+  }
+
+  /// Sends a `PATCH` request to the given route, with a JSON body.
+  pub(crate) fn patch<B: Serialize, R: DeserializeOwned>(
+    &self,
+    route: &str,
+    body: &B,
+  ) -> miette::Result<R> {
+    self.send(self.client.patch(self.url(route)).json(body))
+  }
+
+  /// Sends a `DELETE` request to the given route.
+  pub(crate) fn delete<R: DeserializeOwned>(
+    &self,
+    route: &str,
+  ) -> miette::Result<R> {
+    self.send(self.client.delete(self.url(route)))
+  }
+
+  fn url(&self, route: &str) -> String { format!("{}{route}", self.api_url) }
+
+  fn send<R: DeserializeOwned>(
+    &self,
+    request: reqwest::blocking::RequestBuilder,
+  ) -> miette::Result<R> {
+    let response = request
+      .header(reqwest::header::AUTHORIZATION, self.session.bearer())
+      .send()
+      .map_err(|e| {
+        tracing::error!("failed to reach {:?}: {}", self.api_url, e);
+        miette::miette!("failed to reach the API")
+      })?;
+
+    let status = response.status();
+    if !status.is_success() {
+      let body = response.text().unwrap_or_default();
+      tracing::error!("API responded with {status}: {body}");
+      miette::bail!("request rejected with {status}");
+    }
+
+    response.json().map_err(|e| {
+      tracing::error!("failed to parse API response: {}", e);
+      miette::miette!("failed to parse API response")
+    })
+  }
+}
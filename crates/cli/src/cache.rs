@@ -0,0 +1,144 @@
+use prime_domain::models::{Cache, StoreRecordId, Visibility};
+use serde::Serialize;
+
+use crate::{
+  api::SessionClient, config::ApiConfig, CacheCreateArgs, CacheDeleteArgs,
+  CacheListArgs, CacheShowArgs, CacheUpdateArgs, CacheVisibility,
+};
+
+impl From<CacheVisibility> for Visibility {
+  fn from(visibility: CacheVisibility) -> Self {
+    match visibility {
+      CacheVisibility::Public => Visibility::Public,
+      CacheVisibility::Private => Visibility::Private,
+    }
+  }
+}
+
+/// The body of a cache create request.
+#[derive(Serialize)]
+struct CreateCacheRequest {
+  name:       String,
+  visibility: Visibility,
+  store:      StoreRecordId,
+}
+
+/// The body of a cache update request.
+#[derive(Serialize)]
+struct UpdateCacheRequest {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  name:       Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  visibility: Option<Visibility>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  store:      Option<StoreRecordId>,
+}
+
+fn parse_store_id(id: &str) -> miette::Result<StoreRecordId> {
+  id.parse().map_err(|_| {
+    tracing::error!("{id:?} is not a store ID");
+    miette::miette!("malformed store ID")
+  })
+}
+
+fn print_cache(cache: &Cache) {
+  println!("name:       {}", cache.name);
+  println!("id:         {}", cache.id);
+  println!("visibility: {}", cache.visibility);
+  println!("store:      {}", cache.store);
+  println!("org:        {}", cache.org);
+}
+
+pub(crate) fn create_cache(
+  CacheCreateArgs {
+    name,
+    store,
+    visibility,
+    api,
+  }: CacheCreateArgs,
+) -> miette::Result<()> {
+  let client = SessionClient::new(ApiConfig::resolve(api)?)?;
+
+  let cache: Cache = client.post("/caches", &CreateCacheRequest {
+    name,
+    visibility: visibility.into(),
+    store: parse_store_id(&store)?,
+  })?;
+
+  tracing::info!("created cache {:?}", cache.name.to_string());
+  print_cache(&cache);
+  Ok(())
+}
+
+pub(crate) fn list_caches(
+  CacheListArgs { api }: CacheListArgs,
+) -> miette::Result<()> {
+  let client = SessionClient::new(ApiConfig::resolve(api)?)?;
+  let mut caches: Vec<Cache> = client.get("/caches")?;
+
+  if caches.is_empty() {
+    tracing::info!("your org has no caches");
+    return Ok(());
+  }
+
+  caches.sort_by_key(|c| c.name.to_string());
+  for cache in &caches {
+    println!(
+      "{:<24}  {:<7}  {}",
+      cache.name.to_string(),
+      cache.visibility.to_string(),
+      cache.id
+    );
+  }
+  Ok(())
+}
+
+pub(crate) fn show_cache(
+  CacheShowArgs { name, api }: CacheShowArgs,
+) -> miette::Result<()> {
+  let client = SessionClient::new(ApiConfig::resolve(api)?)?;
+  let cache: Cache = client.get(&format!("/caches/{name}"))?;
+
+  print_cache(&cache);
+  Ok(())
+}
+
+pub(crate) fn update_cache(
+  CacheUpdateArgs {
+    name,
+    new_name,
+    visibility,
+    store,
+    api,
+  }: CacheUpdateArgs,
+) -> miette::Result<()> {
+  if new_name.is_none() && visibility.is_none() && store.is_none() {
+    tracing::error!(
+      "pass at least one of `--name`, `--visibility` or `--store`"
+    );
+    miette::bail!("nothing to update");
+  }
+  let store = store.as_deref().map(parse_store_id).transpose()?;
+
+  let client = SessionClient::new(ApiConfig::resolve(api)?)?;
+  let cache: Cache =
+    client.patch(&format!("/caches/{name}"), &UpdateCacheRequest {
+      name: new_name,
+      visibility: visibility.map(Into::into),
+      store,
+    })?;
+
+  tracing::info!("updated cache {:?}", cache.name.to_string());
+  print_cache(&cache);
+  Ok(())
+}
+
+pub(crate) fn delete_cache(
+  CacheDeleteArgs { name, api }: CacheDeleteArgs,
+) -> miette::Result<()> {
+  let client = SessionClient::new(ApiConfig::resolve(api)?)?;
+  let cache: Cache = client.delete(&format!("/caches/{name}"))?;
+
+  tracing::info!("deleted cache {:?} ({})", cache.name.to_string(), cache.id);
+  Ok(())
+}
@@ -0,0 +1,50 @@
+use nasty::{closure::NixDatabase, StorePath};
+
+use crate::ClosureArgs;
+
+pub(crate) fn compute_closure(
+  ClosureArgs { paths, db }: ClosureArgs,
+) -> miette::Result<()> {
+  let roots = paths
+    .iter()
+    .map(|path| {
+      let canonical = std::fs::canonicalize(path).map_err(|e| {
+        tracing::error!("failed to resolve {:?}: {}", path, e);
+        miette::miette!("failed to resolve path")
+      })?;
+      canonical
+        .to_str()
+        .and_then(StorePath::from_absolute_path)
+        .ok_or_else(|| {
+          tracing::error!("{canonical:?} is not a top-level store path");
+          miette::miette!("not a store path")
+        })
+    })
+    .collect::<miette::Result<Vec<_>>>()?;
+
+  let db = match db {
+    Some(db) => NixDatabase::open(db),
+    None => NixDatabase::open_local(),
+  }
+  .map_err(|e| {
+    tracing::error!("failed to open the Nix database: {}", e);
+    miette::miette!("failed to open the Nix database")
+  })?;
+
+  let closure = db.closure(&roots).map_err(|e| {
+    tracing::error!("failed to compute closure: {}", e);
+    miette::miette!("failed to compute closure")
+  })?;
+  tracing::info!("closure contains {} path(s)", closure.len());
+
+  let closure = closure
+    .iter()
+    .map(|p| p.to_absolute_path())
+    .collect::<Vec<_>>();
+  println!(
+    "{}",
+    serde_json::to_string_pretty(&closure).expect("failed to serialize")
+  );
+
+  Ok(())
+}
@@ -0,0 +1,525 @@
+//! Configuration for talking to Rambit.
+//!
+//! Settings live in named profiles in `~/.config/rambit/config.toml`:
+//!
+//! ```toml
+//! default_profile = "prod"
+//!
+//! [profiles.prod]
+//! api_url = "https://api.example.com"
+//! fetcher_url = "https://cache.example.com"
+//! token_id = "01J..."
+//! token_secret = "..."
+//! ```
+//!
+//! Instead of a token, a profile can hold the session that `cli login` stores
+//! as `session_id` and `session_secret`. A token takes precedence over it.
+//!
+//! The profile is picked with `--profile` or `RAMBIT_PROFILE`, falling back to
+//! `default_profile` and then to `default`. Individual values can be
+//! overridden by environment variables or command-line flags.
+
+use std::{
+  collections::BTreeMap,
+  io::Write,
+  os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
+  path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ApiArgs, ConfigGetArgs, ConfigKey, ConfigListArgs, ConfigSetArgs};
+
+/// The API URL used when none is configured.
+const DEFAULT_API_URL: &str = "http://localhost:3000";
+/// The fetcher URL used when none is configured.
+const DEFAULT_FETCHER_URL: &str = "http://localhost:4000";
+/// The profile used when none is selected.
+const DEFAULT_PROFILE: &str = "default";
+
+/// The contents of the config file.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  default_profile: Option<String>,
+  #[serde(default)]
+  profiles:        BTreeMap<String, Profile>,
+}
+
+/// A named set of connection settings.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Profile {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  api_url:        Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  fetcher_url:    Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  token_id:       Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  token_secret:   Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  session_id:     Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  session_secret: Option<String>,
+}
+
+impl Profile {
+  fn get(&self, key: ConfigKey) -> Option<&String> {
+    match key {
+      ConfigKey::ApiUrl => self.api_url.as_ref(),
+      ConfigKey::FetcherUrl => self.fetcher_url.as_ref(),
+      ConfigKey::TokenId => self.token_id.as_ref(),
+      ConfigKey::TokenSecret => self.token_secret.as_ref(),
+      ConfigKey::SessionId => self.session_id.as_ref(),
+      ConfigKey::SessionSecret => self.session_secret.as_ref(),
+    }
+  }
+
+  fn set(&mut self, key: ConfigKey, value: String) {
+    let field = match key {
+      ConfigKey::ApiUrl => &mut self.api_url,
+      ConfigKey::FetcherUrl => &mut self.fetcher_url,
+      ConfigKey::TokenId => &mut self.token_id,
+      ConfigKey::TokenSecret => &mut self.token_secret,
+      ConfigKey::SessionId => &mut self.session_id,
+      ConfigKey::SessionSecret => &mut self.session_secret,
+    };
+    *field = Some(value);
+  }
+}
+
+/// Returns the path of the config file, if a home directory can be found.
+fn config_path() -> miette::Result<PathBuf> {
+  let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+    Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+    _ => match std::env::var_os("HOME") {
+      Some(home) => PathBuf::from(home).join(".config"),
+      None => {
+        tracing::error!("neither `XDG_CONFIG_HOME` nor `HOME` is set");
+        miette::bail!("failed to locate config file");
+      }
+    },
+  };
+  Ok(config_dir.join("rambit").join("config.toml"))
+}
+
+impl ConfigFile {
+  /// Reads the config file, or returns an empty config if it doesn't exist.
+  fn load(path: &Path) -> miette::Result<Self> {
+    let contents = match std::fs::read_to_string(path) {
+      Ok(contents) => contents,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        return Ok(Self::default());
+      }
+      Err(e) => {
+        tracing::error!("failed to read config file {:?}: {}", path, e);
+        miette::bail!("failed to read config file");
+      }
+    };
+
+    if let Ok(metadata) = std::fs::metadata(path) {
+      if metadata.permissions().mode() & 0o004 != 0 {
+        tracing::warn!(
+          "config file {path:?} is readable by every user on this machine, \
+           and may contain token secrets; run `chmod 600 {}` to fix this",
+          path.display()
+        );
+      }
+    }
+
+    toml::from_str(&contents).map_err(|e| {
+      tracing::error!("failed to parse config file {:?}: {}", path, e);
+      miette::miette!("failed to parse config file")
+    })
+  }
+
+  /// Writes the config file, readable only by the current user.
+  ///
+  /// The file is written to a temporary file and moved into place, so a
+  /// failed write can't leave a truncated config behind.
+  fn save(&self, path: &Path) -> miette::Result<()> {
+    let contents = toml::to_string_pretty(self).map_err(|e| {
+      tracing::error!("failed to serialize config: {}", e);
+      miette::miette!("failed to serialize config")
+    })?;
+
+    let write = || -> std::io::Result<()> {
+      if let Some(parent) = path.parent() {
+        std::fs::DirBuilder::new()
+          .recursive(true)
+          .mode(0o700)
+          .create(parent)?;
+      }
+      let temp_path = path.with_extension("toml.tmp");
+      let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&temp_path)?;
+      // `mode` only applies to new files
+      file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+      file.write_all(contents.as_bytes())?;
+      file.sync_all()?;
+      std::fs::rename(&temp_path, path)
+    };
+
+    write().map_err(|e| {
+      tracing::error!("failed to write config file {:?}: {}", path, e);
+      miette::miette!("failed to write config file")
+    })
+  }
+
+  /// Returns the name of the profile to use.
+  fn profile_name(&self, requested: Option<&str>) -> String {
+    requested
+      .or(self.default_profile.as_deref())
+      .unwrap_or(DEFAULT_PROFILE)
+      .to_string()
+  }
+
+  /// Returns the profile to use. A profile that was asked for by name has to
+  /// exist, but the default one may be missing.
+  fn profile(&self, requested: Option<&str>) -> miette::Result<Profile> {
+    let name = self.profile_name(requested);
+    match self.profiles.get(&name) {
+      Some(profile) => Ok(profile.clone()),
+      None if requested.is_none() => Ok(Profile::default()),
+      None => {
+        tracing::error!("profile {name:?} does not exist in the config file");
+        miette::bail!("profile not found");
+      }
+    }
+  }
+}
+
+/// An API token or session, in `<id>:<secret>` form.
+#[derive(Debug, Clone)]
+pub(crate) struct ApiToken {
+  id:     String,
+  secret: String,
+}
+
+impl ApiToken {
+  fn parse(token: &str) -> miette::Result<Self> {
+    match token.trim().split_once(':') {
+      Some((id, secret)) if !id.is_empty() && !secret.is_empty() => Ok(Self {
+        id:     id.to_string(),
+        secret: secret.to_string(),
+      }),
+      _ => {
+        tracing::error!("the API token is not in `<id>:<secret>` form");
+        miette::bail!("malformed API token");
+      }
+    }
+  }
+
+  /// Returns the token in `<id>:<secret>` form, which is how the fetcher
+  /// expects it in the `Authorization` header.
+  pub(crate) fn pair(&self) -> String { format!("{}:{}", self.id, self.secret) }
+
+  /// Returns the value of the `Authorization` header the API expects.
+  pub(crate) fn bearer(&self) -> String { format!("Bearer {}", self.pair()) }
+}
+
+/// Resolved settings for talking to the API and the fetcher.
+#[derive(Debug, Clone)]
+pub(crate) struct ApiConfig {
+  /// The base URL of the API, without a trailing slash.
+  pub api_url:     String,
+  /// The base URL of the fetcher, without a trailing slash.
+  pub fetcher_url: String,
+  /// The token or session to authenticate with.
+  pub token:       Option<ApiToken>,
+  /// The profile's session, for commands that only accept one.
+  pub session:     Option<ApiToken>,
+}
+
+impl ApiConfig {
+  /// Resolves the API settings. Values given directly (from flags or the
+  /// environment) take precedence over the selected profile.
+  pub(crate) fn resolve(args: ApiArgs) -> miette::Result<Self> {
+    let file = ConfigFile::load(&config_path()?)?;
+    Self::resolve_with(&file, args)
+  }
+
+  fn resolve_with(
+    file: &ConfigFile,
+    ApiArgs {
+      profile,
+      api_url,
+      fetcher_url,
+      token,
+    }: ApiArgs,
+  ) -> miette::Result<Self> {
+    let profile = file.profile(profile.as_deref())?;
+
+    let url = |url: Option<String>, default: &str| {
+      url
+        .unwrap_or(default.to_string())
+        .trim_end_matches('/')
+        .to_string()
+    };
+    let api_url = url(api_url.or(profile.api_url), DEFAULT_API_URL);
+    let fetcher_url =
+      url(fetcher_url.or(profile.fetcher_url), DEFAULT_FETCHER_URL);
+
+    let session = match (profile.session_id, profile.session_secret) {
+      (Some(id), Some(secret)) => Some(ApiToken { id, secret }),
+      (None, None) => None,
+      _ => {
+        tracing::error!(
+          "the profile needs both `session-id` and `session-secret` to be set"
+        );
+        miette::bail!("incomplete session in profile");
+      }
+    };
+    let token = match (token, profile.token_id, profile.token_secret) {
+      (Some(token), _, _) => Some(ApiToken::parse(&token)?),
+      (None, Some(id), Some(secret)) => Some(ApiToken { id, secret }),
+      (None, None, None) => session.clone(),
+      (None, ..) => {
+        tracing::error!(
+          "the profile needs both `token-id` and `token-secret` to be set"
+        );
+        miette::bail!("incomplete token in profile");
+      }
+    };
+
+    Ok(Self {
+      api_url,
+      fetcher_url,
+      token,
+      session,
+    })
+  }
+}
+
+impl ConfigFile {
+  /// Stores a session in a profile, creating the profile if needed.
+  fn set_session(&mut self, name: &str, id: String, secret: String) {
+    let profile = self.profiles.entry(name.to_string()).or_default();
+    profile.session_id = Some(id);
+    profile.session_secret = Some(secret);
+    // the first profile that's written becomes the default
+    if self.default_profile.is_none() && self.profiles.len() == 1 {
+      self.default_profile = Some(name.to_string());
+    }
+  }
+}
+
+/// Stores a session from logging in in the selected profile.
+pub(crate) fn save_session(
+  profile: Option<&str>,
+  id: String,
+  secret: String,
+) -> miette::Result<()> {
+  let path = config_path()?;
+  let mut file = ConfigFile::load(&path)?;
+
+  let name = file.profile_name(profile);
+  file.set_session(&name, id, secret);
+  file.save(&path)?;
+
+  if file.profiles[&name].token_id.is_some() {
+    tracing::warn!(
+      "profile {name:?} also has a token, which is used instead of the \
+       session outside of account management"
+    );
+  }
+  tracing::info!("stored session in profile {name:?}");
+  Ok(())
+}
+
+pub(crate) fn set_config_value(
+  ConfigSetArgs {
+    key,
+    value,
+    profile,
+  }: ConfigSetArgs,
+) -> miette::Result<()> {
+  let path = config_path()?;
+  let mut file = ConfigFile::load(&path)?;
+
+  let name = file.profile_name(profile.as_deref());
+  file
+    .profiles
+    .entry(name.clone())
+    .or_default()
+    .set(key, value);
+  // the first profile that's written becomes the default
+  if file.default_profile.is_none() && file.profiles.len() == 1 {
+    file.default_profile = Some(name.clone());
+  }
+
+  file.save(&path)?;
+  tracing::info!("set `{key}` in profile {name:?}");
+  Ok(())
+}
+
+pub(crate) fn get_config_value(
+  ConfigGetArgs { key, profile }: ConfigGetArgs,
+) -> miette::Result<()> {
+  let file = ConfigFile::load(&config_path()?)?;
+
+  match file.profile(profile.as_deref())?.get(key) {
+    Some(value) => println!("{value}"),
+    None => {
+      tracing::error!(
+        "`{key}` is not set in profile {:?}",
+        file.profile_name(profile.as_deref())
+      );
+      miette::bail!("config value not set");
+    }
+  }
+  Ok(())
+}
+
+pub(crate) fn list_config(
+  ConfigListArgs { show_secrets }: ConfigListArgs,
+) -> miette::Result<()> {
+  let path = config_path()?;
+  let file = ConfigFile::load(&path)?;
+
+  if file.profiles.is_empty() {
+    tracing::info!("no profiles are configured in {path:?}");
+    return Ok(());
+  }
+
+  let default = file.profile_name(None);
+  for (name, profile) in &file.profiles {
+    let marker = if *name == default { " (default)" } else { "" };
+    println!("[{name}]{marker}");
+    for key in [
+      ConfigKey::ApiUrl,
+      ConfigKey::FetcherUrl,
+      ConfigKey::TokenId,
+      ConfigKey::TokenSecret,
+      ConfigKey::SessionId,
+      ConfigKey::SessionSecret,
+    ] {
+      let Some(value) = profile.get(key) else {
+        continue;
+      };
+      let value = match key {
+        ConfigKey::TokenSecret | ConfigKey::SessionSecret if !show_secrets => {
+          "********"
+        }
+        _ => value.as_str(),
+      };
+      println!("  {key} = {value}");
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(profile: Option<&str>) -> ApiArgs {
+    ApiArgs {
+      profile:     profile.map(|p| p.to_string()),
+      api_url:     None,
+      fetcher_url: None,
+      token:       None,
+    }
+  }
+
+  fn config() -> ConfigFile {
+    toml::from_str(concat!(
+      "default_profile = \"prod\"\n",
+      "[profiles.prod]\n",
+      "api_url = \"https://api.example.com/\"\n",
+      "token_id = \"01JAAVH7A8AN7QGJ3HN1TD4TAN\"\n",
+      "token_secret = \"hunter2\"\n",
+      "[profiles.local]\n",
+      "fetcher_url = \"http://localhost:4001\"\n",
+    ))
+    .unwrap()
+  }
+
+  #[test]
+  fn test_resolve_profiles() {
+    let file = config();
+
+    let prod = ApiConfig::resolve_with(&file, args(None)).unwrap();
+    assert_eq!(prod.api_url, "https://api.example.com");
+    assert_eq!(prod.fetcher_url, DEFAULT_FETCHER_URL);
+    assert_eq!(
+      prod.token.unwrap().pair(),
+      "01JAAVH7A8AN7QGJ3HN1TD4TAN:hunter2"
+    );
+
+    let local = ApiConfig::resolve_with(&file, args(Some("local"))).unwrap();
+    assert_eq!(local.api_url, DEFAULT_API_URL);
+    assert_eq!(local.fetcher_url, "http://localhost:4001");
+    assert!(local.token.is_none());
+
+    assert!(ApiConfig::resolve_with(&file, args(Some("missing"))).is_err());
+    // the default profile doesn't have to exist
+    let empty = ApiConfig::resolve_with(&ConfigFile::default(), args(None));
+    assert_eq!(empty.unwrap().api_url, DEFAULT_API_URL);
+  }
+
+  #[test]
+  fn test_overrides_take_precedence() {
+    let resolved = ApiConfig::resolve_with(&config(), ApiArgs {
+      api_url: Some("http://override".to_string()),
+      token: Some("id:secret".to_string()),
+      ..args(None)
+    })
+    .unwrap();
+    assert_eq!(resolved.api_url, "http://override");
+    assert_eq!(resolved.token.unwrap().bearer(), "Bearer id:secret");
+  }
+
+  #[test]
+  fn test_session_fallback() {
+    let mut file = config();
+    file.set_session("local", "01JB3X3T2FZKQ6N0GJ1NVRH9CY".into(), "s".into());
+    file.set_session("prod", "01JB3X3T2FZKQ6N0GJ1NVRH9CY".into(), "s".into());
+
+    // a profile without a token uses its session
+    let local = ApiConfig::resolve_with(&file, args(Some("local"))).unwrap();
+    assert_eq!(
+      local.token.unwrap().bearer(),
+      "Bearer 01JB3X3T2FZKQ6N0GJ1NVRH9CY:s"
+    );
+
+    // but a token takes precedence over it
+    let prod = ApiConfig::resolve_with(&file, args(None)).unwrap();
+    assert_eq!(
+      prod.token.unwrap().pair(),
+      "01JAAVH7A8AN7QGJ3HN1TD4TAN:hunter2"
+    );
+    // while staying available to commands that need a session
+    assert_eq!(prod.session.unwrap().pair(), "01JB3X3T2FZKQ6N0GJ1NVRH9CY:s");
+    assert_eq!(file.default_profile.as_deref(), Some("prod"));
+
+    // half a session is an error, like half a token
+    file.profiles.get_mut("local").unwrap().session_secret = None;
+    assert!(ApiConfig::resolve_with(&file, args(Some("local"))).is_err());
+  }
+
+  #[test]
+  fn test_save_is_private() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rambit").join("config.toml");
+
+    let file = config();
+    file.save(&path).unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert_eq!(ConfigFile::load(&path).unwrap(), file);
+
+    // saving over a world-readable file tightens its permissions
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))
+      .unwrap();
+    file.save(&path).unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+  }
+}
@@ -0,0 +1,189 @@
+use std::io::{self, Read};
+
+use nasty::{
+  narinfo::{NarCompression, NarInfo},
+  NixHash, StorePath,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{config::ApiConfig, FetchArgs};
+
+/// A reader that hashes and counts everything read through it.
+struct HashingReader<R> {
+  inner:  R,
+  hasher: Sha256,
+  size:   u64,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let read = self.inner.read(buf)?;
+    self.hasher.update(&buf[..read]);
+    self.size += read as u64;
+    Ok(read)
+  }
+}
+
+/// Sends a GET request to the fetcher, failing on any unsuccessful status.
+fn get(
+  client: &reqwest::blocking::Client,
+  config: &ApiConfig,
+  url: &str,
+) -> miette::Result<reqwest::blocking::Response> {
+  let mut request = client.get(url);
+  if let Some(token) = &config.token {
+    request = request.header(reqwest::header::AUTHORIZATION, token.pair());
+  }
+
+  let response = request.send().map_err(|e| {
+    tracing::error!("failed to fetch {url:?}: {}", e);
+    miette::miette!("failed to fetch")
+  })?;
+
+  let status = response.status();
+  if status == reqwest::StatusCode::NOT_FOUND {
+    tracing::error!("{url:?} was not found");
+    miette::bail!("not found in cache");
+  }
+  if !status.is_success() {
+    let body = response.text().unwrap_or_default();
+    tracing::error!("fetcher responded with {status}: {body}");
+    miette::bail!("fetch rejected with {status}");
+  }
+
+  Ok(response)
+}
+
+pub(crate) fn fetch_path(
+  FetchArgs {
+    cache,
+    path,
+    output,
+    api,
+  }: FetchArgs,
+) -> miette::Result<()> {
+  let start = std::time::Instant::now();
+  let config = ApiConfig::resolve(api)?;
+
+  let store_path = StorePath::from_absolute_path(&path)
+    .or_else(|| StorePath::try_new(path.as_str()).ok())
+    .ok_or_else(|| {
+      tracing::error!("{path:?} is not a valid store path");
+      miette::miette!("invalid store path")
+    })?;
+  let output = output.unwrap_or(store_path.name_part().into());
+  if output.symlink_metadata().is_ok() {
+    tracing::error!("{output:?} already exists");
+    miette::bail!("output already exists");
+  }
+
+  let client = reqwest::blocking::Client::builder()
+    .timeout(None)
+    .build()
+    .map_err(|e| {
+      tracing::error!("failed to build HTTP client: {}", e);
+      miette::miette!("failed to build HTTP client")
+    })?;
+  let cache_url = format!("{}/{cache}", config.fetcher_url);
+
+  // the narinfo says where the NAR is, how it's compressed, and its hash
+  let narinfo_url = format!("{cache_url}/{}.narinfo", store_path.hash_part());
+  let info = get(&client, &config, &narinfo_url)?
+    .text()
+    .map_err(|e| {
+      tracing::error!("failed to read {narinfo_url:?}: {}", e);
+      miette::miette!("failed to fetch")
+    })?
+    .parse::<NarInfo>()
+    .map_err(|e| {
+      tracing::error!("fetcher sent a malformed `.narinfo` file: {}", e);
+      miette::miette!("malformed `.narinfo` file")
+    })?;
+  if info.store_path != store_path {
+    tracing::error!(
+      "asked for {store_path}, but the cache described {}",
+      info.store_path
+    );
+    miette::bail!("cache sent the wrong path");
+  }
+
+  tracing::info!(
+    "fetching {} ({}) into {output:?}",
+    store_path.to_absolute_path(),
+    humansize::format_size(info.nar_size, humansize::DECIMAL),
+  );
+
+  let nar_url = format!("{cache_url}/{}", info.url);
+  let response = get(&client, &config, &nar_url)?;
+  let reader: Box<dyn Read> = match info.compression {
+    None | Some(NarCompression::None) => Box::new(response),
+    Some(NarCompression::Zstd) => {
+      Box::new(zstd::stream::read::Decoder::new(response).map_err(|e| {
+        tracing::error!("failed to create zstd decoder: {}", e);
+        miette::miette!("failed to create zstd decoder")
+      })?)
+    }
+    Some(compression) => {
+      tracing::error!("NAR compression {compression} is not supported");
+      miette::bail!("unsupported NAR compression");
+    }
+  };
+  let mut reader = HashingReader {
+    inner:  reader,
+    hasher: Sha256::new(),
+    size:   0,
+  };
+
+  // the decoder borrows the reader, so it's scoped to get the hash back out
+  {
+    let decoder = match nasty::nar::Decoder::new(&mut reader) {
+      Ok(d) => d,
+      Err(e) => {
+        tracing::error!("failed to create NAR decoder: {}", e);
+        miette::bail!("failed to create NAR decoder");
+      }
+    };
+    if let Err(e) = decoder.unpack(&output) {
+      tracing::error!("failed to unpack NAR archive: {}", e);
+      miette::bail!("failed to unpack NAR archive");
+    }
+  }
+
+  // anything after the end of the archive still counts towards the hash
+  if let Err(e) = io::copy(&mut reader, &mut io::sink()) {
+    tracing::error!("failed to read {nar_url:?}: {}", e);
+    miette::bail!("failed to fetch");
+  }
+
+  let nar_hash = NixHash::from_sha256_digest(reader.hasher.finalize().into());
+  if nar_hash != info.nar_hash || reader.size != info.nar_size {
+    tracing::error!(
+      "NAR does not match its `.narinfo`: expected {} ({} bytes), got {} ({} \
+       bytes)",
+      info.nar_hash,
+      info.nar_size,
+      nar_hash,
+      reader.size
+    );
+    let removed = match output.is_dir() && !output.is_symlink() {
+      true => std::fs::remove_dir_all(&output),
+      false => std::fs::remove_file(&output),
+    };
+    if let Err(e) = removed {
+      tracing::error!("failed to remove {:?}: {}", output, e);
+    }
+    miette::bail!("NAR hash mismatch");
+  }
+
+  tracing::info!(
+    "successfully fetched and verified {} in {}",
+    store_path.to_absolute_path(),
+    humantime::format_duration(start.elapsed())
+      .to_string()
+      .split(" ")
+      .next()
+      .unwrap(),
+  );
+
+  Ok(())
+}
@@ -0,0 +1,75 @@
+use std::{io::Write, path::Path};
+
+use nasty::signing::SecretKey;
+
+use crate::{KeyGenerateArgs, KeyPublicArgs};
+
+pub(crate) fn read_secret_key(path: &Path) -> miette::Result<SecretKey> {
+  let contents = match std::fs::read_to_string(path) {
+    Ok(c) => c,
+    Err(e) => {
+      tracing::error!("failed to read {:?}: {}", path, e);
+      miette::bail!("failed to read secret key file");
+    }
+  };
+
+  match contents.parse() {
+    Ok(key) => Ok(key),
+    Err(e) => {
+      tracing::error!("{path:?} does not contain a valid secret key: {e}");
+      miette::bail!("invalid secret key");
+    }
+  }
+}
+
+pub(crate) fn generate_key(
+  KeyGenerateArgs { name, output }: KeyGenerateArgs,
+) -> miette::Result<()> {
+  if name.is_empty() || name.contains(':') {
+    tracing::error!("key name {name:?} must be non-empty and contain no `:`");
+    miette::bail!("invalid key name");
+  }
+
+  let output = output.unwrap_or(format!("{name}.sec").into());
+  tracing::info!("generating signing key {name:?}, writing to {output:?}");
+
+  let key = SecretKey::generate(name);
+
+  // the secret key should only be readable by its owner
+  let mut options = std::fs::OpenOptions::new();
+  options.write(true).create_new(true);
+  #[cfg(unix)]
+  std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+  let mut target_file = match options.open(&output) {
+    Ok(f) => f,
+    Err(e) => {
+      if e.kind() == std::io::ErrorKind::AlreadyExists {
+        tracing::error!("{output:?} already exists");
+      } else {
+        tracing::error!("failed to create {:?}: {}", output, e);
+      }
+      miette::bail!("failed to create output file");
+    }
+  };
+  if let Err(e) = target_file.write_all(key.to_string().as_bytes()) {
+    tracing::error!("failed to write to {:?}: {}", output, e);
+    miette::bail!("failed to write to output file");
+  }
+
+  tracing::info!(
+    "wrote secret key to {output:?}; add the public key below to \
+     `trusted-public-keys`"
+  );
+  println!("{}", key.public_key());
+
+  Ok(())
+}
+
+pub(crate) fn print_public_key(
+  KeyPublicArgs { target }: KeyPublicArgs,
+) -> miette::Result<()> {
+  let key = read_secret_key(&target)?;
+  println!("{}", key.public_key());
+  Ok(())
+}
@@ -0,0 +1,88 @@
+use std::io::BufRead;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  config::{save_session, ApiConfig},
+  LoginArgs,
+};
+
+/// The body of a login request.
+#[derive(Serialize)]
+struct LoginRequest<'a> {
+  username: &'a str,
+  password: &'a str,
+}
+
+/// The API's answer to a login request.
+#[derive(Deserialize)]
+struct LoginResponse {
+  session_id:     String,
+  session_secret: String,
+  expires_at:     String,
+}
+
+/// Reads the password, either from the first line of `stdin` or by prompting
+/// for it without echoing.
+fn read_password(from_stdin: bool) -> miette::Result<String> {
+  let password = if from_stdin {
+    let mut line = String::new();
+    std::io::stdin()
+      .lock()
+      .read_line(&mut line)
+      .map(|_| line.trim_end_matches(['\r', '\n']).to_string())
+  } else {
+    rpassword::prompt_password("password: ")
+  };
+
+  password.map_err(|e| {
+    tracing::error!("failed to read password: {}", e);
+    miette::miette!("failed to read password")
+  })
+}
+
+pub(crate) fn login(
+  LoginArgs {
+    username,
+    password_stdin,
+    api,
+  }: LoginArgs,
+) -> miette::Result<()> {
+  let profile = api.profile.clone();
+  let config = ApiConfig::resolve(api)?;
+  let password = read_password(password_stdin)?;
+
+  let client = reqwest::blocking::Client::new();
+  let url = format!("{}/auth/login", config.api_url);
+  let response = client
+    .post(&url)
+    .json(&LoginRequest {
+      username: &username,
+      password: &password,
+    })
+    .send()
+    .map_err(|e| {
+      tracing::error!("failed to reach {url:?}: {}", e);
+      miette::miette!("failed to log in")
+    })?;
+
+  let status = response.status();
+  if !status.is_success() {
+    let body = response.text().unwrap_or_default();
+    tracing::error!("API responded with {status}: {body}");
+    miette::bail!("login rejected with {status}");
+  }
+
+  let LoginResponse {
+    session_id,
+    session_secret,
+    expires_at,
+  } = response.json().map_err(|e| {
+    tracing::error!("failed to parse login response: {}", e);
+    miette::miette!("failed to parse login response")
+  })?;
+
+  save_session(profile.as_deref(), session_id, session_secret)?;
+  tracing::info!("logged in as {username:?}, until {expires_at}");
+  Ok(())
+}
@@ -1,6 +1,16 @@
 //! CLI for the Rambit project.
 
+mod api;
+mod cache;
+mod closure;
+mod config;
+mod fetch;
+mod key;
+mod login;
 mod nar;
+mod push;
+mod serve;
+mod token;
 
 use std::path::PathBuf;
 
@@ -18,6 +28,37 @@ enum Command {
   /// Manipulate NAR archives.
   #[command(subcommand)]
   Nar(NarCommand),
+  /// Manage signing keys.
+  #[command(subcommand)]
+  Key(KeyCommand),
+  /// Upload store paths to a cache.
+  Push(PushArgs),
+  /// Download a store path from a cache and unpack it.
+  Fetch(FetchArgs),
+  /// Print the closure of store paths as JSON, in upload order.
+  Closure(ClosureArgs),
+  /// Manage connection profiles in the config file.
+  #[command(subcommand)]
+  Config(ConfigCommand),
+  /// Log in as a user, and store the session in the config file.
+  ///
+  /// Commands that talk to Rambit use the session when the profile has no
+  /// token.
+  Login(LoginArgs),
+  /// Manage your API tokens. These commands need a session from `cli login`.
+  #[command(subcommand)]
+  Token(TokenCommand),
+  /// Manage the caches in your org. These commands need a session from `cli
+  /// login`.
+  #[command(subcommand)]
+  Cache(CacheCommand),
+  /// Serve a cache over stdin and stdout with the `nix-store --serve`
+  /// protocol.
+  ///
+  /// This reads straight from the database, so it needs the same environment
+  /// as the API. It's meant to be an SSH forced command, which exposes the
+  /// cache to anyone with the key as an `ssh://` store.
+  ServeStdio(ServeStdioArgs),
 }
 
 #[derive(Subcommand, Debug)]
@@ -26,6 +67,12 @@ enum NarCommand {
   Create(NarCreateArgs),
   /// Extract an existing NAR archive.
   Extract(NarExtractArgs),
+  /// Produce a `.narinfo` file for an existing NAR archive.
+  Info(NarInfoArgs),
+  /// List the contents of a directory in a NAR archive.
+  Ls(NarLsArgs),
+  /// Print the contents of a file in a NAR archive.
+  Cat(NarCatArgs),
 }
 
 #[derive(Args, Debug)]
@@ -46,10 +93,337 @@ struct NarExtractArgs {
   output: Option<PathBuf>,
 }
 
+#[derive(Args, Debug)]
+struct NarInfoArgs {
+  /// The NAR archive to describe.
+  target:     PathBuf,
+  /// Sets the store path the archive belongs to.
+  ///
+  /// Defaults to the archive's file name without its extension, under
+  /// `/nix/store`.
+  #[arg(long)]
+  store_path: Option<String>,
+  /// Adds a store path referenced by the archive.
+  #[arg(long = "reference")]
+  references: Vec<String>,
+  /// Sets the derivation that produced the store path.
+  #[arg(long)]
+  deriver:    Option<String>,
+  /// Signs the result with the secret key in the given file.
+  #[arg(long)]
+  secret_key: Option<PathBuf>,
+  /// Sets the path of the output file. Defaults to `stdout`.
+  #[arg(short, long)]
+  output:     Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct NarLsArgs {
+  /// The NAR archive to read.
+  target:  PathBuf,
+  /// The path inside the archive to list. Defaults to the root.
+  #[arg(default_value = "")]
+  subpath: String,
+  /// Shows the type and size of each entry.
+  #[arg(short, long)]
+  long:    bool,
+}
+
+#[derive(Args, Debug)]
+struct NarCatArgs {
+  /// The NAR archive to read.
+  target: PathBuf,
+  /// The path of the file inside the archive.
+  file:   String,
+}
+
+#[derive(Subcommand, Debug)]
+enum KeyCommand {
+  /// Generate a new signing key.
+  Generate(KeyGenerateArgs),
+  /// Print the public key for a secret key.
+  Public(KeyPublicArgs),
+}
+
+#[derive(Args, Debug)]
+struct KeyGenerateArgs {
+  /// The name of the key, e.g. `cache.example.com-1`.
+  name:   String,
+  /// Sets the path of the secret key file. Defaults to `<name>.sec`.
+  #[arg(short, long)]
+  output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct KeyPublicArgs {
+  /// The secret key file.
+  target: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct PushArgs {
+  /// The name of the cache to push to.
+  cache: String,
+  /// The store paths to push. Symlinks into the store, like `./result`, are
+  /// followed.
+  #[arg(required = true)]
+  paths: Vec<PathBuf>,
+  #[command(flatten)]
+  api:   ApiArgs,
+}
+
+#[derive(Args, Debug)]
+struct FetchArgs {
+  /// The name of the cache to fetch from.
+  cache:  String,
+  /// The store path to fetch, with or without the store directory.
+  path:   String,
+  /// Sets the path of the output directory. Defaults to the name of the store
+  /// path, without its hash.
+  #[arg(short, long)]
+  output: Option<PathBuf>,
+  #[command(flatten)]
+  api:    ApiArgs,
+}
+
+#[derive(Args, Debug)]
+struct LoginArgs {
+  /// The username to log in as.
+  username:       String,
+  /// Reads the password from the first line of `stdin`, instead of prompting
+  /// for it.
+  #[arg(long)]
+  password_stdin: bool,
+  #[command(flatten)]
+  api:            ApiArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum TokenCommand {
+  /// Create a token, and print it in `<id>:<secret>` form.
+  Create(TokenCreateArgs),
+  /// List your tokens, including revoked and expired ones.
+  List(TokenListArgs),
+  /// Revoke one of your tokens.
+  Revoke(TokenRevokeArgs),
+}
+
+#[derive(Args, Debug)]
+struct TokenCreateArgs {
+  /// The token's nickname, made of lowercase letters, digits and dashes.
+  nickname:   String,
+  /// Grants read access to the cache with the given ID.
+  #[arg(long, value_name = "CACHE_ID")]
+  read:       Vec<String>,
+  /// Grants write access to the cache with the given ID.
+  #[arg(long, value_name = "CACHE_ID")]
+  write:      Vec<String>,
+  /// Makes the token expire after the given duration, e.g. `30days`.
+  /// Defaults to never.
+  #[arg(long)]
+  expires_in: Option<humantime::Duration>,
+  #[command(flatten)]
+  api:        ApiArgs,
+}
+
+#[derive(Args, Debug)]
+struct TokenListArgs {
+  #[command(flatten)]
+  api: ApiArgs,
+}
+
+#[derive(Args, Debug)]
+struct TokenRevokeArgs {
+  /// The ID of the token to revoke.
+  id:  String,
+  #[command(flatten)]
+  api: ApiArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+  /// Create a cache, and get read and write access to it.
+  Create(CacheCreateArgs),
+  /// List the caches in your org.
+  List(CacheListArgs),
+  /// Print a cache's settings.
+  Show(CacheShowArgs),
+  /// Change a cache's name, visibility or backing store.
+  Update(CacheUpdateArgs),
+  /// Delete an empty cache.
+  Delete(CacheDeleteArgs),
+}
+
+/// Who can read from a cache.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CacheVisibility {
+  /// Anyone can read from the cache.
+  Public,
+  /// Only tokens and users with read access can read from the cache.
+  Private,
+}
+
+#[derive(Args, Debug)]
+struct CacheCreateArgs {
+  /// The name of the cache, made of lowercase letters, digits and dashes.
+  name:       String,
+  /// The ID of the store to keep the cache's paths in. It has to belong to
+  /// your org.
+  #[arg(long, value_name = "STORE_ID")]
+  store:      String,
+  /// Sets who can read from the cache.
+  #[arg(long, default_value = "private")]
+  visibility: CacheVisibility,
+  #[command(flatten)]
+  api:        ApiArgs,
+}
+
+#[derive(Args, Debug)]
+struct CacheListArgs {
+  #[command(flatten)]
+  api: ApiArgs,
+}
+
+#[derive(Args, Debug)]
+struct CacheShowArgs {
+  /// The name of the cache.
+  name: String,
+  #[command(flatten)]
+  api:  ApiArgs,
+}
+
+#[derive(Args, Debug)]
+struct CacheUpdateArgs {
+  /// The name of the cache.
+  name:       String,
+  /// Renames the cache. The name is made of lowercase letters, digits and
+  /// dashes.
+  #[arg(long = "name")]
+  new_name:   Option<String>,
+  /// Sets who can read from the cache.
+  #[arg(long)]
+  visibility: Option<CacheVisibility>,
+  /// Moves the cache to the store with the given ID. Only empty caches can be
+  /// moved.
+  #[arg(long, value_name = "STORE_ID")]
+  store:      Option<String>,
+  #[command(flatten)]
+  api:        ApiArgs,
+}
+
+#[derive(Args, Debug)]
+struct CacheDeleteArgs {
+  /// The name of the cache. Only empty caches can be deleted.
+  name: String,
+  #[command(flatten)]
+  api:  ApiArgs,
+}
+
+/// Connection settings shared by commands that talk to Rambit. These override
+/// the config file.
+#[derive(Args, Debug)]
+struct ApiArgs {
+  /// Selects a profile from the config file.
+  #[arg(long, env = "RAMBIT_PROFILE")]
+  profile:     Option<String>,
+  /// Sets the URL of the Rambit API.
+  #[arg(long, env = "RAMBIT_API_URL")]
+  api_url:     Option<String>,
+  /// Sets the URL of the Rambit fetcher.
+  #[arg(long, env = "RAMBIT_FETCHER_URL")]
+  fetcher_url: Option<String>,
+  /// Sets the API token to authenticate with, in `<id>:<secret>` form.
+  /// Overrides any token or session in the profile.
+  #[arg(long, env = "RAMBIT_TOKEN", hide_env_values = true)]
+  token:       Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ClosureArgs {
+  /// The store paths to compute the closure of. Symlinks into the store, like
+  /// `./result`, are followed.
+  #[arg(required = true)]
+  paths: Vec<PathBuf>,
+  /// Sets the Nix database to read. Defaults to
+  /// `/nix/var/nix/db/db.sqlite`.
+  #[arg(long)]
+  db:    Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ServeStdioArgs {
+  /// The name of the cache to serve.
+  cache: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+  /// Set a value in a profile, creating the profile if needed.
+  Set(ConfigSetArgs),
+  /// Print a value from a profile.
+  Get(ConfigGetArgs),
+  /// List all profiles and their values.
+  List(ConfigListArgs),
+}
+
+/// A value stored in a profile.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ConfigKey {
+  /// The URL of the Rambit API.
+  ApiUrl,
+  /// The URL of the Rambit fetcher.
+  FetcherUrl,
+  /// The ID of the token to authenticate with.
+  TokenId,
+  /// The secret of the token to authenticate with.
+  TokenSecret,
+  /// The ID of the session from `cli login`.
+  SessionId,
+  /// The secret of the session from `cli login`.
+  SessionSecret,
+}
+
+impl std::fmt::Display for ConfigKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    use clap::ValueEnum;
+    let value = self.to_possible_value().expect("no skipped variants");
+    f.write_str(value.get_name())
+  }
+}
+
+#[derive(Args, Debug)]
+struct ConfigSetArgs {
+  /// The key to set.
+  key:     ConfigKey,
+  /// The value to set it to.
+  value:   String,
+  /// Selects the profile to modify.
+  #[arg(long, env = "RAMBIT_PROFILE")]
+  profile: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ConfigGetArgs {
+  /// The key to get.
+  key:     ConfigKey,
+  /// Selects the profile to read.
+  #[arg(long, env = "RAMBIT_PROFILE")]
+  profile: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ConfigListArgs {
+  /// Prints token and session secrets instead of hiding them.
+  #[arg(long)]
+  show_secrets: bool,
+}
+
 fn main() {
   let filter = tracing_subscriber::EnvFilter::try_from_default_env()
     .unwrap_or(tracing_subscriber::EnvFilter::new("info"));
+  // logs go to stderr, since stdout is the protocol for `serve-stdio`
   tracing_subscriber::fmt()
+    .with_writer(std::io::stderr)
     .without_time()
     .with_env_filter(filter)
     .init();
@@ -69,5 +443,131 @@ fn main() {
         std::process::exit(1);
       }
     }
+    Command::Nar(NarCommand::Info(args)) => {
+      let val = crate::nar::describe_nar_archive(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Nar(NarCommand::Ls(args)) => {
+      let val = crate::nar::list_nar_archive(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Nar(NarCommand::Cat(args)) => {
+      let val = crate::nar::cat_nar_file(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Key(KeyCommand::Generate(args)) => {
+      let val = crate::key::generate_key(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Key(KeyCommand::Public(args)) => {
+      let val = crate::key::print_public_key(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Push(args) => {
+      let val = crate::push::push_paths(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Fetch(args) => {
+      let val = crate::fetch::fetch_path(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Config(ConfigCommand::Set(args)) => {
+      let val = crate::config::set_config_value(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Config(ConfigCommand::Get(args)) => {
+      let val = crate::config::get_config_value(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Config(ConfigCommand::List(args)) => {
+      let val = crate::config::list_config(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Login(args) => {
+      let val = crate::login::login(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Token(TokenCommand::Create(args)) => {
+      let val = crate::token::create_token(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Token(TokenCommand::List(args)) => {
+      let val = crate::token::list_tokens(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Token(TokenCommand::Revoke(args)) => {
+      let val = crate::token::revoke_token(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Cache(CacheCommand::Create(args)) => {
+      let val = crate::cache::create_cache(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Cache(CacheCommand::List(args)) => {
+      let val = crate::cache::list_caches(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Cache(CacheCommand::Show(args)) => {
+      let val = crate::cache::show_cache(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Cache(CacheCommand::Update(args)) => {
+      let val = crate::cache::update_cache(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Cache(CacheCommand::Delete(args)) => {
+      let val = crate::cache::delete_cache(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::Closure(args) => {
+      let val = crate::closure::compute_closure(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
+    Command::ServeStdio(args) => {
+      let val = crate::serve::serve_stdio(args);
+      if val.is_err() {
+        std::process::exit(1);
+      }
+    }
   }
 }
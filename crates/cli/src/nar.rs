@@ -1,4 +1,17 @@
-use crate::{NarCreateArgs, NarExtractArgs};
+use std::path::Path;
+
+use nasty::{
+  listing::Node,
+  nar_index::NarIndex,
+  narinfo::{NarCompression, NarInfo},
+  NixHash, StorePath,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+  key::read_secret_key, NarCatArgs, NarCreateArgs, NarExtractArgs, NarInfoArgs,
+  NarLsArgs,
+};
 
 pub(crate) fn create_nar_archive(
   NarCreateArgs { target, output }: NarCreateArgs,
@@ -124,3 +137,251 @@ pub(crate) fn extract_nar_archive(
 
   Ok(())
 }
+
+/// Strips the extension from the file name of a NAR, leaving the store path
+/// it's named after. Store path names can contain dots, so only known NAR
+/// extensions are removed.
+fn strip_nar_extension(file_name: &str) -> &str {
+  [".nar.zst", ".nar.xz", ".nar"]
+    .iter()
+    .find_map(|ext| file_name.strip_suffix(ext))
+    .unwrap_or(file_name)
+}
+
+pub(crate) fn describe_nar_archive(
+  NarInfoArgs {
+    target,
+    store_path,
+    references,
+    deriver,
+    secret_key,
+    output,
+  }: NarInfoArgs,
+) -> miette::Result<()> {
+  tracing::info!("describing NAR archive at {target:?}");
+
+  let open_target = || match std::fs::File::open(&target) {
+    Ok(f) => Ok(std::io::BufReader::new(f)),
+    Err(e) => {
+      tracing::error!("failed to open {:?}: {}", target, e);
+      miette::bail!("failed to open target file");
+    }
+  };
+
+  // make sure the whole file is a valid NAR before describing it
+  let mut target_file = open_target()?;
+  let decoder = match nasty::nar::Decoder::new(&mut target_file) {
+    Ok(d) => d,
+    Err(e) => {
+      tracing::error!("failed to create NAR decoder: {}", e);
+      miette::bail!("failed to create NAR decoder");
+    }
+  };
+  let entries = match decoder.entries() {
+    Ok(entries) => entries,
+    Err(e) => {
+      tracing::error!("failed to read NAR archive: {}", e);
+      miette::bail!("failed to read NAR archive");
+    }
+  };
+  for entry in entries {
+    if let Err(e) = entry {
+      tracing::error!("failed to read NAR archive: {}", e);
+      miette::bail!("failed to read NAR archive");
+    }
+  }
+
+  let mut hasher = Sha256::new();
+  let nar_size = match std::io::copy(&mut open_target()?, &mut hasher) {
+    Ok(size) => size,
+    Err(e) => {
+      tracing::error!("failed to read {:?}: {}", target, e);
+      miette::bail!("failed to read target file");
+    }
+  };
+  let nar_hash = NixHash::from_sha256_digest(hasher.finalize().into());
+
+  // store paths may be given with or without the store directory
+  let parse_store_path = |path: &str| {
+    StorePath::from_absolute_path(path)
+      .or_else(|| StorePath::try_new(path).ok())
+      .ok_or_else(|| {
+        tracing::error!("{path:?} is not a valid store path");
+        miette::miette!("invalid store path")
+      })
+  };
+
+  let store_path = match store_path {
+    Some(store_path) => parse_store_path(&store_path)?,
+    None => {
+      let stem = target
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(strip_nar_extension)
+        .unwrap_or_default();
+      parse_store_path(stem).inspect_err(|_| {
+        tracing::info!("use `--store-path` to set the store path explicitly");
+      })?
+    }
+  };
+  let references = references
+    .iter()
+    .map(|r| parse_store_path(r))
+    .collect::<miette::Result<Vec<_>>>()?;
+  let deriver = deriver.as_deref().map(parse_store_path).transpose()?;
+
+  let secret_key = secret_key.map(|path| read_secret_key(&path)).transpose()?;
+
+  let mut info = NarInfo {
+    store_path,
+    url: format!("nar/{}.nar", nasty::nix32::encode(nar_hash.digest())),
+    compression: Some(NarCompression::None),
+    file_hash: Some(nar_hash),
+    file_size: Some(nar_size),
+    nar_hash,
+    nar_size,
+    references,
+    deriver,
+    sigs: Vec::new(),
+    ca: None,
+  };
+
+  if let Some(secret_key) = secret_key {
+    secret_key.sign_in_place(&mut info);
+  }
+
+  let rendered = info.to_string();
+  match output {
+    Some(output) => {
+      if let Err(e) = std::fs::write(&output, rendered) {
+        tracing::error!("failed to write to {:?}: {}", output, e);
+        miette::bail!("failed to write to output file");
+      }
+      tracing::info!("wrote `.narinfo` file to {output:?}");
+    }
+    None => print!("{rendered}"),
+  }
+
+  Ok(())
+}
+
+/// Indexes the NAR archive at `target` in one pass.
+fn index_nar_archive(target: &Path) -> miette::Result<NarIndex> {
+  let target_file = match std::fs::File::open(target) {
+    Ok(f) => f,
+    Err(e) => {
+      tracing::error!("failed to open {:?}: {}", target, e);
+      miette::bail!("failed to open target file");
+    }
+  };
+
+  match NarIndex::from_reader(target_file) {
+    Ok(index) => Ok(index),
+    Err(e) => {
+      tracing::error!("failed to read NAR archive: {}", e);
+      miette::bail!("failed to read NAR archive");
+    }
+  }
+}
+
+pub(crate) fn list_nar_archive(
+  NarLsArgs {
+    target,
+    subpath,
+    long,
+  }: NarLsArgs,
+) -> miette::Result<()> {
+  let index = index_nar_archive(&target)?;
+
+  let node = match index.node(&subpath) {
+    Ok(node) => node,
+    Err(e) => {
+      tracing::error!("{e}");
+      miette::bail!("path not found in NAR archive");
+    }
+  };
+
+  // like `ls`, listing a file just shows the file
+  let name = subpath.rsplit('/').find(|s| !s.is_empty()).unwrap_or(".");
+  let entries = match node {
+    Node::Directory { entries } => {
+      entries.iter().map(|(n, e)| (n.as_str(), e)).collect()
+    }
+    node => vec![(name, node)],
+  };
+
+  for (name, node) in entries {
+    if !long {
+      println!("{name}");
+      continue;
+    }
+    match node {
+      Node::Regular {
+        size, executable, ..
+      } => {
+        let mode = if *executable {
+          "-r-xr-xr-x"
+        } else {
+          "-r--r--r--"
+        };
+        println!("{mode} {size:>12} {name}");
+      }
+      Node::Symlink { target } => {
+        println!("lrwxrwxrwx {:>12} {name} -> {target}", 0);
+      }
+      Node::Directory { .. } => println!("dr-xr-xr-x {:>12} {name}", 0),
+    }
+  }
+
+  Ok(())
+}
+
+pub(crate) fn cat_nar_file(
+  NarCatArgs { target, file }: NarCatArgs,
+) -> miette::Result<()> {
+  let index = index_nar_archive(&target)?;
+
+  let range = match index.file(&file) {
+    Ok(range) => range,
+    Err(e) => {
+      tracing::error!("{e}");
+      miette::bail!("file not found in NAR archive");
+    }
+  };
+
+  let target_file = match std::fs::File::open(&target) {
+    Ok(f) => f,
+    Err(e) => {
+      tracing::error!("failed to open {:?}: {}", target, e);
+      miette::bail!("failed to open target file");
+    }
+  };
+
+  let copied = range.read_from(target_file).and_then(|mut contents| {
+    std::io::copy(&mut contents, &mut std::io::stdout())
+  });
+  match copied {
+    Ok(size) if size == range.size => Ok(()),
+    Ok(_) => {
+      tracing::error!("{target:?} ended before the end of {file:?}");
+      miette::bail!("NAR archive is truncated");
+    }
+    Err(e) => {
+      tracing::error!("failed to read {:?}: {}", file, e);
+      miette::bail!("failed to read file from NAR archive");
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_strip_nar_extension() {
+    let name = "xqvw9f9hxx6bm3mrnb5qyqm4l8mfv1sp-hello-2.12.1";
+    assert_eq!(strip_nar_extension(&format!("{name}.nar")), name);
+    assert_eq!(strip_nar_extension(&format!("{name}.nar.zst")), name);
+    assert_eq!(strip_nar_extension(name), name);
+  }
+}
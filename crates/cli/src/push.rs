@@ -0,0 +1,239 @@
+use std::{collections::HashSet, os::unix::net::UnixStream, path::Path};
+
+use nasty::{daemon::DaemonClient, StorePath};
+use serde::Deserialize;
+
+use crate::{config::ApiConfig, PushArgs};
+
+/// Resolves a path, following symlinks like `./result`, into a store path.
+fn resolve_store_path(path: &Path) -> miette::Result<StorePath> {
+  let canonical = std::fs::canonicalize(path).map_err(|e| {
+    tracing::error!("failed to resolve {:?}: {}", path, e);
+    miette::miette!("failed to resolve path")
+  })?;
+  canonical
+    .to_str()
+    .and_then(StorePath::from_absolute_path)
+    .ok_or_else(|| {
+      tracing::error!("{canonical:?} is not a top-level store path");
+      miette::miette!("not a store path")
+    })
+}
+
+/// The API's answer to a missing paths query.
+#[derive(Debug, Deserialize)]
+struct MissingPaths {
+  /// The paths that need to be pushed.
+  missing:            HashSet<StorePath>,
+  /// The paths that the cache's upstream caches already have.
+  available_upstream: HashSet<StorePath>,
+}
+
+/// The most paths the API answers for in one missing paths query.
+const MISSING_PATHS_QUERY_SIZE: usize = 1000;
+
+/// Asks the API which of the given store paths are missing from the cache.
+///
+/// The paths are sent in batches, since the API caps how many it takes at
+/// once.
+fn query_missing_paths(
+  client: &reqwest::blocking::Client,
+  config: &ApiConfig,
+  cache: &str,
+  store_paths: &[StorePath],
+) -> miette::Result<MissingPaths> {
+  let url = format!("{}/missing-paths/{cache}", config.api_url);
+  let mut missing = MissingPaths {
+    missing:            HashSet::new(),
+    available_upstream: HashSet::new(),
+  };
+
+  for batch in store_paths.chunks(MISSING_PATHS_QUERY_SIZE) {
+    let mut request = client.post(&url).json(batch);
+    if let Some(token) = &config.token {
+      request = request.header(reqwest::header::AUTHORIZATION, token.bearer());
+    }
+
+    let response = request.send().map_err(|e| {
+      tracing::error!("failed to query {url:?}: {}", e);
+      miette::miette!("failed to query missing paths")
+    })?;
+
+    let status = response.status();
+    if !status.is_success() {
+      let body = response.text().unwrap_or_default();
+      tracing::error!("API responded with {status}: {body}");
+      miette::bail!("missing paths query rejected with {status}");
+    }
+
+    let batch_missing = response.json::<MissingPaths>().map_err(|e| {
+      tracing::error!("failed to parse missing paths response: {}", e);
+      miette::miette!("failed to parse missing paths response")
+    })?;
+    missing.missing.extend(batch_missing.missing);
+    missing
+      .available_upstream
+      .extend(batch_missing.available_upstream);
+  }
+
+  Ok(missing)
+}
+
+/// Encodes a single store path and streams it to the API.
+///
+/// The Nix daemon is asked first whether the path is valid, so that a path
+/// that's still being built or was partially deleted isn't uploaded.
+fn push_path(
+  client: &reqwest::blocking::Client,
+  config: &ApiConfig,
+  cache: &str,
+  daemon: &mut DaemonClient<UnixStream, UnixStream>,
+  store_path: &StorePath,
+) -> miette::Result<()> {
+  let valid = daemon.is_valid_path(store_path).map_err(|e| {
+    tracing::error!("failed to query the Nix daemon: {}", e);
+    miette::miette!("failed to query path validity")
+  })?;
+  if !valid {
+    tracing::error!("{store_path} is not valid in the Nix store");
+    miette::bail!("path not valid");
+  }
+
+  let encoder = nasty::nar::Encoder::new(store_path.to_absolute_path())
+    .map_err(|e| {
+      tracing::error!("failed to create NAR encoder: {}", e);
+      miette::miette!("failed to create NAR encoder")
+    })?;
+
+  let url = format!("{}/naive-upload/{cache}/{store_path}", config.api_url);
+  let mut request = client
+    .post(&url)
+    .body(reqwest::blocking::Body::new(encoder));
+  if let Some(token) = &config.token {
+    request = request.header(reqwest::header::AUTHORIZATION, token.bearer());
+  }
+
+  let response = request.send().map_err(|e| {
+    tracing::error!("failed to upload to {url:?}: {}", e);
+    miette::miette!("failed to upload")
+  })?;
+
+  let status = response.status();
+  if !status.is_success() {
+    let body = response.text().unwrap_or_default();
+    tracing::error!("API responded with {status}: {body}");
+    miette::bail!("upload rejected with {status}");
+  }
+
+  Ok(())
+}
+
+pub(crate) fn push_paths(
+  PushArgs { cache, paths, api }: PushArgs,
+) -> miette::Result<()> {
+  let config = ApiConfig::resolve(api)?;
+  if config.token.is_none() {
+    tracing::warn!(
+      "no API token configured; run `cli login`, set `RAMBIT_TOKEN`, or run \
+       `cli config set token-id` and `cli config set token-secret`"
+    );
+  }
+
+  let client = reqwest::blocking::Client::builder()
+    .timeout(None)
+    .build()
+    .map_err(|e| {
+      tracing::error!("failed to build HTTP client: {}", e);
+      miette::miette!("failed to build HTTP client")
+    })?;
+
+  tracing::info!(
+    "pushing {} path(s) to cache {cache:?} at {}",
+    paths.len(),
+    config.api_url
+  );
+
+  // resolve everything up front, so the API can be asked about all of it
+  let mut failures = 0;
+  let mut store_paths = Vec::with_capacity(paths.len());
+  for path in &paths {
+    match resolve_store_path(path) {
+      Ok(store_path) => store_paths.push(store_path),
+      Err(e) => {
+        failures += 1;
+        println!("failed {}: {e}", path.display());
+      }
+    }
+  }
+
+  let missing =
+    match query_missing_paths(&client, &config, &cache, &store_paths) {
+      Ok(missing) => missing,
+      Err(e) => {
+        tracing::warn!(
+          "couldn't check which paths are already in the cache, pushing all \
+           of them: {e}"
+        );
+        MissingPaths {
+          missing:            store_paths.iter().cloned().collect(),
+          available_upstream: HashSet::new(),
+        }
+      }
+    };
+
+  // only connected once there's something to push, so that a push with
+  // nothing missing works without a Nix daemon
+  let mut daemon = None;
+
+  for store_path in &store_paths {
+    if missing.available_upstream.contains(store_path) {
+      println!(
+        "skipped {} (available upstream)",
+        store_path.to_absolute_path()
+      );
+      continue;
+    }
+    if !missing.missing.contains(store_path) {
+      println!(
+        "skipped {} (already in cache)",
+        store_path.to_absolute_path()
+      );
+      continue;
+    }
+
+    let daemon = match &mut daemon {
+      Some(daemon) => daemon,
+      None => daemon.insert(DaemonClient::connect_local().map_err(|e| {
+        tracing::error!("failed to connect to the Nix daemon: {}", e);
+        miette::miette!("failed to connect to the Nix daemon")
+      })?),
+    };
+
+    let start = std::time::Instant::now();
+    match push_path(&client, &config, &cache, daemon, store_path) {
+      Ok(()) => {
+        println!(
+          "pushed {} in {}",
+          store_path.to_absolute_path(),
+          humantime::format_duration(start.elapsed())
+            .to_string()
+            .split(" ")
+            .next()
+            .unwrap(),
+        );
+      }
+      Err(e) => {
+        failures += 1;
+        println!("failed {}: {e}", store_path.to_absolute_path());
+      }
+    }
+  }
+
+  if failures > 0 {
+    tracing::error!("{failures} of {} path(s) failed to push", paths.len());
+    miette::bail!("some paths failed to push");
+  }
+
+  tracing::info!("successfully pushed {} path(s)", paths.len());
+  Ok(())
+}
@@ -0,0 +1,276 @@
+use std::{io::Write, sync::Arc};
+
+use nasty::{
+  narinfo::NarInfo,
+  serve::{PathInfo, ServeError, ServeServer, ServeStore},
+  signing::SecretKey,
+  StorePath,
+};
+use prime_domain::{
+  models::{self, StrictSlug},
+  DynPrimeDomainService,
+};
+use tokio::io::AsyncReadExt;
+
+use crate::ServeStdioArgs;
+
+/// The size of the chunks NARs are copied to the client in.
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Serves a cache straight from the prime-domain service.
+///
+/// The serve protocol is synchronous, so every call blocks on the runtime.
+struct CacheServeStore {
+  runtime:     tokio::runtime::Runtime,
+  service:     DynPrimeDomainService,
+  cache:       models::Cache,
+  signing_key: Option<SecretKey>,
+}
+
+fn store_error(
+  e: impl std::error::Error + Send + Sync + 'static,
+) -> ServeError {
+  ServeError::Store(Box::new(e))
+}
+
+impl ServeStore for CacheServeStore {
+  fn query_valid_paths(
+    &mut self,
+    paths: &[StorePath],
+  ) -> Result<Vec<StorePath>, ServeError> {
+    let entries = self
+      .runtime
+      .block_on(
+        self
+          .service
+          .find_entries_by_id_and_paths(self.cache.id, paths.to_vec()),
+      )
+      .map_err(store_error)?;
+
+    Ok(
+      paths
+        .iter()
+        .zip(entries)
+        .filter(|(_, entry)| entry.as_ref().is_some_and(|e| !e.is_legacy()))
+        .map(|(path, _)| path.clone())
+        .collect(),
+    )
+  }
+
+  fn query_path_info(
+    &mut self,
+    path: &StorePath,
+  ) -> Result<Option<PathInfo>, ServeError> {
+    let Some(entry) = self
+      .runtime
+      .block_on(
+        self
+          .service
+          .find_entry_by_id_and_path(self.cache.id, path.clone()),
+      )
+      .map_err(store_error)?
+    else {
+      return Ok(None);
+    };
+    // entries from before hashes were recorded can't be described until
+    // they're migrated
+    let Some(nar_hash) = entry.nar_hash else {
+      return Ok(None);
+    };
+
+    let nar_size = match &entry.c_status {
+      models::CompressionStatus::Compressed {
+        uncompressed_size, ..
+      } => *uncompressed_size.as_ref(),
+      models::CompressionStatus::Uncompressed { size } => *size.as_ref(),
+    };
+    let known_references = entry.meta.is_some();
+    let meta = entry.meta.unwrap_or_default();
+    let mut info = PathInfo {
+      path: entry.path,
+      deriver: meta.deriver,
+      nar_hash,
+      references: meta.references,
+      nar_size,
+      sigs: meta.sigs,
+      ca: meta.ca,
+    };
+
+    // sign like the narinfo served over HTTP would be, which it isn't if the
+    // references are unknown
+    if let (Some(key), true) = (&self.signing_key, known_references) {
+      let narinfo = NarInfo {
+        store_path:  info.path.clone(),
+        url:         String::new(),
+        compression: None,
+        file_hash:   None,
+        file_size:   None,
+        nar_hash:    info.nar_hash,
+        nar_size:    info.nar_size,
+        references:  info.references.clone(),
+        deriver:     None,
+        sigs:        Vec::new(),
+        ca:          None,
+      };
+      let sig = key.sign(&narinfo);
+      if !info.sigs.contains(&sig) {
+        info.sigs.push(sig);
+      }
+    }
+
+    Ok(Some(info))
+  }
+
+  fn dump_path(
+    &mut self,
+    path: &StorePath,
+    writer: &mut dyn Write,
+  ) -> Result<(), ServeError> {
+    self.runtime.block_on(async {
+      let entry = self
+        .service
+        .find_entry_by_id_and_path(self.cache.id, path.clone())
+        .await
+        .map_err(store_error)?
+        .ok_or_else(|| ServeError::PathNotValid(path.clone()))?;
+
+      let mut reader = self
+        .service
+        .read_from_entry(entry.id)
+        .await
+        .map_err(store_error)?
+        .adapt_to_no_comp()
+        .to_async_buf_read();
+
+      let mut buf = vec![0; COPY_CHUNK_SIZE];
+      loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+          break;
+        }
+        writer.write_all(&buf[..n])?;
+      }
+      Ok(())
+    })
+  }
+}
+
+/// Builds the prime-domain service from the same environment as the API.
+///
+/// Temp storage is never used when serving, so it's mocked.
+async fn build_prime_domain_service() -> miette::Result<DynPrimeDomainService> {
+  let tikv_store =
+    prime_domain::repos::db::kv::tikv::TikvClient::new_from_env().await?;
+  let kv_db_adapter =
+    Arc::new(prime_domain::repos::db::KvDatabaseAdapter::new(tikv_store));
+  let cache_repo =
+    prime_domain::repos::CacheRepositoryCanonical::new(kv_db_adapter.clone());
+  let debuginfo_repo = prime_domain::repos::DebugInfoRepositoryCanonical::new(
+    kv_db_adapter.clone(),
+  );
+  let entry_repo =
+    prime_domain::repos::EntryRepositoryCanonical::new(kv_db_adapter.clone());
+  let realisation_repo =
+    prime_domain::repos::RealisationRepositoryCanonical::new(
+      kv_db_adapter.clone(),
+    );
+  let signing_key_repo =
+    prime_domain::repos::SigningKeyRepositoryCanonical::new(
+      kv_db_adapter.clone(),
+    );
+  let store_repo =
+    prime_domain::repos::StoreRepositoryCanonical::new(kv_db_adapter.clone());
+  let token_repo =
+    prime_domain::repos::TokenRepositoryCanonical::new(kv_db_adapter.clone());
+  let session_repo =
+    prime_domain::repos::SessionRepositoryCanonical::new(kv_db_adapter.clone());
+  let user_repo =
+    prime_domain::repos::UserRepositoryCanonical::new(kv_db_adapter.clone());
+  let temp_storage_repo = prime_domain::repos::TempStorageRepositoryMock::new(
+    std::path::PathBuf::from("/tmp/rambit-temp-storage"),
+  );
+  let user_storage_repo =
+    prime_domain::repos::UserStorageRepositoryCanonical::new();
+  let token_hasher = prime_domain::TokenSecretHasher::new_from_env()?;
+
+  let prime_domain_service = prime_domain::PrimeDomainServiceCanonical::new(
+    cache_repo,
+    debuginfo_repo,
+    entry_repo,
+    realisation_repo,
+    session_repo,
+    signing_key_repo,
+    store_repo,
+    token_repo,
+    temp_storage_repo,
+    user_repo,
+    user_storage_repo,
+    token_hasher,
+  );
+  Ok(Arc::new(Box::new(prime_domain_service)))
+}
+
+pub(crate) fn serve_stdio(
+  ServeStdioArgs { cache }: ServeStdioArgs,
+) -> miette::Result<()> {
+  let runtime = tokio::runtime::Builder::new_multi_thread()
+    .enable_all()
+    .build()
+    .map_err(|e| {
+      tracing::error!("failed to start the async runtime: {e}");
+      miette::miette!("failed to start the async runtime")
+    })?;
+
+  let service =
+    runtime
+      .block_on(build_prime_domain_service())
+      .map_err(|e| {
+        tracing::error!("failed to connect to the database: {e:?}");
+        miette::miette!("failed to connect to the database")
+      })?;
+
+  let cache = runtime
+    .block_on(service.find_cache_by_name(StrictSlug::new(cache.clone())))
+    .map_err(|e| {
+      tracing::error!("failed to look up cache {cache:?}: {e}");
+      miette::miette!("failed to look up cache")
+    })?
+    .ok_or_else(|| {
+      tracing::error!("cache {cache:?} does not exist");
+      miette::miette!("cache does not exist")
+    })?;
+
+  let signing_key = runtime
+    .block_on(service.find_signing_key_by_cache_id(cache.id))
+    .map_err(|e| {
+      tracing::error!("failed to look up the cache's signing key: {e}");
+      miette::miette!("failed to look up signing key")
+    })?
+    .map(|key| key.secret.as_ref().parse::<SecretKey>())
+    .transpose()
+    .map_err(|e| {
+      tracing::error!("the cache's signing key is malformed: {e}");
+      miette::miette!("malformed signing key")
+    })?;
+
+  tracing::info!("serving cache {:?} over stdio", cache.name.to_string());
+  let store = CacheServeStore {
+    runtime,
+    service,
+    cache,
+    signing_key,
+  };
+  let server = ServeServer::handshake(
+    store,
+    std::io::stdin().lock(),
+    std::io::stdout().lock(),
+  )
+  .map_err(|e| {
+    tracing::error!("handshake failed: {e}");
+    miette::miette!("handshake failed")
+  })?;
+  server.run().map_err(|e| {
+    tracing::error!("failed to serve: {e}");
+    miette::miette!("failed to serve")
+  })
+}
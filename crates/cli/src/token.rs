@@ -0,0 +1,158 @@
+use std::{str::FromStr, time::SystemTime};
+
+use prime_domain::models::{
+  CachePermissionType, CacheRecordId, Permission, PermissionSet,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  api::SessionClient, config::ApiConfig, TokenCreateArgs, TokenListArgs,
+  TokenRevokeArgs,
+};
+
+/// The body of a token create request.
+#[derive(Serialize)]
+struct CreateTokenRequest {
+  nickname:   String,
+  perms:      PermissionSet,
+  expires_at: Option<String>,
+}
+
+/// A token, as the API describes it.
+#[derive(Deserialize)]
+struct TokenInfo {
+  id:         String,
+  nickname:   String,
+  perms:      PermissionSet,
+  expires_at: Option<String>,
+  revoked_at: Option<String>,
+}
+
+impl TokenInfo {
+  /// Describes whether the token can still be used.
+  fn status(&self) -> &'static str {
+    if self.revoked_at.is_some() {
+      return "revoked";
+    }
+    let expired = self
+      .expires_at
+      .as_deref()
+      .and_then(|t| humantime::parse_rfc3339_weak(t).ok())
+      .is_some_and(|t| t <= SystemTime::now());
+    if expired {
+      "expired"
+    } else {
+      "active"
+    }
+  }
+
+  /// Lists the token's permissions, in a stable order.
+  fn perms(&self) -> String {
+    let mut perms = self
+      .perms
+      .0
+      .iter()
+      .map(ToString::to_string)
+      .collect::<Vec<_>>();
+    perms.sort();
+    perms.join(", ")
+  }
+}
+
+/// A newly created token, with its secret.
+#[derive(Deserialize)]
+struct CreateTokenResponse {
+  #[serde(flatten)]
+  token:  TokenInfo,
+  secret: String,
+}
+
+/// Parses cache IDs into permissions of the given type.
+fn cache_perms(
+  ids: &[String],
+  permission: CachePermissionType,
+) -> miette::Result<Vec<Permission>> {
+  ids
+    .iter()
+    .map(|id| {
+      let cache_id = CacheRecordId::from_str(id).map_err(|_| {
+        tracing::error!("{id:?} is not a cache ID");
+        miette::miette!("malformed cache ID")
+      })?;
+      Ok(Permission::CachePermission {
+        cache_id,
+        permission: permission.clone(),
+      })
+    })
+    .collect()
+}
+
+pub(crate) fn create_token(
+  TokenCreateArgs {
+    nickname,
+    read,
+    write,
+    expires_in,
+    api,
+  }: TokenCreateArgs,
+) -> miette::Result<()> {
+  let client = SessionClient::new(ApiConfig::resolve(api)?)?;
+
+  let mut perms = cache_perms(&read, CachePermissionType::Read)?;
+  perms.extend(cache_perms(&write, CachePermissionType::Write)?);
+  if perms.is_empty() {
+    tracing::warn!("the token has no permissions; pass `--read` or `--write`");
+  }
+  let expires_at = expires_in.map(|duration| {
+    humantime::format_rfc3339(SystemTime::now() + *duration).to_string()
+  });
+
+  let CreateTokenResponse { token, secret } =
+    client.post("/tokens", &CreateTokenRequest {
+      nickname,
+      perms: PermissionSet::from_iter(perms),
+      expires_at,
+    })?;
+
+  tracing::info!(
+    "created token {:?} ({}), which won't be shown again",
+    token.nickname,
+    token.id
+  );
+  println!("{}:{secret}", token.id);
+  Ok(())
+}
+
+pub(crate) fn list_tokens(
+  TokenListArgs { api }: TokenListArgs,
+) -> miette::Result<()> {
+  let client = SessionClient::new(ApiConfig::resolve(api)?)?;
+  let tokens: Vec<TokenInfo> = client.get("/tokens")?;
+
+  if tokens.is_empty() {
+    tracing::info!("you have no tokens");
+    return Ok(());
+  }
+
+  for token in &tokens {
+    println!(
+      "{:<26}  {:<20}  {:<7}  {}",
+      token.id,
+      token.nickname,
+      token.status(),
+      token.perms()
+    );
+  }
+  Ok(())
+}
+
+pub(crate) fn revoke_token(
+  TokenRevokeArgs { id, api }: TokenRevokeArgs,
+) -> miette::Result<()> {
+  let client = SessionClient::new(ApiConfig::resolve(api)?)?;
+  let token: TokenInfo =
+    client.post(&format!("/tokens/{id}/revoke"), &serde_json::Value::Null)?;
+
+  tracing::info!("revoked token {:?} ({})", token.nickname, token.id);
+  Ok(())
+}
@@ -26,8 +26,66 @@ pub trait DatabaseAdapter: Hexagonal {
     index_name: String,
     index_value: EitherSlug,
   ) -> Result<Option<M>, FetchModelByIndexError>;
+  /// Fetches models by many values of the same index, in a single
+  /// transaction.
+  ///
+  /// Returns one result per value, in the same order. Must be a valid index,
+  /// defined in the model's [`UNIQUE_INDICES`](model::Model::UNIQUE_INDICES)
+  /// constant.
+  async fn fetch_models_by_index<M: model::Model>(
+    &self,
+    index_name: String,
+    index_values: Vec<EitherSlug>,
+  ) -> Result<Vec<Option<M>>, FetchModelByIndexError>;
+  /// Fetches every model whose value of an index starts with a prefix
+  /// followed by a dash, in a single transaction.
+  ///
+  /// This finds, for example, everything belonging to a cache in an index
+  /// whose values are `{cache_id}-{...}`. Must be a valid index, defined in
+  /// the model's [`UNIQUE_INDICES`](model::Model::UNIQUE_INDICES) constant.
+  async fn fetch_models_by_index_prefix<M: model::Model>(
+    &self,
+    index_name: String,
+    prefix: EitherSlug,
+  ) -> Result<Vec<M>, FetchModelByIndexError>;
   /// Produces a list of all model IDs.
   async fn enumerate_models<M: model::Model>(&self) -> Result<Vec<M>>;
+  /// Replaces an existing model.
+  ///
+  /// Index entries, as defined in the model's
+  /// [`UNIQUE_INDICES`](model::Model::UNIQUE_INDICES) constant, are moved
+  /// along with any values that changed.
+  async fn update_model<M: model::Model>(
+    &self,
+    model: M,
+  ) -> Result<M, UpdateModelError>;
+  /// Deletes a model, along with its index entries.
+  ///
+  /// Returns the deleted model, or `None` if it didn't exist.
+  async fn delete_model<M: model::Model>(
+    &self,
+    id: model::RecordId<M>,
+  ) -> Result<Option<M>, DeleteModelError>;
+  /// Removes an entry from an index that the model no longer defines.
+  ///
+  /// This is for migrations that retire an index. Indices still listed in the
+  /// model's [`UNIQUE_INDICES`](model::Model::UNIQUE_INDICES) constant are
+  /// refused, since their entries are managed with the model. Returns whether
+  /// an entry was removed.
+  async fn remove_retired_index_entry<M: model::Model>(
+    &self,
+    index_name: String,
+    index_value: EitherSlug,
+  ) -> Result<bool>;
+  /// Writes any of a model's index entries that are missing.
+  ///
+  /// This is for migrations that add an index, since models created before it
+  /// have no entries in it. An entry that's taken by another model is an
+  /// error. Returns how many entries were written.
+  async fn restore_index_entries<M: model::Model>(
+    &self,
+    id: model::RecordId<M>,
+  ) -> Result<usize>;
 }
 
 // impl for Arc
@@ -59,9 +117,60 @@ impl<
     (**self).fetch_model_by_index(index_name, index_value).await
   }
 
+  async fn fetch_models_by_index<M: model::Model>(
+    &self,
+    index_name: String,
+    index_values: Vec<EitherSlug>,
+  ) -> Result<Vec<Option<M>>, FetchModelByIndexError> {
+    (**self)
+      .fetch_models_by_index(index_name, index_values)
+      .await
+  }
+
+  async fn fetch_models_by_index_prefix<M: model::Model>(
+    &self,
+    index_name: String,
+    prefix: EitherSlug,
+  ) -> Result<Vec<M>, FetchModelByIndexError> {
+    (**self)
+      .fetch_models_by_index_prefix(index_name, prefix)
+      .await
+  }
+
   async fn enumerate_models<M: model::Model>(&self) -> Result<Vec<M>> {
     (**self).enumerate_models().await
   }
+
+  async fn update_model<M: model::Model>(
+    &self,
+    model: M,
+  ) -> Result<M, UpdateModelError> {
+    (**self).update_model(model).await
+  }
+
+  async fn delete_model<M: model::Model>(
+    &self,
+    id: model::RecordId<M>,
+  ) -> Result<Option<M>, DeleteModelError> {
+    (**self).delete_model(id).await
+  }
+
+  async fn remove_retired_index_entry<M: model::Model>(
+    &self,
+    index_name: String,
+    index_value: EitherSlug,
+  ) -> Result<bool> {
+    (**self)
+      .remove_retired_index_entry::<M>(index_name, index_value)
+      .await
+  }
+
+  async fn restore_index_entries<M: model::Model>(
+    &self,
+    id: model::RecordId<M>,
+  ) -> Result<usize> {
+    (**self).restore_index_entries(id).await
+  }
 }
 
 /// Errors that can occur when creating a model.
@@ -109,6 +218,68 @@ pub enum CreateModelError {
   Db(miette::Report),
 }
 
+/// Errors that can occur when updating a model.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum UpdateModelError {
+  /// No model with that ID exists.
+  #[error("model with that ID does not exist")]
+  ModelNotFound,
+  /// Another model already holds one of the updated index values.
+  ///
+  /// This is a constraint violation, and should be handled by the caller.
+  #[error("index {index_name:?} with value \"{index_value}\" already exists")]
+  IndexAlreadyExists {
+    /// The name of the index.
+    index_name:  String,
+    /// The value of the index.
+    index_value: EitherSlug,
+  },
+  /// An error occurred while deserializing or serializing the model.
+  ///
+  /// This is a bug. Since we're serializing and deserializing to messagepack,
+  /// it's most likely that this results from an improper deserialization
+  /// caused by trying to deserialize to the wrong type.
+  #[error("failed to deserialize or serialize model")]
+  #[diagnostic_source]
+  Serde(miette::Report),
+  /// A retryable transaction error occurred.
+  ///
+  /// This is not a bug, but a transient error. It should be retried.
+  #[error("retryable transaction error: {0}")]
+  #[diagnostic_source]
+  RetryableTransaction(miette::Report),
+  /// A database error occurred.
+  ///
+  /// THis is an unknown error. Something we didn't expect to fail failed.
+  #[error("db error: {0}")]
+  #[diagnostic_source]
+  Db(miette::Report),
+}
+
+/// Errors that can occur when deleting a model.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum DeleteModelError {
+  /// An error occurred while deserializing the model.
+  ///
+  /// The model has to be read to find its index entries, so this is the same
+  /// bug as when fetching it.
+  #[error("failed to deserialize or serialize model")]
+  #[diagnostic_source]
+  Serde(miette::Report),
+  /// A retryable transaction error occurred.
+  ///
+  /// This is not a bug, but a transient error. It should be retried.
+  #[error("retryable transaction error: {0}")]
+  #[diagnostic_source]
+  RetryableTransaction(miette::Report),
+  /// A database error occurred.
+  ///
+  /// THis is an unknown error. Something we didn't expect to fail failed.
+  #[error("db error: {0}")]
+  #[diagnostic_source]
+  Db(miette::Report),
+}
+
 /// Errors that can occur when fetching a model.
 #[derive(Debug, thiserror::Error, miette::Diagnostic)]
 pub enum FetchModelError {
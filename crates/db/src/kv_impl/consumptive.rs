@@ -23,6 +23,33 @@ pub trait ConsumptiveTransaction:
     Ok(self)
   }
 
+  async fn csm_put(mut self, key: &Key, value: Value) -> Result<Self> {
+    if let Err(e) = self.put(key, value).await {
+      return Err(
+        self
+          .to_rollback_with_error(e.into(), "failed to put value")
+          .await,
+      );
+    }
+
+    Ok(self)
+  }
+
+  async fn csm_delete(mut self, key: &Key) -> Result<(Self, bool)> {
+    let deleted = match self.delete(key).await {
+      Ok(d) => d,
+      Err(e) => {
+        return Err(
+          self
+            .to_rollback_with_error(e.into(), "failed to delete value")
+            .await,
+        );
+      }
+    };
+
+    Ok((self, deleted))
+  }
+
   async fn csm_get(mut self, key: &Key) -> Result<(Self, Option<Value>)> {
     let value = match self.get(key).await {
       Ok(v) => v,
@@ -15,7 +15,7 @@ use tracing::instrument;
 use self::{consumptive::ConsumptiveTransaction, keys::*};
 use crate::{
   adapter::{FetchModelByIndexError, FetchModelError},
-  CreateModelError, DatabaseAdapter,
+  CreateModelError, DatabaseAdapter, DeleteModelError, UpdateModelError,
 };
 
 /// A TiKV-based database adapter.
@@ -226,6 +226,164 @@ impl<KV: KvTransactional> DatabaseAdapter for KvDatabaseAdapter<KV> {
     Ok(Some(model))
   }
 
+  #[instrument(skip(self, index_values), fields(table = M::TABLE_NAME, count = index_values.len()))]
+  async fn fetch_models_by_index<M: model::Model>(
+    &self,
+    index_name: String,
+    index_values: Vec<EitherSlug>,
+  ) -> Result<Vec<Option<M>>, FetchModelByIndexError> {
+    tracing::info!("fetching models by index");
+
+    if !M::UNIQUE_INDICES
+      .iter()
+      .any(|(name, _)| name == &index_name)
+    {
+      return Err(FetchModelByIndexError::IndexDoesNotExistOnModel {
+        index_name,
+      });
+    }
+
+    let mut txn = self
+      .0
+      .begin_optimistic_transaction()
+      .await
+      .context("failed to begin optimistic transaction")
+      .map_err(FetchModelByIndexError::RetryableTransaction)?;
+
+    // both the index and the model are read in the same transaction, so the
+    // results are consistent with each other
+    let mut models = Vec::with_capacity(index_values.len());
+    for index_value in index_values {
+      let index_key =
+        index_base_key::<M>(&index_name).with_either(index_value.clone());
+
+      let (_txn, id_value) = txn
+        .csm_get(&index_key)
+        .await
+        .map_err(FetchModelByIndexError::Db)?;
+      txn = _txn;
+
+      let Some(id_value) = id_value else {
+        models.push(None);
+        continue;
+      };
+      let id = kv::value::Value::deserialize::<model::RecordId<M>>(id_value)
+        .into_diagnostic()
+        .context("failed to deserialize id")
+        .map_err(FetchModelByIndexError::Serde)?;
+
+      let (_txn, model_value) = txn
+        .csm_get(&model_base_key::<M>(&id))
+        .await
+        .map_err(FetchModelByIndexError::Db)?;
+      txn = _txn;
+
+      let Some(model_value) = model_value else {
+        txn
+          .to_rollback()
+          .await
+          .map_err(FetchModelByIndexError::RetryableTransaction)?;
+        return Err(FetchModelByIndexError::IndexMalformed {
+          index_name,
+          index_value,
+        });
+      };
+      let model = kv::value::Value::deserialize::<M>(model_value)
+        .into_diagnostic()
+        .context("failed to deserialize model")
+        .map_err(FetchModelByIndexError::Serde)?;
+      models.push(Some(model));
+    }
+
+    txn
+      .to_commit()
+      .await
+      .map_err(FetchModelByIndexError::RetryableTransaction)?;
+
+    Ok(models)
+  }
+
+  #[instrument(skip(self), fields(table = M::TABLE_NAME))]
+  async fn fetch_models_by_index_prefix<M: model::Model>(
+    &self,
+    index_name: String,
+    prefix: EitherSlug,
+  ) -> Result<Vec<M>, FetchModelByIndexError> {
+    tracing::info!("fetching models by index prefix");
+
+    if !M::UNIQUE_INDICES
+      .iter()
+      .any(|(name, _)| name == &index_name)
+    {
+      return Err(FetchModelByIndexError::IndexDoesNotExistOnModel {
+        index_name,
+      });
+    }
+
+    // every value starting with `{prefix}-` sorts before `{prefix}.`, since
+    // '.' directly follows '-' in the slug alphabets
+    let first_key = index_base_key::<M>(&index_name)
+      .with_either(EitherSlug::from(LaxSlug::new(format!("{prefix}-"))));
+    let last_key = index_base_key::<M>(&index_name)
+      .with_either(EitherSlug::from(LaxSlug::new(format!("{prefix}."))));
+
+    let txn = self
+      .0
+      .begin_optimistic_transaction()
+      .await
+      .context("failed to begin optimistic transaction")
+      .map_err(FetchModelByIndexError::RetryableTransaction)?;
+
+    let (mut txn, scan_results) = txn
+      .csm_scan(Bound::Included(first_key), Bound::Excluded(last_key), None)
+      .await
+      .map_err(FetchModelByIndexError::Db)?;
+
+    // both the index and the models are read in the same transaction, so the
+    // results are consistent with each other
+    let mut models = Vec::with_capacity(scan_results.len());
+    for (index_key, id_value) in scan_results {
+      let id = kv::value::Value::deserialize::<model::RecordId<M>>(id_value)
+        .into_diagnostic()
+        .context("failed to deserialize id")
+        .map_err(FetchModelByIndexError::Serde)?;
+
+      let (_txn, model_value) = txn
+        .csm_get(&model_base_key::<M>(&id))
+        .await
+        .map_err(FetchModelByIndexError::Db)?;
+      txn = _txn;
+
+      let Some(model_value) = model_value else {
+        txn
+          .to_rollback()
+          .await
+          .map_err(FetchModelByIndexError::RetryableTransaction)?;
+        let index_value = index_key
+          .segments()
+          .last()
+          .map(|s| LaxSlug::new(s.to_string()))
+          .unwrap_or_else(|| LaxSlug::new(prefix.to_string()));
+        return Err(FetchModelByIndexError::IndexMalformed {
+          index_name,
+          index_value: index_value.into(),
+        });
+      };
+      let model = kv::value::Value::deserialize::<M>(model_value)
+        .into_diagnostic()
+        .context("failed to deserialize model")
+        .map_err(FetchModelByIndexError::Serde)?;
+      models.push(model);
+    }
+
+    txn
+      .to_commit()
+      .await
+      .map_err(FetchModelByIndexError::RetryableTransaction)?;
+
+    Ok(models)
+  }
+
   #[instrument(skip(self), fields(table = M::TABLE_NAME))]
   async fn enumerate_models<M: model::Model>(&self) -> Result<Vec<M>> {
     let first_key = model_base_key::<M>(&model::RecordId::<M>::MIN());
@@ -261,6 +419,285 @@ impl<KV: KvTransactional> DatabaseAdapter for KvDatabaseAdapter<KV> {
 
     Ok(ids)
   }
+
+  #[instrument(skip(self, model), fields(id = model.id().to_string(), table = M::TABLE_NAME))]
+  async fn update_model<M: model::Model>(
+    &self,
+    model: M,
+  ) -> Result<M, UpdateModelError> {
+    tracing::info!("updating model");
+
+    let model_key = model_base_key::<M>(&model.id());
+    let id_ulid: model::Ulid = model.id().into();
+
+    let model_value = kv::value::Value::serialize(&model)
+      .into_diagnostic()
+      .context("failed to serialize model")
+      .map_err(UpdateModelError::Serde)?;
+    let id_value = kv::value::Value::serialize(&id_ulid)
+      .into_diagnostic()
+      .context("failed to serialize id")
+      .map_err(UpdateModelError::Serde)?;
+
+    let txn = self
+      .0
+      .begin_pessimistic_transaction()
+      .await
+      .context("failed to begin pessimistic transaction")
+      .map_err(UpdateModelError::Db)?;
+
+    // fetch the current model, so we know which index entries to move
+    let (txn, old_value) = txn
+      .csm_get(&model_key)
+      .await
+      .context("failed to fetch existing model")
+      .map_err(UpdateModelError::Db)?;
+    let Some(old_value) = old_value else {
+      txn
+        .to_rollback()
+        .await
+        .map_err(UpdateModelError::RetryableTransaction)?;
+      return Err(UpdateModelError::ModelNotFound);
+    };
+    let old_model = match kv::value::Value::deserialize::<M>(old_value)
+      .into_diagnostic()
+      .context("failed to deserialize existing model")
+    {
+      Ok(m) => m,
+      Err(e) => {
+        txn
+          .to_rollback()
+          .await
+          .map_err(UpdateModelError::RetryableTransaction)?;
+        return Err(UpdateModelError::Serde(e));
+      }
+    };
+
+    let mut txn = txn
+      .csm_put(&model_key, model_value)
+      .await
+      .context("failed to put model")
+      .map_err(UpdateModelError::Db)?;
+
+    for (index_name, index_fn) in M::UNIQUE_INDICES.iter() {
+      let old_index_value = index_fn(&old_model);
+      let new_index_value = index_fn(&model);
+      if old_index_value == new_index_value {
+        continue;
+      }
+
+      let new_index_key =
+        index_base_key::<M>(index_name).with_either(new_index_value.clone());
+      let (_txn, exists) = txn
+        .csm_exists(&new_index_key)
+        .await
+        .context("failed to check if index exists")
+        .map_err(UpdateModelError::Db)?;
+      txn = _txn;
+      if exists {
+        txn
+          .to_rollback()
+          .await
+          .map_err(UpdateModelError::RetryableTransaction)?;
+        return Err(UpdateModelError::IndexAlreadyExists {
+          index_name:  index_name.to_string(),
+          index_value: new_index_value,
+        });
+      }
+
+      let old_index_key =
+        index_base_key::<M>(index_name).with_either(old_index_value);
+      let (_txn, _) = txn
+        .csm_delete(&old_index_key)
+        .await
+        .context("failed to delete old index")
+        .map_err(UpdateModelError::Db)?;
+      txn = _txn
+        .csm_insert(&new_index_key, id_value.clone())
+        .await
+        .context("failed to insert index")
+        .map_err(UpdateModelError::Db)?;
+    }
+
+    txn
+      .to_commit()
+      .await
+      .map_err(UpdateModelError::RetryableTransaction)?;
+
+    Ok(model)
+  }
+
+  #[instrument(skip(self), fields(id = id.to_string(), table = M::TABLE_NAME))]
+  async fn delete_model<M: model::Model>(
+    &self,
+    id: model::RecordId<M>,
+  ) -> Result<Option<M>, DeleteModelError> {
+    tracing::info!("deleting model");
+
+    let model_key = model_base_key::<M>(&id);
+
+    let txn = self
+      .0
+      .begin_pessimistic_transaction()
+      .await
+      .context("failed to begin pessimistic transaction")
+      .map_err(DeleteModelError::Db)?;
+
+    // fetch the model first, so we know which index entries to remove
+    let (txn, value) = txn
+      .csm_get(&model_key)
+      .await
+      .context("failed to fetch existing model")
+      .map_err(DeleteModelError::Db)?;
+    let Some(value) = value else {
+      txn
+        .to_rollback()
+        .await
+        .map_err(DeleteModelError::RetryableTransaction)?;
+      return Ok(None);
+    };
+    let model = match kv::value::Value::deserialize::<M>(value)
+      .into_diagnostic()
+      .context("failed to deserialize existing model")
+    {
+      Ok(m) => m,
+      Err(e) => {
+        txn
+          .to_rollback()
+          .await
+          .map_err(DeleteModelError::RetryableTransaction)?;
+        return Err(DeleteModelError::Serde(e));
+      }
+    };
+
+    let (mut txn, _) = txn
+      .csm_delete(&model_key)
+      .await
+      .context("failed to delete model")
+      .map_err(DeleteModelError::Db)?;
+
+    for (index_name, index_fn) in M::UNIQUE_INDICES.iter() {
+      let index_key =
+        index_base_key::<M>(index_name).with_either(index_fn(&model));
+      let (_txn, _) = txn
+        .csm_delete(&index_key)
+        .await
+        .context("failed to delete index")
+        .map_err(DeleteModelError::Db)?;
+      txn = _txn;
+    }
+
+    txn
+      .to_commit()
+      .await
+      .map_err(DeleteModelError::RetryableTransaction)?;
+
+    Ok(Some(model))
+  }
+
+  #[instrument(skip(self), fields(table = M::TABLE_NAME))]
+  async fn remove_retired_index_entry<M: model::Model>(
+    &self,
+    index_name: String,
+    index_value: EitherSlug,
+  ) -> Result<bool> {
+    if M::UNIQUE_INDICES
+      .iter()
+      .any(|(name, _)| name == &index_name)
+    {
+      miette::bail!(
+        "index {index_name:?} is still defined on model {:?}",
+        M::TABLE_NAME
+      );
+    }
+
+    let index_key = index_base_key::<M>(&index_name).with_either(index_value);
+
+    let txn = self
+      .0
+      .begin_pessimistic_transaction()
+      .await
+      .context("failed to begin pessimistic transaction")?;
+    let (txn, deleted) = txn
+      .csm_delete(&index_key)
+      .await
+      .context("failed to delete index")?;
+    txn.to_commit().await?;
+
+    Ok(deleted)
+  }
+
+  #[instrument(skip(self), fields(id = id.to_string(), table = M::TABLE_NAME))]
+  async fn restore_index_entries<M: model::Model>(
+    &self,
+    id: model::RecordId<M>,
+  ) -> Result<usize> {
+    let model_key = model_base_key::<M>(&id);
+    let id_ulid: model::Ulid = id.into();
+    let id_value = kv::value::Value::serialize(&id_ulid)
+      .into_diagnostic()
+      .context("failed to serialize id")?;
+
+    let txn = self
+      .0
+      .begin_pessimistic_transaction()
+      .await
+      .context("failed to begin pessimistic transaction")?;
+
+    let (mut txn, value) = txn
+      .csm_get(&model_key)
+      .await
+      .context("failed to fetch model")?;
+    let model = match value.map(|v| {
+      kv::value::Value::deserialize::<M>(v)
+        .into_diagnostic()
+        .context("failed to deserialize model")
+    }) {
+      Some(Ok(model)) => model,
+      Some(Err(e)) => {
+        txn.to_rollback().await?;
+        return Err(e);
+      }
+      None => {
+        txn.to_rollback().await?;
+        miette::bail!("model {id} does not exist");
+      }
+    };
+
+    let mut restored = 0;
+    for (index_name, index_fn) in M::UNIQUE_INDICES.iter() {
+      let index_value = index_fn(&model);
+      let index_key =
+        index_base_key::<M>(index_name).with_either(index_value.clone());
+      let (_txn, existing) = txn
+        .csm_get(&index_key)
+        .await
+        .context("failed to fetch index")?;
+      txn = _txn;
+
+      match existing {
+        Some(existing) if existing == id_value => continue,
+        Some(_) => {
+          txn.to_rollback().await?;
+          miette::bail!(
+            "index {index_name:?} value {index_value:?} belongs to another \
+             model"
+          );
+        }
+        None => (),
+      }
+
+      txn = txn
+        .csm_insert(&index_key, id_value.clone())
+        .await
+        .context("failed to insert index")?;
+      restored += 1;
+    }
+
+    txn.to_commit().await?;
+
+    Ok(restored)
+  }
 }
 
 #[async_trait::async_trait]
@@ -179,6 +179,79 @@ async fn test_fetch_model_by_index_malformed() {
   ));
 }
 
+#[tokio::test]
+async fn test_fetch_models_by_index() {
+  let store = MockStore::new();
+  let adapter = KvDatabaseAdapter::new(store);
+
+  let model1 = TestModel {
+    id:   model::RecordId::new(),
+    name: StrictSlug::new("test1"),
+  };
+  let model2 = TestModel {
+    id:   model::RecordId::new(),
+    name: StrictSlug::new("test2"),
+  };
+
+  adapter.create_model(model1.clone()).await.unwrap();
+  adapter.create_model(model2.clone()).await.unwrap();
+
+  let fetched_models = adapter
+    .fetch_models_by_index::<TestModel>("name".to_string(), vec![
+      EitherSlug::Strict(StrictSlug::new("test2")),
+      EitherSlug::Strict(StrictSlug::new("not_test")),
+      EitherSlug::Strict(StrictSlug::new("test1")),
+    ])
+    .await
+    .unwrap();
+  assert_eq!(fetched_models, vec![Some(model2), None, Some(model1)]);
+}
+
+#[tokio::test]
+async fn test_fetch_models_by_index_prefix() {
+  let store = MockStore::new();
+  let adapter = KvDatabaseAdapter::new(store);
+
+  let names = [
+    "team-one",
+    "team-one-a",
+    "team-one-b",
+    "team-onex",
+    "team-two-a",
+  ];
+  let mut models = Vec::new();
+  for name in names {
+    let model = TestModel {
+      id:   model::RecordId::new(),
+      name: StrictSlug::new(name),
+    };
+    adapter.create_model(model.clone()).await.unwrap();
+    models.push(model);
+  }
+
+  let mut fetched_models = adapter
+    .fetch_models_by_index_prefix::<TestModel>(
+      "name".to_string(),
+      EitherSlug::Strict(StrictSlug::new("team-one")),
+    )
+    .await
+    .unwrap();
+  // the mock store doesn't scan in order
+  fetched_models.sort_by(|a, b| a.name.cmp(&b.name));
+  assert_eq!(fetched_models, vec![models[1].clone(), models[2].clone()]);
+
+  let result = adapter
+    .fetch_models_by_index_prefix::<TestModel>(
+      "non_existent_index".to_string(),
+      EitherSlug::Strict(StrictSlug::new("team-one")),
+    )
+    .await;
+  assert!(matches!(
+    result,
+    Err(FetchModelByIndexError::IndexDoesNotExistOnModel { .. })
+  ));
+}
+
 #[tokio::test]
 async fn test_create_model_already_exists() {
   let store = MockStore::new();
@@ -218,3 +291,232 @@ async fn test_create_model_index_already_exists() {
     Err(CreateModelError::IndexAlreadyExists { .. })
   ));
 }
+
+#[tokio::test]
+async fn test_update_model() {
+  let store = MockStore::new();
+  let adapter = KvDatabaseAdapter::new(store);
+
+  let model = TestModel {
+    id:   model::RecordId::new(),
+    name: StrictSlug::new("test"),
+  };
+  adapter.create_model(model.clone()).await.unwrap();
+
+  let updated = TestModel {
+    id:   model.id,
+    name: StrictSlug::new("renamed"),
+  };
+  adapter.update_model(updated.clone()).await.unwrap();
+
+  let fetched_model = adapter
+    .fetch_model_by_id::<TestModel>(model.id())
+    .await
+    .unwrap();
+  assert_eq!(fetched_model, Some(updated.clone()));
+
+  // the index entry moves with the value
+  let by_new_name = adapter
+    .fetch_model_by_index::<TestModel>(
+      "name".to_string(),
+      EitherSlug::Strict(StrictSlug::new("renamed")),
+    )
+    .await
+    .unwrap();
+  assert_eq!(by_new_name, Some(updated));
+  let by_old_name = adapter
+    .fetch_model_by_index::<TestModel>(
+      "name".to_string(),
+      EitherSlug::Strict(StrictSlug::new("test")),
+    )
+    .await
+    .unwrap();
+  assert_eq!(by_old_name, None);
+}
+
+#[tokio::test]
+async fn test_update_model_not_found() {
+  let store = MockStore::new();
+  let adapter = KvDatabaseAdapter::new(store);
+
+  let model = TestModel {
+    id:   model::RecordId::new(),
+    name: StrictSlug::new("test"),
+  };
+
+  let result = adapter.update_model(model).await;
+  assert!(matches!(result, Err(UpdateModelError::ModelNotFound)));
+}
+
+#[tokio::test]
+async fn test_update_model_index_already_exists() {
+  let store = MockStore::new();
+  let adapter = KvDatabaseAdapter::new(store);
+
+  let model = TestModel {
+    id:   model::RecordId::new(),
+    name: StrictSlug::new("test"),
+  };
+  let model2 = TestModel {
+    id:   model::RecordId::new(),
+    name: StrictSlug::new("test2"),
+  };
+  adapter.create_model(model.clone()).await.unwrap();
+  adapter.create_model(model2.clone()).await.unwrap();
+
+  let result = adapter
+    .update_model(TestModel {
+      id:   model2.id,
+      name: StrictSlug::new("test"),
+    })
+    .await;
+  assert!(matches!(
+    result,
+    Err(UpdateModelError::IndexAlreadyExists { .. })
+  ));
+
+  // nothing changed
+  let fetched_model = adapter
+    .fetch_model_by_id::<TestModel>(model2.id())
+    .await
+    .unwrap();
+  assert_eq!(fetched_model, Some(model2));
+}
+
+#[tokio::test]
+async fn test_delete_model() {
+  let store = MockStore::new();
+  let adapter = KvDatabaseAdapter::new(store);
+
+  let model = TestModel {
+    id:   model::RecordId::new(),
+    name: StrictSlug::new("test"),
+  };
+  adapter.create_model(model.clone()).await.unwrap();
+
+  let deleted = adapter.delete_model::<TestModel>(model.id()).await.unwrap();
+  assert_eq!(deleted, Some(model.clone()));
+
+  let fetched_model = adapter
+    .fetch_model_by_id::<TestModel>(model.id())
+    .await
+    .unwrap();
+  assert_eq!(fetched_model, None);
+
+  // the index entry goes with it, so the name can be reused
+  let by_name = adapter
+    .fetch_model_by_index::<TestModel>(
+      "name".to_string(),
+      EitherSlug::Strict(StrictSlug::new("test")),
+    )
+    .await
+    .unwrap();
+  assert_eq!(by_name, None);
+  adapter
+    .create_model(TestModel {
+      id:   model::RecordId::new(),
+      name: StrictSlug::new("test"),
+    })
+    .await
+    .unwrap();
+
+  // deleting it again is a no-op
+  let deleted = adapter.delete_model::<TestModel>(model.id()).await.unwrap();
+  assert_eq!(deleted, None);
+}
+
+#[tokio::test]
+async fn test_remove_retired_index_entry() {
+  let store = MockStore::new();
+  let adapter = KvDatabaseAdapter::new(store.clone());
+
+  let model = TestModel {
+    id:   model::RecordId::new(),
+    name: StrictSlug::new("test"),
+  };
+  adapter.create_model(model.clone()).await.unwrap();
+
+  // plant an entry for an index the model doesn't define
+  let retired_key = index_base_key::<TestModel>("nickname")
+    .with_either(EitherSlug::Strict(StrictSlug::new("old")));
+  let id_ulid: model::Ulid = model.id().into();
+  store.screw_with_internal_data().write().await.insert(
+    retired_key.clone(),
+    kv::value::Value::serialize(&id_ulid).unwrap(),
+  );
+
+  let removed = adapter
+    .remove_retired_index_entry::<TestModel>(
+      "nickname".to_string(),
+      EitherSlug::Strict(StrictSlug::new("old")),
+    )
+    .await
+    .unwrap();
+  assert!(removed);
+  assert!(!store
+    .screw_with_internal_data()
+    .read()
+    .await
+    .contains_key(&retired_key));
+
+  // live indices are managed with the model
+  let result = adapter
+    .remove_retired_index_entry::<TestModel>(
+      "name".to_string(),
+      EitherSlug::Strict(StrictSlug::new("test")),
+    )
+    .await;
+  assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_restore_index_entries() {
+  let store = MockStore::new();
+  let adapter = KvDatabaseAdapter::new(store.clone());
+
+  let model = TestModel {
+    id:   model::RecordId::new(),
+    name: StrictSlug::new("test"),
+  };
+  adapter.create_model(model.clone()).await.unwrap();
+
+  // drop the index entry, as if the index was added after the model
+  let index_key = index_base_key::<TestModel>("name")
+    .with_either(EitherSlug::Strict(model.name.clone()));
+  store
+    .screw_with_internal_data()
+    .write()
+    .await
+    .remove(&index_key);
+
+  let restored = adapter
+    .restore_index_entries::<TestModel>(model.id())
+    .await
+    .unwrap();
+  assert_eq!(restored, 1);
+  let fetched_model = adapter
+    .fetch_model_by_index::<TestModel>(
+      "name".to_string(),
+      EitherSlug::Strict(model.name.clone()),
+    )
+    .await
+    .unwrap();
+  assert_eq!(fetched_model, Some(model.clone()));
+
+  // entries that are already there are left alone
+  let restored = adapter
+    .restore_index_entries::<TestModel>(model.id())
+    .await
+    .unwrap();
+  assert_eq!(restored, 0);
+
+  // entries that belong to another model are refused
+  let other_id: model::Ulid = TestModelRecordId::new().into();
+  store
+    .screw_with_internal_data()
+    .write()
+    .await
+    .insert(index_key, kv::value::Value::serialize(&other_id).unwrap());
+  let result = adapter.restore_index_entries::<TestModel>(model.id()).await;
+  assert!(result.is_err());
+}
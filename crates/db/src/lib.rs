@@ -20,8 +20,10 @@
 //! # Errors
 //! Ideally, each method in [`DatabaseAdapter`] should return a specific,
 //! concrete error. This is the case for all but
-//! [`enumerate_models`](DatabaseAdapter::enumerate_models), which returns a
-//! [`miette::Report`].
+//! [`enumerate_models`](DatabaseAdapter::enumerate_models),
+//! [`remove_retired_index_entry`](DatabaseAdapter::remove_retired_index_entry)
+//! and [`restore_index_entries`](DatabaseAdapter::restore_index_entries),
+//! which return a [`miette::Report`].
 //!
 //! # Implementers
 //! The [`KvDatabaseAdapter`] is the only implementer of the [`DatabaseAdapter`]
@@ -3,9 +3,9 @@ use std::{path::PathBuf, str::FromStr};
 use miette::Result;
 use models::{
   CachePermissionType, CacheRecordId, EntityName, EntityNickname, HumanName,
-  LocalStorageCredentials, Org, Permission, PermissionSet, RecordId,
-  StorageCredentials, StoreRecordId, StrictSlug, TokenRecordId, TokenSecret,
-  UserRecordId,
+  LocalStorageCredentials, Org, PasswordHash, Permission, PermissionSet,
+  RecordId, SigningKeyRecordId, SigningKeySecret, StorageCredentials,
+  StoreRecordId, StrictSlug, TokenRecordId, TokenSecret, UserRecordId,
 };
 
 use crate::DatabaseAdapter;
@@ -24,12 +24,6 @@ impl<T: DatabaseAdapter> Migratable for T {
       name: EntityName::new(StrictSlug::confident("dev-org")),
     };
 
-    let user = models::User {
-      id:   UserRecordId::from_str("01J53N6ARQGFTBQ41T25TAJ949").unwrap(),
-      name: HumanName::try_new("John Lewis".to_string()).unwrap(),
-      org:  org.id,
-    };
-
     let local_file_store = models::Store {
       id:                 StoreRecordId::from_str("01J53YYCCJW4B4QBM1CG0CHAMP")
         .unwrap(),
@@ -46,21 +40,71 @@ impl<T: DatabaseAdapter> Migratable for T {
     };
 
     let albert_cache = models::Cache {
-      id:         CacheRecordId::from_str("01J799MSHXPPY5RJ8KGHVR9GWQ")
+      id:              CacheRecordId::from_str("01J799MSHXPPY5RJ8KGHVR9GWQ")
         .unwrap(),
-      name:       EntityName::new(StrictSlug::confident("albert")),
-      visibility: models::Visibility::Private,
-      store:      local_file_store.id,
-      org:        org.id,
+      name:            EntityName::new(StrictSlug::confident("albert")),
+      visibility:      models::Visibility::Private,
+      store:           local_file_store.id,
+      org:             org.id,
+      index_debuginfo: true,
+      upstreams:       vec![models::UpstreamCache {
+        url:        "https://cache.nixos.org".to_string(),
+        public_key: "cache.nixos.org-1:\
+                     6NCHdD59X431o0gWypbMrAURkbJ16ZPMQFGspcDShjY="
+          .to_string(),
+      }],
+      proxy:           None,
     };
 
+    // the password is `dev-password`
+    let user = models::User {
+      id:            UserRecordId::from_str("01J53N6ARQGFTBQ41T25TAJ949")
+        .unwrap(),
+      name:          HumanName::try_new("John Lewis".to_string()).unwrap(),
+      username:      EntityName::new(StrictSlug::confident("john-lewis")),
+      password_hash: Some(PasswordHash::new(
+        "$argon2id$v=19$m=19456,t=2,p=1$j+zm7aC/\
+         qzTQWLq0BmrSSw$Z6TMIgQPtb+0qly1zCAeIR6lXMwlazqHFFqRu3+JHpY",
+      )),
+      perms:         PermissionSet(
+        vec![
+          Permission::CachePermission {
+            cache_id:   albert_cache.id,
+            permission: CachePermissionType::Read,
+          },
+          Permission::CachePermission {
+            cache_id:   albert_cache.id,
+            permission: CachePermissionType::Write,
+          },
+        ]
+        .into_iter()
+        .collect(),
+      ),
+      org:           org.id,
+    };
+
+    let albert_signing_key = models::SigningKey {
+      id:     SigningKeyRecordId::from_str("01JAAVH7A8AN7QGJ3HN1TD4TAN")
+        .unwrap(),
+      // generated fresh, so no seeded deployment shares a known private key
+      secret: SigningKeySecret::new(
+        nasty::signing::SecretKey::generate("albert-1").to_string(),
+      ),
+      cache:  albert_cache.id,
+      org:    org.id,
+    };
+
+    // the migrator doesn't know the server's pepper, so the secret is seeded
+    // in plaintext and gets hashed the first time it's verified
     let omnitoken_token = models::Token {
-      id:       TokenRecordId::from_str("01J53ZA38PS1P5KWCE4FMG58F0").unwrap(),
-      nickname: EntityNickname::new(StrictSlug::confident("omnitoken")),
-      secret:   TokenSecret::new(StrictSlug::confident(
+      id:            TokenRecordId::from_str("01J53ZA38PS1P5KWCE4FMG58F0")
+        .unwrap(),
+      nickname:      EntityNickname::new(StrictSlug::confident("omnitoken")),
+      secret_hash:   None,
+      legacy_secret: Some(TokenSecret::new(StrictSlug::confident(
         "zvka5d29dgvpujdyqa6ftnkei02i-qm1n-fjzuqfbyrq7avxbzi6ma8flxsuwe4l",
-      )),
-      perms:    PermissionSet(
+      ))),
+      perms:         PermissionSet(
         vec![
           Permission::CachePermission {
             cache_id:   albert_cache.id,
@@ -74,14 +118,18 @@ impl<T: DatabaseAdapter> Migratable for T {
         .into_iter()
         .collect(),
       ),
-      owner:    user.id,
-      org:      org.id,
+      owner:         user.id,
+      org:           org.id,
+      expires_at:    None,
+      revoked_at:    None,
+      last_used_at:  None,
     };
 
     self.create_model(org).await?;
     self.create_model(user).await?;
     self.create_model(local_file_store).await?;
     self.create_model(albert_cache).await?;
+    self.create_model(albert_signing_key).await?;
     self.create_model(omnitoken_token).await?;
 
     Ok(())
@@ -17,6 +17,16 @@ impl TempStoragePath {
       size,
     }
   }
+  /// Creates a temporary storage path with a fixed name.
+  ///
+  /// Unlike [`new_random`](Self::new_random), storing data at the same name
+  /// twice overwrites the earlier data.
+  pub fn new_named(name: &str, size: FileSize) -> Self {
+    Self {
+      path: PathBuf::from(name),
+      size,
+    }
+  }
   /// Returns the path.
   pub fn path(&self) -> &PathBuf { &self.path }
   /// Returns the size.
@@ -14,12 +14,14 @@ mod creds;
 mod email;
 mod files;
 mod names;
+mod nix;
+pub mod nix32;
 mod record_id;
 mod secrets;
 
 pub use slugger;
 
 pub use self::{
-  compression::*, creds::*, email::*, files::*, names::*, record_id::*,
+  compression::*, creds::*, email::*, files::*, names::*, nix::*, record_id::*,
   secrets::*,
 };
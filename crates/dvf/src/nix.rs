@@ -0,0 +1,384 @@
+use std::{fmt, str::FromStr};
+
+use base64::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::nix32;
+
+/// The Nix store directory.
+pub const STORE_DIR: &str = "/nix/store";
+
+/// The length of the hash part of a store path.
+const STORE_PATH_HASH_LEN: usize = 32;
+/// The maximum length of the name part of a store path.
+const STORE_PATH_NAME_MAX_LEN: usize = 211;
+
+/// Validate that a string is the hash part of a store path, i.e. 32
+/// characters of nix-base32.
+pub fn validate_store_path_hash(hash: &str) -> bool {
+  hash.len() == STORE_PATH_HASH_LEN
+    && hash.bytes().all(|b| nix32::ALPHABET.contains(&b))
+}
+
+/// Validate that a string is a store path basename, i.e. `<hash>-<name>`.
+///
+/// The hash is 32 characters of nix-base32. The name is made of
+/// `[A-Za-z0-9+-._?=]`, and may not start with a period.
+pub fn validate_store_path(path: &str) -> bool {
+  let Some((hash, name)) = path.split_once('-') else {
+    return false;
+  };
+
+  let valid_hash = validate_store_path_hash(hash);
+  let valid_name = !name.is_empty()
+    && name.len() <= STORE_PATH_NAME_MAX_LEN
+    && !name.starts_with('.')
+    && name
+      .bytes()
+      .all(|b| b.is_ascii_alphanumeric() || b"+-._?=".contains(&b));
+
+  valid_hash && valid_name
+}
+
+/// A Nix store path, without the store directory, i.e. `<hash>-<name>`.
+#[nutype::nutype(
+  derive(
+    Debug,
+    Clone,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    AsRef,
+    Display
+  ),
+  validate(predicate = validate_store_path)
+)]
+pub struct StorePath(String);
+
+impl StorePath {
+  /// Parses an absolute store path, i.e. `/nix/store/<hash>-<name>`.
+  ///
+  /// Returns `None` if the path is outside the store or is malformed. Paths
+  /// inside a store path are not accepted.
+  pub fn from_absolute_path(path: &str) -> Option<Self> {
+    let basename = path.strip_prefix(STORE_DIR)?.strip_prefix('/')?;
+    Self::try_new(basename).ok()
+  }
+
+  /// Returns the store path with the store directory prepended.
+  pub fn to_absolute_path(&self) -> String { format!("{STORE_DIR}/{self}") }
+
+  /// Returns the hash part of the store path.
+  pub fn hash_part(&self) -> &str { &self.as_ref()[..STORE_PATH_HASH_LEN] }
+
+  /// Returns the name part of the store path.
+  pub fn name_part(&self) -> &str { &self.as_ref()[STORE_PATH_HASH_LEN + 1..] }
+}
+
+/// The length of the base16 hash in a derivation output ID.
+const DRV_OUTPUT_HASH_LEN: usize = 64;
+
+/// Validate that a string is a derivation output ID, i.e.
+/// `sha256:<base16>!<output>`.
+///
+/// The output name follows the same rules as the name part of a store path.
+pub fn validate_drv_output(id: &str) -> bool {
+  let Some((hash, output)) = id
+    .strip_prefix("sha256:")
+    .and_then(|rest| rest.split_once('!'))
+  else {
+    return false;
+  };
+
+  let valid_hash = hash.len() == DRV_OUTPUT_HASH_LEN
+    && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+  let valid_output = !output.is_empty()
+    && output.len() <= STORE_PATH_NAME_MAX_LEN
+    && !output.starts_with('.')
+    && output
+      .bytes()
+      .all(|b| b.is_ascii_alphanumeric() || b"+-._?=".contains(&b));
+
+  valid_hash && valid_output
+}
+
+/// The ID of a derivation output, i.e. `sha256:<base16>!<output>`.
+///
+/// This identifies a realisation of a content-addressed derivation. The hash is
+/// the derivation's hash modulo its fixed-output inputs, in base16.
+#[nutype::nutype(
+  derive(
+    Debug,
+    Clone,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    AsRef,
+    Display
+  ),
+  validate(predicate = validate_drv_output)
+)]
+pub struct DrvOutput(String);
+
+impl DrvOutput {
+  /// Returns the derivation hash.
+  pub fn drv_hash(&self) -> NixHash {
+    let (hash, _) = self.as_ref().split_once('!').expect("validated");
+    hash.parse().expect("validated")
+  }
+
+  /// Returns the output name, e.g. `out`.
+  pub fn output_name(&self) -> &str {
+    let (_, output) = self.as_ref().split_once('!').expect("validated");
+    output
+  }
+}
+
+/// An error encountered while parsing a [`NixHash`].
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum NixHashParseError {
+  /// The hash is not in a recognized form.
+  #[error("the hash is not in `sha256:<hash>` or `sha256-<base64>` form")]
+  UnknownForm,
+  /// The hash uses an unsupported algorithm.
+  #[error("unsupported hash algorithm: `{0}`")]
+  UnsupportedAlgorithm(String),
+  /// The hash digest could not be decoded.
+  #[error("the hash digest is malformed")]
+  MalformedDigest,
+}
+
+/// A sha256 hash, as used by Nix.
+///
+/// Nix writes hashes in several forms. This type parses all of the following:
+/// - `sha256:<nix32>`, the form used in `.narinfo` files.
+/// - `sha256:<base16>`.
+/// - `sha256-<base64>`, the SRI form.
+///
+/// It's displayed and serialized in the first form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NixHash([u8; 32]);
+
+impl NixHash {
+  /// Creates a hash from a sha256 digest.
+  pub fn from_sha256_digest(digest: [u8; 32]) -> Self { Self(digest) }
+
+  /// Returns the raw sha256 digest.
+  pub fn digest(&self) -> &[u8; 32] { &self.0 }
+
+  /// Returns the hash in `sha256:<nix32>` form.
+  pub fn to_nix32(&self) -> String {
+    format!("sha256:{}", nix32::encode(&self.0))
+  }
+
+  /// Returns the hash in `sha256:<base16>` form.
+  pub fn to_base16(&self) -> String {
+    let hex = self
+      .0
+      .iter()
+      .map(|b| format!("{b:02x}"))
+      .collect::<String>();
+    format!("sha256:{hex}")
+  }
+
+  /// Returns the hash in SRI (`sha256-<base64>`) form.
+  pub fn to_sri(&self) -> String {
+    format!("sha256-{}", BASE64_STANDARD.encode(self.0))
+  }
+}
+
+/// Decodes a lowercase or uppercase base16 string.
+fn decode_base16(s: &str) -> Option<Vec<u8>> {
+  if s.len() % 2 != 0 {
+    return None;
+  }
+  (0..s.len())
+    .step_by(2)
+    .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+    .collect()
+}
+
+impl FromStr for NixHash {
+  type Err = NixHashParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (algorithm, digest) = match (s.split_once(':'), s.split_once('-')) {
+      (Some((algorithm, digest)), _) => {
+        let digest = match digest.len() {
+          52 => nix32::decode(digest),
+          64 => decode_base16(digest),
+          _ => None,
+        };
+        (algorithm, digest)
+      }
+      (None, Some((algorithm, digest))) => {
+        (algorithm, BASE64_STANDARD.decode(digest).ok())
+      }
+      (None, None) => return Err(NixHashParseError::UnknownForm),
+    };
+
+    if algorithm != "sha256" {
+      return Err(NixHashParseError::UnsupportedAlgorithm(
+        algorithm.to_string(),
+      ));
+    }
+
+    digest
+      .and_then(|d| d.try_into().ok())
+      .map(Self)
+      .ok_or(NixHashParseError::MalformedDigest)
+  }
+}
+
+impl fmt::Display for NixHash {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.to_nix32())
+  }
+}
+
+impl Serialize for NixHash {
+  fn serialize<S: serde::Serializer>(
+    &self,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&self.to_nix32())
+  }
+}
+
+impl<'de> Deserialize<'de> for NixHash {
+  fn deserialize<D: serde::Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HELLO: &str = "kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1";
+
+  #[test]
+  fn test_store_path_parts() {
+    let path = StorePath::try_new(HELLO).unwrap();
+    assert_eq!(path.hash_part(), "kwmqk7ygvhypxadsdaai27gl6qfxv7za");
+    assert_eq!(path.name_part(), "hello-2.12.1");
+    assert_eq!(path.to_absolute_path(), format!("/nix/store/{HELLO}"));
+    assert_eq!(
+      StorePath::from_absolute_path(&path.to_absolute_path()),
+      Some(path)
+    );
+  }
+
+  #[test]
+  fn test_store_path_rejects_malformed() {
+    for path in [
+      "",
+      "hello",
+      "kwmqk7ygvhypxadsdaai27gl6qfxv7za",
+      "kwmqk7ygvhypxadsdaai27gl6qfxv7za-",
+      // `e` is not in the nix-base32 alphabet
+      "ewmqk7ygvhypxadsdaai27gl6qfxv7za-hello",
+      // the hash is too short
+      "wmqk7ygvhypxadsdaai27gl6qfxv7za-hello",
+      "kwmqk7ygvhypxadsdaai27gl6qfxv7za-.hello",
+      "kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello/bin",
+      "kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello world",
+    ] {
+      assert!(StorePath::try_new(path).is_err(), "{path:?}");
+    }
+    assert!(StorePath::from_absolute_path(HELLO).is_none());
+    assert!(StorePath::from_absolute_path(&format!("/tmp/{HELLO}")).is_none());
+  }
+
+  #[test]
+  fn test_store_path_hash() {
+    assert!(validate_store_path_hash("kwmqk7ygvhypxadsdaai27gl6qfxv7za"));
+    for hash in [
+      "",
+      "wmqk7ygvhypxadsdaai27gl6qfxv7za",
+      "ewmqk7ygvhypxadsdaai27gl6qfxv7za",
+      "kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello",
+      "../../../../../../../etc/passwd",
+    ] {
+      assert!(!validate_store_path_hash(hash), "{hash:?}");
+    }
+  }
+
+  #[test]
+  fn test_drv_output_parts() {
+    let id = "sha256:\
+              e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855!\
+              dev";
+    let drv_output = DrvOutput::try_new(id).unwrap();
+    assert_eq!(drv_output.output_name(), "dev");
+    assert_eq!(
+      drv_output.drv_hash(),
+      "sha256:0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73"
+        .parse()
+        .unwrap()
+    );
+
+    for id in [
+      "",
+      "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+      "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855!",
+      "sha256:E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855!\
+       out",
+      "sha256:0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73!out",
+      "md5:e3b0c44298fc1c149afbf4c8996fb924!out",
+      "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855!\
+       out/bin",
+    ] {
+      assert!(DrvOutput::try_new(id).is_err(), "{id:?}");
+    }
+  }
+
+  #[test]
+  fn test_nix_hash_forms() {
+    let nix32 = "sha256:0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73";
+    let base16 =
+      "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+    let sri = "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=";
+
+    let hash: NixHash = nix32.parse().unwrap();
+    assert_eq!(base16.parse::<NixHash>().unwrap(), hash);
+    assert_eq!(sri.parse::<NixHash>().unwrap(), hash);
+    assert_eq!(hash.to_nix32(), nix32);
+    assert_eq!(hash.to_base16(), base16);
+    assert_eq!(hash.to_sri(), sri);
+    assert_eq!(hash.to_string(), nix32);
+  }
+
+  #[test]
+  fn test_nix_hash_rejects_malformed() {
+    assert_eq!(
+      "0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73".parse::<NixHash>(),
+      Err(NixHashParseError::UnknownForm)
+    );
+    assert_eq!(
+      "md5:0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73"
+        .parse::<NixHash>(),
+      Err(NixHashParseError::UnsupportedAlgorithm("md5".to_string()))
+    );
+    assert_eq!(
+      "sha256:0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c7"
+        .parse::<NixHash>(),
+      Err(NixHashParseError::MalformedDigest)
+    );
+    assert_eq!(
+      "sha256-AAAA".parse::<NixHash>(),
+      Err(NixHashParseError::MalformedDigest)
+    );
+  }
+}
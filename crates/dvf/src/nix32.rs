@@ -0,0 +1,100 @@
+//! Nix's base-32 encoding, used for hashes in store paths and `.narinfo`
+//! files.
+
+/// The Nix base-32 alphabet. It omits `e`, `o`, `u`, and `t`.
+pub const ALPHABET: &[u8; 32] = b"0123456789abcdfghijklmnpqrsvwxyz";
+
+/// Returns the length of the encoding of `len` bytes.
+pub const fn encoded_len(len: usize) -> usize {
+  if len == 0 {
+    return 0;
+  }
+  (len * 8 - 1) / 5 + 1
+}
+
+/// Encodes bytes with Nix's base-32 encoding.
+///
+/// This is not RFC 4648 base-32: the alphabet differs, and the bytes are
+/// consumed from the end.
+pub fn encode(bytes: &[u8]) -> String {
+  (0..encoded_len(bytes.len()))
+    .rev()
+    .map(|n| {
+      let bit = n * 5;
+      let (i, j) = (bit / 8, bit % 8);
+      let low = u16::from(bytes[i]) >> j;
+      let high = bytes.get(i + 1).map_or(0, |b| u16::from(*b) << (8 - j));
+      ALPHABET[usize::from((low | high) & 0x1f)] as char
+    })
+    .collect()
+}
+
+/// Decodes a string in Nix's base-32 encoding.
+///
+/// Returns `None` if the string contains characters outside the alphabet, or
+/// if it's not the canonical encoding of any byte string.
+pub fn decode(s: &str) -> Option<Vec<u8>> {
+  let len = s.len() * 5 / 8;
+  if encoded_len(len) != s.len() {
+    return None;
+  }
+
+  let mut bytes = vec![0u8; len];
+  for (n, c) in s.bytes().rev().enumerate() {
+    let digit = ALPHABET.iter().position(|a| *a == c)? as u16;
+    let bit = n * 5;
+    let (i, j) = (bit / 8, bit % 8);
+    let value = digit << j;
+    bytes[i] |= value as u8;
+    let carry = (value >> 8) as u8;
+    match bytes.get_mut(i + 1) {
+      Some(next) => *next |= carry,
+      // bits past the end must be zero for the encoding to be canonical
+      None if carry != 0 => return None,
+      None => {}
+    }
+  }
+  Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // sha256 of the empty string
+  const EMPTY_SHA256: [u8; 32] = [
+    0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8,
+    0x99, 0x6f, 0xb9, 0x24, 0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c,
+    0xa4, 0x95, 0x99, 0x1b, 0x78, 0x52, 0xb8, 0x55,
+  ];
+  const EMPTY_SHA256_NIX32: &str =
+    "0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73";
+
+  #[test]
+  fn test_nix32_encode() {
+    assert_eq!(encode(&[]), "");
+    assert_eq!(encode(&[0x1f]), "0z");
+    assert_eq!(encode(&EMPTY_SHA256), EMPTY_SHA256_NIX32);
+  }
+
+  #[test]
+  fn test_nix32_decode() {
+    assert_eq!(decode(""), Some(vec![]));
+    assert_eq!(decode("0z"), Some(vec![0x1f]));
+    assert_eq!(decode(EMPTY_SHA256_NIX32), Some(EMPTY_SHA256.to_vec()));
+    for len in 0..40 {
+      let bytes = (0..len).map(|i| (i * 37 + 11) as u8).collect::<Vec<_>>();
+      assert_eq!(decode(&encode(&bytes)), Some(bytes));
+    }
+  }
+
+  #[test]
+  fn test_nix32_decode_rejects_invalid() {
+    // `e`, `o`, `u` and `t` are not in the alphabet
+    assert_eq!(decode("0e"), None);
+    // no byte string encodes to a single character
+    assert_eq!(decode("0"), None);
+    // the top bits of the first character would overflow
+    assert_eq!(decode("8z"), None);
+  }
+}
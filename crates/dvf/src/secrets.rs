@@ -13,3 +13,60 @@ use slugger::StrictSlug;
   Display
 ))]
 pub struct TokenSecret(StrictSlug);
+
+/// A keyed hash of a [`TokenSecret`], hex-encoded.
+#[nutype::nutype(derive(
+  Debug,
+  Clone,
+  Serialize,
+  Deserialize,
+  PartialEq,
+  Eq,
+  Hash,
+  AsRef,
+  Display
+))]
+pub struct TokenSecretHash(String);
+
+/// A signing key secret, in Nix's `<key-name>:<base64>` form.
+#[nutype::nutype(derive(
+  Debug,
+  Clone,
+  Serialize,
+  Deserialize,
+  PartialEq,
+  Eq,
+  Hash,
+  AsRef,
+  Display
+))]
+pub struct SigningKeySecret(String);
+
+/// A user's password, as they typed it.
+#[nutype::nutype(
+  derive(Clone, Serialize, Deserialize, PartialEq, Eq, AsRef),
+  validate(not_empty, len_char_max = 1024)
+)]
+pub struct Password(String);
+
+// passwords end up in request structs that get logged, so keep them out of
+// debug output
+impl std::fmt::Debug for Password {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("Password(********)")
+  }
+}
+
+/// An argon2id hash of a [`Password`], in PHC string format.
+#[nutype::nutype(derive(
+  Debug,
+  Clone,
+  Serialize,
+  Deserialize,
+  PartialEq,
+  Eq,
+  Hash,
+  AsRef,
+  Display
+))]
+pub struct PasswordHash(String);
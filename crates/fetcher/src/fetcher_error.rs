@@ -3,6 +3,18 @@ use miette::Diagnostic;
 use mollusk::CredsFetchingError;
 use storage::ReadError;
 
+/// An error storing a pulled-through NAR in the API.
+///
+/// These can't be returned to the client, which has already been served the
+/// NAR by the time the API answers.
+#[derive(thiserror::Error, Debug)]
+pub enum ProxyStoreError {
+  #[error("failed to reach the API: {0}")]
+  ApiError(reqwest::Error),
+  #[error("the API rejected the NAR with {status}: {body}")]
+  Rejected { status: StatusCode, body: String },
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(thiserror::Error, Diagnostic, Debug)]
 pub enum FetcherError {
@@ -12,6 +24,10 @@ pub enum FetcherError {
   ReadError(#[from] storage::ReadError),
   #[error("Failed to build the store")]
   StoreInitError(#[diagnostic_source] miette::Report),
+  #[error("Failed to reach the API: {0}")]
+  ApiError(reqwest::Error),
+  #[error("Failed to fetch from upstream: {0}")]
+  UpstreamError(reqwest::Error),
 }
 
 impl mollusk::MolluskError for FetcherError {
@@ -34,6 +50,9 @@ impl mollusk::MolluskError for FetcherError {
       | FetcherError::CredsFetchingError(
         CredsFetchingError::TempStorageCredsError(_),
       ) => StatusCode::INTERNAL_SERVER_ERROR,
+      FetcherError::ApiError(_) | FetcherError::UpstreamError(_) => {
+        StatusCode::BAD_GATEWAY
+      }
       FetcherError::ReadError(ReadError::InvalidPath(_)) => {
         StatusCode::BAD_REQUEST
       }
@@ -58,6 +77,8 @@ impl mollusk::MolluskError for FetcherError {
         CredsFetchingError::TempStorageCredsError(_),
       )
       | FetcherError::StoreInitError(_) => "internal-error",
+      FetcherError::ApiError(_) => "api-unreachable",
+      FetcherError::UpstreamError(_) => "upstream-unreachable",
     }
   }
 
@@ -85,6 +106,10 @@ impl mollusk::MolluskError for FetcherError {
       FetcherError::ReadError(ReadError::IoError(e)) => {
         format!("An internal error occurred: {e}")
       }
+      FetcherError::ApiError(_) => "Failed to reach the API".to_string(),
+      FetcherError::UpstreamError(_) => {
+        "Failed to fetch from the upstream cache".to_string()
+      }
     }
   }
 
@@ -122,6 +147,12 @@ impl mollusk::MolluskError for FetcherError {
       FetcherError::StoreInitError(e) => {
         tracing::error!("failed to init store: {e}");
       }
+      FetcherError::ApiError(e) => {
+        tracing::error!("failed to reach the API: {e}");
+      }
+      FetcherError::UpstreamError(e) => {
+        tracing::warn!("failed to fetch from upstream: {e}");
+      }
     }
   }
 }
@@ -2,21 +2,40 @@
 
 mod fetcher_error;
 
-use std::{ops::Deref, path::PathBuf, str::FromStr};
+use std::{
+  io, ops::Deref, path::PathBuf, str::FromStr, sync::Arc, time::Duration,
+};
 
 use axum::{
   body::Body,
-  extract::Path,
-  http::HeaderMap,
+  extract::{Path, State},
+  http::{header, HeaderMap},
   response::{IntoResponse, Response},
   routing::get,
   Router,
 };
-use mollusk::ExternalApiError;
+use base64::prelude::*;
+use dvf::slugger::LaxSlug;
+use futures::StreamExt;
+use mollusk::{
+  BinaryCacheFile, BinaryCachePayload, ExternalApiError, ProxiedCompression,
+};
 use serde::Deserialize;
-use storage::{DynStorageClient, StorageClientGenerator};
+use storage::{
+  belt::{self, Belt},
+  DynStorageClient, StorageClientGenerator,
+};
+use tokio::io::AsyncReadExt;
+
+use self::fetcher_error::{FetcherError, ProxyStoreError};
 
-use self::fetcher_error::FetcherError;
+/// Where the API listens.
+const API_URL: &str = "http://localhost:3000";
+/// How many chunks of a pulled-through NAR to buffer for the slower of the
+/// client and the API.
+const PULL_THROUGH_BUFFER: usize = 16;
+/// How long to wait for an upstream cache to connect or send more data.
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Deserialize)]
 #[serde(untagged)]
@@ -35,51 +54,350 @@ impl<T, E> UntaggedResult<T, E> {
 }
 
 async fn get_fetch_payload(
+  app_state: &AppState,
   store_name: String,
   path: String,
   token_id: Option<String>,
   token_secret: Option<String>,
-) -> Result<dvf::StorageCredentials, mollusk::PrepareFetchPayloadError> {
+) -> Result<dvf::StorageCredentials, ExternalApiError> {
   let client = reqwest::Client::new();
   let response = client
-    .get("http://localhost:3000/fetch_payload".to_string())
+    .get(format!("{}/fetch_payload", app_state.api_url))
     .json(&(store_name, path, token_id, token_secret))
     .send()
     .await
-    .unwrap()
+    .map_err(FetcherError::ApiError)?
     .json::<UntaggedResult<
       dvf::StorageCredentials,
       mollusk::PrepareFetchPayloadError,
     >>()
     .await
-    .unwrap()
+    .map_err(FetcherError::ApiError)?
+    .into_result()?;
+  Ok(response)
+}
+
+async fn get_binary_cache_payload(
+  app_state: &AppState,
+  store_name: String,
+  file: BinaryCacheFile,
+  token_id: Option<String>,
+  token_secret: Option<String>,
+) -> Result<BinaryCachePayload, ExternalApiError> {
+  let client = reqwest::Client::new();
+  let response = client
+    .get(format!("{}/binary_cache_payload", app_state.api_url))
+    .json(&(store_name, file, token_id, token_secret))
+    .send()
+    .await
+    .map_err(FetcherError::ApiError)?
+    .json::<UntaggedResult<
+      BinaryCachePayload,
+      mollusk::PrepareFetchPayloadError,
+    >>()
+    .await
+    .map_err(FetcherError::ApiError)?
     .into_result()?;
   Ok(response)
 }
 
-#[tracing::instrument(skip(headers))]
+/// Extracts the token ID and secret from the `Authorization` header.
+///
+/// Accepts either a raw `id:secret` pair, or HTTP basic auth with the token ID
+/// as the username and the secret as the password, which is what Nix sends
+/// when credentials for the cache are in its `netrc` file.
+fn token_from_headers(headers: &HeaderMap) -> (Option<String>, Option<String>) {
+  let Some(value) = headers
+    .get("authorization")
+    .and_then(|value| value.to_str().ok())
+  else {
+    return (None, None);
+  };
+
+  let pair = match value.strip_prefix("Basic ") {
+    Some(encoded) => BASE64_STANDARD
+      .decode(encoded.trim())
+      .ok()
+      .and_then(|decoded| String::from_utf8(decoded).ok())
+      .unwrap_or_default(),
+    None => value.to_string(),
+  };
+
+  let mut parts = pair.splitn(2, ':').map(|s| s.to_string());
+  (parts.next(), parts.next())
+}
+
+/// The fetcher's state.
+#[derive(Clone)]
+struct AppState {
+  /// The secret shared with the API, for the routes only the fetcher may call.
+  fetcher_secret: Arc<str>,
+  /// Where the API listens.
+  api_url:        Arc<str>,
+  /// The client for fetching from upstream caches.
+  upstream:       reqwest::Client,
+}
+
+impl AppState {
+  /// Builds the state, with a client that gives up on stalled upstreams.
+  fn new(fetcher_secret: Arc<str>, api_url: Arc<str>) -> Self {
+    // NARs can be large, so this bounds each wait for the upstream rather
+    // than the whole download
+    let upstream = reqwest::Client::builder()
+      .connect_timeout(UPSTREAM_TIMEOUT)
+      .read_timeout(UPSTREAM_TIMEOUT)
+      .build()
+      .expect("failed to build HTTP client");
+    Self {
+      fetcher_secret,
+      api_url,
+      upstream,
+    }
+  }
+}
+
+#[tracing::instrument(skip(app_state, headers))]
 async fn fetch_handler(
+  State(app_state): State<AppState>,
   Path((store_name, path)): Path<(String, String)>,
   headers: HeaderMap,
 ) -> Result<Response, ExternalApiError> {
-  let token_id_secret_pair = headers
-    .get("authorization")
-    .and_then(|value| value.to_str().ok())
-    .map(|value| value.to_string());
-  let token_id = token_id_secret_pair
-    .clone()
-    .and_then(|pair| pair.split(':').next().map(|s| s.to_string()));
-  let token_secret = token_id_secret_pair
-    .and_then(|pair| pair.split(':').nth(1).map(|s| s.to_string()));
-
-  let creds =
-    get_fetch_payload(store_name, path.clone(), token_id, token_secret).await?;
+  let (token_id, token_secret) = token_from_headers(&headers);
+
+  if let Some(file) = BinaryCacheFile::from_request_path(&path) {
+    return fetch_binary_cache_file(
+      app_state,
+      store_name,
+      file,
+      token_id,
+      token_secret,
+    )
+    .await;
+  }
+
+  let creds = get_fetch_payload(
+    &app_state,
+    store_name,
+    path.clone(),
+    token_id,
+    token_secret,
+  )
+  .await?;
   let client = creds.client().await.map_err(FetcherError::StoreInitError)?;
 
   let response = fetch_path_from_client(&client, path).await?;
   Ok(response)
 }
 
+#[tracing::instrument(skip(app_state, token_secret))]
+async fn fetch_binary_cache_file(
+  app_state: AppState,
+  store_name: String,
+  file: BinaryCacheFile,
+  token_id: Option<String>,
+  token_secret: Option<String>,
+) -> Result<Response, ExternalApiError> {
+  let content_type = file.content_type();
+  let payload = get_binary_cache_payload(
+    &app_state,
+    store_name.clone(),
+    file,
+    token_id.clone(),
+    token_secret.clone(),
+  )
+  .await?;
+
+  let response = match payload {
+    BinaryCachePayload::Text(text) => text.into_response(),
+    BinaryCachePayload::Nar { path, credentials } => {
+      let client = credentials
+        .client()
+        .await
+        .map_err(FetcherError::StoreInitError)?;
+      fetch_path_from_client(&client, path.to_string()).await?
+    }
+    BinaryCachePayload::ProxiedNar {
+      hash,
+      url,
+      compression,
+    } => {
+      pull_through_nar(
+        app_state,
+        store_name,
+        hash,
+        url,
+        compression,
+        token_id,
+        token_secret,
+      )
+      .await?
+    }
+    BinaryCachePayload::NarMember {
+      path,
+      credentials,
+      compression,
+      offset,
+      size,
+    } => {
+      let client = credentials
+        .client()
+        .await
+        .map_err(FetcherError::StoreInitError)?;
+      fetch_nar_member_from_client(&client, path, compression, offset, size)
+        .await?
+    }
+    BinaryCachePayload::Listing {
+      path,
+      credentials,
+      compression,
+    } => {
+      let client = credentials
+        .client()
+        .await
+        .map_err(FetcherError::StoreInitError)?;
+      let response = fetch_path_from_client(&client, path).await?;
+      // the listing is served as stored, and clients decompress it
+      match compression {
+        Some(dvf::CompressionAlgorithm::Zstd) => {
+          ([(header::CONTENT_ENCODING, "zstd")], response).into_response()
+        }
+        None => response,
+      }
+    }
+  };
+
+  Ok(([(header::CONTENT_TYPE, content_type)], response).into_response())
+}
+
+/// Pulls a NAR through from the upstream of a proxy cache.
+///
+/// The NAR is decompressed and tee'd, so that it's streamed to the client
+/// while the API stores it as an entry. Storing carries on if the client goes
+/// away.
+#[tracing::instrument(skip(app_state, token_secret))]
+async fn pull_through_nar(
+  app_state: AppState,
+  store_name: String,
+  hash: LaxSlug,
+  url: String,
+  compression: Option<ProxiedCompression>,
+  token_id: Option<String>,
+  token_secret: Option<String>,
+) -> Result<Response, FetcherError> {
+  let response = app_state
+    .upstream
+    .get(&url)
+    .send()
+    .await
+    .and_then(|response| response.error_for_status())
+    .map_err(FetcherError::UpstreamError)?;
+
+  let data = Belt::from_stream(
+    response
+      .bytes_stream()
+      .map(|res| res.map_err(io::Error::other)),
+    Some(belt::DEFAULT_CHUNK_SIZE),
+  );
+  let data = match compression {
+    Some(ProxiedCompression::Xz) => {
+      data.adapt_from_comp(belt::CompressionAlgorithm::Xz)
+    }
+    Some(ProxiedCompression::Zstd) => {
+      data.adapt_from_comp(belt::CompressionAlgorithm::Zstd)
+    }
+    None => data,
+  };
+  let (to_client, to_store) = data.tee(PULL_THROUGH_BUFFER);
+
+  let credentials = token_id.zip(token_secret);
+  tokio::spawn(async move {
+    let result = store_pulled_through_nar(
+      &app_state,
+      &store_name,
+      &hash,
+      credentials,
+      to_store,
+    )
+    .await;
+    match result {
+      Ok(()) => tracing::info!(%store_name, %hash, "stored pulled-through NAR"),
+      // the client has already been served, so this is the only trace of it
+      Err(e) => tracing::error!(
+        %store_name,
+        %hash,
+        "failed to store pulled-through NAR, so the cache won't have it: {e}"
+      ),
+    }
+  });
+
+  tracing::info!("pulling NAR through from upstream");
+  Ok(Body::from_stream(to_client).into_response())
+}
+
+/// Sends a pulled-through NAR to the API to be stored as an entry.
+///
+/// The API only responds once the NAR is stored or rejected, so its errors,
+/// such as a NAR hash mismatch, are returned here.
+async fn store_pulled_through_nar(
+  app_state: &AppState,
+  store_name: &str,
+  hash: &LaxSlug,
+  credentials: Option<(String, String)>,
+  data: Belt,
+) -> Result<(), ProxyStoreError> {
+  let mut request = reqwest::Client::new()
+    .put(format!(
+      "{}/proxy-store/{store_name}/{hash}",
+      app_state.api_url
+    ))
+    .header(
+      mollusk::FETCHER_SECRET_HEADER,
+      app_state.fetcher_secret.as_ref(),
+    )
+    .body(reqwest::Body::wrap_stream(data));
+  if let Some((token_id, token_secret)) = credentials {
+    request = request.bearer_auth(format!("{token_id}:{token_secret}"));
+  }
+
+  let response = request.send().await.map_err(ProxyStoreError::ApiError)?;
+  let status = response.status();
+  if !status.is_success() {
+    let body = response.text().await.unwrap_or_default();
+    return Err(ProxyStoreError::Rejected { status, body });
+  }
+  Ok(())
+}
+
+/// Forwards an upload from Nix's HTTP binary cache store to the API.
+///
+/// This lets the same URL be used for `nix copy --from` and `nix copy --to`.
+#[tracing::instrument(skip(app_state, headers, body))]
+async fn upload_handler(
+  State(app_state): State<AppState>,
+  Path((store_name, path)): Path<(String, String)>,
+  headers: HeaderMap,
+  body: Body,
+) -> Result<Response, ExternalApiError> {
+  let (token_id, token_secret) = token_from_headers(&headers);
+
+  let client = reqwest::Client::new();
+  let mut request = client
+    .put(format!(
+      "{}/binary-cache-upload/{store_name}/{path}",
+      app_state.api_url
+    ))
+    .body(reqwest::Body::wrap_stream(body.into_data_stream()));
+  if let (Some(token_id), Some(token_secret)) = (token_id, token_secret) {
+    request = request.bearer_auth(format!("{token_id}:{token_secret}"));
+  }
+  let response = request.send().await.map_err(FetcherError::ApiError)?;
+
+  // relay the API's response as-is, so its errors reach the client
+  let status = response.status();
+  let body = response.bytes().await.map_err(FetcherError::ApiError)?;
+  Ok((status, body).into_response())
+}
+
 #[tracing::instrument(skip(client))]
 async fn fetch_path_from_client(
   client: impl Deref<Target = DynStorageClient>,
@@ -94,6 +412,40 @@ async fn fetch_path_from_client(
   Ok(Body::from_stream(stream).into_response())
 }
 
+/// Serves a single file out of a stored NAR.
+///
+/// Uncompressed NARs are read with a range read. Compressed ones have to be
+/// decompressed from the start, but only the file's contents are sent.
+#[tracing::instrument(skip(client))]
+async fn fetch_nar_member_from_client(
+  client: impl Deref<Target = DynStorageClient>,
+  path: dvf::StorePath,
+  compression: Option<dvf::CompressionAlgorithm>,
+  offset: u64,
+  size: u64,
+) -> Result<Response, FetcherError> {
+  // the error type here is `Infalliable`
+  let path = PathBuf::from_str(path.as_ref()).unwrap();
+
+  tracing::info!("fetching file from NAR");
+  let stream = match compression {
+    None => client.read_range(&path, offset, size).await?,
+    Some(dvf::CompressionAlgorithm::Zstd) => {
+      let mut reader = client
+        .read(&path)
+        .await?
+        .adapt_from_comp(storage::belt::CompressionAlgorithm::Zstd)
+        .to_async_buf_read();
+      tokio::io::copy(&mut (&mut reader).take(offset), &mut tokio::io::sink())
+        .await
+        .map_err(storage::ReadError::IoError)?;
+      storage::belt::Belt::from_async_buf_read(reader.take(size), None)
+    }
+  };
+
+  Ok(Body::from_stream(stream).into_response())
+}
+
 #[tokio::main]
 async fn main() -> miette::Result<()> {
   let filter = tracing_subscriber::EnvFilter::try_from_default_env()
@@ -102,7 +454,17 @@ async fn main() -> miette::Result<()> {
 
   art::ascii_art!("../../media/ascii_logo.png");
 
-  let app = Router::new().route("/:name/*path", get(fetch_handler));
+  let fetcher_secret = std::env::var("FETCHER_SECRET")
+    .ok()
+    .filter(|s| !s.is_empty())
+    .ok_or_else(|| {
+      miette::miette!("missing environment variable: `FETCHER_SECRET`")
+    })?;
+  let app_state = AppState::new(fetcher_secret.into(), API_URL.into());
+
+  let app = Router::new()
+    .route("/:name/*path", get(fetch_handler).put(upload_handler))
+    .with_state(app_state);
 
   let bind_address = "0.0.0.0:4000";
   let listener = tokio::net::TcpListener::bind(bind_address).await.unwrap();
@@ -112,3 +474,116 @@ async fn main() -> miette::Result<()> {
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Bytes, http::StatusCode, routing::put};
+  use tokio::sync::mpsc;
+
+  use super::*;
+
+  const NAR: &[u8] = b"nix-archive-1 stand-in contents";
+
+  /// Starts a stand-in server for the given routes on a local port. Returns
+  /// its URL.
+  async fn stand_in(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+  }
+
+  /// Starts a stand-in API that answers `/proxy-store` with the given status,
+  /// and sends on the headers and body of each request it gets.
+  async fn stand_in_api(
+    status: StatusCode,
+  ) -> (String, mpsc::UnboundedReceiver<(HeaderMap, Bytes)>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+      "/proxy-store/:name/:hash",
+      put(move |headers: HeaderMap, body: Bytes| async move {
+        tx.send((headers, body)).unwrap();
+        (status, "stand-in response")
+      }),
+    );
+    (stand_in(app).await, rx)
+  }
+
+  fn state(api_url: &str) -> AppState {
+    AppState::new("fetcher-secret".into(), api_url.into())
+  }
+
+  #[tokio::test]
+  async fn test_pull_through_nar_stores_what_it_serves() {
+    let upstream =
+      stand_in(Router::new().route("/nar/a.nar", get(|| async { NAR }))).await;
+    let (api_url, mut stored) = stand_in_api(StatusCode::OK).await;
+
+    let response = pull_through_nar(
+      state(&api_url),
+      "albert".to_string(),
+      LaxSlug::new("a".to_string()),
+      format!("{upstream}/nar/a.nar"),
+      None,
+      Some("token-id".to_string()),
+      Some("token-secret".to_string()),
+    )
+    .await
+    .unwrap();
+    let served = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    assert_eq!(served.as_ref(), NAR);
+
+    let (headers, body) = stored.recv().await.unwrap();
+    assert_eq!(body.as_ref(), NAR);
+    assert_eq!(headers[mollusk::FETCHER_SECRET_HEADER], "fetcher-secret");
+    assert_eq!(
+      headers[header::AUTHORIZATION],
+      "Bearer token-id:token-secret"
+    );
+  }
+
+  #[tokio::test]
+  async fn test_pull_through_nar_reports_missing_upstream() {
+    let upstream = stand_in(Router::new()).await;
+    let (api_url, _stored) = stand_in_api(StatusCode::OK).await;
+
+    let result = pull_through_nar(
+      state(&api_url),
+      "albert".to_string(),
+      LaxSlug::new("a".to_string()),
+      format!("{upstream}/nar/a.nar"),
+      None,
+      None,
+      None,
+    )
+    .await;
+    assert!(matches!(result, Err(FetcherError::UpstreamError(_))));
+  }
+
+  #[tokio::test]
+  async fn test_store_pulled_through_nar_reports_rejections() {
+    let (api_url, mut stored) = stand_in_api(StatusCode::BAD_REQUEST).await;
+
+    let result = store_pulled_through_nar(
+      &state(&api_url),
+      "albert",
+      &LaxSlug::new("a".to_string()),
+      None,
+      Belt::from_async_read(std::io::Cursor::new(NAR.to_vec()), None),
+    )
+    .await;
+    match result {
+      Err(ProxyStoreError::Rejected { status, body }) => {
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, "stand-in response");
+      }
+      other => panic!("expected a rejection, got {other:?}"),
+    }
+
+    let (headers, body) = stored.recv().await.unwrap();
+    assert_eq!(body.as_ref(), NAR);
+    assert!(!headers.contains_key(header::AUTHORIZATION));
+  }
+}
@@ -94,7 +94,8 @@ impl From<TransactionError> for KvError {
 pub struct OptimisticTransaction {
   store:     MockStore,
   read_set:  HashMap<Key, Option<Value>>,
-  write_set: HashMap<Key, Value>,
+  // `None` marks a deletion
+  write_set: HashMap<Key, Option<Value>>,
 }
 
 impl Drop for OptimisticTransaction {
@@ -131,7 +132,7 @@ impl KvPrimitive for OptimisticTransaction {
     self
       .read_set
       .insert(key.clone(), self.store.data.read().await.get(key).cloned());
-    self.write_set.insert(key.clone(), value);
+    self.write_set.insert(key.clone(), Some(value));
     Ok(())
   }
   async fn insert(&mut self, key: &Key, value: Value) -> KvResult<()> {
@@ -142,7 +143,7 @@ impl KvPrimitive for OptimisticTransaction {
       )));
     }
     self.read_set.insert(key.clone(), data.get(key).cloned());
-    self.write_set.insert(key.clone(), value.clone());
+    self.write_set.insert(key.clone(), Some(value.clone()));
     Ok(())
   }
   async fn scan(
@@ -175,11 +176,11 @@ impl KvPrimitive for OptimisticTransaction {
     Ok(result)
   }
   async fn delete(&mut self, key: &Key) -> KvResult<bool> {
-    self
-      .read_set
-      .insert(key.clone(), self.store.data.read().await.get(key).cloned());
-    self.write_set.insert(key.clone(), Value::new(vec![]));
-    Ok(true)
+    let value = self.store.data.read().await.get(key).cloned();
+    let exists = value.is_some();
+    self.read_set.insert(key.clone(), value);
+    self.write_set.insert(key.clone(), None);
+    Ok(exists)
   }
 }
 
@@ -188,7 +189,10 @@ impl KvTransaction for OptimisticTransaction {
     self.check_conflicts().await?;
     let mut data = self.store.data.write().await;
     for (key, value) in self.write_set.drain() {
-      data.insert(key, value);
+      match value {
+        Some(value) => data.insert(key, value),
+        None => data.remove(&key),
+      };
     }
     self.read_set.clear();
     Ok(())
@@ -205,7 +209,8 @@ impl KvTransaction for OptimisticTransaction {
 pub struct PessimisticTransaction {
   store:       MockStore,
   locked_keys: HashSet<Key>,
-  write_set:   HashMap<Key, Value>,
+  // `None` marks a deletion
+  write_set:   HashMap<Key, Option<Value>>,
 }
 
 impl Drop for PessimisticTransaction {
@@ -250,7 +255,7 @@ impl KvPrimitive for PessimisticTransaction {
   }
   async fn put(&mut self, key: &Key, value: Value) -> KvResult<()> {
     self.lock_key(key).await?;
-    self.write_set.insert(key.clone(), value);
+    self.write_set.insert(key.clone(), Some(value));
     Ok(())
   }
   async fn insert(&mut self, key: &Key, value: Value) -> KvResult<()> {
@@ -260,7 +265,7 @@ impl KvPrimitive for PessimisticTransaction {
         "Key already exists"
       )));
     }
-    self.write_set.insert(key.clone(), value);
+    self.write_set.insert(key.clone(), Some(value));
     Ok(())
   }
   async fn scan(
@@ -293,8 +298,8 @@ impl KvPrimitive for PessimisticTransaction {
   }
   async fn delete(&mut self, key: &Key) -> KvResult<bool> {
     self.lock_key(key).await?;
-    self.write_set.insert(key.clone(), Value::new(vec![]));
-    Ok(true)
+    self.write_set.insert(key.clone(), None);
+    Ok(self.store.data.read().await.contains_key(key))
   }
 }
 
@@ -302,7 +307,10 @@ impl KvTransaction for PessimisticTransaction {
   async fn commit(&mut self) -> KvResult<()> {
     let mut data = self.store.data.write().await;
     for (key, value) in self.write_set.drain() {
-      data.insert(key, value);
+      match value {
+        Some(value) => data.insert(key, value),
+        None => data.remove(&key),
+      };
     }
     self.unlock_keys().await;
     self.locked_keys.clear();
@@ -12,15 +12,25 @@ pub type CacheRecordId = RecordId<Cache>;
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Cache {
   /// The cache's ID.
-  pub id:         CacheRecordId,
+  pub id:              CacheRecordId,
   /// The cache's nickname.
-  pub name:       dvf::EntityName,
+  pub name:            dvf::EntityName,
   /// The cache's visibility
-  pub visibility: dvf::Visibility,
+  pub visibility:      dvf::Visibility,
   /// The cache's backing store.
-  pub store:      StoreRecordId,
+  pub store:           StoreRecordId,
   /// The [`Org`](crate::Org) the store belongs to.
-  pub org:        OrgRecordId,
+  pub org:             OrgRecordId,
+  /// Whether to index the debug info in uploaded NARs by build ID, to be
+  /// served under `debuginfo/`.
+  #[serde(default)]
+  pub index_debuginfo: bool,
+  /// The caches to defer to for paths they already have.
+  #[serde(default)]
+  pub upstreams:       Vec<UpstreamCache>,
+  /// The cache to pull paths through from when they're missing here.
+  #[serde(default)]
+  pub proxy:           Option<UpstreamCache>,
 }
 
 impl Model for Cache {
@@ -37,23 +47,74 @@ impl Model for Cache {
 #[derive(Clone, Debug)]
 pub struct CacheCreateRequest {
   /// The cache's nickname.
-  pub name:       dvf::EntityName,
+  pub name:            dvf::EntityName,
   /// The cache's visibility
-  pub visibility: dvf::Visibility,
+  pub visibility:      dvf::Visibility,
   /// The cache's backing store.
-  pub store:      StoreRecordId,
+  pub store:           StoreRecordId,
   /// The [`Org`](crate::Org) the store belongs to.
-  pub org:        OrgRecordId,
+  pub org:             OrgRecordId,
+  /// Whether to index the debug info in uploaded NARs by build ID, to be
+  /// served under `debuginfo/`.
+  pub index_debuginfo: bool,
+  /// The caches to defer to for paths they already have.
+  pub upstreams:       Vec<UpstreamCache>,
+  /// The cache to pull paths through from when they're missing here.
+  pub proxy:           Option<UpstreamCache>,
 }
 
 impl From<CacheCreateRequest> for Cache {
   fn from(req: CacheCreateRequest) -> Self {
     Self {
-      id:         Default::default(),
-      name:       req.name,
-      visibility: req.visibility,
-      store:      req.store,
-      org:        req.org,
+      id:              Default::default(),
+      name:            req.name,
+      visibility:      req.visibility,
+      store:           req.store,
+      org:             req.org,
+      index_debuginfo: req.index_debuginfo,
+      upstreams:       req.upstreams,
+      proxy:           req.proxy,
     }
   }
 }
+
+/// The request to change a cache's settings. Fields left as `None` are kept
+/// as they are.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CacheUpdateRequest {
+  /// The cache's new name.
+  #[serde(default)]
+  pub name:       Option<dvf::EntityName>,
+  /// The cache's new visibility.
+  #[serde(default)]
+  pub visibility: Option<dvf::Visibility>,
+  /// The cache's new backing store.
+  #[serde(default)]
+  pub store:      Option<StoreRecordId>,
+}
+
+impl CacheUpdateRequest {
+  /// Applies the changes to a cache.
+  pub fn apply(self, cache: Cache) -> Cache {
+    Cache {
+      name: self.name.unwrap_or(cache.name),
+      visibility: self.visibility.unwrap_or(cache.visibility),
+      store: self.store.unwrap_or(cache.store),
+      ..cache
+    }
+  }
+}
+
+/// An external binary cache that a [`Cache`] defers to, such as a mirror of
+/// `cache.nixos.org`.
+///
+/// Paths that the upstream cache has, signed with its public key, don't need
+/// to be pushed, and can be pulled through a cache that proxies it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamCache {
+  /// The URL of the upstream cache, e.g. `https://cache.nixos.org`.
+  pub url:        String,
+  /// The public key that the upstream cache signs paths with, in
+  /// `<key-name>:<base64>` form.
+  pub public_key: String,
+}
@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  CacheRecordId, EntryRecordId, LaxSlug, Model, OrgRecordId, RecordId,
+};
+
+/// The [`DebugInfo`] table name.
+pub const DEBUGINFO_TABLE_NAME: &str = "debuginfo";
+
+/// A debug info record ID.
+pub type DebugInfoRecordId = RecordId<DebugInfo>;
+
+/// A debug info file found in an [`Entry`](crate::Entry), by its build ID.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DebugInfo {
+  /// The debug info's ID.
+  pub id:       DebugInfoRecordId,
+  /// The build ID, in lowercase hex.
+  pub build_id: LaxSlug,
+  /// The entry whose NAR holds the debug info.
+  pub entry:    EntryRecordId,
+  /// The `/`-separated path of the debug info file in the entry's NAR.
+  pub member:   String,
+  /// The debug info's cache.
+  pub cache:    CacheRecordId,
+  /// The [`Org`](crate::Org) the debug info belongs to.
+  pub org:      OrgRecordId,
+}
+
+impl Model for DebugInfo {
+  const TABLE_NAME: &'static str = DEBUGINFO_TABLE_NAME;
+  const UNIQUE_INDICES: &'static [(
+    &'static str,
+    crate::SlugFieldGetter<Self>,
+  )] = &[("cache-id-build-id", |s| {
+    LaxSlug::new(format!("{}-{}", s.cache, s.build_id)).into()
+  })];
+
+  fn id(&self) -> DebugInfoRecordId { self.id }
+}
+
+/// The request to create a debug info record.
+#[derive(Clone, Debug)]
+pub struct DebugInfoCreateRequest {
+  /// The build ID, in lowercase hex.
+  pub build_id: LaxSlug,
+  /// The entry whose NAR holds the debug info.
+  pub entry:    EntryRecordId,
+  /// The `/`-separated path of the debug info file in the entry's NAR.
+  pub member:   String,
+  /// The debug info's cache.
+  pub cache:    CacheRecordId,
+  /// The [`Org`](crate::Org) the debug info belongs to.
+  pub org:      OrgRecordId,
+}
+
+impl From<DebugInfoCreateRequest> for DebugInfo {
+  fn from(req: DebugInfoCreateRequest) -> Self {
+    Self {
+      id:       Default::default(),
+      build_id: req.build_id,
+      entry:    req.entry,
+      member:   req.member,
+      cache:    req.cache,
+      org:      req.org,
+    }
+  }
+}
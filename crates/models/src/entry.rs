@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{CacheRecordId, LaxSlug, Model, OrgRecordId, RecordId};
+use crate::{
+  CacheRecordId, LaxSlug, Model, NixHash, OrgRecordId, RecordId, StorePath,
+};
 
 /// The [`Entry`] table name.
 pub const ENTRY_TABLE_NAME: &str = "entry";
@@ -12,15 +14,33 @@ pub type EntryRecordId = RecordId<Entry>;
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Entry {
   /// The entry's ID.
-  pub id:       EntryRecordId,
+  pub id:        EntryRecordId,
   /// The entry's path.
-  pub path:     LaxSlug,
+  pub path:      StorePath,
   /// The entry's compression status.
-  pub c_status: dvf::CompressionStatus,
+  pub c_status:  dvf::CompressionStatus,
+  /// The hash of the entry's uncompressed data, i.e. the NAR.
+  ///
+  /// This is only missing on entries written before hashes were recorded,
+  /// until they're migrated.
+  #[serde(default)]
+  pub nar_hash:  Option<NixHash>,
+  /// The hash of the entry's data as stored, after compression.
+  ///
+  /// This is only missing on entries written before hashes were recorded,
+  /// until they're migrated.
+  #[serde(default)]
+  pub file_hash: Option<NixHash>,
+  /// The entry's store path metadata.
+  ///
+  /// This is `None` if the uploader didn't report it, in which case the
+  /// entry's references are unknown.
+  #[serde(default)]
+  pub meta:      Option<EntryMetadata>,
   /// The entry's cache.
-  pub cache:    CacheRecordId,
+  pub cache:     CacheRecordId,
   /// The [`Org`](crate::Org) the store belongs to.
-  pub org:      OrgRecordId,
+  pub org:       OrgRecordId,
 }
 
 impl Model for Entry {
@@ -28,34 +48,77 @@ impl Model for Entry {
   const UNIQUE_INDICES: &'static [(
     &'static str,
     crate::SlugFieldGetter<Self>,
-  )] = &[("cache-id-path", |s| {
-    LaxSlug::new(format!("{}-{}", s.cache, s.path)).into()
-  })];
+  )] = &[
+    ("cache-id-path", |s| {
+      LaxSlug::new(format!("{}-{}", s.cache, s.path)).into()
+    }),
+    ("cache-id-path-hash", |s| {
+      LaxSlug::new(format!("{}-{}", s.cache, s.path_hash())).into()
+    }),
+  ];
 
   fn id(&self) -> EntryRecordId { self.id }
 }
 
+impl Entry {
+  /// Returns the hash part of the entry's store path.
+  ///
+  /// This is the part of the path that Nix uses to look up `.narinfo` files.
+  pub fn path_hash(&self) -> &str { self.path.hash_part() }
+
+  /// Returns whether the entry was written before its hashes were recorded,
+  /// and so still needs migrating.
+  pub fn is_legacy(&self) -> bool {
+    self.nar_hash.is_none() || self.file_hash.is_none()
+  }
+}
+
+/// The store path metadata of an entry, as reported by whoever uploaded it.
+///
+/// These are the fields of a `.narinfo` file that can't be derived from the
+/// NAR itself.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EntryMetadata {
+  /// The store paths that the entry references.
+  pub references: Vec<StorePath>,
+  /// The derivation that produced the entry, if known.
+  pub deriver:    Option<StorePath>,
+  /// Signatures over the entry, in `<key-name>:<base64>` form.
+  pub sigs:       Vec<String>,
+  /// The entry's content address, if it's content-addressed.
+  pub ca:         Option<String>,
+}
+
 /// The request to create an entry.
 #[derive(Clone, Debug)]
 pub struct EntryCreateRequest {
   /// The entry's path.
-  pub path:     LaxSlug,
+  pub path:      StorePath,
   /// The entry's compression status.
-  pub c_status: dvf::CompressionStatus,
+  pub c_status:  dvf::CompressionStatus,
+  /// The hash of the entry's uncompressed data, i.e. the NAR.
+  pub nar_hash:  NixHash,
+  /// The hash of the entry's data as stored, after compression.
+  pub file_hash: NixHash,
+  /// The entry's store path metadata, if the uploader reported it.
+  pub meta:      Option<EntryMetadata>,
   /// The entry's cache.
-  pub cache:    CacheRecordId,
+  pub cache:     CacheRecordId,
   /// The [`Org`](crate::Org) the store belongs to.
-  pub org:      OrgRecordId,
+  pub org:       OrgRecordId,
 }
 
 impl From<EntryCreateRequest> for Entry {
   fn from(req: EntryCreateRequest) -> Self {
     Self {
-      id:       Default::default(),
-      path:     req.path,
-      c_status: req.c_status,
-      cache:    req.cache,
-      org:      req.org,
+      id:        Default::default(),
+      path:      req.path,
+      c_status:  req.c_status,
+      nar_hash:  Some(req.nar_hash),
+      file_hash: Some(req.file_hash),
+      meta:      req.meta,
+      cache:     req.cache,
+      org:       req.org,
     }
   }
 }
@@ -13,9 +13,13 @@
 //! [`dvf`], or [`ulid`].
 
 mod cache;
+mod debuginfo;
 mod entry;
 mod org;
 mod perms;
+mod realisation;
+mod session;
+mod signing_key;
 mod store;
 mod token;
 mod user;
@@ -25,5 +29,6 @@ pub use model::*;
 pub use slugger::*;
 
 pub use self::{
-  cache::*, entry::*, org::*, perms::*, store::*, token::*, user::*,
+  cache::*, debuginfo::*, entry::*, org::*, perms::*, realisation::*,
+  session::*, signing_key::*, store::*, token::*, user::*,
 };
@@ -38,6 +38,17 @@ pub enum Permission {
   },
 }
 
+impl Display for Permission {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      Permission::CachePermission {
+        cache_id,
+        permission,
+      } => write!(f, "{permission} on cache {cache_id}"),
+    }
+  }
+}
+
 /// The types of permissions that can be granted to a `User` for a `Store`.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum CachePermissionType {
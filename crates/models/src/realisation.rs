@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  CacheRecordId, DrvOutput, LaxSlug, Model, OrgRecordId, RecordId, StorePath,
+};
+
+/// The [`Realisation`] table name.
+pub const REALISATION_TABLE_NAME: &str = "realisation";
+
+/// A realisation record ID.
+pub type RealisationRecordId = RecordId<Realisation>;
+
+/// A realisation of a content-addressed derivation output, i.e. the store path
+/// it was built to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Realisation {
+  /// The realisation's ID.
+  pub id:                     RealisationRecordId,
+  /// The derivation output that was realised.
+  pub drv_output:             DrvOutput,
+  /// The store path it was realised to.
+  pub out_path:               StorePath,
+  /// Signatures over the realisation, in `<key-name>:<base64>` form.
+  pub signatures:             Vec<String>,
+  /// The realisations of the derivation outputs this one depends on.
+  pub dependent_realisations: BTreeMap<DrvOutput, StorePath>,
+  /// The realisation's cache.
+  pub cache:                  CacheRecordId,
+  /// The [`Org`](crate::Org) the realisation belongs to.
+  pub org:                    OrgRecordId,
+}
+
+impl Model for Realisation {
+  const TABLE_NAME: &'static str = REALISATION_TABLE_NAME;
+  const UNIQUE_INDICES: &'static [(
+    &'static str,
+    crate::SlugFieldGetter<Self>,
+  )] = &[("cache-id-drv-output", |s| {
+    LaxSlug::new(format!("{}-{}", s.cache, s.drv_output)).into()
+  })];
+
+  fn id(&self) -> RealisationRecordId { self.id }
+}
+
+/// The request to create a realisation.
+#[derive(Clone, Debug)]
+pub struct RealisationCreateRequest {
+  /// The derivation output that was realised.
+  pub drv_output:             DrvOutput,
+  /// The store path it was realised to.
+  pub out_path:               StorePath,
+  /// Signatures over the realisation, in `<key-name>:<base64>` form.
+  pub signatures:             Vec<String>,
+  /// The realisations of the derivation outputs this one depends on.
+  pub dependent_realisations: BTreeMap<DrvOutput, StorePath>,
+  /// The realisation's cache.
+  pub cache:                  CacheRecordId,
+  /// The [`Org`](crate::Org) the realisation belongs to.
+  pub org:                    OrgRecordId,
+}
+
+impl From<RealisationCreateRequest> for Realisation {
+  fn from(req: RealisationCreateRequest) -> Self {
+    Self {
+      id:                     Default::default(),
+      drv_output:             req.drv_output,
+      out_path:               req.out_path,
+      signatures:             req.signatures,
+      dependent_realisations: req.dependent_realisations,
+      cache:                  req.cache,
+      org:                    req.org,
+    }
+  }
+}
@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::{Model, OrgRecordId, RecordId, UserRecordId};
+
+/// The [`Session`] table name.
+pub const SESSION_TABLE_NAME: &str = "session";
+
+/// A session record ID.
+pub type SessionRecordId = RecordId<Session>;
+
+/// A session, created when a user logs in.
+///
+/// A session acts with the permissions of its user, and stops being valid
+/// once it expires. Logging out expires it immediately.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+  /// The session's ID.
+  pub id:          SessionRecordId,
+  /// The keyed hash of the session's secret.
+  pub secret_hash: dvf::TokenSecretHash,
+  /// The user the session belongs to.
+  pub user:        UserRecordId,
+  /// The session's org.
+  pub org:         OrgRecordId,
+  /// When the session was created.
+  pub created_at:  DateTime<Utc>,
+  /// When the session stops being valid.
+  pub expires_at:  DateTime<Utc>,
+}
+
+impl Model for Session {
+  const TABLE_NAME: &'static str = SESSION_TABLE_NAME;
+  const UNIQUE_INDICES: &'static [(
+    &'static str,
+    crate::SlugFieldGetter<Self>,
+  )] = &[];
+
+  fn id(&self) -> SessionRecordId { self.id }
+}
+
+impl Session {
+  /// Check if the session has expired as of the given time.
+  pub fn expired(&self, now: DateTime<Utc>) -> bool { self.expires_at <= now }
+}
+
+/// A session create request.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionCreateRequest {
+  /// The keyed hash of the session's secret.
+  pub secret_hash: dvf::TokenSecretHash,
+  /// The user the session belongs to.
+  pub user:        UserRecordId,
+  /// The session's org.
+  pub org:         OrgRecordId,
+  /// When the session was created.
+  pub created_at:  DateTime<Utc>,
+  /// When the session stops being valid.
+  pub expires_at:  DateTime<Utc>,
+}
+
+impl From<SessionCreateRequest> for Session {
+  fn from(input: SessionCreateRequest) -> Self {
+    Self {
+      id:          SessionRecordId::default(),
+      secret_hash: input.secret_hash,
+      user:        input.user,
+      org:         input.org,
+      created_at:  input.created_at,
+      expires_at:  input.expires_at,
+    }
+  }
+}
+
+/// An `<id>:<secret>` pair presented by a client.
+///
+/// The ID can name either a [`Token`](crate::Token) or a [`Session`]. Both are
+/// ULIDs, so they never collide.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Credentials {
+  /// The ID of the token or session.
+  pub id:     Ulid,
+  /// The secret of the token or session.
+  pub secret: dvf::TokenSecret,
+}
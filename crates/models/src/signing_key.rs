@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{CacheRecordId, LaxSlug, Model, OrgRecordId, RecordId};
+
+/// The [`SigningKey`] table name.
+pub const SIGNING_KEY_TABLE_NAME: &str = "signing_key";
+
+/// A signing key record ID.
+pub type SigningKeyRecordId = RecordId<SigningKey>;
+
+/// A signing key, used to sign the paths served from a [`Cache`](crate::Cache).
+///
+/// Each cache has at most one signing key.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SigningKey {
+  /// The signing key's ID.
+  pub id:     SigningKeyRecordId,
+  /// The signing key's secret.
+  pub secret: dvf::SigningKeySecret,
+  /// The cache the signing key belongs to.
+  pub cache:  CacheRecordId,
+  /// The [`Org`](crate::Org) the signing key belongs to.
+  pub org:    OrgRecordId,
+}
+
+impl Model for SigningKey {
+  const TABLE_NAME: &'static str = SIGNING_KEY_TABLE_NAME;
+  const UNIQUE_INDICES: &'static [(
+    &'static str,
+    crate::SlugFieldGetter<Self>,
+  )] = &[("cache", |s| LaxSlug::new(s.cache.to_string()).into())];
+
+  fn id(&self) -> SigningKeyRecordId { self.id }
+}
+
+/// The request to create a signing key.
+#[derive(Clone, Debug)]
+pub struct SigningKeyCreateRequest {
+  /// The signing key's secret.
+  pub secret: dvf::SigningKeySecret,
+  /// The cache the signing key belongs to.
+  pub cache:  CacheRecordId,
+  /// The [`Org`](crate::Org) the signing key belongs to.
+  pub org:    OrgRecordId,
+}
+
+impl From<SigningKeyCreateRequest> for SigningKey {
+  fn from(req: SigningKeyCreateRequest) -> Self {
+    Self {
+      id:     Default::default(),
+      secret: req.secret,
+      cache:  req.cache,
+      org:    req.org,
+    }
+  }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::{Model, OrgRecordId, PermissionSet, RecordId, UserRecordId};
@@ -8,21 +9,49 @@ pub const TOKEN_TABLE_NAME: &str = "token";
 /// A token record ID.
 pub type TokenRecordId = RecordId<Token>;
 
+/// The name of the unique index that plaintext token secrets used to be
+/// stored under.
+///
+/// Its entries still hold plaintext secrets, so they're removed as the tokens
+/// they belong to have their secrets hashed.
+pub const TOKEN_LEGACY_SECRET_INDEX_NAME: &str = "secret";
+
 /// A token.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Token {
   /// The token's ID.
-  pub id:       TokenRecordId,
+  pub id:            TokenRecordId,
   /// The token's nickname.
-  pub nickname: dvf::EntityNickname,
-  /// The token's secret.
-  pub secret:   dvf::TokenSecret,
+  pub nickname:      dvf::EntityNickname,
+  /// The keyed hash of the token's secret.
+  ///
+  /// This is only missing on tokens that still have a `legacy_secret`.
+  #[serde(default)]
+  pub secret_hash:   Option<dvf::TokenSecretHash>,
+  /// The token's secret in plaintext, from before secrets were hashed.
+  ///
+  /// This is replaced with `secret_hash` the first time the token is
+  /// verified.
+  #[serde(rename = "secret", default, skip_serializing_if = "Option::is_none")]
+  pub legacy_secret: Option<dvf::TokenSecret>,
   /// The token's permissions.
-  pub perms:    PermissionSet,
+  pub perms:         PermissionSet,
   /// The token's owner (a User).
-  pub owner:    UserRecordId,
+  pub owner:         UserRecordId,
   /// THe token's org.
-  pub org:      OrgRecordId,
+  pub org:           OrgRecordId,
+  /// When the token stops being valid, if ever.
+  #[serde(default)]
+  pub expires_at:    Option<DateTime<Utc>>,
+  /// When the token was revoked, if it has been.
+  #[serde(default)]
+  pub revoked_at:    Option<DateTime<Utc>>,
+  /// When the token was last used.
+  ///
+  /// This is only updated periodically, so it can lag behind by a few
+  /// minutes.
+  #[serde(default)]
+  pub last_used_at:  Option<DateTime<Utc>>,
 }
 
 impl Model for Token {
@@ -30,7 +59,7 @@ impl Model for Token {
   const UNIQUE_INDICES: &'static [(
     &'static str,
     crate::SlugFieldGetter<Self>,
-  )] = &[("secret", |t| t.secret.clone().into_inner().into())];
+  )] = &[];
 
   fn id(&self) -> TokenRecordId { self.id }
 }
@@ -40,32 +69,46 @@ impl Token {
   pub fn authorized(&self, perms: &PermissionSet) -> bool {
     self.perms.contains_set(perms)
   }
+
+  /// Check if the token has been revoked.
+  pub fn revoked(&self) -> bool { self.revoked_at.is_some() }
+
+  /// Check if the token has expired as of the given time.
+  pub fn expired(&self, now: DateTime<Utc>) -> bool {
+    self.expires_at.is_some_and(|expires_at| expires_at <= now)
+  }
 }
 
 /// A token create request.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TokenCreateRequest {
   /// The token's nickname.
-  pub nickname: dvf::EntityNickname,
-  /// The token's secret.
-  pub secret:   dvf::TokenSecret,
+  pub nickname:    dvf::EntityNickname,
+  /// The keyed hash of the token's secret.
+  pub secret_hash: dvf::TokenSecretHash,
   /// The token's permissions.
-  pub perms:    PermissionSet,
+  pub perms:       PermissionSet,
   /// The token's owner (a User).
-  pub owner:    UserRecordId,
+  pub owner:       UserRecordId,
   /// The token's org.
-  pub org:      OrgRecordId,
+  pub org:         OrgRecordId,
+  /// When the token stops being valid, if ever.
+  pub expires_at:  Option<DateTime<Utc>>,
 }
 
 impl From<TokenCreateRequest> for Token {
   fn from(input: TokenCreateRequest) -> Self {
     Self {
-      id:       TokenRecordId::default(),
-      nickname: input.nickname,
-      secret:   input.secret,
-      perms:    input.perms,
-      owner:    input.owner,
-      org:      input.org,
+      id:            TokenRecordId::default(),
+      nickname:      input.nickname,
+      secret_hash:   Some(input.secret_hash),
+      legacy_secret: None,
+      perms:         input.perms,
+      owner:         input.owner,
+      org:           input.org,
+      expires_at:    input.expires_at,
+      revoked_at:    None,
+      last_used_at:  None,
     }
   }
 }
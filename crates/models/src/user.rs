@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{Model, OrgRecordId, RecordId};
+use crate::{Model, OrgRecordId, PermissionSet, RecordId};
 
 /// The [`User`] table name.
 pub const USER_TABLE_NAME: &str = "user";
@@ -12,11 +12,20 @@ pub type UserRecordId = RecordId<User>;
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct User {
   /// The user's ID.
-  pub id:   UserRecordId,
+  pub id:            UserRecordId,
   /// The user's name.
-  pub name: dvf::HumanName,
+  pub name:          dvf::HumanName,
+  /// The name the user logs in with.
+  pub username:      dvf::EntityName,
+  /// The argon2id hash of the user's password.
+  ///
+  /// Users without one can't log in.
+  #[serde(default)]
+  pub password_hash: Option<dvf::PasswordHash>,
+  /// The user's permissions, which their sessions act with.
+  pub perms:         PermissionSet,
   /// The user's org.
-  pub org:  OrgRecordId,
+  pub org:           OrgRecordId,
 }
 
 impl Model for User {
@@ -24,7 +33,42 @@ impl Model for User {
   const UNIQUE_INDICES: &'static [(
     &'static str,
     crate::SlugFieldGetter<Self>,
-  )] = &[];
+  )] = &[("username", |u| u.username.clone().into_inner().into())];
 
   fn id(&self) -> RecordId<User> { self.id }
 }
+
+impl User {
+  /// Check if the user has the given permissions.
+  pub fn authorized(&self, perms: &PermissionSet) -> bool {
+    self.perms.contains_set(perms)
+  }
+}
+
+/// A user create request.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserCreateRequest {
+  /// The user's name.
+  pub name:          dvf::HumanName,
+  /// The name the user logs in with.
+  pub username:      dvf::EntityName,
+  /// The argon2id hash of the user's password.
+  pub password_hash: Option<dvf::PasswordHash>,
+  /// The user's permissions.
+  pub perms:         PermissionSet,
+  /// The user's org.
+  pub org:           OrgRecordId,
+}
+
+impl From<UserCreateRequest> for User {
+  fn from(input: UserCreateRequest) -> Self {
+    Self {
+      id:            UserRecordId::default(),
+      name:          input.name,
+      username:      input.username,
+      password_hash: input.password_hash,
+      perms:         input.perms,
+      org:           input.org,
+    }
+  }
+}
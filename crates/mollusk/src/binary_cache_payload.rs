@@ -0,0 +1,203 @@
+use models::{
+  CompressionAlgorithm, DrvOutput, LaxSlug, StorageCredentials, StorePath,
+};
+use serde::{Deserialize, Serialize};
+
+/// A file served under the Nix binary cache protocol.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BinaryCacheFile {
+  /// The `nix-cache-info` file.
+  CacheInfo,
+  /// A `.narinfo` file, by the hash part of its store path.
+  NarInfo(LaxSlug),
+  /// A `.ls` listing of a NAR's contents, by the hash part of its store path.
+  Listing(LaxSlug),
+  /// A single file out of a NAR, as `file/<hash>/<member>`.
+  ///
+  /// This isn't part of the Nix protocol, but uses the same index as the `.ls`
+  /// listing to avoid downloading the whole NAR.
+  NarMember {
+    /// The hash part of the store path.
+    hash:   LaxSlug,
+    /// The `/`-separated path of the file inside the NAR.
+    member: String,
+  },
+  /// A NAR, by the key used in its narinfo `URL` field.
+  Nar {
+    /// The key of the NAR.
+    key:         LaxSlug,
+    /// The compression indicated by the requested file extension.
+    compression: Option<CompressionAlgorithm>,
+  },
+  /// A `.doi` realisation of a content-addressed derivation output, as
+  /// `realisations/<drv-output>.doi`.
+  Realisation(DrvOutput),
+  /// A link to separated debug info, as `debuginfo/<build-id>`.
+  DebugInfo(LaxSlug),
+}
+
+impl BinaryCacheFile {
+  /// Parses a path requested under a cache into a [`BinaryCacheFile`].
+  ///
+  /// Returns `None` if the path is not part of the binary cache protocol.
+  pub fn from_request_path(path: &str) -> Option<Self> {
+    if path == "nix-cache-info" {
+      return Some(Self::CacheInfo);
+    }
+    if let Some(hash) = path.strip_suffix(".narinfo") {
+      return valid_key(hash).map(Self::NarInfo);
+    }
+    if let Some(hash) = path.strip_suffix(".ls") {
+      return valid_key(hash).map(Self::Listing);
+    }
+    if let Some(rest) = path.strip_prefix("file/") {
+      let (hash, member) = rest.split_once('/')?;
+      return valid_key(hash).map(|hash| Self::NarMember {
+        hash,
+        member: member.to_string(),
+      });
+    }
+    if let Some(file) = path.strip_prefix("nar/") {
+      let (key, compression) = match file.strip_suffix(".nar.zst") {
+        Some(key) => (key, Some(CompressionAlgorithm::Zstd)),
+        None => (file.strip_suffix(".nar")?, None),
+      };
+      return valid_key(key).map(|key| Self::Nar { key, compression });
+    }
+    if let Some(id) = realisation_id(path) {
+      return Some(Self::Realisation(id));
+    }
+    if let Some(build_id) = path.strip_prefix("debuginfo/") {
+      return valid_key(build_id).map(Self::DebugInfo);
+    }
+    None
+  }
+
+  /// Returns the `URL` field value of the NAR with the given key.
+  pub fn nar_url(
+    key: &str,
+    compression: Option<CompressionAlgorithm>,
+  ) -> String {
+    match compression {
+      Some(CompressionAlgorithm::Zstd) => format!("nar/{key}.nar.zst"),
+      None => format!("nar/{key}.nar"),
+    }
+  }
+
+  /// Returns the `Content-Type` that the file should be served with.
+  pub fn content_type(&self) -> &'static str {
+    match self {
+      Self::CacheInfo => "text/x-nix-cache-info",
+      Self::NarInfo(_) => "text/x-nix-narinfo",
+      Self::Listing(_) => "application/json",
+      Self::NarMember { .. } => "application/octet-stream",
+      Self::Nar { .. } => "application/x-nix-nar",
+      Self::Realisation(_) => "application/json",
+      Self::DebugInfo(_) => "application/json",
+    }
+  }
+}
+
+/// A file uploaded under the Nix binary cache protocol, as done by
+/// `nix copy --to http://...`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BinaryCacheUpload {
+  /// A `.narinfo` file, by the hash part of its store path.
+  NarInfo(LaxSlug),
+  /// A NAR, by its file name under `nar/`, e.g. `<file-hash>.nar.xz`.
+  Nar(LaxSlug),
+  /// A `.doi` realisation, by its derivation output.
+  Realisation(DrvOutput),
+}
+
+impl BinaryCacheUpload {
+  /// Parses a path uploaded under a cache into a [`BinaryCacheUpload`].
+  ///
+  /// Returns `None` if the path is not an uploadable part of the binary cache
+  /// protocol. The NAR's compression is taken from its narinfo, so any
+  /// extension is accepted here.
+  pub fn from_request_path(path: &str) -> Option<Self> {
+    if let Some(hash) = path.strip_suffix(".narinfo") {
+      return valid_key(hash).map(Self::NarInfo);
+    }
+    if let Some(file) = path.strip_prefix("nar/") {
+      let (key, _) = file.split_once(".nar")?;
+      valid_key(key)?;
+      return valid_key(file).map(Self::Nar);
+    }
+    if let Some(id) = realisation_id(path) {
+      return Some(Self::Realisation(id));
+    }
+    None
+  }
+}
+
+/// Makes sure a key is a single, unmodified path segment.
+fn valid_key(key: &str) -> Option<LaxSlug> {
+  let slug = LaxSlug::new(key.to_string());
+  (!key.is_empty() && slug.as_ref() == key).then_some(slug)
+}
+
+/// Parses the derivation output out of a `realisations/<drv-output>.doi` path.
+fn realisation_id(path: &str) -> Option<DrvOutput> {
+  let id = path.strip_prefix("realisations/")?.strip_suffix(".doi")?;
+  DrvOutput::try_new(id.to_string()).ok()
+}
+
+/// The payload for serving a [`BinaryCacheFile`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BinaryCachePayload {
+  /// Text content to be served directly.
+  Text(String),
+  /// A NAR to be read from the cache's backing store.
+  Nar {
+    /// The path of the NAR in the store.
+    path:        StorePath,
+    /// The credentials of the store.
+    credentials: StorageCredentials,
+  },
+  /// A single file to be read out of a NAR in the cache's backing store.
+  NarMember {
+    /// The path of the NAR in the store.
+    path:        StorePath,
+    /// The credentials of the store.
+    credentials: StorageCredentials,
+    /// The compression the NAR was stored with.
+    compression: Option<CompressionAlgorithm>,
+    /// The offset of the file's contents in the uncompressed NAR.
+    offset:      u64,
+    /// The size of the file's contents.
+    size:        u64,
+  },
+  /// A NAR to be pulled through from the upstream of a proxy cache, and stored
+  /// in the cache while it's served.
+  ProxiedNar {
+    /// The hash part of the NAR's store path.
+    hash:        LaxSlug,
+    /// The URL of the NAR upstream.
+    url:         String,
+    /// The compression of the NAR upstream.
+    compression: Option<ProxiedCompression>,
+  },
+  /// A NAR listing to be read from the cache's backing store.
+  Listing {
+    /// The path of the listing in the store.
+    path:        String,
+    /// The credentials of the store.
+    credentials: StorageCredentials,
+    /// The compression the listing was stored with.
+    compression: Option<CompressionAlgorithm>,
+  },
+}
+
+/// The compression of a NAR pulled through from upstream.
+///
+/// It's decompressed on the way through, so only what we can decompress is
+/// supported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProxiedCompression {
+  /// `xz` compression.
+  Xz,
+  /// `zstd` compression.
+  Zstd,
+}
@@ -0,0 +1,80 @@
+use http::StatusCode;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  common::{
+    NonExistentCacheError, UnauthenticatedStoreAccessError,
+    UnauthorizedCacheAccessError,
+  },
+  ExpiredTokenError, InternalError, InvalidPathError, MalformedNarInfoError,
+  MalformedRealisationError, MissingPathError, MolluskError,
+  NarHashMismatchError, NonExistentTokenError, RealisationConflictError,
+  RevokedTokenError, UnsupportedCompressionError,
+};
+
+/// An error that occurs when uploading to a cache through the binary cache
+/// protocol.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+pub enum BinaryCacheUploadError {
+  /// No matching store was found.
+  #[error(transparent)]
+  NoMatchingStore(#[from] NonExistentCacheError),
+  /// The store access was unauthenticated (no token supplied).
+  #[error(transparent)]
+  UnauthenticatedStoreAccess(#[from] UnauthenticatedStoreAccessError),
+  /// The store access was unauthorized (token supplied but insufficient).
+  #[error(transparent)]
+  UnauthorizedStoreAccess(#[from] UnauthorizedCacheAccessError),
+  /// The supplied token does not exist.
+  #[error(transparent)]
+  NonExistentToken(#[from] NonExistentTokenError),
+  /// The supplied token has expired.
+  #[error(transparent)]
+  ExpiredToken(#[from] ExpiredTokenError),
+  /// The supplied token has been revoked.
+  #[error(transparent)]
+  RevokedToken(#[from] RevokedTokenError),
+  /// The path is not part of the binary cache protocol.
+  #[error(transparent)]
+  InvalidPath(#[from] InvalidPathError),
+  /// The NAR referenced by a narinfo hasn't been uploaded.
+  #[error(transparent)]
+  MissingPath(#[from] MissingPathError),
+  /// The narinfo is malformed.
+  #[error(transparent)]
+  MalformedNarInfo(#[from] MalformedNarInfoError),
+  /// The NAR's compression is not supported.
+  #[error(transparent)]
+  UnsupportedCompression(#[from] UnsupportedCompressionError),
+  /// The NAR doesn't match its narinfo.
+  #[error(transparent)]
+  NarHashMismatch(#[from] NarHashMismatchError),
+  /// The realisation is malformed.
+  #[error(transparent)]
+  MalformedRealisation(#[from] MalformedRealisationError),
+  /// A different realisation already exists for the derivation output.
+  #[error(transparent)]
+  RealisationConflict(#[from] RealisationConflictError),
+  /// Internal error
+  #[error(transparent)]
+  InternalError(#[from] InternalError),
+}
+
+crate::delegate_mollusk_error!(
+  BinaryCacheUploadError,
+  NoMatchingStore,
+  UnauthenticatedStoreAccess,
+  UnauthorizedStoreAccess,
+  NonExistentToken,
+  ExpiredToken,
+  RevokedToken,
+  InvalidPath,
+  MissingPath,
+  MalformedNarInfo,
+  UnsupportedCompression,
+  NarHashMismatch,
+  MalformedRealisation,
+  RealisationConflict,
+  InternalError,
+);
@@ -103,6 +103,55 @@ impl MolluskError for InvalidPathError {
   }
 }
 
+/// An error that occurs when the hash given is not the hash part of a store
+/// path.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The given hash is not a valid store path hash: {hash:?}")]
+pub struct InvalidStorePathHashError {
+  /// The invalid hash.
+  pub hash: String,
+}
+
+impl MolluskError for InvalidStorePathHashError {
+  fn status_code(&self) -> StatusCode { StatusCode::BAD_REQUEST }
+  fn slug(&self) -> &'static str { "invalid-store-path-hash" }
+  fn description(&self) -> String {
+    format!(
+      "The given hash {:?} is not valid. It must be the 32-character \
+       nix-base32 hash part of a store path.",
+      self.hash
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!("invalid store path hash: {:?}", self.hash);
+  }
+}
+
+/// An error that occurs when a request names more paths than it may.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The request names {count} paths, but at most {max} are allowed")]
+pub struct TooManyPathsError {
+  /// The number of paths in the request.
+  pub count: usize,
+  /// The most paths allowed in one request.
+  pub max:   usize,
+}
+
+impl MolluskError for TooManyPathsError {
+  fn status_code(&self) -> StatusCode { StatusCode::PAYLOAD_TOO_LARGE }
+  fn slug(&self) -> &'static str { "too-many-paths" }
+  fn description(&self) -> String {
+    format!(
+      "The request names {} paths, but at most {} are allowed. Split it into \
+       smaller requests.",
+      self.count, self.max
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!("too many paths: {} of at most {}", self.count, self.max);
+  }
+}
+
 /// An error that occurs when the token is malformed.
 #[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
 #[error("The token is malformed: {token:?}")]
@@ -141,6 +190,187 @@ impl MolluskError for NonExistentTokenError {
   }
 }
 
+/// An error that occurs when the token has expired.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The supplied token has expired: {token:?}")]
+pub struct ExpiredTokenError {
+  /// The expired token.
+  pub token: String,
+}
+
+impl MolluskError for ExpiredTokenError {
+  fn status_code(&self) -> StatusCode { StatusCode::UNAUTHORIZED }
+  fn slug(&self) -> &'static str { "expired-token" }
+  fn description(&self) -> String {
+    format!("The supplied token {:?} has expired.", self.token)
+  }
+  fn tracing(&self) {
+    tracing::warn!("supplied token has expired: {:?}", self.token);
+  }
+}
+
+/// An error that occurs when a route that only the fetcher may call is called
+/// without its secret.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("This route can only be called by the fetcher")]
+pub struct FetcherOnlyError;
+
+impl MolluskError for FetcherOnlyError {
+  fn status_code(&self) -> StatusCode { StatusCode::FORBIDDEN }
+  fn slug(&self) -> &'static str { "fetcher-only" }
+  fn description(&self) -> String {
+    "This route can only be called by the fetcher.".to_string()
+  }
+  fn tracing(&self) {
+    tracing::warn!("fetcher-only route called without the fetcher secret");
+  }
+}
+
+/// An error that occurs when the token has been revoked.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The supplied token has been revoked: {token:?}")]
+pub struct RevokedTokenError {
+  /// The revoked token.
+  pub token: String,
+}
+
+impl MolluskError for RevokedTokenError {
+  fn status_code(&self) -> StatusCode { StatusCode::UNAUTHORIZED }
+  fn slug(&self) -> &'static str { "revoked-token" }
+  fn description(&self) -> String {
+    format!("The supplied token {:?} has been revoked.", self.token)
+  }
+  fn tracing(&self) {
+    tracing::warn!("supplied token has been revoked: {:?}", self.token);
+  }
+}
+
+/// An error that occurs when a route requires a token but none was given.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("No token was supplied")]
+pub struct MissingTokenError;
+
+impl MolluskError for MissingTokenError {
+  fn status_code(&self) -> StatusCode { StatusCode::UNAUTHORIZED }
+  fn slug(&self) -> &'static str { "missing-token" }
+  fn description(&self) -> String {
+    "A token or session is required, as `Authorization: Bearer <id>:<secret>`."
+      .to_string()
+  }
+  fn tracing(&self) {
+    tracing::warn!("no token supplied");
+  }
+}
+
+/// An error that occurs when logging in with a wrong username or password.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The username or password is incorrect")]
+pub struct InvalidCredentialsError;
+
+impl MolluskError for InvalidCredentialsError {
+  fn status_code(&self) -> StatusCode { StatusCode::UNAUTHORIZED }
+  fn slug(&self) -> &'static str { "invalid-credentials" }
+  fn description(&self) -> String {
+    "The username or password is incorrect.".to_string()
+  }
+  fn tracing(&self) {
+    tracing::warn!("login with invalid credentials");
+  }
+}
+
+/// An error that occurs when a route requires a session but got a token.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The supplied token is not a session: {token:?}")]
+pub struct NotASessionError {
+  /// The token that was supplied.
+  pub token: String,
+}
+
+impl MolluskError for NotASessionError {
+  fn status_code(&self) -> StatusCode { StatusCode::BAD_REQUEST }
+  fn slug(&self) -> &'static str { "not-a-session" }
+  fn description(&self) -> String {
+    format!(
+      "The supplied token {:?} is an API token, not a session from logging in.",
+      self.token
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!("supplied token is not a session: {:?}", self.token);
+  }
+}
+
+/// An error that occurs when a token would get permissions its issuer doesn't
+/// hold.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The issuer doesn't hold the permissions: {permissions:?}")]
+pub struct ExcessTokenPermissionsError {
+  /// The permissions the issuer doesn't hold.
+  pub permissions: Vec<String>,
+}
+
+impl MolluskError for ExcessTokenPermissionsError {
+  fn status_code(&self) -> StatusCode { StatusCode::FORBIDDEN }
+  fn slug(&self) -> &'static str { "excess-token-permissions" }
+  fn description(&self) -> String {
+    format!(
+      "A token can't be given permissions you don't hold yourself: {}.",
+      self.permissions.join(", ")
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!(
+      "token issuance with excess permissions: {:?}",
+      self.permissions
+    );
+  }
+}
+
+/// An error that occurs when a nickname isn't already a valid slug.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The nickname is not a valid slug: {nickname:?}")]
+pub struct InvalidNicknameError {
+  /// The invalid nickname.
+  pub nickname: String,
+}
+
+impl MolluskError for InvalidNicknameError {
+  fn status_code(&self) -> StatusCode { StatusCode::BAD_REQUEST }
+  fn slug(&self) -> &'static str { "invalid-nickname" }
+  fn description(&self) -> String {
+    format!(
+      "The nickname {:?} is not valid. It must be non-empty, and only contain \
+       lowercase letters, digits and single dashes between them.",
+      self.nickname
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!("invalid nickname: {:?}", self.nickname);
+  }
+}
+
+/// An error that occurs when a token would expire before it's created.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The expiry time is in the past: {expires_at}")]
+pub struct PastTokenExpiryError {
+  /// The requested expiry time.
+  pub expires_at: String,
+}
+
+impl MolluskError for PastTokenExpiryError {
+  fn status_code(&self) -> StatusCode { StatusCode::BAD_REQUEST }
+  fn slug(&self) -> &'static str { "past-token-expiry" }
+  fn description(&self) -> String {
+    format!(
+      "The expiry time {} is in the past, so the token would never be valid.",
+      self.expires_at
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!("token expiry in the past: {}", self.expires_at);
+  }
+}
+
 /// An error that occurs when the path is missing.
 #[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
 #[error("The path is missing: {path:?}")]
@@ -150,7 +380,7 @@ pub struct MissingPathError {
 }
 
 impl MolluskError for MissingPathError {
-  fn status_code(&self) -> StatusCode { StatusCode::BAD_REQUEST }
+  fn status_code(&self) -> StatusCode { StatusCode::NOT_FOUND }
   fn slug(&self) -> &'static str { "missing-path" }
   fn description(&self) -> String {
     format!("The path {:?} is missing.", self.path)
@@ -159,3 +389,224 @@ impl MolluskError for MissingPathError {
     tracing::warn!("missing path: {:?}", self.path);
   }
 }
+
+/// An error that occurs when an uploaded `.narinfo` file is malformed.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The narinfo is malformed: {reason}")]
+pub struct MalformedNarInfoError {
+  /// Why the narinfo is malformed.
+  pub reason: String,
+}
+
+impl MolluskError for MalformedNarInfoError {
+  fn status_code(&self) -> StatusCode { StatusCode::BAD_REQUEST }
+  fn slug(&self) -> &'static str { "malformed-narinfo" }
+  fn description(&self) -> String {
+    format!("The narinfo is malformed: {}.", self.reason)
+  }
+  fn tracing(&self) {
+    tracing::warn!("malformed narinfo: {}", self.reason);
+  }
+}
+
+/// An error that occurs when an uploaded NAR uses an unsupported compression.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The compression is not supported: {compression:?}")]
+pub struct UnsupportedCompressionError {
+  /// The unsupported compression.
+  pub compression: String,
+}
+
+impl MolluskError for UnsupportedCompressionError {
+  fn status_code(&self) -> StatusCode { StatusCode::BAD_REQUEST }
+  fn slug(&self) -> &'static str { "unsupported-compression" }
+  fn description(&self) -> String {
+    format!(
+      "The compression {:?} is not supported; use \"xz\", \"zstd\", or \
+       \"none\".",
+      self.compression
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!("unsupported compression: {:?}", self.compression);
+  }
+}
+
+/// An error that occurs when an uploaded NAR doesn't match its narinfo.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error(
+  "The NAR for {path:?} does not match its narinfo: expected {expected}, got \
+   {actual}"
+)]
+pub struct NarHashMismatchError {
+  /// The store path that the NAR was uploaded for.
+  pub path:     String,
+  /// The NAR hash given in the narinfo.
+  pub expected: String,
+  /// The actual hash of the uploaded NAR.
+  pub actual:   String,
+}
+
+impl MolluskError for NarHashMismatchError {
+  fn status_code(&self) -> StatusCode { StatusCode::BAD_REQUEST }
+  fn slug(&self) -> &'static str { "nar-hash-mismatch" }
+  fn description(&self) -> String {
+    format!(
+      "The NAR uploaded for {:?} does not match its narinfo: expected {:?}, \
+       got {:?}.",
+      self.path, self.expected, self.actual
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!(
+      "NAR hash mismatch for {:?}: expected {:?}, got {:?}",
+      self.path,
+      self.expected,
+      self.actual
+    );
+  }
+}
+
+/// An error that occurs when an uploaded `.doi` realisation is malformed.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The realisation is malformed: {reason}")]
+pub struct MalformedRealisationError {
+  /// Why the realisation is malformed.
+  pub reason: String,
+}
+
+impl MolluskError for MalformedRealisationError {
+  fn status_code(&self) -> StatusCode { StatusCode::BAD_REQUEST }
+  fn slug(&self) -> &'static str { "malformed-realisation" }
+  fn description(&self) -> String {
+    format!("The realisation is malformed: {}.", self.reason)
+  }
+  fn tracing(&self) {
+    tracing::warn!("malformed realisation: {}", self.reason);
+  }
+}
+
+/// An error that occurs when an uploaded realisation conflicts with an
+/// existing one for the same derivation output.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error(
+  "A different realisation already exists for {id:?}: {existing_out_path}"
+)]
+pub struct RealisationConflictError {
+  /// The derivation output of the realisation.
+  pub id:                String,
+  /// The output path of the existing realisation.
+  pub existing_out_path: String,
+}
+
+impl MolluskError for RealisationConflictError {
+  fn status_code(&self) -> StatusCode { StatusCode::CONFLICT }
+  fn slug(&self) -> &'static str { "realisation-conflict" }
+  fn description(&self) -> String {
+    format!(
+      "A different realisation already exists for {:?}, with output path {:?}.",
+      self.id, self.existing_out_path
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!(
+      "conflicting realisation for {:?}: existing output path is {:?}",
+      self.id,
+      self.existing_out_path
+    );
+  }
+}
+
+/// An error that occurs when a cache would take a name that another cache
+/// already has.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("A cache named {0:?} already exists")]
+pub struct DuplicateCacheNameError(pub String);
+
+impl MolluskError for DuplicateCacheNameError {
+  fn status_code(&self) -> StatusCode { StatusCode::CONFLICT }
+  fn slug(&self) -> &'static str { "duplicate-cache-name" }
+  fn description(&self) -> String {
+    format!("A cache named {:?} already exists.", self.0)
+  }
+  fn tracing(&self) {
+    tracing::warn!("cache name is already taken: {:?}", self.0);
+  }
+}
+
+/// An error that occurs when a cache name isn't already a valid slug.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The cache name is not a valid slug: {0:?}")]
+pub struct InvalidCacheNameError(pub String);
+
+impl MolluskError for InvalidCacheNameError {
+  fn status_code(&self) -> StatusCode { StatusCode::BAD_REQUEST }
+  fn slug(&self) -> &'static str { "invalid-cache-name" }
+  fn description(&self) -> String {
+    format!(
+      "The cache name {:?} is not valid. It must be non-empty, and only \
+       contain lowercase letters, digits and single dashes between them.",
+      self.0
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!("invalid cache name: {:?}", self.0);
+  }
+}
+
+/// An error that occurs when the store does not exist.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The store does not exist: {0:?}")]
+pub struct NonExistentStoreError(pub String);
+
+impl MolluskError for NonExistentStoreError {
+  fn status_code(&self) -> StatusCode { StatusCode::NOT_FOUND }
+  fn slug(&self) -> &'static str { "missing-store" }
+  fn description(&self) -> String {
+    format!("The store {:?} does not exist.", self.0)
+  }
+  fn tracing(&self) {
+    tracing::warn!("requested store does not exist: {:?}", self.0);
+  }
+}
+
+/// An error that occurs when a cache would be backed by a store from another
+/// org.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The store belongs to another org: {0:?}")]
+pub struct ForeignStoreError(pub String);
+
+impl MolluskError for ForeignStoreError {
+  fn status_code(&self) -> StatusCode { StatusCode::FORBIDDEN }
+  fn slug(&self) -> &'static str { "foreign-store" }
+  fn description(&self) -> String {
+    format!(
+      "The store {:?} belongs to another org, so it can't back this cache.",
+      self.0
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!("requested store belongs to another org: {:?}", self.0);
+  }
+}
+
+/// An error that occurs when a cache still has entries, so it can't be
+/// deleted or moved to another store.
+#[derive(thiserror::Error, Diagnostic, Debug, Serialize, Deserialize)]
+#[error("The cache still has entries: {0:?}")]
+pub struct CacheNotEmptyError(pub String);
+
+impl MolluskError for CacheNotEmptyError {
+  fn status_code(&self) -> StatusCode { StatusCode::CONFLICT }
+  fn slug(&self) -> &'static str { "cache-not-empty" }
+  fn description(&self) -> String {
+    format!(
+      "The cache {:?} still has entries, so it can't be deleted or moved to \
+       another store.",
+      self.0
+    )
+  }
+  fn tracing(&self) {
+    tracing::warn!("cache still has entries: {:?}", self.0);
+  }
+}
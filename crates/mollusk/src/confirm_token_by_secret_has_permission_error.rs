@@ -3,7 +3,8 @@ use miette::Diagnostic;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-  InternalError, MalformedTokenSecretError, MolluskError, NonExistentTokenError,
+  ExpiredTokenError, InternalError, MalformedTokenSecretError, MolluskError,
+  NonExistentTokenError, RevokedTokenError,
 };
 
 /// An error that occurs while confirming a token (by secret) has a permission.
@@ -12,6 +13,12 @@ pub enum ConfirmTokenBySecretHasPermissionError {
   /// The token does not exist.
   #[error(transparent)]
   NonExistentToken(#[from] NonExistentTokenError),
+  /// The token has expired.
+  #[error(transparent)]
+  ExpiredToken(#[from] ExpiredTokenError),
+  /// The token has been revoked.
+  #[error(transparent)]
+  RevokedToken(#[from] RevokedTokenError),
   /// The token is malformed.
   #[error(transparent)]
   MalformedTokenSecret(#[from] MalformedTokenSecretError),
@@ -23,6 +30,8 @@ pub enum ConfirmTokenBySecretHasPermissionError {
 crate::delegate_mollusk_error!(
   ConfirmTokenBySecretHasPermissionError,
   NonExistentToken,
+  ExpiredToken,
+  RevokedToken,
   MalformedTokenSecret,
   InternalError
 );
@@ -1,6 +1,8 @@
 //! Provides standardized API schemas and errors for inter-service use.
 
 mod axum_json;
+mod binary_cache_payload;
+mod binary_cache_upload_error;
 mod common;
 mod confirm_token_by_secret_has_permission_error;
 mod creds_fetching_error;
@@ -13,12 +15,20 @@ use serde::Serialize;
 
 use self::axum_json::Json;
 pub use self::{
+  binary_cache_payload::{
+    BinaryCacheFile, BinaryCachePayload, BinaryCacheUpload, ProxiedCompression,
+  },
+  binary_cache_upload_error::BinaryCacheUploadError,
   common::*,
   confirm_token_by_secret_has_permission_error::ConfirmTokenBySecretHasPermissionError,
   creds_fetching_error::CredsFetchingError,
   prepare_fetch_payload_error::PrepareFetchPayloadError,
 };
 
+/// The header the fetcher proves itself to the API with, on routes that only
+/// it may call.
+pub const FETCHER_SECRET_HEADER: &str = "x-fetcher-secret";
+
 /// An error that can be directly returned to a user from an API route.
 pub trait MolluskError: Diagnostic + Sized {
   /// The [`StatusCode`] that the error should return.
@@ -7,8 +7,9 @@ use crate::{
     NonExistentCacheError, UnauthenticatedStoreAccessError,
     UnauthorizedCacheAccessError,
   },
-  InternalError, MalformedTokenSecretError, MissingPathError, MolluskError,
-  NonExistentTokenError,
+  ExpiredTokenError, InternalError, InvalidPathError,
+  MalformedTokenSecretError, MissingPathError, MolluskError,
+  NonExistentTokenError, RevokedTokenError,
 };
 
 /// An error that occurs when preparing to fetch a payload.
@@ -26,12 +27,21 @@ pub enum PrepareFetchPayloadError {
   /// The supplied token does not exist.
   #[error(transparent)]
   NonExistentToken(#[from] NonExistentTokenError),
+  /// The supplied token has expired.
+  #[error(transparent)]
+  ExpiredToken(#[from] ExpiredTokenError),
+  /// The supplied token has been revoked.
+  #[error(transparent)]
+  RevokedToken(#[from] RevokedTokenError),
   /// The token secret was malformed.
   #[error(transparent)]
   MalformedTokenSecret(#[from] MalformedTokenSecretError),
   /// The path is missing.
   #[error(transparent)]
   MissingPath(#[from] MissingPathError),
+  /// The path is not a valid store path.
+  #[error(transparent)]
+  InvalidPath(#[from] InvalidPathError),
   /// Internal error
   #[error(transparent)]
   InternalError(#[from] InternalError),
@@ -43,7 +53,10 @@ crate::delegate_mollusk_error!(
   UnauthenticatedStoreAccess,
   UnauthorizedStoreAccess,
   NonExistentToken,
+  ExpiredToken,
+  RevokedToken,
   MalformedTokenSecret,
   MissingPath,
+  InvalidPath,
   InternalError,
 );
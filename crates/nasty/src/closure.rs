@@ -0,0 +1,238 @@
+//! Utilities for computing the closure of store paths.
+//!
+//! References are read from the local Nix database, which lists every valid
+//! path in `ValidPaths` and the references between them in `Refs`.
+
+use std::{
+  collections::{HashMap, HashSet},
+  path::Path,
+};
+
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+
+use crate::StorePath;
+
+/// The location of the local Nix database.
+pub const NIX_DB_PATH: &str = "/nix/var/nix/db/db.sqlite";
+
+/// An error encountered while computing a closure.
+#[derive(thiserror::Error, Debug)]
+pub enum ClosureError {
+  /// The Nix database could not be read.
+  #[error("failed to read the Nix database: {0}")]
+  Database(#[from] rusqlite::Error),
+  /// The path is not valid in the Nix database.
+  #[error("the path is not valid in the Nix database: `{0}`")]
+  PathNotValid(StorePath),
+  /// The Nix database contains a malformed store path.
+  #[error("the Nix database contains a malformed store path: `{0}`")]
+  MalformedPath(String),
+}
+
+/// A read-only handle to a Nix database.
+#[derive(Debug)]
+pub struct NixDatabase {
+  conn: Connection,
+}
+
+impl NixDatabase {
+  /// Opens the local Nix database at [`NIX_DB_PATH`].
+  pub fn open_local() -> Result<Self, ClosureError> { Self::open(NIX_DB_PATH) }
+
+  /// Opens the Nix database at the given path.
+  pub fn open(path: impl AsRef<Path>) -> Result<Self, ClosureError> {
+    let conn = Connection::open_with_flags(
+      path,
+      OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    Ok(Self { conn })
+  }
+
+  /// Returns the direct references of a path, sorted and excluding the path
+  /// itself.
+  pub fn references(
+    &self,
+    path: &StorePath,
+  ) -> Result<Vec<StorePath>, ClosureError> {
+    let absolute_path = path.to_absolute_path();
+
+    let id: Option<i64> = self
+      .conn
+      .query_row(
+        "SELECT id FROM ValidPaths WHERE path = ?1",
+        [&absolute_path],
+        |row| row.get(0),
+      )
+      .optional()?;
+    let Some(id) = id else {
+      return Err(ClosureError::PathNotValid(path.clone()));
+    };
+
+    let mut statement = self.conn.prepare_cached(
+      "SELECT ValidPaths.path FROM Refs JOIN ValidPaths ON Refs.reference = \
+       ValidPaths.id WHERE Refs.referrer = ?1 AND Refs.reference != ?1",
+    )?;
+    let mut references = statement
+      .query_map([id], |row| row.get::<_, String>(0))?
+      .map(|reference| {
+        let reference = reference?;
+        StorePath::from_absolute_path(&reference)
+          .ok_or(ClosureError::MalformedPath(reference))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    references.sort();
+
+    Ok(references)
+  }
+
+  /// Computes the closure of the given paths.
+  ///
+  /// The result is topologically sorted so that every path comes after all of
+  /// its references, which is the order paths need to be uploaded in. Ties
+  /// are broken by path, so the output is deterministic.
+  pub fn closure(
+    &self,
+    roots: &[StorePath],
+  ) -> Result<Vec<StorePath>, ClosureError> {
+    let mut roots = roots.to_vec();
+    roots.sort();
+
+    let mut references: HashMap<StorePath, Vec<StorePath>> = HashMap::new();
+    let mut emitted = HashSet::new();
+    let mut sorted = Vec::new();
+
+    // depth-first, emitting each path once all of its references have been
+    // emitted. Nix doesn't allow reference cycles other than self-references,
+    // which are excluded above, so this always terminates.
+    let mut stack = roots
+      .into_iter()
+      .rev()
+      .map(|p| (p, false))
+      .collect::<Vec<_>>();
+    while let Some((path, expanded)) = stack.pop() {
+      if emitted.contains(&path) {
+        continue;
+      }
+      if expanded {
+        emitted.insert(path.clone());
+        sorted.push(path);
+        continue;
+      }
+
+      if !references.contains_key(&path) {
+        let refs = self.references(&path)?;
+        references.insert(path.clone(), refs);
+      }
+      stack.push((path.clone(), true));
+      for reference in references[&path].iter().rev() {
+        if !emitted.contains(reference) {
+          stack.push((reference.clone(), false));
+        }
+      }
+    }
+
+    Ok(sorted)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Builds a fixture database with the same schema as Nix's:
+  ///
+  /// ```text
+  /// hello -> glibc, hello (self)
+  /// bash  -> glibc, readline
+  /// readline -> ncurses
+  /// glibc -> libidn2
+  /// ```
+  fn fixture() -> (tempfile::TempDir, NixDatabase) {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("db.sqlite");
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn
+      .execute_batch(concat!(
+        "CREATE TABLE ValidPaths (id integer primary key autoincrement not ",
+        "null, path text unique not null, hash text not null, \
+         registrationTime ",
+        "integer not null, deriver text, narSize integer, ultimate integer, ",
+        "sigs text, ca text);\n",
+        "CREATE TABLE Refs (referrer integer not null, reference integer not ",
+        "null, primary key (referrer, reference));\n",
+      ))
+      .unwrap();
+
+    let paths = [
+      "0c7c96gvzr5z4ab6nhmcpbj5xrx7rhxl-glibc-2.39-52",
+      "1sjw6hbgn0qhl5h8nq1q8b3pq7w8xzrp-libidn2-2.3.7",
+      "2kmvbrw1qkzm5w5fkwnzs5ff5d9z2fdl-hello-2.12.1",
+      "3w8q0yhs6mlpdiwv7l53ycmhqlp7rl6j-bash-5.2p32",
+      "4b5dlsg64cwx4aplyq9bmnqb6jrinkjz-readline-8.2p10",
+      "5v6ch2bfy7dc5hjh9mdrq6j5zz4pi3ny-ncurses-6.4",
+    ];
+    for (i, path) in paths.iter().enumerate() {
+      conn
+        .execute(
+          "INSERT INTO ValidPaths (id, path, hash, registrationTime) VALUES \
+           (?1, ?2, 'sha256:0', 0)",
+          rusqlite::params![i as i64 + 1, format!("/nix/store/{path}")],
+        )
+        .unwrap();
+    }
+    for (referrer, reference) in
+      [(3, 1), (3, 3), (4, 1), (4, 5), (5, 6), (1, 2)]
+    {
+      conn
+        .execute("INSERT INTO Refs VALUES (?1, ?2)", [referrer, reference])
+        .unwrap();
+    }
+    drop(conn);
+
+    let db = NixDatabase::open(&db_path).unwrap();
+    (dir, db)
+  }
+
+  fn path(p: &str) -> StorePath { StorePath::try_new(p).unwrap() }
+
+  #[test]
+  fn test_references() {
+    let (_dir, db) = fixture();
+    assert_eq!(
+      db.references(&path("2kmvbrw1qkzm5w5fkwnzs5ff5d9z2fdl-hello-2.12.1"))
+        .unwrap(),
+      vec![path("0c7c96gvzr5z4ab6nhmcpbj5xrx7rhxl-glibc-2.39-52")]
+    );
+  }
+
+  #[test]
+  fn test_closure_is_topologically_sorted() {
+    let (_dir, db) = fixture();
+    let closure = db
+      .closure(&[
+        path("3w8q0yhs6mlpdiwv7l53ycmhqlp7rl6j-bash-5.2p32"),
+        path("2kmvbrw1qkzm5w5fkwnzs5ff5d9z2fdl-hello-2.12.1"),
+      ])
+      .unwrap();
+
+    assert_eq!(closure, vec![
+      path("1sjw6hbgn0qhl5h8nq1q8b3pq7w8xzrp-libidn2-2.3.7"),
+      path("0c7c96gvzr5z4ab6nhmcpbj5xrx7rhxl-glibc-2.39-52"),
+      path("2kmvbrw1qkzm5w5fkwnzs5ff5d9z2fdl-hello-2.12.1"),
+      path("5v6ch2bfy7dc5hjh9mdrq6j5zz4pi3ny-ncurses-6.4"),
+      path("4b5dlsg64cwx4aplyq9bmnqb6jrinkjz-readline-8.2p10"),
+      path("3w8q0yhs6mlpdiwv7l53ycmhqlp7rl6j-bash-5.2p32"),
+    ]);
+  }
+
+  #[test]
+  fn test_closure_of_invalid_path() {
+    let (_dir, db) = fixture();
+    let missing = path("6wmqk7ygvhypxadsdaai27gl6qfxv7za-missing");
+    assert!(matches!(
+      db.closure(std::slice::from_ref(&missing)),
+      Err(ClosureError::PathNotValid(p)) if p == missing
+    ));
+  }
+}
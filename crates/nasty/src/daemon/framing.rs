@@ -0,0 +1,118 @@
+//! Copies a single NAR off a stream.
+//!
+//! The daemon sends `NarFromPath` results unframed, so the only way to know
+//! where the NAR ends is to follow its structure.
+
+use std::io::{self, Read, Write};
+
+use crate::wire;
+
+/// The deepest directory nesting we'll follow.
+const MAX_DEPTH: usize = 256;
+
+fn malformed(msg: impl Into<String>) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+struct NarCopier<'a, R, W> {
+  reader: &'a mut R,
+  writer: &'a mut W,
+  copied: u64,
+}
+
+impl<R: Read, W: Write> NarCopier<'_, R, W> {
+  /// Copies a string token and returns its contents.
+  fn token(&mut self) -> io::Result<Vec<u8>> {
+    let bytes = wire::read_bytes(self.reader)?;
+    wire::write_bytes(self.writer, &bytes)?;
+    self.copied +=
+      8 + bytes.len() as u64 + wire::padding_len(bytes.len() as u64) as u64;
+    Ok(bytes)
+  }
+
+  fn expect(&mut self, expected: &str) -> io::Result<()> {
+    let token = self.token()?;
+    if token != expected.as_bytes() {
+      return Err(malformed(format!(
+        "expected `{expected}`, got `{}`",
+        String::from_utf8_lossy(&token)
+      )));
+    }
+    Ok(())
+  }
+
+  /// Copies file contents without holding them in memory.
+  fn contents(&mut self) -> io::Result<()> {
+    let len = wire::read_u64(self.reader)?;
+    wire::write_u64(self.writer, len)?;
+    let copied = io::copy(&mut self.reader.take(len), self.writer)?;
+    if copied != len {
+      return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    wire::read_padding(self.reader, len)?;
+    self.writer.write_all(&[0; 8][..wire::padding_len(len)])?;
+    self.copied += 8 + len + wire::padding_len(len) as u64;
+    Ok(())
+  }
+
+  fn node(&mut self, depth: usize) -> io::Result<()> {
+    if depth > MAX_DEPTH {
+      return Err(malformed("directories are nested too deeply"));
+    }
+
+    self.expect("(")?;
+    self.expect("type")?;
+    match self.token()?.as_slice() {
+      b"regular" => {
+        let mut token = self.token()?;
+        if token == b"executable" {
+          self.expect("")?;
+          token = self.token()?;
+        }
+        if token != b"contents" {
+          return Err(malformed("expected `contents`"));
+        }
+        self.contents()?;
+        self.expect(")")
+      }
+      b"symlink" => {
+        self.expect("target")?;
+        self.token()?;
+        self.expect(")")
+      }
+      b"directory" => loop {
+        match self.token()?.as_slice() {
+          b")" => return Ok(()),
+          b"entry" => {
+            self.expect("(")?;
+            self.expect("name")?;
+            self.token()?;
+            self.expect("node")?;
+            self.node(depth + 1)?;
+            self.expect(")")?;
+          }
+          _ => return Err(malformed("expected `entry` or `)`")),
+        }
+      },
+      other => Err(malformed(format!(
+        "unknown node type `{}`",
+        String::from_utf8_lossy(other)
+      ))),
+    }
+  }
+}
+
+/// Copies exactly one NAR from `reader` to `writer`, returning its size.
+pub(crate) fn copy_nar(
+  reader: &mut impl Read,
+  writer: &mut impl Write,
+) -> io::Result<u64> {
+  let mut copier = NarCopier {
+    reader,
+    writer,
+    copied: 0,
+  };
+  copier.expect("nix-archive-1")?;
+  copier.node(0)?;
+  Ok(copier.copied)
+}
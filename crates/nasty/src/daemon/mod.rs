@@ -0,0 +1,614 @@
+//! A client for the Nix daemon's worker protocol.
+//!
+//! The daemon listens on a Unix socket, usually [`DAEMON_SOCKET_PATH`]. After
+//! a handshake, the client sends operations and the daemon answers each with
+//! a stream of log messages followed by the result. Only the read-only
+//! operations we need are implemented.
+
+mod framing;
+
+use std::{
+  io::{self, BufReader, BufWriter, Read, Write},
+  os::unix::net::UnixStream,
+  path::Path,
+};
+
+use crate::{wire, NixHash, StorePath};
+
+/// The location of the local Nix daemon's socket.
+pub const DAEMON_SOCKET_PATH: &str = "/nix/var/nix/daemon-socket/socket";
+
+/// Sent by the client to start the handshake.
+const WORKER_MAGIC_1: u64 = 0x6e697863;
+/// Sent by the daemon in response to [`WORKER_MAGIC_1`].
+const WORKER_MAGIC_2: u64 = 0x6478696f;
+
+/// The newest protocol version this client speaks, `1.35`.
+const PROTOCOL_VERSION: u64 = 1 << 8 | 35;
+/// The oldest daemon protocol version this client accepts, `1.21`.
+const MIN_PROTOCOL_VERSION: u64 = 1 << 8 | 21;
+
+const fn minor(version: u64) -> u64 { version & 0xff }
+
+/// Worker operation codes.
+#[derive(Clone, Copy, Debug)]
+#[repr(u64)]
+enum Op {
+  IsValidPath = 1,
+  QueryPathInfo = 26,
+  QueryValidPaths = 31,
+  NarFromPath = 38,
+}
+
+/// Log message codes sent by the daemon before each result.
+mod stderr {
+  pub const NEXT: u64 = 0x6f6c6d67;
+  pub const WRITE: u64 = 0x64617416;
+  pub const LAST: u64 = 0x616c7473;
+  pub const ERROR: u64 = 0x63787470;
+  pub const START_ACTIVITY: u64 = 0x53545254;
+  pub const STOP_ACTIVITY: u64 = 0x53544f50;
+  pub const RESULT: u64 = 0x52534c54;
+}
+
+/// An error encountered while talking to the Nix daemon.
+#[derive(thiserror::Error, Debug)]
+pub enum DaemonError {
+  /// The connection failed.
+  #[error("failed to communicate with the Nix daemon: {0}")]
+  Io(#[from] io::Error),
+  /// The other end of the socket is not a Nix daemon.
+  #[error("the socket did not answer with the Nix daemon magic number")]
+  BadMagic,
+  /// The daemon speaks a protocol version we don't support.
+  #[error("unsupported daemon protocol version {}.{}", .0 >> 8, .0 & 0xff)]
+  UnsupportedVersion(u64),
+  /// The daemon sent a log message we don't understand.
+  #[error("unknown message from the daemon: {0:#x}")]
+  UnknownMessage(u64),
+  /// The daemon reported an error.
+  #[error("the Nix daemon reported an error: {0}")]
+  Remote(String),
+  /// The daemon sent a malformed store path.
+  #[error("the Nix daemon sent a malformed store path: `{0}`")]
+  InvalidPath(String),
+  /// The daemon sent a malformed hash.
+  #[error("the Nix daemon sent a malformed hash: `{0}`")]
+  InvalidHash(String),
+}
+
+/// Whether the daemon trusts this client, as reported during the handshake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trust {
+  /// The daemon trusts this client.
+  Trusted,
+  /// The daemon does not trust this client.
+  NotTrusted,
+  /// The daemon did not say.
+  Unknown,
+}
+
+/// The metadata of a valid store path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathInfo {
+  /// The store path.
+  pub path:              StorePath,
+  /// The derivation that produced this path.
+  pub deriver:           Option<StorePath>,
+  /// The hash of the path's NAR.
+  pub nar_hash:          NixHash,
+  /// The store paths this path references.
+  pub references:        Vec<StorePath>,
+  /// When the path was registered, in seconds since the epoch.
+  pub registration_time: u64,
+  /// The size of the path's NAR.
+  pub nar_size:          u64,
+  /// Whether the path was built locally rather than substituted.
+  pub ultimate:          bool,
+  /// Signatures of this path, in `<key-name>:<signature>` form.
+  pub sigs:              Vec<String>,
+  /// The content address of this path, if it's content-addressed.
+  pub ca:                Option<String>,
+}
+
+fn parse_store_path(path: String) -> Result<StorePath, DaemonError> {
+  StorePath::from_absolute_path(&path).ok_or(DaemonError::InvalidPath(path))
+}
+
+/// A connection to a Nix daemon.
+#[derive(Debug)]
+pub struct DaemonClient<R: Read, W: Write> {
+  reader:             BufReader<R>,
+  writer:             BufWriter<W>,
+  version:            u64,
+  daemon_nix_version: Option<String>,
+  trust:              Trust,
+}
+
+impl DaemonClient<UnixStream, UnixStream> {
+  /// Connects to the local Nix daemon at [`DAEMON_SOCKET_PATH`].
+  pub fn connect_local() -> Result<Self, DaemonError> {
+    Self::connect(DAEMON_SOCKET_PATH)
+  }
+
+  /// Connects to the Nix daemon listening on the given socket.
+  pub fn connect(socket: impl AsRef<Path>) -> Result<Self, DaemonError> {
+    let stream = UnixStream::connect(socket)?;
+    Self::handshake(stream.try_clone()?, stream)
+  }
+}
+
+impl<R: Read, W: Write> DaemonClient<R, W> {
+  /// Performs the handshake over an existing connection.
+  pub fn handshake(reader: R, writer: W) -> Result<Self, DaemonError> {
+    let mut reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(writer);
+
+    wire::write_u64(&mut writer, WORKER_MAGIC_1)?;
+    writer.flush()?;
+    if wire::read_u64(&mut reader)? != WORKER_MAGIC_2 {
+      return Err(DaemonError::BadMagic);
+    }
+    let daemon_version = wire::read_u64(&mut reader)?;
+    if daemon_version >> 8 != 1 || daemon_version < MIN_PROTOCOL_VERSION {
+      return Err(DaemonError::UnsupportedVersion(daemon_version));
+    }
+    let version = daemon_version.min(PROTOCOL_VERSION);
+
+    wire::write_u64(&mut writer, PROTOCOL_VERSION)?;
+    // no CPU affinity, and don't reserve space
+    wire::write_bool(&mut writer, false)?;
+    wire::write_bool(&mut writer, false)?;
+    writer.flush()?;
+
+    let daemon_nix_version = if minor(version) >= 33 {
+      Some(wire::read_string(&mut reader)?)
+    } else {
+      None
+    };
+    let trust = if minor(version) >= 35 {
+      match wire::read_u64(&mut reader)? {
+        1 => Trust::Trusted,
+        2 => Trust::NotTrusted,
+        _ => Trust::Unknown,
+      }
+    } else {
+      Trust::Unknown
+    };
+
+    let mut client = Self {
+      reader,
+      writer,
+      version,
+      daemon_nix_version,
+      trust,
+    };
+    client.process_stderr()?;
+    Ok(client)
+  }
+
+  /// Returns the negotiated protocol version, e.g. `0x123` for `1.35`.
+  pub fn protocol_version(&self) -> u64 { self.version }
+
+  /// Returns the daemon's Nix version, if it reported one.
+  pub fn daemon_nix_version(&self) -> Option<&str> {
+    self.daemon_nix_version.as_deref()
+  }
+
+  /// Returns whether the daemon trusts this client.
+  pub fn trust(&self) -> Trust { self.trust }
+
+  /// Reads log messages until the daemon signals the end of them, turning a
+  /// reported error into [`DaemonError::Remote`].
+  fn process_stderr(&mut self) -> Result<(), DaemonError> {
+    let r = &mut self.reader;
+    loop {
+      match wire::read_u64(r)? {
+        stderr::LAST => return Ok(()),
+        stderr::NEXT | stderr::WRITE => {
+          let message = wire::read_string(r)?;
+          tracing::debug!("nix daemon: {}", message.trim_end());
+        }
+        stderr::ERROR if minor(self.version) >= 26 => {
+          // type, level, name, message, position, traces
+          let _type = wire::read_string(r)?;
+          let _level = wire::read_u64(r)?;
+          let _name = wire::read_string(r)?;
+          let message = wire::read_string(r)?;
+          let _have_pos = wire::read_u64(r)?;
+          for _ in 0..wire::read_u64(r)? {
+            let _have_pos = wire::read_u64(r)?;
+            let _trace = wire::read_string(r)?;
+          }
+          return Err(DaemonError::Remote(message));
+        }
+        stderr::ERROR => {
+          let message = wire::read_string(r)?;
+          let _status = wire::read_u64(r)?;
+          return Err(DaemonError::Remote(message));
+        }
+        stderr::START_ACTIVITY => {
+          let _id = wire::read_u64(r)?;
+          let _level = wire::read_u64(r)?;
+          let _type = wire::read_u64(r)?;
+          let text = wire::read_string(r)?;
+          skip_fields(r)?;
+          let _parent = wire::read_u64(r)?;
+          if !text.is_empty() {
+            tracing::debug!("nix daemon: {text}");
+          }
+        }
+        stderr::STOP_ACTIVITY => {
+          let _id = wire::read_u64(r)?;
+        }
+        stderr::RESULT => {
+          let _id = wire::read_u64(r)?;
+          let _type = wire::read_u64(r)?;
+          skip_fields(r)?;
+        }
+        // this includes requests for data (`STDERR_READ`), as we never send
+        // the daemon any
+        message => return Err(DaemonError::UnknownMessage(message)),
+      }
+    }
+  }
+
+  /// Sends an operation and its arguments, then waits for the daemon to
+  /// finish logging.
+  fn send_op(
+    &mut self,
+    op: Op,
+    args: impl FnOnce(&mut BufWriter<W>, u64) -> io::Result<()>,
+  ) -> Result<(), DaemonError> {
+    wire::write_u64(&mut self.writer, op as u64)?;
+    args(&mut self.writer, self.version)?;
+    self.writer.flush()?;
+    self.process_stderr()
+  }
+
+  /// Checks whether a path is valid in the daemon's store.
+  pub fn is_valid_path(
+    &mut self,
+    path: &StorePath,
+  ) -> Result<bool, DaemonError> {
+    self.send_op(Op::IsValidPath, |w, _| {
+      wire::write_bytes(w, path.to_absolute_path().as_bytes())
+    })?;
+    Ok(wire::read_bool(&mut self.reader)?)
+  }
+
+  /// Returns the subset of the given paths that are valid in the daemon's
+  /// store.
+  pub fn query_valid_paths(
+    &mut self,
+    paths: &[StorePath],
+  ) -> Result<Vec<StorePath>, DaemonError> {
+    let paths = paths
+      .iter()
+      .map(|p| p.to_absolute_path())
+      .collect::<Vec<_>>();
+    self.send_op(Op::QueryValidPaths, |w, version| {
+      wire::write_strings(w, &paths)?;
+      if minor(version) >= 27 {
+        // don't try substituters
+        wire::write_bool(w, false)?;
+      }
+      Ok(())
+    })?;
+    wire::read_strings(&mut self.reader)?
+      .into_iter()
+      .map(parse_store_path)
+      .collect()
+  }
+
+  /// Returns the metadata of a path, or `None` if it isn't valid.
+  pub fn query_path_info(
+    &mut self,
+    path: &StorePath,
+  ) -> Result<Option<PathInfo>, DaemonError> {
+    self.send_op(Op::QueryPathInfo, |w, _| {
+      wire::write_bytes(w, path.to_absolute_path().as_bytes())
+    })?;
+
+    let r = &mut self.reader;
+    if !wire::read_bool(r)? {
+      return Ok(None);
+    }
+
+    let deriver = Some(wire::read_string(r)?)
+      .filter(|d| !d.is_empty())
+      .map(parse_store_path)
+      .transpose()?;
+    // the daemon sends the digest in base16, without the algorithm
+    let nar_hash = wire::read_string(r)?;
+    let nar_hash = format!("sha256:{nar_hash}")
+      .parse()
+      .map_err(|_| DaemonError::InvalidHash(nar_hash))?;
+    let references = wire::read_strings(r)?
+      .into_iter()
+      .map(parse_store_path)
+      .collect::<Result<_, _>>()?;
+    let registration_time = wire::read_u64(r)?;
+    let nar_size = wire::read_u64(r)?;
+    let ultimate = wire::read_bool(r)?;
+    let sigs = wire::read_strings(r)?;
+    let ca = Some(wire::read_string(r)?).filter(|ca| !ca.is_empty());
+
+    Ok(Some(PathInfo {
+      path: path.clone(),
+      deriver,
+      nar_hash,
+      references,
+      registration_time,
+      nar_size,
+      ultimate,
+      sigs,
+      ca,
+    }))
+  }
+
+  /// Streams the NAR of a path into `writer`, returning its size.
+  pub fn nar_from_path(
+    &mut self,
+    path: &StorePath,
+    writer: &mut impl Write,
+  ) -> Result<u64, DaemonError> {
+    self.send_op(Op::NarFromPath, |w, _| {
+      wire::write_bytes(w, path.to_absolute_path().as_bytes())
+    })?;
+    Ok(framing::copy_nar(&mut self.reader, writer)?)
+  }
+}
+
+/// Skips the fields attached to an activity or result message.
+fn skip_fields(r: &mut impl Read) -> Result<(), DaemonError> {
+  for _ in 0..wire::read_u64(r)? {
+    match wire::read_u64(r)? {
+      0 => {
+        wire::read_u64(r)?;
+      }
+      1 => {
+        wire::read_string(r)?;
+      }
+      other => return Err(DaemonError::UnknownMessage(other)),
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{os::unix::net::UnixListener, thread::JoinHandle};
+
+  use super::*;
+
+  const HELLO: &str = "kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1";
+  const GLIBC: &str = "yaz7pyf0ah88g2v505l38n0f3wg2vzdj-glibc-2.37-8";
+  const HASH_BASE16: &str =
+    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+  fn path(p: &str) -> StorePath { StorePath::try_new(p).unwrap() }
+
+  fn abs(p: &str) -> String { format!("/nix/store/{p}") }
+
+  /// Starts a stand-in daemon on a temporary socket, which runs the given
+  /// script against the first connection after answering the handshake.
+  fn scripted_daemon(
+    version: u64,
+    script: impl FnOnce(&mut UnixStream) + Send + 'static,
+  ) -> (tempfile::TempDir, JoinHandle<()>) {
+    let dir = tempfile::tempdir().unwrap();
+    let listener = UnixListener::bind(dir.path().join("socket")).unwrap();
+
+    let handle = std::thread::spawn(move || {
+      let (mut s, _) = listener.accept().unwrap();
+      assert_eq!(wire::read_u64(&mut s).unwrap(), WORKER_MAGIC_1);
+      wire::write_u64(&mut s, WORKER_MAGIC_2).unwrap();
+      wire::write_u64(&mut s, version).unwrap();
+      if version < MIN_PROTOCOL_VERSION {
+        return;
+      }
+      assert_eq!(wire::read_u64(&mut s).unwrap(), PROTOCOL_VERSION);
+      assert!(!wire::read_bool(&mut s).unwrap());
+      assert!(!wire::read_bool(&mut s).unwrap());
+      if minor(version) >= 33 {
+        wire::write_bytes(&mut s, b"2.24.9").unwrap();
+      }
+      if minor(version) >= 35 {
+        wire::write_u64(&mut s, 1).unwrap();
+      }
+      wire::write_u64(&mut s, stderr::LAST).unwrap();
+
+      script(&mut s);
+    });
+
+    (dir, handle)
+  }
+
+  fn connect(dir: &tempfile::TempDir) -> DaemonClient<UnixStream, UnixStream> {
+    DaemonClient::connect(dir.path().join("socket")).unwrap()
+  }
+
+  fn expect_op(s: &mut UnixStream, op: Op, path: &str) {
+    assert_eq!(wire::read_u64(s).unwrap(), op as u64);
+    assert_eq!(wire::read_string(s).unwrap(), abs(path));
+  }
+
+  #[test]
+  fn test_handshake() {
+    let (dir, daemon) = scripted_daemon(PROTOCOL_VERSION, |_| {});
+    let client = connect(&dir);
+    assert_eq!(client.protocol_version(), PROTOCOL_VERSION);
+    assert_eq!(client.daemon_nix_version(), Some("2.24.9"));
+    assert_eq!(client.trust(), Trust::Trusted);
+    daemon.join().unwrap();
+
+    // older daemons don't report their version or trust
+    let (dir, daemon) = scripted_daemon(1 << 8 | 32, |_| {});
+    let client = connect(&dir);
+    assert_eq!(client.protocol_version(), 1 << 8 | 32);
+    assert_eq!(client.daemon_nix_version(), None);
+    assert_eq!(client.trust(), Trust::Unknown);
+    daemon.join().unwrap();
+
+    let (dir, daemon) = scripted_daemon(1 << 8 | 20, |_| {});
+    assert!(matches!(
+      DaemonClient::connect(dir.path().join("socket")),
+      Err(DaemonError::UnsupportedVersion(0x114))
+    ));
+    daemon.join().unwrap();
+  }
+
+  #[test]
+  fn test_query_path_info() {
+    let (dir, daemon) = scripted_daemon(PROTOCOL_VERSION, |s| {
+      expect_op(s, Op::QueryPathInfo, HELLO);
+      // some logging that has to be skipped
+      wire::write_u64(s, stderr::START_ACTIVITY).unwrap();
+      wire::write_u64(s, 7).unwrap();
+      wire::write_u64(s, 3).unwrap();
+      wire::write_u64(s, 100).unwrap();
+      wire::write_bytes(s, b"querying info").unwrap();
+      wire::write_u64(s, 2).unwrap();
+      wire::write_u64(s, 0).unwrap();
+      wire::write_u64(s, 42).unwrap();
+      wire::write_u64(s, 1).unwrap();
+      wire::write_bytes(s, b"field").unwrap();
+      wire::write_u64(s, 0).unwrap();
+      wire::write_u64(s, stderr::NEXT).unwrap();
+      wire::write_bytes(s, b"hello from the daemon\n").unwrap();
+      wire::write_u64(s, stderr::STOP_ACTIVITY).unwrap();
+      wire::write_u64(s, 7).unwrap();
+      wire::write_u64(s, stderr::LAST).unwrap();
+
+      wire::write_bool(s, true).unwrap();
+      wire::write_bytes(s, b"").unwrap();
+      wire::write_bytes(s, HASH_BASE16.as_bytes()).unwrap();
+      wire::write_strings(s, &[abs(GLIBC), abs(HELLO)]).unwrap();
+      wire::write_u64(s, 1700000000).unwrap();
+      wire::write_u64(s, 226488).unwrap();
+      wire::write_bool(s, false).unwrap();
+      wire::write_strings(s, &["cache.nixos.org-1:c2lnbmF0dXJl"]).unwrap();
+      wire::write_bytes(s, b"").unwrap();
+
+      expect_op(s, Op::QueryPathInfo, GLIBC);
+      wire::write_u64(s, stderr::LAST).unwrap();
+      wire::write_bool(s, false).unwrap();
+    });
+
+    let mut client = connect(&dir);
+    let info = client.query_path_info(&path(HELLO)).unwrap().unwrap();
+    assert_eq!(info, PathInfo {
+      path:              path(HELLO),
+      deriver:           None,
+      nar_hash:          format!("sha256:{HASH_BASE16}").parse().unwrap(),
+      references:        vec![path(GLIBC), path(HELLO)],
+      registration_time: 1700000000,
+      nar_size:          226488,
+      ultimate:          false,
+      sigs:              vec!["cache.nixos.org-1:c2lnbmF0dXJl".to_string()],
+      ca:                None,
+    });
+    assert_eq!(client.query_path_info(&path(GLIBC)).unwrap(), None);
+    daemon.join().unwrap();
+  }
+
+  #[test]
+  fn test_valid_paths() {
+    let (dir, daemon) = scripted_daemon(PROTOCOL_VERSION, |s| {
+      expect_op(s, Op::IsValidPath, HELLO);
+      wire::write_u64(s, stderr::LAST).unwrap();
+      wire::write_bool(s, true).unwrap();
+
+      assert_eq!(wire::read_u64(s).unwrap(), Op::QueryValidPaths as u64);
+      assert_eq!(wire::read_strings(s).unwrap(), vec![abs(HELLO), abs(GLIBC)]);
+      assert!(!wire::read_bool(s).unwrap());
+      wire::write_u64(s, stderr::LAST).unwrap();
+      wire::write_strings(s, &[abs(GLIBC)]).unwrap();
+    });
+
+    let mut client = connect(&dir);
+    assert!(client.is_valid_path(&path(HELLO)).unwrap());
+    assert_eq!(
+      client
+        .query_valid_paths(&[path(HELLO), path(GLIBC)])
+        .unwrap(),
+      vec![path(GLIBC)]
+    );
+    daemon.join().unwrap();
+  }
+
+  #[test]
+  fn test_nar_from_path() {
+    let mut nar = Vec::new();
+    for token in [
+      "nix-archive-1",
+      "(",
+      "type",
+      "directory",
+      "entry",
+      "(",
+      "name",
+      "bin",
+      "node",
+      "(",
+      "type",
+      "regular",
+      "executable",
+      "",
+      "contents",
+    ] {
+      wire::write_bytes(&mut nar, token.as_bytes()).unwrap();
+    }
+    wire::write_bytes(&mut nar, b"#!/bin/sh\necho hello\n").unwrap();
+    for token in [
+      ")", ")", "entry", "(", "name", "link", "node", "(", "type", "symlink",
+      "target", "bin", ")", ")", ")",
+    ] {
+      wire::write_bytes(&mut nar, token.as_bytes()).unwrap();
+    }
+
+    let served = nar.clone();
+    let (dir, daemon) = scripted_daemon(PROTOCOL_VERSION, move |s| {
+      expect_op(s, Op::NarFromPath, HELLO);
+      wire::write_u64(s, stderr::LAST).unwrap();
+      // the NAR is sent unframed, directly followed by the next response
+      s.write_all(&served).unwrap();
+
+      expect_op(s, Op::IsValidPath, HELLO);
+      wire::write_u64(s, stderr::LAST).unwrap();
+      wire::write_bool(s, true).unwrap();
+    });
+
+    let mut client = connect(&dir);
+    let mut received = Vec::new();
+    let size = client.nar_from_path(&path(HELLO), &mut received).unwrap();
+    assert_eq!(received, nar);
+    assert_eq!(size, nar.len() as u64);
+    assert!(client.is_valid_path(&path(HELLO)).unwrap());
+    daemon.join().unwrap();
+  }
+
+  #[test]
+  fn test_remote_error() {
+    let (dir, daemon) = scripted_daemon(PROTOCOL_VERSION, |s| {
+      expect_op(s, Op::IsValidPath, HELLO);
+      wire::write_u64(s, stderr::ERROR).unwrap();
+      wire::write_bytes(s, b"Error").unwrap();
+      wire::write_u64(s, 0).unwrap();
+      wire::write_bytes(s, b"Error").unwrap();
+      wire::write_bytes(s, b"path is not allowed").unwrap();
+      wire::write_u64(s, 0).unwrap();
+      wire::write_u64(s, 1).unwrap();
+      wire::write_u64(s, 0).unwrap();
+      wire::write_bytes(s, b"while checking validity").unwrap();
+    });
+
+    let mut client = connect(&dir);
+    assert!(matches!(
+      client.is_valid_path(&path(HELLO)),
+      Err(DaemonError::Remote(message)) if message == "path is not allowed"
+    ));
+    daemon.join().unwrap();
+  }
+}
@@ -0,0 +1,138 @@
+//! Build-ID indexing of separated debug info.
+//!
+//! Packages built with separate debug info keep it at
+//! `lib/debug/.build-id/<xx>/<rest>.debug`, where `<xx><rest>` is the ELF
+//! build ID. Binary caches can serve `debuginfo/<build-id>` links pointing to
+//! those files, which tools like `dwarffs` use to find debug info by build ID.
+
+use serde::{Deserialize, Serialize};
+
+use crate::listing::{Listing, Node};
+
+/// The directory in a store path that debug info is kept in by build ID.
+const BUILD_ID_DIR: &str = "lib/debug/.build-id";
+
+/// A debug info file in a NAR, keyed by its build ID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugInfoFile {
+  /// The build ID, in lowercase hex.
+  pub build_id: String,
+  /// The `/`-separated path of the file in the NAR.
+  pub member:   String,
+}
+
+/// Finds the debug info files in a NAR's listing.
+///
+/// Like Nix, only two-digit directories holding 38-digit `.debug` files are
+/// considered, i.e. 160-bit build IDs.
+pub fn debuginfo_files(listing: &Listing) -> Vec<DebugInfoFile> {
+  let Some(Node::Directory { entries: dirs }) = listing.root.find(BUILD_ID_DIR)
+  else {
+    return Vec::new();
+  };
+
+  let mut files = Vec::new();
+  for (dir, node) in dirs {
+    let Node::Directory { entries } = node else {
+      continue;
+    };
+    if dir.len() != 2 || !is_lower_hex(dir) {
+      continue;
+    }
+    for (name, node) in entries {
+      let Some(rest) = name.strip_suffix(".debug") else {
+        continue;
+      };
+      if rest.len() != 38 || !is_lower_hex(rest) {
+        continue;
+      }
+      if !matches!(node, Node::Regular { .. }) {
+        continue;
+      }
+      files.push(DebugInfoFile {
+        build_id: format!("{dir}{rest}"),
+        member:   format!("{BUILD_ID_DIR}/{dir}/{name}"),
+      });
+    }
+  }
+  files
+}
+
+fn is_lower_hex(s: &str) -> bool {
+  s.bytes()
+    .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// The document served at `debuginfo/<build-id>`, pointing to the NAR and the
+/// file in it that holds the debug info.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DebugInfoLink {
+  /// The URL of the NAR, relative to the link.
+  pub archive: String,
+  /// The `/`-separated path of the file in the NAR.
+  pub member:  String,
+}
+
+impl DebugInfoLink {
+  /// Creates a link to a member of the NAR at the given narinfo `URL`.
+  pub fn new(nar_url: &str, member: String) -> Self {
+    Self {
+      archive: format!("../{nar_url}"),
+      member,
+    }
+  }
+
+  /// Renders the link as JSON, like Nix does.
+  pub fn to_json(&self) -> String {
+    serde_json::to_string(self).expect("debuginfo links always serialize")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_debuginfo_files() {
+    let listing = Listing::from_json(
+      concat!(
+        r#"{"version":1,"root":{"type":"directory","entries":{"lib":{"#,
+        r#""type":"directory","entries":{"debug":{"type":"directory","#,
+        r#""entries":{".build-id":{"type":"directory","entries":{"#,
+        r#""ab":{"type":"directory","entries":{"#,
+        r#""cdef0123456789abcdef0123456789abcdef01.debug":"#,
+        r#"{"type":"regular","size":3,"narOffset":800},"#,
+        r#""cdef0123456789abcdef0123456789abcdef01":"#,
+        r#"{"type":"symlink","target":"../../../bin/hello"}}},"#,
+        r#""zz":{"type":"directory","entries":{"#,
+        r#""cdef0123456789abcdef0123456789abcdef01.debug":"#,
+        r#"{"type":"regular","size":3,"narOffset":900}}}}}}}}}}}}"#,
+      )
+      .as_bytes(),
+    )
+    .unwrap();
+
+    assert_eq!(debuginfo_files(&listing), vec![DebugInfoFile {
+      build_id: "abcdef0123456789abcdef0123456789abcdef01".to_string(),
+      member:   "lib/debug/.build-id/ab/\
+                 cdef0123456789abcdef0123456789abcdef01.debug"
+        .to_string(),
+    }]);
+  }
+
+  #[test]
+  fn test_debuginfo_link_json() {
+    let link = DebugInfoLink::new(
+      "nar/1w1fff338fvdw53sqgamddn1b2xgds473pv6y13gizdbqjv4i5p3.nar.zst",
+      "lib/debug/.build-id/ab/cdef.debug".to_string(),
+    );
+    assert_eq!(
+      link.to_json(),
+      concat!(
+        r#"{"archive":"../nar/"#,
+        r#"1w1fff338fvdw53sqgamddn1b2xgds473pv6y13gizdbqjv4i5p3.nar.zst","#,
+        r#""member":"lib/debug/.build-id/ab/cdef.debug"}"#,
+      )
+    );
+  }
+}
@@ -5,5 +5,28 @@
 //!
 //! Expect more docs when this crate is more mature.
 
+pub use dvf::{nix32, DrvOutput, NixHash, StorePath};
+
+#[cfg(feature = "closure")]
+pub mod closure;
+#[cfg(feature = "daemon")]
+pub mod daemon;
+#[cfg(feature = "listing")]
+pub mod debuginfo;
+#[cfg(feature = "listing")]
+pub mod listing;
 #[cfg(feature = "nar")]
 pub mod nar;
+#[cfg(feature = "listing")]
+pub mod nar_index;
+pub mod narinfo;
+#[cfg(feature = "realisation")]
+pub mod realisation;
+#[cfg(feature = "serve")]
+pub mod serve;
+#[cfg(feature = "signing")]
+pub mod signing;
+#[cfg(all(test, feature = "listing"))]
+mod test_util;
+#[cfg(any(feature = "daemon", feature = "serve"))]
+mod wire;
@@ -0,0 +1,652 @@
+//! Builds NAR listings, i.e. `.ls` files, from a stream of NAR bytes.
+//!
+//! A listing describes the file tree inside a NAR without its contents, so
+//! tools like `nix store ls` can browse a path without downloading it. Each
+//! regular file records its `narOffset`, the position of its contents in the
+//! NAR, which also makes it possible to serve single files out of a NAR.
+//!
+//! [`ListingBuilder`] is fed the NAR in chunks as it goes by, so it never
+//! holds more than a single token in memory.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// The magic string at the start of every NAR.
+const NAR_MAGIC: &[u8] = b"nix-archive-1";
+/// The longest non-contents token we'll hold in memory. This covers names and
+/// symlink targets, which Nix limits to well under this.
+const MAX_TOKEN_LEN: u64 = 64 * 1024;
+/// The deepest directory nesting we'll follow.
+const MAX_DEPTH: usize = 256;
+
+/// A node in a NAR listing.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Node {
+  /// A regular file.
+  Regular {
+    /// The size of the file's contents.
+    size:       u64,
+    /// Whether the file is executable.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    executable: bool,
+    /// The offset of the file's contents in the NAR.
+    #[serde(rename = "narOffset")]
+    nar_offset: u64,
+  },
+  /// A symlink.
+  Symlink {
+    /// The symlink's target.
+    target: String,
+  },
+  /// A directory.
+  Directory {
+    /// The directory's entries, by name.
+    entries: BTreeMap<String, Node>,
+  },
+}
+
+impl Node {
+  /// Finds the node at a `/`-separated path below this one.
+  ///
+  /// Symlinks are not followed. An empty path returns this node.
+  pub fn find(&self, path: &str) -> Option<&Node> {
+    path
+      .split('/')
+      .filter(|segment| !segment.is_empty())
+      .try_fold(self, |node, segment| match node {
+        Node::Directory { entries } => entries.get(segment),
+        _ => None,
+      })
+  }
+}
+
+/// A NAR listing, as served in `.ls` files.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Listing {
+  /// The version of the listing format. Always 1.
+  pub version: u32,
+  /// The root node of the NAR.
+  pub root:    Node,
+}
+
+impl Listing {
+  /// Renders the listing as JSON, like Nix does.
+  pub fn to_json(&self) -> String {
+    serde_json::to_string(self).expect("listings always serialize")
+  }
+
+  /// Parses a listing from JSON.
+  pub fn from_json(json: &[u8]) -> Result<Self, serde_json::Error> {
+    serde_json::from_slice(json)
+  }
+}
+
+/// An error encountered while building a listing.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ListingError {
+  /// A token didn't match the NAR grammar.
+  #[error("expected {expected}, got `{found}`")]
+  UnexpectedToken {
+    /// What the grammar expected.
+    expected: &'static str,
+    /// The token that was found.
+    found:    String,
+  },
+  /// A name or symlink target was too long.
+  #[error("token of {0} bytes is too long")]
+  TokenTooLong(u64),
+  /// A token's padding contained non-zero bytes.
+  #[error("token padding is not zeroed")]
+  NonZeroPadding,
+  /// A directory entry had an invalid name.
+  #[error("invalid entry name: `{0}`")]
+  InvalidName(String),
+  /// Directory entries weren't in strictly ascending order.
+  #[error("entry `{0}` is out of order")]
+  UnsortedEntry(String),
+  /// A name or symlink target wasn't valid UTF-8.
+  #[error("a name or symlink target is not valid UTF-8")]
+  NonUtf8,
+  /// Directories were nested too deeply.
+  #[error("directories are nested too deeply")]
+  TooDeep,
+  /// The NAR ended early.
+  #[error("the NAR ended early")]
+  Truncated,
+  /// There was data after the end of the NAR.
+  #[error("there is data after the end of the NAR")]
+  TrailingData,
+}
+
+/// Where the lexer is within the current token.
+#[derive(Clone, Copy, Debug)]
+enum Lex {
+  /// Reading the 8-byte length prefix.
+  Len,
+  /// Reading a token into the buffer.
+  Str { remaining: u64, padding: u64 },
+  /// Skipping file contents.
+  Skip { remaining: u64, padding: u64 },
+  /// Skipping padding, then finishing the token.
+  Pad { remaining: u64, emit: bool },
+}
+
+/// The next token the NAR grammar expects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Expect {
+  Magic,
+  OpenNode,
+  TypeKey,
+  TypeValue,
+  RegularField,
+  ExecutableValue,
+  ContentsKey,
+  Contents,
+  TargetKey,
+  TargetValue,
+  EntryOrClose,
+  EntryOpen,
+  NameKey,
+  NameValue,
+  NodeKey,
+  CloseNode,
+  CloseEntry,
+  Done,
+}
+
+/// A directory that's still being read.
+#[derive(Debug, Default)]
+struct DirFrame {
+  /// The directory's name in its parent.
+  name:    Option<String>,
+  entries: BTreeMap<String, Node>,
+}
+
+/// Builds a [`Listing`] from NAR bytes, fed in chunks with
+/// [`update`](Self::update).
+#[derive(Debug)]
+pub struct ListingBuilder {
+  /// How many bytes have been consumed.
+  offset:       u64,
+  lex:          Lex,
+  buf:          Vec<u8>,
+  expect:       Expect,
+  dirs:         Vec<DirFrame>,
+  /// The name of the entry whose node is being read.
+  pending_name: Option<String>,
+  /// The leaf node being read.
+  leaf:         Option<Node>,
+  root:         Option<Node>,
+  error:        Option<ListingError>,
+}
+
+impl Default for ListingBuilder {
+  fn default() -> Self { Self::new() }
+}
+
+impl ListingBuilder {
+  /// Creates a builder that expects the start of a NAR.
+  pub fn new() -> Self {
+    Self {
+      offset:       0,
+      lex:          Lex::Len,
+      buf:          Vec::new(),
+      expect:       Expect::Magic,
+      dirs:         Vec::new(),
+      pending_name: None,
+      leaf:         None,
+      root:         None,
+      error:        None,
+    }
+  }
+
+  /// Feeds the next chunk of the NAR.
+  ///
+  /// Errors are held until [`finish`](Self::finish), and any data after an
+  /// error is ignored.
+  pub fn update(&mut self, mut data: &[u8]) {
+    while !data.is_empty() && self.error.is_none() {
+      if let Err(e) = self.step(&mut data) {
+        self.error = Some(e);
+      }
+    }
+  }
+
+  /// Finishes the listing, once the whole NAR has been fed.
+  pub fn finish(self) -> Result<Listing, ListingError> {
+    if let Some(error) = self.error {
+      return Err(error);
+    }
+    match (self.expect, self.root) {
+      (Expect::Done, Some(root)) => Ok(Listing { version: 1, root }),
+      _ => Err(ListingError::Truncated),
+    }
+  }
+
+  /// Consumes as much of `data` as the current lexer state wants.
+  fn step(&mut self, data: &mut &[u8]) -> Result<(), ListingError> {
+    if self.expect == Expect::Done {
+      return Err(ListingError::TrailingData);
+    }
+
+    match self.lex {
+      Lex::Len => {
+        let n = (8 - self.buf.len()).min(data.len());
+        self.consume(data, n, true);
+        if self.buf.len() == 8 {
+          let len = u64::from_le_bytes(self.buf[..].try_into().unwrap());
+          self.buf.clear();
+          self.start_token(len)?;
+        }
+      }
+      Lex::Str { remaining, padding } => {
+        let n = remaining.min(data.len() as u64) as usize;
+        self.consume(data, n, true);
+        self.lex = Lex::Str {
+          remaining: remaining - n as u64,
+          padding,
+        };
+        self.maybe_end_token()?;
+      }
+      Lex::Skip { remaining, padding } => {
+        let n = remaining.min(data.len() as u64) as usize;
+        self.consume(data, n, false);
+        self.lex = Lex::Skip {
+          remaining: remaining - n as u64,
+          padding,
+        };
+        self.maybe_end_token()?;
+      }
+      Lex::Pad { remaining, emit } => {
+        let n = remaining.min(data.len() as u64) as usize;
+        if data[..n].iter().any(|b| *b != 0) {
+          return Err(ListingError::NonZeroPadding);
+        }
+        self.consume(data, n, false);
+        self.lex = Lex::Pad {
+          remaining: remaining - n as u64,
+          emit,
+        };
+        self.maybe_end_token()?;
+      }
+    }
+    Ok(())
+  }
+
+  fn consume(&mut self, data: &mut &[u8], n: usize, keep: bool) {
+    if keep {
+      self.buf.extend_from_slice(&data[..n]);
+    }
+    *data = &data[n..];
+    self.offset += n as u64;
+  }
+
+  /// Handles a token's length prefix.
+  fn start_token(&mut self, len: u64) -> Result<(), ListingError> {
+    let padding = (8 - len % 8) % 8;
+
+    if self.expect == Expect::Contents {
+      self.leaf = Some(match self.leaf.take() {
+        Some(Node::Regular { executable, .. }) => Node::Regular {
+          size: len,
+          executable,
+          nar_offset: self.offset,
+        },
+        _ => unreachable!("contents are only expected in a regular file"),
+      });
+      self.expect = Expect::CloseNode;
+      self.lex = Lex::Skip {
+        remaining: len,
+        padding,
+      };
+    } else {
+      if len > MAX_TOKEN_LEN {
+        return Err(ListingError::TokenTooLong(len));
+      }
+      self.lex = Lex::Str {
+        remaining: len,
+        padding,
+      };
+    }
+    self.maybe_end_token()
+  }
+
+  /// Moves the lexer along if the current stage of the token is done.
+  fn maybe_end_token(&mut self) -> Result<(), ListingError> {
+    loop {
+      match self.lex {
+        Lex::Str {
+          remaining: 0,
+          padding,
+        } => {
+          self.lex = Lex::Pad {
+            remaining: padding,
+            emit:      true,
+          };
+        }
+        Lex::Skip {
+          remaining: 0,
+          padding,
+        } => {
+          self.lex = Lex::Pad {
+            remaining: padding,
+            emit:      false,
+          };
+        }
+        Lex::Pad { remaining: 0, emit } => {
+          self.lex = Lex::Len;
+          if emit {
+            let token = std::mem::take(&mut self.buf);
+            self.token(token)?;
+          }
+          return Ok(());
+        }
+        _ => return Ok(()),
+      }
+    }
+  }
+
+  /// Advances the grammar with a complete token.
+  fn token(&mut self, token: Vec<u8>) -> Result<(), ListingError> {
+    let expect = |expected: &'static str, wanted: &[u8]| {
+      if token == wanted {
+        Ok(())
+      } else {
+        Err(ListingError::UnexpectedToken {
+          expected,
+          found: String::from_utf8_lossy(&token).into_owned(),
+        })
+      }
+    };
+
+    self.expect = match self.expect {
+      Expect::Magic => {
+        expect("the NAR magic", NAR_MAGIC)?;
+        Expect::OpenNode
+      }
+      Expect::OpenNode => {
+        expect("`(`", b"(")?;
+        Expect::TypeKey
+      }
+      Expect::TypeKey => {
+        expect("`type`", b"type")?;
+        Expect::TypeValue
+      }
+      Expect::TypeValue => match &token[..] {
+        b"regular" => {
+          self.leaf = Some(Node::Regular {
+            size:       0,
+            executable: false,
+            nar_offset: 0,
+          });
+          Expect::RegularField
+        }
+        b"symlink" => Expect::TargetKey,
+        b"directory" => {
+          if self.dirs.len() >= MAX_DEPTH {
+            return Err(ListingError::TooDeep);
+          }
+          self.dirs.push(DirFrame {
+            name:    self.pending_name.take(),
+            entries: BTreeMap::new(),
+          });
+          Expect::EntryOrClose
+        }
+        _ => {
+          return Err(ListingError::UnexpectedToken {
+            expected: "a node type",
+            found:    String::from_utf8_lossy(&token).into_owned(),
+          })
+        }
+      },
+      Expect::RegularField => match &token[..] {
+        b"executable" => {
+          if let Some(Node::Regular { executable, .. }) = &mut self.leaf {
+            *executable = true;
+          }
+          Expect::ExecutableValue
+        }
+        _ => {
+          expect("`executable` or `contents`", b"contents")?;
+          Expect::Contents
+        }
+      },
+      Expect::ExecutableValue => {
+        expect("an empty string", b"")?;
+        Expect::ContentsKey
+      }
+      Expect::ContentsKey => {
+        expect("`contents`", b"contents")?;
+        Expect::Contents
+      }
+      Expect::Contents => {
+        unreachable!("contents are skipped, not read as a token")
+      }
+      Expect::TargetKey => {
+        expect("`target`", b"target")?;
+        Expect::TargetValue
+      }
+      Expect::TargetValue => {
+        let target =
+          String::from_utf8(token).map_err(|_| ListingError::NonUtf8)?;
+        self.leaf = Some(Node::Symlink { target });
+        Expect::CloseNode
+      }
+      Expect::EntryOrClose => match &token[..] {
+        b"entry" => Expect::EntryOpen,
+        _ => {
+          expect("`entry` or `)`", b")")?;
+          let frame = self.dirs.pop().expect("a directory is being read");
+          self.pending_name = frame.name;
+          self.attach(Node::Directory {
+            entries: frame.entries,
+          })
+        }
+      },
+      Expect::EntryOpen => {
+        expect("`(`", b"(")?;
+        Expect::NameKey
+      }
+      Expect::NameKey => {
+        expect("`name`", b"name")?;
+        Expect::NameValue
+      }
+      Expect::NameValue => {
+        let name =
+          String::from_utf8(token).map_err(|_| ListingError::NonUtf8)?;
+        if name.is_empty()
+          || name == "."
+          || name == ".."
+          || name.contains(['/', '\0'])
+        {
+          return Err(ListingError::InvalidName(name));
+        }
+        let frame = self.dirs.last().expect("a directory is being read");
+        if frame
+          .entries
+          .keys()
+          .next_back()
+          .is_some_and(|last| *last >= name)
+        {
+          return Err(ListingError::UnsortedEntry(name));
+        }
+        self.pending_name = Some(name);
+        Expect::NodeKey
+      }
+      Expect::NodeKey => {
+        expect("`node`", b"node")?;
+        Expect::OpenNode
+      }
+      Expect::CloseNode => {
+        expect("`)`", b")")?;
+        let leaf = self.leaf.take().expect("a leaf is being read");
+        self.attach(leaf)
+      }
+      Expect::CloseEntry => {
+        expect("`)`", b")")?;
+        Expect::EntryOrClose
+      }
+      Expect::Done => return Err(ListingError::TrailingData),
+    };
+    Ok(())
+  }
+
+  /// Attaches a finished node to its parent, returning what comes next.
+  fn attach(&mut self, node: Node) -> Expect {
+    match self.dirs.last_mut() {
+      Some(parent) => {
+        let name = self.pending_name.take().expect("entries have names");
+        parent.entries.insert(name, node);
+        Expect::CloseEntry
+      }
+      None => {
+        self.root = Some(node);
+        Expect::Done
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{sample_nar, token, tokens};
+
+  fn listing_in_chunks(nar: &[u8], chunk_size: usize) -> Listing {
+    let mut builder = ListingBuilder::new();
+    for chunk in nar.chunks(chunk_size) {
+      builder.update(chunk);
+    }
+    builder.finish().unwrap()
+  }
+
+  #[test]
+  fn test_listing_json() {
+    let nar = sample_nar();
+    let listing = listing_in_chunks(&nar, nar.len());
+
+    assert_eq!(
+      listing.to_json(),
+      concat!(
+        r#"{"version":1,"root":{"type":"directory","entries":{"#,
+        r#""bin":{"type":"directory","entries":{"hello":{"type":"regular","#,
+        r#""size":21,"executable":true,"narOffset":400}}},"#,
+        r#""latest":{"type":"symlink","target":"bin/hello"},"#,
+        r#""readme":{"type":"regular","size":2,"narOffset":840}}}}"#,
+      )
+    );
+
+    // the offsets point at the contents
+    let Some(Node::Regular {
+      size, nar_offset, ..
+    }) = listing.root.find("bin/hello")
+    else {
+      panic!("expected a regular file");
+    };
+    let start = *nar_offset as usize;
+    assert_eq!(
+      &nar[start..start + *size as usize],
+      b"#!/bin/sh\necho hello\n"
+    );
+
+    let round_trip = Listing::from_json(listing.to_json().as_bytes()).unwrap();
+    assert_eq!(round_trip, listing);
+  }
+
+  #[test]
+  fn test_listing_chunking_does_not_matter() {
+    let nar = sample_nar();
+    let whole = listing_in_chunks(&nar, nar.len());
+    for chunk_size in [1, 3, 7, 8, 13, 64] {
+      assert_eq!(listing_in_chunks(&nar, chunk_size), whole, "{chunk_size}");
+    }
+  }
+
+  #[test]
+  fn test_listing_single_file() {
+    let mut nar = Vec::new();
+    tokens(&mut nar, &[
+      b"nix-archive-1",
+      b"(",
+      b"type",
+      b"regular",
+      b"contents",
+      b"",
+      b")",
+    ]);
+    assert_eq!(listing_in_chunks(&nar, 5).root, Node::Regular {
+      size:       0,
+      executable: false,
+      nar_offset: 96,
+    });
+  }
+
+  #[test]
+  fn test_listing_errors() {
+    let finish = |nar: &[u8]| {
+      let mut builder = ListingBuilder::new();
+      builder.update(nar);
+      builder.finish().unwrap_err()
+    };
+
+    let nar = sample_nar();
+    assert_eq!(finish(&nar[..nar.len() - 8]), ListingError::Truncated);
+
+    let mut trailing = nar.clone();
+    token(&mut trailing, b"(");
+    assert_eq!(finish(&trailing), ListingError::TrailingData);
+
+    let mut bad_magic = Vec::new();
+    token(&mut bad_magic, b"nix-archive-2");
+    assert!(matches!(
+      finish(&bad_magic),
+      ListingError::UnexpectedToken { .. }
+    ));
+
+    let mut unsorted = Vec::new();
+    tokens(&mut unsorted, &[
+      b"nix-archive-1",
+      b"(",
+      b"type",
+      b"directory",
+      b"entry",
+      b"(",
+      b"name",
+      b"b",
+      b"node",
+      b"(",
+      b"type",
+      b"symlink",
+      b"target",
+      b"x",
+      b")",
+      b")",
+      b"entry",
+      b"(",
+      b"name",
+      b"a",
+    ]);
+    assert_eq!(
+      finish(&unsorted),
+      ListingError::UnsortedEntry("a".to_string())
+    );
+
+    let mut traversal = Vec::new();
+    tokens(&mut traversal, &[
+      b"nix-archive-1",
+      b"(",
+      b"type",
+      b"directory",
+      b"entry",
+      b"(",
+      b"name",
+      b"..",
+    ]);
+    assert_eq!(
+      finish(&traversal),
+      ListingError::InvalidName("..".to_string())
+    );
+  }
+}
@@ -0,0 +1,165 @@
+//! Random access into NARs.
+//!
+//! A [`NarIndex`] records where every file's contents live in a NAR, so that
+//! single files can be read out of it without unpacking the rest. It's built
+//! in one streaming pass with a [`ListingBuilder`], so it's the same data that
+//! gets served in `.ls` files.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::listing::{Listing, ListingBuilder, ListingError, Node};
+
+/// The size of the chunks read while indexing.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// An error encountered while indexing or looking up files in a NAR.
+#[derive(thiserror::Error, Debug)]
+pub enum NarIndexError {
+  /// Reading the NAR failed.
+  #[error("failed to read the NAR: {0}")]
+  Io(#[from] std::io::Error),
+  /// The NAR was malformed.
+  #[error("the NAR is malformed: {0}")]
+  Malformed(#[from] ListingError),
+  /// Nothing exists at the path.
+  #[error("no such file or directory in the NAR: `{0}`")]
+  NotFound(String),
+  /// The path exists but isn't a regular file.
+  #[error("not a regular file: `{0}`")]
+  NotARegularFile(String),
+}
+
+/// The location of a regular file's contents in a NAR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileRange {
+  /// The offset of the contents from the start of the NAR.
+  pub offset:     u64,
+  /// The size of the contents.
+  pub size:       u64,
+  /// Whether the file is executable.
+  pub executable: bool,
+}
+
+impl FileRange {
+  /// Returns a reader over the file's contents in a seekable NAR.
+  pub fn read_from<R: Read + Seek>(
+    &self,
+    mut nar: R,
+  ) -> std::io::Result<std::io::Take<R>> {
+    nar.seek(SeekFrom::Start(self.offset))?;
+    Ok(nar.take(self.size))
+  }
+}
+
+/// An index of the files in a NAR.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NarIndex {
+  listing: Listing,
+}
+
+impl NarIndex {
+  /// Indexes a NAR in one pass over `reader`.
+  pub fn from_reader(mut reader: impl Read) -> Result<Self, NarIndexError> {
+    let mut builder = ListingBuilder::new();
+    let mut buf = vec![0; READ_CHUNK_SIZE];
+    loop {
+      let n = match reader.read(&mut buf) {
+        Ok(0) => break,
+        Ok(n) => n,
+        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+        Err(e) => return Err(e.into()),
+      };
+      builder.update(&buf[..n]);
+    }
+    Ok(Self::from_listing(builder.finish()?))
+  }
+
+  /// Uses an existing listing as an index.
+  pub fn from_listing(listing: Listing) -> Self { Self { listing } }
+
+  /// Returns the listing the index is built on.
+  pub fn listing(&self) -> &Listing { &self.listing }
+
+  /// Finds the node at a `/`-separated path in the NAR.
+  pub fn node(&self, path: &str) -> Result<&Node, NarIndexError> {
+    self
+      .listing
+      .root
+      .find(path)
+      .ok_or_else(|| NarIndexError::NotFound(path.to_string()))
+  }
+
+  /// Finds the contents of the regular file at a `/`-separated path in the
+  /// NAR.
+  pub fn file(&self, path: &str) -> Result<FileRange, NarIndexError> {
+    match self.node(path)? {
+      Node::Regular {
+        size,
+        executable,
+        nar_offset,
+      } => Ok(FileRange {
+        offset:     *nar_offset,
+        size:       *size,
+        executable: *executable,
+      }),
+      _ => Err(NarIndexError::NotARegularFile(path.to_string())),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use super::*;
+  use crate::test_util::sample_nar;
+
+  #[test]
+  fn test_nar_index_reads_files() {
+    let nar = sample_nar();
+    let index = NarIndex::from_reader(Cursor::new(&nar)).unwrap();
+
+    let read = |path: &str| {
+      let range = index.file(path).unwrap();
+      let mut contents = String::new();
+      range
+        .read_from(Cursor::new(&nar))
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+      (contents, range.executable)
+    };
+
+    assert_eq!(read("readme"), ("hi".to_string(), false));
+    assert_eq!(
+      read("/bin/hello"),
+      ("#!/bin/sh\necho hello\n".to_string(), true)
+    );
+  }
+
+  #[test]
+  fn test_nar_index_lookup_errors() {
+    let index = NarIndex::from_reader(Cursor::new(sample_nar())).unwrap();
+
+    assert!(matches!(
+      index.file("bin"),
+      Err(NarIndexError::NotARegularFile(_))
+    ));
+    assert!(matches!(
+      index.file("latest"),
+      Err(NarIndexError::NotARegularFile(_))
+    ));
+    assert!(matches!(
+      index.file("bin/bye"),
+      Err(NarIndexError::NotFound(_))
+    ));
+    assert!(matches!(
+      index.file("readme/hello"),
+      Err(NarIndexError::NotFound(_))
+    ));
+    assert!(matches!(
+      NarIndex::from_reader(Cursor::new(&sample_nar()[..40])),
+      Err(NarIndexError::Malformed(ListingError::Truncated))
+    ));
+  }
+}
@@ -0,0 +1,487 @@
+//! Utilities for working with `.narinfo` files.
+//!
+//! A `.narinfo` file describes a single store path in a binary cache: where
+//! its NAR lives, how it's compressed, its hashes and sizes, and which other
+//! store paths it references. The format is a list of `Key: value` lines.
+
+use std::{fmt, str::FromStr};
+
+use dvf::{NixHash, StorePath};
+
+/// The compression applied to a NAR in a binary cache.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NarCompression {
+  /// No compression.
+  None,
+  /// `xz` compression.
+  Xz,
+  /// `bzip2` compression.
+  Bzip2,
+  /// `zstd` compression.
+  Zstd,
+  /// `brotli` compression.
+  Brotli,
+  /// `gzip` compression.
+  Gzip,
+  /// `lzip` compression.
+  Lzip,
+  /// `lz4` compression.
+  Lz4,
+}
+
+impl NarCompression {
+  /// Returns the name of the compression as it appears in a `.narinfo` file.
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Self::None => "none",
+      Self::Xz => "xz",
+      Self::Bzip2 => "bzip2",
+      Self::Zstd => "zstd",
+      Self::Brotli => "br",
+      Self::Gzip => "gzip",
+      Self::Lzip => "lzip",
+      Self::Lz4 => "lz4",
+    }
+  }
+}
+
+impl fmt::Display for NarCompression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for NarCompression {
+  type Err = NarInfoParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Ok(match s {
+      "none" => Self::None,
+      "xz" => Self::Xz,
+      "bzip2" => Self::Bzip2,
+      "zstd" => Self::Zstd,
+      "br" => Self::Brotli,
+      "gzip" => Self::Gzip,
+      "lzip" => Self::Lzip,
+      "lz4" => Self::Lz4,
+      _ => return Err(NarInfoParseError::UnknownCompression(s.to_string())),
+    })
+  }
+}
+
+/// The contents of a `.narinfo` file.
+///
+/// The [`Display`](fmt::Display) implementation produces the file exactly as
+/// Nix writes it, and [`FromStr`] parses it back. Hashes are always written
+/// in nix32 form, like Nix does. Narinfos from elsewhere should be parsed with
+/// [`NarInfo::parse_lenient`] instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NarInfo {
+  /// The store path. Written in full, e.g. `/nix/store/<hash>-<name>`.
+  pub store_path:  StorePath,
+  /// The URL of the NAR, relative to the binary cache root.
+  pub url:         String,
+  /// The compression of the NAR file.
+  pub compression: Option<NarCompression>,
+  /// The hash of the (compressed) NAR file.
+  pub file_hash:   Option<NixHash>,
+  /// The size of the (compressed) NAR file.
+  pub file_size:   Option<u64>,
+  /// The hash of the uncompressed NAR.
+  pub nar_hash:    NixHash,
+  /// The size of the uncompressed NAR.
+  pub nar_size:    u64,
+  /// The store paths this path references. Written without the store
+  /// directory.
+  pub references:  Vec<StorePath>,
+  /// The derivation that produced this path. Written without the store
+  /// directory.
+  pub deriver:     Option<StorePath>,
+  /// Signatures of this path, in `<key-name>:<signature>` form.
+  pub sigs:        Vec<String>,
+  /// The content address of this path, if it's content-addressed.
+  pub ca:          Option<String>,
+}
+
+/// An error encountered while parsing a `.narinfo` file.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum NarInfoParseError {
+  /// A line was not in `Key: value` form.
+  #[error("line {0} is not a `Key: value` pair")]
+  MalformedLine(usize),
+  /// The file did not end with a newline.
+  #[error("the file does not end with a newline")]
+  MissingTrailingNewline,
+  /// A key was not recognized.
+  #[error("unknown key: `{0}`")]
+  UnknownKey(String),
+  /// A key that may appear only once appeared more than once.
+  #[error("duplicate key: `{0}`")]
+  DuplicateKey(&'static str),
+  /// A required key was missing.
+  #[error("missing required key: `{0}`")]
+  MissingKey(&'static str),
+  /// A value was invalid for its key.
+  #[error("invalid value for `{key}`: `{value}`")]
+  InvalidValue {
+    /// The key the value belongs to.
+    key:   &'static str,
+    /// The invalid value.
+    value: String,
+  },
+  /// The compression was not recognized.
+  #[error("unknown compression: `{0}`")]
+  UnknownCompression(String),
+}
+
+/// Sets a field that may only be set once.
+fn set_once<T>(
+  field: &mut Option<T>,
+  key: &'static str,
+  value: T,
+) -> Result<(), NarInfoParseError> {
+  if field.replace(value).is_some() {
+    return Err(NarInfoParseError::DuplicateKey(key));
+  }
+  Ok(())
+}
+
+/// Parses a size field, rejecting anything but plain decimal digits.
+fn parse_size(
+  key: &'static str,
+  value: &str,
+) -> Result<u64, NarInfoParseError> {
+  let invalid = || NarInfoParseError::InvalidValue {
+    key,
+    value: value.to_string(),
+  };
+  if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+    return Err(invalid());
+  }
+  if value.len() > 1 && value.starts_with('0') {
+    return Err(invalid());
+  }
+  value.parse().map_err(|_| invalid())
+}
+
+/// Parses a hash field.
+fn parse_hash(
+  key: &'static str,
+  value: &str,
+) -> Result<NixHash, NarInfoParseError> {
+  value.parse().map_err(|_| NarInfoParseError::InvalidValue {
+    key,
+    value: value.to_string(),
+  })
+}
+
+/// Parses a store path without the store directory, as used in `References`
+/// and `Deriver`.
+fn parse_basename(
+  key: &'static str,
+  value: &str,
+) -> Result<StorePath, NarInfoParseError> {
+  StorePath::try_new(value).map_err(|_| NarInfoParseError::InvalidValue {
+    key,
+    value: value.to_string(),
+  })
+}
+
+/// Validates a content address, which must be in `<method>:<hash>` form.
+fn parse_ca(value: &str) -> Result<String, NarInfoParseError> {
+  match value.split_once(':') {
+    Some((method, hash)) if !method.is_empty() && !hash.is_empty() => {
+      Ok(value.to_string())
+    }
+    _ => Err(NarInfoParseError::InvalidValue {
+      key:   "CA",
+      value: value.to_string(),
+    }),
+  }
+}
+
+impl FromStr for NarInfo {
+  type Err = NarInfoParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> { Self::parse(s, true) }
+}
+
+impl NarInfo {
+  /// Parses a `.narinfo` file from another binary cache or an uploader.
+  ///
+  /// Unlike [`FromStr`], unknown keys are skipped rather than rejected, like
+  /// Nix does, since other caches may add their own. They're dropped from the
+  /// result.
+  pub fn parse_lenient(s: &str) -> Result<Self, NarInfoParseError> {
+    Self::parse(s, false)
+  }
+
+  fn parse(s: &str, strict: bool) -> Result<Self, NarInfoParseError> {
+    let body = s
+      .strip_suffix('\n')
+      .ok_or(NarInfoParseError::MissingTrailingNewline)?;
+
+    let mut store_path = None;
+    let mut url = None;
+    let mut compression = None;
+    let mut file_hash = None;
+    let mut file_size = None;
+    let mut nar_hash = None;
+    let mut nar_size = None;
+    let mut references = None;
+    let mut deriver = None;
+    let mut sigs = Vec::new();
+    let mut ca = None;
+
+    for (index, line) in body.split('\n').enumerate() {
+      let (key, value) = line
+        .split_once(": ")
+        .ok_or(NarInfoParseError::MalformedLine(index + 1))?;
+
+      match key {
+        "StorePath" => {
+          let parsed =
+            StorePath::from_absolute_path(value).ok_or_else(|| {
+              NarInfoParseError::InvalidValue {
+                key:   "StorePath",
+                value: value.to_string(),
+              }
+            })?;
+          set_once(&mut store_path, "StorePath", parsed)?;
+        }
+        "URL" => {
+          if value.is_empty() {
+            return Err(NarInfoParseError::InvalidValue {
+              key:   "URL",
+              value: value.to_string(),
+            });
+          }
+          set_once(&mut url, "URL", value.to_string())?;
+        }
+        "Compression" => {
+          set_once(&mut compression, "Compression", value.parse()?)?;
+        }
+        "FileHash" => {
+          set_once(&mut file_hash, "FileHash", parse_hash("FileHash", value)?)?;
+        }
+        "FileSize" => {
+          set_once(&mut file_size, "FileSize", parse_size("FileSize", value)?)?;
+        }
+        "NarHash" => {
+          set_once(&mut nar_hash, "NarHash", parse_hash("NarHash", value)?)?;
+        }
+        "NarSize" => {
+          set_once(&mut nar_size, "NarSize", parse_size("NarSize", value)?)?;
+        }
+        "References" => {
+          let parsed = value
+            .split(' ')
+            .filter(|r| !r.is_empty())
+            .map(|r| parse_basename("References", r))
+            .collect::<Result<Vec<_>, _>>()?;
+          set_once(&mut references, "References", parsed)?;
+        }
+        "Deriver" => {
+          set_once(&mut deriver, "Deriver", parse_basename("Deriver", value)?)?;
+        }
+        "Sig" => {
+          match value.split_once(':') {
+            Some((name, sig)) if !name.is_empty() && !sig.is_empty() => {}
+            _ => {
+              return Err(NarInfoParseError::InvalidValue {
+                key:   "Sig",
+                value: value.to_string(),
+              })
+            }
+          }
+          sigs.push(value.to_string());
+        }
+        "CA" => {
+          set_once(&mut ca, "CA", parse_ca(value)?)?;
+        }
+        _ if strict => {
+          return Err(NarInfoParseError::UnknownKey(key.to_string()))
+        }
+        _ => {}
+      }
+    }
+
+    Ok(NarInfo {
+      store_path: store_path
+        .ok_or(NarInfoParseError::MissingKey("StorePath"))?,
+      url: url.ok_or(NarInfoParseError::MissingKey("URL"))?,
+      compression,
+      file_hash,
+      file_size,
+      nar_hash: nar_hash.ok_or(NarInfoParseError::MissingKey("NarHash"))?,
+      nar_size: nar_size.ok_or(NarInfoParseError::MissingKey("NarSize"))?,
+      references: references.unwrap_or_default(),
+      deriver,
+      sigs,
+      ca,
+    })
+  }
+}
+
+impl fmt::Display for NarInfo {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "StorePath: {}", self.store_path.to_absolute_path())?;
+    writeln!(f, "URL: {}", self.url)?;
+    if let Some(compression) = &self.compression {
+      writeln!(f, "Compression: {compression}")?;
+    }
+    if let Some(file_hash) = &self.file_hash {
+      writeln!(f, "FileHash: {file_hash}")?;
+    }
+    if let Some(file_size) = &self.file_size {
+      writeln!(f, "FileSize: {file_size}")?;
+    }
+    writeln!(f, "NarHash: {}", self.nar_hash)?;
+    writeln!(f, "NarSize: {}", self.nar_size)?;
+    let references = self
+      .references
+      .iter()
+      .map(|r| r.to_string())
+      .collect::<Vec<_>>();
+    writeln!(f, "References: {}", references.join(" "))?;
+    if let Some(deriver) = &self.deriver {
+      writeln!(f, "Deriver: {deriver}")?;
+    }
+    for sig in &self.sigs {
+      writeln!(f, "Sig: {sig}")?;
+    }
+    if let Some(ca) = &self.ca {
+      writeln!(f, "CA: {ca}")?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HELLO: &str = "StorePath: \
+                       /nix/store/kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1\n\
+                       URL: nar/1kp0asp3gbl5mhqgknfp8dp6jxwyyzvv3ya2vg5hrv3bwqlczrzi.nar.xz\n\
+                       Compression: xz\n\
+                       FileHash: \
+                       sha256:1kp0asp3gbl5mhqgknfp8dp6jxwyyzvv3ya2vg5hrv3bwqlczrzi\n\
+                       FileSize: 50088\n\
+                       NarHash: \
+                       sha256:0yvj6i0qjkgpbg4i0wkhbjlqdq8yqcs6j2gpbb6m4pbimb6m1bp9\n\
+                       NarSize: 226488\n\
+                       References: \
+                       kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1 \
+                       yaz7pyf0ah88g2v505l38n0f3wg2vzdj-glibc-2.37-8\n\
+                       Deriver: 7c1ncbqg8xc1p7zxn0y3ad0b6rs0xyps-hello-2.12.1.drv\n\
+                       Sig: \
+                       cache.nixos.org-1:8ijECciSFzWHwwGVOIVYdp2fOIOJAfmzGHPQVwpktfTQJF6kMPPDre7UtFw3o+VqenC5P8RikKOAAfN7CvPEAg==\n";
+
+  #[test]
+  fn test_narinfo_round_trip() {
+    let info: NarInfo = HELLO.parse().unwrap();
+    assert_eq!(info.compression, Some(NarCompression::Xz));
+    assert_eq!(info.file_size, Some(50088));
+    assert_eq!(info.nar_size, 226488);
+    assert_eq!(info.references.len(), 2);
+    assert_eq!(info.sigs.len(), 1);
+    assert_eq!(info.to_string(), HELLO);
+  }
+
+  #[test]
+  fn test_narinfo_empty_references() {
+    let text = concat!(
+      "StorePath: /nix/store/yaz7pyf0ah88g2v505l38n0f3wg2vzdj-foo\n",
+      "URL: nar/aaaa.nar\n",
+      "Compression: none\n",
+      "NarHash: sha256:0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73\n",
+      "NarSize: 8\n",
+      "References: \n",
+    );
+    let info: NarInfo = text.parse().unwrap();
+    assert!(info.references.is_empty());
+    assert_eq!(info.to_string(), text);
+  }
+
+  #[test]
+  fn test_narinfo_rejects_malformed() {
+    let cases = [
+      (
+        "StorePath: /nix/store/yaz7pyf0ah88g2v505l38n0f3wg2vzdj-foo",
+        NarInfoParseError::MissingTrailingNewline,
+      ),
+      (
+        "StorePath: /nix/store/yaz7pyf0ah88g2v505l38n0f3wg2vzdj-foo\nBogus: \
+         1\n",
+        NarInfoParseError::UnknownKey("Bogus".into()),
+      ),
+      (
+        "StorePath: \
+         /nix/store/yaz7pyf0ah88g2v505l38n0f3wg2vzdj-foo\nStorePath: \
+         /nix/store/yaz7pyf0ah88g2v505l38n0f3wg2vzdj-foo\n",
+        NarInfoParseError::DuplicateKey("StorePath"),
+      ),
+      (
+        "StorePath: /nix/store/yaz7pyf0ah88g2v505l38n0f3wg2vzdj-foo\n\n",
+        NarInfoParseError::MalformedLine(2),
+      ),
+      (
+        "StorePath: /nix/store/yaz7pyf0ah88g2v505l38n0f3wg2vzdj-foo\n",
+        NarInfoParseError::MissingKey("URL"),
+      ),
+      ("NarSize: 012\n", NarInfoParseError::InvalidValue {
+        key:   "NarSize",
+        value: "012".into(),
+      }),
+      (
+        "StorePath: /tmp/yaz7pyf0ah88g2v505l38n0f3wg2vzdj-foo\n",
+        NarInfoParseError::InvalidValue {
+          key:   "StorePath",
+          value: "/tmp/yaz7pyf0ah88g2v505l38n0f3wg2vzdj-foo".into(),
+        },
+      ),
+      ("NarHash: sha256:aaaa\n", NarInfoParseError::InvalidValue {
+        key:   "NarHash",
+        value: "sha256:aaaa".into(),
+      }),
+      ("References: foo\n", NarInfoParseError::InvalidValue {
+        key:   "References",
+        value: "foo".into(),
+      }),
+      (
+        "Compression: lzma\n",
+        NarInfoParseError::UnknownCompression("lzma".into()),
+      ),
+    ];
+
+    for (text, expected) in cases {
+      assert_eq!(text.parse::<NarInfo>().unwrap_err(), expected, "{text:?}");
+    }
+  }
+
+  #[test]
+  fn test_narinfo_lenient_skips_unknown_keys() {
+    let text = HELLO.replace(
+      "NarSize: 226488\n",
+      "NarSize: 226488\nSystem: x86_64-linux\nIndexedAt: 1700000000\n",
+    );
+    assert_eq!(
+      text.parse::<NarInfo>().unwrap_err(),
+      NarInfoParseError::UnknownKey("System".into())
+    );
+
+    let info = NarInfo::parse_lenient(&text).unwrap();
+    assert_eq!(info, HELLO.parse().unwrap());
+    assert_eq!(info.to_string(), HELLO);
+
+    // everything else is still checked
+    assert_eq!(
+      NarInfo::parse_lenient("NarSize: 012\n").unwrap_err(),
+      NarInfoParseError::InvalidValue {
+        key:   "NarSize",
+        value: "012".into(),
+      }
+    );
+  }
+}
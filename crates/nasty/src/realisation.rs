@@ -0,0 +1,75 @@
+//! The JSON format of realisations, as served in `.doi` files.
+//!
+//! A realisation records which store path a content-addressed derivation
+//! output was built to. Binary caches serve them at
+//! `realisations/<drv-output>.doi`.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DrvOutput, StorePath};
+
+/// A realisation of a derivation output.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Realisation {
+  /// The derivation output that was realised.
+  pub id:                     DrvOutput,
+  /// The store path it was realised to.
+  pub out_path:               StorePath,
+  /// Signatures over the realisation, in `<key-name>:<base64>` form.
+  #[serde(default)]
+  pub signatures:             Vec<String>,
+  /// The realisations of the derivation outputs this one depends on.
+  #[serde(default)]
+  pub dependent_realisations: BTreeMap<DrvOutput, StorePath>,
+}
+
+impl Realisation {
+  /// Parses a realisation from JSON.
+  pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+    serde_json::from_str(json)
+  }
+
+  /// Renders the realisation as JSON, like Nix does.
+  pub fn to_json(&self) -> String {
+    serde_json::to_string(self).expect("realisations always serialize")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_realisation_roundtrip() {
+    let json = concat!(
+      r#"{"id":"sha256:"#,
+      r#"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855!out","#,
+      r#""outPath":"kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1","#,
+      r#""signatures":["cache.example.com-1:c2lnbmF0dXJl"],"#,
+      r#""dependentRealisations":{}}"#,
+    );
+
+    let realisation = Realisation::from_json(json).unwrap();
+    assert_eq!(realisation.id.output_name(), "out");
+    assert_eq!(
+      realisation.out_path.as_ref(),
+      "kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1"
+    );
+    assert_eq!(realisation.to_json(), json);
+  }
+
+  #[test]
+  fn test_realisation_rejects_malformed() {
+    assert!(Realisation::from_json(
+      r#"{"id":"sha256:abc!out","outPath":"kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello"}"#
+    )
+    .is_err());
+    assert!(Realisation::from_json(
+      r#"{"id":"sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855!out","outPath":"/nix/store/kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello"}"#
+    )
+    .is_err());
+  }
+}
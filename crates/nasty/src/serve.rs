@@ -0,0 +1,427 @@
+//! A server for the legacy `nix-store --serve` protocol.
+//!
+//! This is what Nix speaks to `ssh://` stores: it runs `nix-store --serve` on
+//! the remote host and talks to it over the SSH session's stdin and stdout.
+//! After a handshake, the client sends commands and the server answers each
+//! one directly, without any log messages in between. Only the read-only
+//! commands are implemented; the store itself is provided by a [`ServeStore`].
+
+use std::{
+  collections::BTreeSet,
+  io::{self, BufReader, BufWriter, Read, Write},
+};
+
+use crate::{wire, NixHash, StorePath};
+
+/// Sent by the client to start the handshake.
+const SERVE_MAGIC_1: u64 = 0x390c9deb;
+/// Sent by the server in response to [`SERVE_MAGIC_1`].
+const SERVE_MAGIC_2: u64 = 0x5452eecb;
+
+/// The protocol version this server speaks, `2.7`.
+const PROTOCOL_VERSION: u64 = 2 << 8 | 7;
+
+const fn minor(version: u64) -> u64 { version & 0xff }
+
+/// Serve command codes.
+mod cmd {
+  pub const QUERY_VALID_PATHS: u64 = 1;
+  pub const QUERY_PATH_INFOS: u64 = 2;
+  pub const DUMP_STORE_PATH: u64 = 3;
+  pub const QUERY_CLOSURE: u64 = 7;
+}
+
+/// An error encountered while serving a client.
+#[derive(thiserror::Error, Debug)]
+pub enum ServeError {
+  /// The connection failed.
+  #[error("failed to communicate with the client: {0}")]
+  Io(#[from] io::Error),
+  /// The client didn't start with the serve protocol magic number.
+  #[error("the client did not send the serve protocol magic number")]
+  BadMagic,
+  /// The client speaks a protocol version we don't support.
+  #[error("unsupported client protocol version {}.{}", .0 >> 8, .0 & 0xff)]
+  UnsupportedVersion(u64),
+  /// The client sent a command we don't support.
+  #[error("unsupported serve command {0}")]
+  UnsupportedCommand(u64),
+  /// The client sent a malformed store path.
+  #[error("the client sent a malformed store path: `{0}`")]
+  InvalidPath(String),
+  /// The client asked for the NAR of a path that isn't in the store.
+  #[error("path `{0}` is not valid")]
+  PathNotValid(StorePath),
+  /// The store failed.
+  #[error("the store failed: {0}")]
+  Store(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// The metadata of a valid store path, as sent to the client.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathInfo {
+  /// The store path.
+  pub path:       StorePath,
+  /// The derivation that produced this path.
+  pub deriver:    Option<StorePath>,
+  /// The hash of the path's NAR.
+  pub nar_hash:   NixHash,
+  /// The store paths this path references.
+  pub references: Vec<StorePath>,
+  /// The size of the path's NAR.
+  pub nar_size:   u64,
+  /// Signatures of this path, in `<key-name>:<signature>` form.
+  pub sigs:       Vec<String>,
+  /// The content address of this path, if it's content-addressed.
+  pub ca:         Option<String>,
+}
+
+/// The store that a [`ServeServer`] serves.
+pub trait ServeStore {
+  /// Returns the subset of the given paths that are in the store.
+  fn query_valid_paths(
+    &mut self,
+    paths: &[StorePath],
+  ) -> Result<Vec<StorePath>, ServeError>;
+  /// Returns the metadata of a path, or `None` if it isn't in the store.
+  fn query_path_info(
+    &mut self,
+    path: &StorePath,
+  ) -> Result<Option<PathInfo>, ServeError>;
+  /// Writes the uncompressed NAR of a path to `writer`.
+  fn dump_path(
+    &mut self,
+    path: &StorePath,
+    writer: &mut dyn Write,
+  ) -> Result<(), ServeError>;
+}
+
+fn parse_store_path(path: String) -> Result<StorePath, ServeError> {
+  StorePath::from_absolute_path(&path).ok_or(ServeError::InvalidPath(path))
+}
+
+fn read_store_paths(r: &mut impl Read) -> Result<Vec<StorePath>, ServeError> {
+  wire::read_strings(r)?
+    .into_iter()
+    .map(parse_store_path)
+    .collect()
+}
+
+fn write_store_paths(
+  w: &mut impl Write,
+  paths: &[StorePath],
+) -> io::Result<()> {
+  let paths = paths
+    .iter()
+    .map(|p| p.to_absolute_path())
+    .collect::<Vec<_>>();
+  wire::write_strings(w, &paths)
+}
+
+/// A connection to a `nix-store --serve` client.
+#[derive(Debug)]
+pub struct ServeServer<S: ServeStore, R: Read, W: Write> {
+  store:   S,
+  reader:  BufReader<R>,
+  writer:  BufWriter<W>,
+  version: u64,
+}
+
+impl<S: ServeStore, R: Read, W: Write> ServeServer<S, R, W> {
+  /// Performs the handshake over an existing connection.
+  pub fn handshake(store: S, reader: R, writer: W) -> Result<Self, ServeError> {
+    let mut reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(writer);
+
+    if wire::read_u64(&mut reader)? != SERVE_MAGIC_1 {
+      return Err(ServeError::BadMagic);
+    }
+    wire::write_u64(&mut writer, SERVE_MAGIC_2)?;
+    wire::write_u64(&mut writer, PROTOCOL_VERSION)?;
+    writer.flush()?;
+
+    let client_version = wire::read_u64(&mut reader)?;
+    if client_version >> 8 != 2 {
+      return Err(ServeError::UnsupportedVersion(client_version));
+    }
+
+    Ok(Self {
+      store,
+      reader,
+      writer,
+      version: client_version.min(PROTOCOL_VERSION),
+    })
+  }
+
+  /// Returns the negotiated protocol version, e.g. `0x207` for `2.7`.
+  pub fn protocol_version(&self) -> u64 { self.version }
+
+  /// Answers commands until the client hangs up.
+  pub fn run(mut self) -> Result<(), ServeError> {
+    loop {
+      let command = match wire::read_u64(&mut self.reader) {
+        Ok(command) => command,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+        Err(e) => return Err(e.into()),
+      };
+      match command {
+        cmd::QUERY_VALID_PATHS => self.query_valid_paths()?,
+        cmd::QUERY_PATH_INFOS => self.query_path_infos()?,
+        cmd::DUMP_STORE_PATH => self.dump_store_path()?,
+        cmd::QUERY_CLOSURE => self.query_closure()?,
+        other => return Err(ServeError::UnsupportedCommand(other)),
+      }
+      self.writer.flush()?;
+    }
+  }
+
+  fn query_valid_paths(&mut self) -> Result<(), ServeError> {
+    // we can neither lock nor substitute paths, so these are ignored
+    let _lock = wire::read_bool(&mut self.reader)?;
+    let _substitute = wire::read_bool(&mut self.reader)?;
+    let paths = read_store_paths(&mut self.reader)?;
+
+    let valid = self.store.query_valid_paths(&paths)?;
+    write_store_paths(&mut self.writer, &valid)?;
+    Ok(())
+  }
+
+  fn query_path_infos(&mut self) -> Result<(), ServeError> {
+    let paths = read_store_paths(&mut self.reader)?;
+
+    for path in paths {
+      let Some(info) = self.store.query_path_info(&path)? else {
+        continue;
+      };
+      let w = &mut self.writer;
+      wire::write_bytes(w, info.path.to_absolute_path().as_bytes())?;
+      let deriver = info.deriver.map(|d| d.to_absolute_path());
+      wire::write_bytes(w, deriver.unwrap_or_default().as_bytes())?;
+      write_store_paths(w, &info.references)?;
+      // the download size is the NAR size, since NARs are sent uncompressed
+      wire::write_u64(w, info.nar_size)?;
+      wire::write_u64(w, info.nar_size)?;
+      if minor(self.version) >= 4 {
+        wire::write_bytes(w, info.nar_hash.to_nix32().as_bytes())?;
+        wire::write_bytes(w, info.ca.unwrap_or_default().as_bytes())?;
+        wire::write_strings(w, &info.sigs)?;
+      }
+    }
+    // an empty path ends the list
+    wire::write_bytes(&mut self.writer, b"")?;
+    Ok(())
+  }
+
+  fn dump_store_path(&mut self) -> Result<(), ServeError> {
+    let path = parse_store_path(wire::read_string(&mut self.reader)?)?;
+    self.store.dump_path(&path, &mut self.writer)
+  }
+
+  fn query_closure(&mut self) -> Result<(), ServeError> {
+    // there are no derivations to include the outputs of
+    let _include_outputs = wire::read_bool(&mut self.reader)?;
+    let paths = read_store_paths(&mut self.reader)?;
+
+    let mut closure = BTreeSet::new();
+    let mut queue = paths;
+    while let Some(path) = queue.pop() {
+      if closure.contains(&path) {
+        continue;
+      }
+      let Some(info) = self.store.query_path_info(&path)? else {
+        return Err(ServeError::PathNotValid(path));
+      };
+      queue.extend(info.references);
+      closure.insert(path);
+    }
+
+    write_store_paths(
+      &mut self.writer,
+      &closure.into_iter().collect::<Vec<_>>(),
+    )?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use super::*;
+
+  const HELLO: &str = "kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1";
+  const GLIBC: &str = "yaz7pyf0ah88g2v505l38n0f3wg2vzdj-glibc-2.37-8";
+  const MISSING: &str = "5g5nzkrq9bw1bcfbbiwdh8pdmdrxdpy7-missing";
+
+  fn path(p: &str) -> StorePath { StorePath::try_new(p).unwrap() }
+
+  fn abs(p: &str) -> String { format!("/nix/store/{p}") }
+
+  /// A store holding `hello`, which references `glibc`.
+  struct MemoryStore(BTreeMap<StorePath, (PathInfo, Vec<u8>)>);
+
+  impl MemoryStore {
+    fn new() -> Self {
+      let info = |p: &str, references: Vec<StorePath>| PathInfo {
+        path: path(p),
+        deriver: None,
+        nar_hash: NixHash::from_sha256_digest([7; 32]),
+        references,
+        nar_size: 4,
+        sigs: vec!["cache.example.com-1:c2ln".to_string()],
+        ca: None,
+      };
+      Self(BTreeMap::from([
+        (
+          path(HELLO),
+          (info(HELLO, vec![path(GLIBC)]), b"NAR1".to_vec()),
+        ),
+        (path(GLIBC), (info(GLIBC, vec![]), b"NAR2".to_vec())),
+      ]))
+    }
+  }
+
+  impl ServeStore for MemoryStore {
+    fn query_valid_paths(
+      &mut self,
+      paths: &[StorePath],
+    ) -> Result<Vec<StorePath>, ServeError> {
+      Ok(
+        paths
+          .iter()
+          .filter(|p| self.0.contains_key(*p))
+          .cloned()
+          .collect(),
+      )
+    }
+
+    fn query_path_info(
+      &mut self,
+      path: &StorePath,
+    ) -> Result<Option<PathInfo>, ServeError> {
+      Ok(self.0.get(path).map(|(info, _)| info.clone()))
+    }
+
+    fn dump_path(
+      &mut self,
+      path: &StorePath,
+      writer: &mut dyn Write,
+    ) -> Result<(), ServeError> {
+      let (_, nar) = self
+        .0
+        .get(path)
+        .ok_or_else(|| ServeError::PathNotValid(path.clone()))?;
+      Ok(writer.write_all(nar)?)
+    }
+  }
+
+  /// Runs a session with the client's side already written out, and returns
+  /// everything the server sent after the handshake.
+  fn session(version: u64, commands: &[u8]) -> Result<Vec<u8>, ServeError> {
+    let mut input = Vec::new();
+    wire::write_u64(&mut input, SERVE_MAGIC_1).unwrap();
+    wire::write_u64(&mut input, version).unwrap();
+    input.extend_from_slice(commands);
+
+    let mut output = Vec::new();
+    let server = ServeServer::handshake(
+      MemoryStore::new(),
+      input.as_slice(),
+      &mut output,
+    )?;
+    server.run()?;
+
+    let mut r = output.as_slice();
+    assert_eq!(wire::read_u64(&mut r).unwrap(), SERVE_MAGIC_2);
+    assert_eq!(wire::read_u64(&mut r).unwrap(), PROTOCOL_VERSION);
+    Ok(r.to_vec())
+  }
+
+  #[test]
+  fn test_serve_queries() {
+    let mut commands = Vec::new();
+    let c = &mut commands;
+    wire::write_u64(c, cmd::QUERY_VALID_PATHS).unwrap();
+    wire::write_bool(c, false).unwrap();
+    wire::write_bool(c, false).unwrap();
+    wire::write_strings(c, &[abs(MISSING), abs(HELLO)]).unwrap();
+    wire::write_u64(c, cmd::QUERY_PATH_INFOS).unwrap();
+    wire::write_strings(c, &[abs(HELLO), abs(MISSING)]).unwrap();
+    wire::write_u64(c, cmd::QUERY_CLOSURE).unwrap();
+    wire::write_bool(c, false).unwrap();
+    wire::write_strings(c, &[abs(HELLO)]).unwrap();
+    wire::write_u64(c, cmd::DUMP_STORE_PATH).unwrap();
+    wire::write_bytes(c, abs(GLIBC).as_bytes()).unwrap();
+
+    let output = session(PROTOCOL_VERSION, &commands).unwrap();
+    let r = &mut output.as_slice();
+
+    assert_eq!(wire::read_strings(r).unwrap(), vec![abs(HELLO)]);
+
+    assert_eq!(wire::read_string(r).unwrap(), abs(HELLO));
+    assert_eq!(wire::read_string(r).unwrap(), "");
+    assert_eq!(wire::read_strings(r).unwrap(), vec![abs(GLIBC)]);
+    assert_eq!(wire::read_u64(r).unwrap(), 4);
+    assert_eq!(wire::read_u64(r).unwrap(), 4);
+    assert_eq!(
+      wire::read_string(r).unwrap(),
+      NixHash::from_sha256_digest([7; 32]).to_nix32()
+    );
+    assert_eq!(wire::read_string(r).unwrap(), "");
+    assert_eq!(wire::read_strings(r).unwrap(), vec![
+      "cache.example.com-1:c2ln"
+    ]);
+    assert_eq!(wire::read_string(r).unwrap(), "");
+
+    assert_eq!(wire::read_strings(r).unwrap(), vec![abs(HELLO), abs(GLIBC)]);
+
+    assert_eq!(*r, b"NAR2");
+  }
+
+  #[test]
+  fn test_serve_old_client_gets_no_hashes() {
+    let mut commands = Vec::new();
+    wire::write_u64(&mut commands, cmd::QUERY_PATH_INFOS).unwrap();
+    wire::write_strings(&mut commands, &[abs(GLIBC)]).unwrap();
+
+    let output = session(2 << 8 | 3, &commands).unwrap();
+    let r = &mut output.as_slice();
+    assert_eq!(wire::read_string(r).unwrap(), abs(GLIBC));
+    assert_eq!(wire::read_string(r).unwrap(), "");
+    assert_eq!(wire::read_strings(r).unwrap(), Vec::<String>::new());
+    assert_eq!(wire::read_u64(r).unwrap(), 4);
+    assert_eq!(wire::read_u64(r).unwrap(), 4);
+    assert_eq!(wire::read_string(r).unwrap(), "");
+    assert!(r.is_empty());
+  }
+
+  #[test]
+  fn test_serve_errors() {
+    let mut input = Vec::new();
+    wire::write_u64(&mut input, 0x6e697863).unwrap();
+    assert!(matches!(
+      ServeServer::handshake(MemoryStore::new(), input.as_slice(), Vec::new()),
+      Err(ServeError::BadMagic)
+    ));
+
+    assert!(matches!(
+      session(1 << 8 | 35, &[]),
+      Err(ServeError::UnsupportedVersion(_))
+    ));
+
+    let mut commands = Vec::new();
+    wire::write_u64(&mut commands, 6).unwrap();
+    assert!(matches!(
+      session(PROTOCOL_VERSION, &commands),
+      Err(ServeError::UnsupportedCommand(6))
+    ));
+
+    let mut commands = Vec::new();
+    wire::write_u64(&mut commands, cmd::DUMP_STORE_PATH).unwrap();
+    wire::write_bytes(&mut commands, MISSING.as_bytes()).unwrap();
+    assert!(matches!(
+      session(PROTOCOL_VERSION, &commands),
+      Err(ServeError::InvalidPath(_))
+    ));
+  }
+}
@@ -0,0 +1,282 @@
+//! Utilities for signing `.narinfo` files.
+//!
+//! Nix uses ed25519 keys, written as `<key-name>:<base64>`. The secret key
+//! holds the 32-byte seed followed by the 32-byte public key, and the public
+//! key holds just the latter. Signatures are written in the same form, and
+//! cover the [fingerprint](fingerprint) of a path rather than the whole file.
+
+use std::{fmt, str::FromStr};
+
+use base64::prelude::*;
+use ed25519_dalek::{Signer, Verifier};
+
+use crate::narinfo::NarInfo;
+
+/// An error encountered while parsing a key.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum KeyParseError {
+  /// The key was not in `<key-name>:<base64>` form.
+  #[error("the key is not in `<key-name>:<base64>` form")]
+  MissingName,
+  /// The key material was not valid base64.
+  #[error("the key is not valid base64")]
+  InvalidBase64,
+  /// The key material was not a valid ed25519 key.
+  #[error("the key is not a valid ed25519 key")]
+  InvalidKey,
+}
+
+/// Splits a `<key-name>:<base64>` string and decodes the base64 part.
+fn split_named(s: &str) -> Result<(&str, Vec<u8>), KeyParseError> {
+  let (name, encoded) = s
+    .trim()
+    .split_once(':')
+    .filter(|(name, _)| !name.is_empty())
+    .ok_or(KeyParseError::MissingName)?;
+  let bytes = BASE64_STANDARD
+    .decode(encoded)
+    .map_err(|_| KeyParseError::InvalidBase64)?;
+  Ok((name, bytes))
+}
+
+/// Computes the fingerprint of a path, which is the message that gets signed.
+///
+/// The fingerprint is `1;<store-path>;<nar-hash>;<nar-size>;<references>`,
+/// with the references written as full store paths and separated by commas.
+pub fn fingerprint(info: &NarInfo) -> String {
+  let mut references = info
+    .references
+    .iter()
+    .map(|r| r.to_absolute_path())
+    .collect::<Vec<_>>();
+  references.sort();
+
+  format!(
+    "1;{};{};{};{}",
+    info.store_path.to_absolute_path(),
+    info.nar_hash.to_nix32(),
+    info.nar_size,
+    references.join(",")
+  )
+}
+
+/// A secret key used to sign paths.
+#[derive(Clone, Debug)]
+pub struct SecretKey {
+  name: String,
+  key:  ed25519_dalek::SigningKey,
+}
+
+impl SecretKey {
+  /// Generates a new random secret key with the given name.
+  pub fn generate(name: impl Into<String>) -> Self {
+    Self {
+      name: name.into(),
+      key:  ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng),
+    }
+  }
+
+  /// Returns the name of the key.
+  pub fn name(&self) -> &str { &self.name }
+
+  /// Returns the public key that matches this secret key.
+  pub fn public_key(&self) -> PublicKey {
+    PublicKey {
+      name: self.name.clone(),
+      key:  self.key.verifying_key(),
+    }
+  }
+
+  /// Signs a path, producing a signature in `<key-name>:<base64>` form.
+  pub fn sign(&self, info: &NarInfo) -> String {
+    let signature = self.key.sign(fingerprint(info).as_bytes());
+    format!(
+      "{}:{}",
+      self.name,
+      BASE64_STANDARD.encode(signature.to_bytes())
+    )
+  }
+
+  /// Signs a path and adds the signature to its `Sig` fields.
+  pub fn sign_in_place(&self, info: &mut NarInfo) {
+    let signature = self.sign(info);
+    info.sigs.push(signature);
+  }
+}
+
+impl fmt::Display for SecretKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}:{}",
+      self.name,
+      BASE64_STANDARD.encode(self.key.to_keypair_bytes())
+    )
+  }
+}
+
+impl FromStr for SecretKey {
+  type Err = KeyParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (name, bytes) = split_named(s)?;
+    let bytes: [u8; 64] =
+      bytes.try_into().map_err(|_| KeyParseError::InvalidKey)?;
+    let key = ed25519_dalek::SigningKey::from_keypair_bytes(&bytes)
+      .map_err(|_| KeyParseError::InvalidKey)?;
+    Ok(Self {
+      name: name.to_string(),
+      key,
+    })
+  }
+}
+
+/// A public key used to verify signatures, as listed in Nix's
+/// `trusted-public-keys`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+  name: String,
+  key:  ed25519_dalek::VerifyingKey,
+}
+
+impl PublicKey {
+  /// Returns the name of the key.
+  pub fn name(&self) -> &str { &self.name }
+
+  /// Checks whether any of a path's signatures were made by this key.
+  pub fn verify(&self, info: &NarInfo) -> bool {
+    let message = fingerprint(info);
+    info.sigs.iter().any(|sig| {
+      let Ok((name, bytes)) = split_named(sig) else {
+        return false;
+      };
+      let Ok(signature) = ed25519_dalek::Signature::from_slice(&bytes) else {
+        return false;
+      };
+      name == self.name
+        && self.key.verify(message.as_bytes(), &signature).is_ok()
+    })
+  }
+}
+
+impl fmt::Display for PublicKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}:{}",
+      self.name,
+      BASE64_STANDARD.encode(self.key.to_bytes())
+    )
+  }
+}
+
+impl FromStr for PublicKey {
+  type Err = KeyParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (name, bytes) = split_named(s)?;
+    let bytes: [u8; 32] =
+      bytes.try_into().map_err(|_| KeyParseError::InvalidKey)?;
+    let key = ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+      .map_err(|_| KeyParseError::InvalidKey)?;
+    Ok(Self {
+      name: name.to_string(),
+      key,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use dvf::StorePath;
+
+  use super::*;
+
+  const SECRET_KEY: &str =
+    "albert-1:LItIHXrqHkn+MqygsrjIisHe4qGcC/\
+     j0unBHVDv22YAiCpss4tOMtOZJOYR4XcE8qSKTYZEtdc90u4o5cOnBsA==";
+  const PUBLIC_KEY: &str =
+    "albert-1:IgqbLOLTjLTmSTmEeF3BPKkik2GRLXXPdLuKOXDpwbA=";
+
+  fn narinfo() -> NarInfo {
+    let path = |p: &str| StorePath::try_new(p).unwrap();
+    NarInfo {
+      store_path:  path("kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1"),
+      url:         "nar/kwmqk7ygvhypxadsdaai27gl6qfxv7za.nar".into(),
+      compression: None,
+      file_hash:   None,
+      file_size:   None,
+      nar_hash:
+        "sha256:0yvj6i0qjkgpbg4i0wkhbjlqdq8yqcs6j2gpbb6m4pbimb6m1bp9"
+          .parse()
+          .unwrap(),
+      nar_size:    226488,
+      references:  vec![
+        path("yaz7pyf0ah88g2v505l38n0f3wg2vzdj-glibc-2.37-8"),
+        path("kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1"),
+      ],
+      deriver:     None,
+      sigs:        Vec::new(),
+      ca:          None,
+    }
+  }
+
+  #[test]
+  fn test_fingerprint() {
+    assert_eq!(
+      fingerprint(&narinfo()),
+      "1;/nix/store/kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1;sha256:\
+       0yvj6i0qjkgpbg4i0wkhbjlqdq8yqcs6j2gpbb6m4pbimb6m1bp9;226488;/nix/store/\
+       kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1,/nix/store/\
+       yaz7pyf0ah88g2v505l38n0f3wg2vzdj-glibc-2.37-8"
+    );
+  }
+
+  #[test]
+  fn test_key_round_trip() {
+    let secret: SecretKey = SECRET_KEY.parse().unwrap();
+    assert_eq!(secret.to_string(), SECRET_KEY);
+    assert_eq!(secret.public_key().to_string(), PUBLIC_KEY);
+    assert_eq!(
+      PUBLIC_KEY.parse::<PublicKey>().unwrap(),
+      secret.public_key()
+    );
+  }
+
+  #[test]
+  fn test_sign_and_verify() {
+    let secret: SecretKey = SECRET_KEY.parse().unwrap();
+    let public: PublicKey = PUBLIC_KEY.parse().unwrap();
+
+    let mut info = narinfo();
+    assert!(!public.verify(&info));
+    secret.sign_in_place(&mut info);
+    assert!(info.sigs[0].starts_with("albert-1:"));
+    assert!(public.verify(&info));
+
+    // a signature doesn't carry over to a different path
+    info.nar_size += 1;
+    assert!(!public.verify(&info));
+
+    // nor does it verify under a different key
+    let other = SecretKey::generate("albert-1").public_key();
+    info.nar_size -= 1;
+    assert!(!other.verify(&info));
+  }
+
+  #[test]
+  fn test_key_parse_errors() {
+    assert_eq!(
+      "no-name".parse::<PublicKey>().unwrap_err(),
+      KeyParseError::MissingName
+    );
+    assert_eq!(
+      "name:%%%".parse::<PublicKey>().unwrap_err(),
+      KeyParseError::InvalidBase64
+    );
+    assert_eq!(
+      PUBLIC_KEY.parse::<SecretKey>().unwrap_err(),
+      KeyParseError::InvalidKey
+    );
+  }
+}
@@ -0,0 +1,76 @@
+//! Fixtures shared by the tests of the NAR parsing modules.
+
+/// Appends a NAR token: its length, its bytes, and padding to 8 bytes.
+pub(crate) fn token(nar: &mut Vec<u8>, bytes: &[u8]) {
+  nar.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+  nar.extend_from_slice(bytes);
+  nar.resize(nar.len().next_multiple_of(8), 0);
+}
+
+/// Appends several NAR tokens.
+pub(crate) fn tokens(nar: &mut Vec<u8>, tokens: &[&[u8]]) {
+  for t in tokens {
+    token(nar, t);
+  }
+}
+
+/// A NAR with a directory holding an executable, a file, and a symlink.
+pub(crate) fn sample_nar() -> Vec<u8> {
+  let mut nar = Vec::new();
+  tokens(&mut nar, &[
+    b"nix-archive-1",
+    b"(",
+    b"type",
+    b"directory",
+    b"entry",
+    b"(",
+    b"name",
+    b"bin",
+    b"node",
+    b"(",
+    b"type",
+    b"directory",
+    b"entry",
+    b"(",
+    b"name",
+    b"hello",
+    b"node",
+    b"(",
+    b"type",
+    b"regular",
+    b"executable",
+    b"",
+    b"contents",
+    b"#!/bin/sh\necho hello\n",
+    b")",
+    b")",
+    b")",
+    b")",
+    b"entry",
+    b"(",
+    b"name",
+    b"latest",
+    b"node",
+    b"(",
+    b"type",
+    b"symlink",
+    b"target",
+    b"bin/hello",
+    b")",
+    b")",
+    b"entry",
+    b"(",
+    b"name",
+    b"readme",
+    b"node",
+    b"(",
+    b"type",
+    b"regular",
+    b"contents",
+    b"hi",
+    b")",
+    b")",
+    b")",
+  ]);
+  nar
+}
@@ -0,0 +1,88 @@
+//! Primitives of the Nix wire format.
+//!
+//! Everything is sent as little-endian `u64`s, or as byte strings made of a
+//! `u64` length followed by the bytes, zero-padded to a multiple of 8.
+
+use std::io::{self, Read, Write};
+
+/// The longest string we'll read into memory. NAR contents are streamed
+/// instead, so this only bounds metadata.
+pub(crate) const MAX_STRING_LEN: u64 = 1024 * 1024;
+
+/// Returns the number of padding bytes that follow a string of `len` bytes.
+pub(crate) fn padding_len(len: u64) -> usize { ((8 - len % 8) % 8) as usize }
+
+pub(crate) fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+  let mut buf = [0; 8];
+  r.read_exact(&mut buf)?;
+  Ok(u64::from_le_bytes(buf))
+}
+
+pub(crate) fn read_bool(r: &mut impl Read) -> io::Result<bool> {
+  Ok(read_u64(r)? != 0)
+}
+
+pub(crate) fn read_padding(r: &mut impl Read, len: u64) -> io::Result<()> {
+  let mut padding = [0; 8];
+  let padding = &mut padding[..padding_len(len)];
+  r.read_exact(padding)?;
+  if padding.iter().any(|b| *b != 0) {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      "non-zero string padding",
+    ));
+  }
+  Ok(())
+}
+
+pub(crate) fn read_bytes(r: &mut impl Read) -> io::Result<Vec<u8>> {
+  let len = read_u64(r)?;
+  if len > MAX_STRING_LEN {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("string of {len} bytes is too long"),
+    ));
+  }
+  let mut buf = vec![0; len as usize];
+  r.read_exact(&mut buf)?;
+  read_padding(r, len)?;
+  Ok(buf)
+}
+
+pub(crate) fn read_string(r: &mut impl Read) -> io::Result<String> {
+  String::from_utf8(read_bytes(r)?)
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub(crate) fn read_strings(r: &mut impl Read) -> io::Result<Vec<String>> {
+  let count = read_u64(r)?;
+  (0..count).map(|_| read_string(r)).collect()
+}
+
+pub(crate) fn write_u64(w: &mut impl Write, n: u64) -> io::Result<()> {
+  w.write_all(&n.to_le_bytes())
+}
+
+#[cfg_attr(
+  not(feature = "daemon"),
+  allow(dead_code, reason = "only the daemon client sends booleans")
+)]
+pub(crate) fn write_bool(w: &mut impl Write, b: bool) -> io::Result<()> {
+  write_u64(w, b as u64)
+}
+
+pub(crate) fn write_bytes(w: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+  write_u64(w, bytes.len() as u64)?;
+  w.write_all(bytes)?;
+  w.write_all(&[0; 8][..padding_len(bytes.len() as u64)])
+}
+
+pub(crate) fn write_strings<S: AsRef<str>>(
+  w: &mut impl Write,
+  strings: &[S],
+) -> io::Result<()> {
+  write_u64(w, strings.len() as u64)?;
+  strings
+    .iter()
+    .try_for_each(|s| write_bytes(w, s.as_ref().as_bytes()))
+}
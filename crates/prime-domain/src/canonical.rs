@@ -2,19 +2,29 @@ use std::{path::PathBuf, str::FromStr};
 
 pub use hex;
 use hex::health;
-use miette::Result;
+use miette::{Context, IntoDiagnostic, Result};
 pub use models;
 use models::{
-  Cache, CacheRecordId, Entry, EntryCreateRequest, EntryRecordId, LaxSlug,
-  Store, StoreRecordId, StrictSlug, Token, TokenRecordId,
+  Cache, CacheRecordId, DebugInfo, DebugInfoCreateRequest, Entry,
+  EntryCreateRequest, EntryRecordId, LaxSlug, Realisation,
+  RealisationCreateRequest, Session, SessionCreateRequest, SessionRecordId,
+  SigningKey, Store, StoreRecordId, StrictSlug, Token, TokenCreateRequest,
+  TokenRecordId, User, UserRecordId,
+};
+use nasty::listing::{Listing, ListingBuilder};
+pub use repos::{
+  self, StorageDeleteError, StorageReadError, StorageWriteError,
 };
-pub use repos::{self, StorageReadError, StorageWriteError};
 use repos::{
   belt::{self, Belt},
-  db::{FetchModelByIndexError, FetchModelError},
-  CacheRepository, EntryRepository, StoreRepository, TempStorageRepository,
-  TokenRepository, UserStorageClient,
+  db::{
+    CreateModelError, FetchModelByIndexError, FetchModelError, UpdateModelError,
+  },
+  CacheRepository, DebugInfoRepository, EntryRepository, RealisationRepository,
+  SessionRepository, SigningKeyRepository, StoreRepository,
+  TempStorageRepository, TokenRepository, UserRepository, UserStorageClient,
 };
+use tokio::io::AsyncReadExt;
 use tracing::instrument;
 
 fn dvf_comp_to_belt_comp(
@@ -25,63 +35,466 @@ fn dvf_comp_to_belt_comp(
 }
 
 use crate::{
-  CreateEntryError, PrimeDomainService, ReadFromEntryError, TokenVerifyError,
+  password::{hash_password, verify_password},
+  token_secret::{generate_token_secret, TokenSecretHasher},
+  upstream::UpstreamClient,
+  CacheStoreError, CreateCacheError, CreateEntryError, CreateRealisationError,
+  DeleteCacheError, IssueTokenError, LoginError, PrimeDomainService, Principal,
+  ReadFromEntryError, RevokeTokenError, TokenVerifyError, UpdateCacheError,
 };
 
+/// How long a token's `last_used_at` may lag behind, in seconds.
+const TOKEN_LAST_USED_DEBOUNCE_SECS: i64 = 5 * 60;
+/// How long a session lasts after logging in, in seconds.
+const SESSION_LIFETIME_SECS: i64 = 30 * 24 * 60 * 60;
+
 /// The canonical implementation of [`PrimeDomainService`].
 pub struct PrimeDomainServiceCanonical<
   CR: CacheRepository,
+  DIR: DebugInfoRepository,
   ER: EntryRepository,
+  RR: RealisationRepository,
+  SER: SessionRepository,
+  SKR: SigningKeyRepository,
   SR: StoreRepository,
   TR: TokenRepository,
   TSR: TempStorageRepository,
+  UR: UserRepository,
   USR: repos::UserStorageRepository,
 > {
   cache_repo:        CR,
+  debuginfo_repo:    DIR,
   entry_repo:        ER,
+  realisation_repo:  RR,
+  session_repo:      SER,
+  signing_key_repo:  SKR,
   store_repo:        SR,
   token_repo:        TR,
   temp_storage_repo: TSR,
+  user_repo:         UR,
   user_storage_repo: USR,
+  token_hasher:      TokenSecretHasher,
+  upstream_client:   UpstreamClient,
 }
 
-impl<CR, ER, SR, TR, TSR, USR>
-  PrimeDomainServiceCanonical<CR, ER, SR, TR, TSR, USR>
+impl<CR, DIR, ER, RR, SER, SKR, SR, TR, TSR, UR, USR>
+  PrimeDomainServiceCanonical<CR, DIR, ER, RR, SER, SKR, SR, TR, TSR, UR, USR>
 where
   CR: CacheRepository,
+  DIR: DebugInfoRepository,
   ER: EntryRepository,
+  RR: RealisationRepository,
+  SER: SessionRepository,
+  SKR: SigningKeyRepository,
   SR: StoreRepository,
   TR: TokenRepository,
   TSR: TempStorageRepository,
+  UR: UserRepository,
   USR: repos::UserStorageRepository,
 {
   /// Create a new instance of the canonical prime domain service.
+  #[allow(
+    clippy::too_many_arguments,
+    reason = "Each repository is a separate dependency of the service."
+  )]
   pub fn new(
     cache_repo: CR,
+    debuginfo_repo: DIR,
     entry_repo: ER,
+    realisation_repo: RR,
+    session_repo: SER,
+    signing_key_repo: SKR,
     store_repo: SR,
     token_repo: TR,
     temp_storage_repo: TSR,
+    user_repo: UR,
     user_storage_repo: USR,
+    token_hasher: TokenSecretHasher,
   ) -> Self {
     tracing::info!("creating new `PrimeDomainServiceCanonical` instance");
     Self {
       cache_repo,
+      debuginfo_repo,
       entry_repo,
+      realisation_repo,
+      session_repo,
+      signing_key_repo,
       store_repo,
       token_repo,
       temp_storage_repo,
+      user_repo,
       user_storage_repo,
+      token_hasher,
+      upstream_client: UpstreamClient::new(),
+    }
+  }
+
+  /// Records that a token was used.
+  ///
+  /// To keep verification a read in the common case, `last_used_at` is only
+  /// written once it's older than [`TOKEN_LAST_USED_DEBOUNCE_SECS`]. Failing to
+  /// write it doesn't fail the verification.
+  async fn record_token_use(
+    &self,
+    token: Token,
+    now: chrono::DateTime<chrono::Utc>,
+  ) -> Token {
+    let debounce = chrono::TimeDelta::seconds(TOKEN_LAST_USED_DEBOUNCE_SECS);
+    if token
+      .last_used_at
+      .is_some_and(|last_used_at| now - last_used_at < debounce)
+    {
+      return token;
+    }
+
+    // refetch right before writing, so that a concurrent change like a
+    // revocation isn't overwritten with what we read earlier
+    let result = match self.token_repo.fetch_model_by_id(token.id).await {
+      Ok(Some(mut fresh_token)) => {
+        fresh_token.last_used_at = Some(now);
+        self
+          .token_repo
+          .update_model(fresh_token)
+          .await
+          .map_err(miette::Report::from)
+      }
+      Ok(None) => Err(miette::miette!("token disappeared while verifying")),
+      Err(e) => Err(e.into()),
+    };
+
+    match result {
+      Ok(_) => Token {
+        last_used_at: Some(now),
+        ..token
+      },
+      Err(e) => {
+        tracing::warn!(token = %token.id, "failed to record token use: {e:?}");
+        token
+      }
+    }
+  }
+
+  /// Replaces a token's plaintext secret with its hash, optionally recording
+  /// that it was just used.
+  ///
+  /// The token is refetched right before writing, so that a concurrent change
+  /// like a revocation isn't overwritten with what the caller read earlier.
+  /// The plaintext's entry in the retired secret index goes first, so that a
+  /// failure part way through leaves the token to be retried.
+  async fn hash_legacy_token_secret(
+    &self,
+    id: TokenRecordId,
+    used_at: Option<chrono::DateTime<chrono::Utc>>,
+  ) -> Result<Token> {
+    let mut token = self
+      .token_repo
+      .fetch_model_by_id(id)
+      .await?
+      .ok_or_else(|| miette::miette!("token disappeared while hashing"))?;
+    let Some(legacy_secret) = token.legacy_secret.take() else {
+      return Ok(token);
+    };
+
+    self
+      .token_repo
+      .remove_retired_index_entry(
+        models::TOKEN_LEGACY_SECRET_INDEX_NAME.to_string(),
+        legacy_secret.clone().into_inner().into(),
+      )
+      .await?;
+
+    token.secret_hash = Some(self.token_hasher.hash(&legacy_secret));
+    if used_at.is_some() {
+      token.last_used_at = used_at;
+    }
+    let token = self.token_repo.update_model(token).await?;
+    tracing::info!(token = %token.id, "hashed legacy token secret");
+
+    Ok(token)
+  }
+
+  /// Records the hashes of an entry written before they were, by reading its
+  /// data back from the store, and writes its missing index entries.
+  async fn migrate_legacy_entry(&self, entry: Entry) -> Result<Entry> {
+    let mut data = self.read_from_entry(entry.id).await?;
+    let file_hasher = data.hasher();
+    let mut data = data.adapt_to_no_comp();
+    let nar_hasher = data.hasher();
+
+    tokio::io::copy(&mut data.to_async_buf_read(), &mut tokio::io::sink())
+      .await
+      .into_diagnostic()
+      .context("failed to read entry data")?;
+
+    let entry = self
+      .entry_repo
+      .update_model(Entry {
+        nar_hash: Some(models::NixHash::from_sha256_digest(
+          nar_hasher.current(),
+        )),
+        file_hash: Some(models::NixHash::from_sha256_digest(
+          file_hasher.current(),
+        )),
+        ..entry
+      })
+      .await?;
+    // entries from before the path hash index was added aren't in it
+    self.entry_repo.restore_index_entries(entry.id).await?;
+    tracing::info!(entry = %entry.id, "migrated legacy entry");
+
+    Ok(entry)
+  }
+
+  /// Verifies a [`Session`] by its ID and secret, and fetches its user.
+  async fn verify_session_id_and_secret(
+    &self,
+    id: SessionRecordId,
+    secret: models::TokenSecret,
+  ) -> Result<(Session, User), TokenVerifyError> {
+    let session = self
+      .session_repo
+      .fetch_model_by_id(id)
+      .await
+      .map_err(TokenVerifyError::FetchError)?
+      .ok_or(TokenVerifyError::IdNotFound)?;
+
+    if !self.token_hasher.verify(&secret, &session.secret_hash) {
+      return Err(TokenVerifyError::SecretMismatch);
+    }
+    if session.expired(chrono::Utc::now()) {
+      return Err(TokenVerifyError::Expired);
+    }
+
+    // a session can't outlive its user
+    let user = self
+      .user_repo
+      .fetch_model_by_id(session.user)
+      .await
+      .map_err(TokenVerifyError::FetchError)?
+      .ok_or(TokenVerifyError::Revoked)?;
+
+    Ok((session, user))
+  }
+
+  /// Fetches a store that a cache in the given org can be backed by.
+  async fn fetch_store_for_cache(
+    &self,
+    org: models::OrgRecordId,
+    id: StoreRecordId,
+  ) -> Result<Store, CacheStoreError> {
+    let store = self
+      .store_repo
+      .fetch_model_by_id(id)
+      .await
+      .map_err(CacheStoreError::FetchError)?
+      .ok_or(CacheStoreError::NotFound(id))?;
+    if store.org != org {
+      return Err(CacheStoreError::ForeignOrg(id));
+    }
+    Ok(store)
+  }
+
+  /// Fetches a cache, if it's in the user's org, and checks whether the user
+  /// has write access to it.
+  async fn fetch_cache_for_writing(
+    &self,
+    user: &User,
+    id: CacheRecordId,
+  ) -> Result<Option<(Cache, bool)>> {
+    let Some(cache) = self
+      .cache_repo
+      .fetch_model_by_id(id)
+      .await?
+      .filter(|c| c.org == user.org)
+    else {
+      return Ok(None);
+    };
+
+    let writable = user.perms.contains(&models::Permission::CachePermission {
+      cache_id:   cache.id,
+      permission: models::CachePermissionType::Write,
+    });
+    Ok(Some((cache, writable)))
+  }
+
+  /// Checks whether a cache has any entries.
+  async fn cache_has_entries(&self, id: CacheRecordId) -> Result<bool> {
+    Ok(
+      !self
+        .entry_repo
+        .find_entries_by_cache_id(id)
+        .await?
+        .is_empty(),
+    )
+  }
+
+  /// Gives a new cache its signing key, and grants its creator read and write
+  /// access to it.
+  async fn set_up_cache(
+    &self,
+    creator: &User,
+    cache: &Cache,
+  ) -> Result<(), CreateCacheError> {
+    let secret =
+      nasty::signing::SecretKey::generate(format!("{}-1", cache.name));
+    self
+      .signing_key_repo
+      .create_model(models::SigningKeyCreateRequest {
+        secret: models::SigningKeySecret::new(secret.to_string()),
+        cache:  cache.id,
+        org:    cache.org,
+      })
+      .await
+      .map_err(CreateCacheError::SigningKeyError)?;
+
+    // refetch the user, so that a concurrent change to their permissions
+    // isn't overwritten
+    let mut user = self
+      .user_repo
+      .fetch_model_by_id(creator.id)
+      .await
+      .map_err(|e| CreateCacheError::GrantError(e.into()))?
+      .ok_or_else(|| {
+        CreateCacheError::GrantError(miette::miette!(
+          "user disappeared while creating cache"
+        ))
+      })?;
+    for permission in [
+      models::CachePermissionType::Read,
+      models::CachePermissionType::Write,
+    ] {
+      user.perms.0.insert(models::Permission::CachePermission {
+        cache_id: cache.id,
+        permission,
+      });
+    }
+    self
+      .user_repo
+      .update_model(user)
+      .await
+      .map_err(|e| CreateCacheError::GrantError(e.into()))?;
+
+    Ok(())
+  }
+
+  /// Removes a cache whose creation failed partway, along with its signing
+  /// key if it got one.
+  ///
+  /// Failures are only logged, since the error that stopped the creation is
+  /// the one worth reporting.
+  async fn roll_back_cache(&self, id: CacheRecordId) {
+    let signing_key = match self.signing_key_repo.find_by_cache_id(id).await {
+      Ok(signing_key) => signing_key,
+      Err(e) => {
+        tracing::error!(cache = %id, "failed to find signing key: {e}");
+        None
+      }
+    };
+    if let Some(signing_key) = signing_key {
+      if let Err(e) = self.signing_key_repo.delete_model(signing_key.id).await {
+        tracing::error!(cache = %id, "failed to roll back signing key: {e}");
+      }
+    }
+    if let Err(e) = self.cache_repo.delete_model(id).await {
+      tracing::error!(cache = %id, "failed to roll back cache: {e}");
+    }
+  }
+
+  /// Removes every permission on a cache from the users and tokens in its org.
+  ///
+  /// Each one is refetched right before it's updated, so that a concurrent
+  /// change to its permissions isn't overwritten.
+  async fn revoke_cache_grants(&self, cache: &Cache) -> Result<()> {
+    let on_cache = |perm: &models::Permission| match perm {
+      models::Permission::CachePermission { cache_id, .. } => {
+        *cache_id == cache.id
+      }
+    };
+
+    // users and tokens aren't indexed by org, and there are few enough to scan
+    let users = self.user_repo.enumerate_models().await?;
+    for user in users
+      .into_iter()
+      .filter(|u| u.org == cache.org && u.perms.0.iter().any(on_cache))
+    {
+      let Some(mut user) = self.user_repo.fetch_model_by_id(user.id).await?
+      else {
+        continue;
+      };
+      user.perms.0.retain(|p| !on_cache(p));
+      self.user_repo.update_model(user).await?;
+    }
+
+    let tokens = self.token_repo.enumerate_models().await?;
+    for token in tokens
+      .into_iter()
+      .filter(|t| t.org == cache.org && t.perms.0.iter().any(on_cache))
+    {
+      let Some(mut token) = self.token_repo.fetch_model_by_id(token.id).await?
+      else {
+        continue;
+      };
+      token.perms.0.retain(|p| !on_cache(p));
+      self.token_repo.update_model(token).await?;
     }
+
+    Ok(())
+  }
+
+  /// Fetches an entry and connects to the storage of the store it's in.
+  async fn connect_to_entry_storage(
+    &self,
+    entry_id: EntryRecordId,
+  ) -> Result<(Entry, USR::Client), ReadFromEntryError> {
+    let entry = self
+      .fetch_entry_by_id(entry_id)
+      .await
+      .map_err(ReadFromEntryError::FetchModelError)?
+      .ok_or_else(|| ReadFromEntryError::EntryNotFound(entry_id))?;
+
+    let cache = self
+      .fetch_cache_by_id(entry.cache)
+      .await
+      .map_err(ReadFromEntryError::FetchModelError)?
+      .ok_or_else(|| {
+        ReadFromEntryError::DataIntegrityError(miette::miette!(
+          "entry references non-existent cache: {}",
+          entry.cache
+        ))
+      })?;
+
+    let store = self
+      .fetch_store_by_id(cache.store)
+      .await
+      .map_err(ReadFromEntryError::FetchModelError)?
+      .ok_or_else(|| {
+        ReadFromEntryError::DataIntegrityError(miette::miette!(
+          "cache references non-existent store: {}",
+          cache.store
+        ))
+      })?;
+
+    // get the user storage client
+    let client = self
+      .user_storage_repo
+      .connect_to_user_storage(store.credentials.clone())
+      .await
+      .map_err(ReadFromEntryError::StorageConnectionError)?;
+
+    Ok((entry, client))
   }
 
   /// Write data to a store, respecting compression settings.
+  ///
+  /// The NAR's listing is built as the data streams by, and written next to
+  /// it at [`listing_storage_path`](crate::listing_storage_path). It's also
+  /// returned, if it could be built.
   async fn write_to_store(
     &self,
     store_id: StoreRecordId,
-    path: models::LaxSlug,
-    data: Belt,
-  ) -> Result<models::CompressionStatus, crate::WriteToStoreError> {
+    path: models::StorePath,
+    mut data: Belt,
+  ) -> Result<WrittenData, crate::WriteToStoreError> {
     // fetch the store
     let store = self
       .fetch_store_by_id(store_id)
@@ -89,22 +502,25 @@ where
       .map_err(crate::WriteToStoreError::FetchError)?
       .ok_or_else(|| crate::WriteToStoreError::StoreNotFound(store_id))?;
 
-    // count the uncompressed size
+    // count and hash the uncompressed data, and build its listing
     let uncompressed_counter = data.counter();
+    let uncompressed_hasher = data.hasher();
+    let listing = data.observe(ListingObserver::default());
 
     // check what compression algorithm is configured in the store
     let algorithm = store.compression_config.algorithm();
 
     // adapt to compress the data if needed
-    let data = match algorithm {
+    let mut data = match algorithm {
       Some(models::CompressionAlgorithm::Zstd) => {
         data.adapt_to_comp(belt::CompressionAlgorithm::Zstd)
       }
       None => data,
     };
 
-    // count the compressed size
+    // count and hash the compressed data
     let compressed_counter = data.counter();
+    let compressed_hasher = data.hasher();
 
     // get the user storage client
     let client = self
@@ -114,12 +530,41 @@ where
       .map_err(crate::WriteToStoreError::StorageConnectionError)?;
 
     // write the data to the store
-    let path = PathBuf::from_str(path.as_ref()).unwrap();
+    let nar_path = PathBuf::from_str(path.as_ref()).unwrap();
     let _ = client
-      .write(&path, data)
+      .write(&nar_path, data)
       .await
       .map_err(crate::WriteToStoreError::StorageWriteError)?;
 
+    // a bad listing shouldn't stop the NAR from being served, so just skip it
+    let listing = match listing.into_inner().map(|l| l.0.finish()) {
+      Some(Ok(listing)) => {
+        let listing_path =
+          PathBuf::from_str(&crate::listing_storage_path(&path)).unwrap();
+        let data = Belt::from_async_read(
+          std::io::Cursor::new(listing.to_json().into_bytes()),
+          None,
+        );
+        let data = match dvf_comp_to_belt_comp(algorithm) {
+          Some(algorithm) => data.adapt_to_comp(algorithm),
+          None => data,
+        };
+        let _ = client
+          .write(&listing_path, data)
+          .await
+          .map_err(crate::WriteToStoreError::StorageWriteError)?;
+        Some(listing)
+      }
+      Some(Err(e)) => {
+        tracing::warn!("failed to build listing for {path}: {e}");
+        None
+      }
+      None => {
+        tracing::warn!("listing for {path} was still in use after writing");
+        None
+      }
+    };
+
     // get the sizes
     let uncompressed_file_size = uncompressed_counter.current();
     let compressed_file_size = compressed_counter.current();
@@ -136,19 +581,134 @@ where
       },
     };
 
-    Ok(c_status)
+    Ok(WrittenData {
+      c_status,
+      nar_hash: models::NixHash::from_sha256_digest(
+        uncompressed_hasher.current(),
+      ),
+      file_hash: models::NixHash::from_sha256_digest(
+        compressed_hasher.current(),
+      ),
+      listing,
+    })
+  }
+
+  /// Stages data in temp storage while hashing it, and only returns where
+  /// it's staged if its NAR hash matches the expected one.
+  ///
+  /// This keeps data that doesn't match out of the store entirely, so it
+  /// can't linger there or overwrite what's already at its path. Data that
+  /// doesn't match is deleted again; data that does is left for the caller to
+  /// delete once it's written.
+  async fn stage_verified_nar(
+    &self,
+    expected: models::NixHash,
+    mut data: Belt,
+  ) -> Result<models::TempStoragePath, CreateEntryError> {
+    let hasher = data.hasher();
+    let temp_path = self
+      .temp_storage_repo
+      .store(data)
+      .await
+      .map_err(CreateEntryError::StorageWriteError)?;
+
+    let actual = models::NixHash::from_sha256_digest(hasher.current());
+    if expected != actual {
+      self.discard_temp_storage(temp_path).await;
+      return Err(CreateEntryError::NarHashMismatch { expected, actual });
+    }
+
+    Ok(temp_path)
+  }
+
+  /// Deletes data from temp storage that's no longer needed.
+  ///
+  /// Failures are only logged, since whatever the data was needed for is
+  /// already done.
+  async fn discard_temp_storage(&self, path: models::TempStoragePath) {
+    if let Err(e) = self.temp_storage_repo.delete(path.clone()).await {
+      tracing::warn!("failed to delete {path:?} from temp storage: {e}");
+    }
+  }
+
+  /// Records the debug info files in an entry's NAR by build ID.
+  ///
+  /// Build IDs that are already known in the cache keep pointing to the entry
+  /// they were first seen in. Failures are only logged, since the entry itself
+  /// is fine without them.
+  async fn index_debuginfo(&self, entry: &Entry, listing: &Listing) {
+    for file in nasty::debuginfo::debuginfo_files(listing) {
+      let build_id = LaxSlug::new(file.build_id);
+      let existing = self
+        .debuginfo_repo
+        .find_by_cache_id_and_build_id(entry.cache, build_id.clone())
+        .await;
+      match existing {
+        Ok(None) => (),
+        Ok(Some(_)) => continue,
+        Err(e) => {
+          tracing::warn!("failed to look up build ID {build_id}: {e}");
+          continue;
+        }
+      }
+
+      let debuginfo_cr = DebugInfoCreateRequest {
+        build_id: build_id.clone(),
+        entry:    entry.id,
+        member:   file.member,
+        cache:    entry.cache,
+        org:      entry.org,
+      };
+      if let Err(e) = self.debuginfo_repo.create_model(debuginfo_cr).await {
+        tracing::warn!("failed to index build ID {build_id}: {e}");
+      }
+    }
   }
 }
 
+/// Feeds the bytes read from a [`Belt`] into a [`ListingBuilder`].
+#[derive(Debug, Default)]
+struct ListingObserver(ListingBuilder);
+
+impl belt::Observer for ListingObserver {
+  fn observe(&mut self, chunk: &[u8]) { self.0.update(chunk); }
+}
+
+/// The properties of data written by
+/// [`PrimeDomainServiceCanonical::write_to_store`].
+struct WrittenData {
+  c_status:  models::CompressionStatus,
+  nar_hash:  models::NixHash,
+  file_hash: models::NixHash,
+  listing:   Option<Listing>,
+}
+
 #[async_trait::async_trait]
-impl<CR, ER, SR, TR, TSR, USR> PrimeDomainService
-  for PrimeDomainServiceCanonical<CR, ER, SR, TR, TSR, USR>
+impl<CR, DIR, ER, RR, SER, SKR, SR, TR, TSR, UR, USR> PrimeDomainService
+  for PrimeDomainServiceCanonical<
+    CR,
+    DIR,
+    ER,
+    RR,
+    SER,
+    SKR,
+    SR,
+    TR,
+    TSR,
+    UR,
+    USR,
+  >
 where
   CR: CacheRepository,
+  DIR: DebugInfoRepository,
   ER: EntryRepository,
+  RR: RealisationRepository,
+  SER: SessionRepository,
+  SKR: SigningKeyRepository,
   SR: StoreRepository,
   TR: TokenRepository,
   TSR: TempStorageRepository,
+  UR: UserRepository,
   USR: repos::UserStorageRepository,
 {
   async fn fetch_cache_by_id(
@@ -175,9 +735,29 @@ where
   ) -> Result<Option<Token>, FetchModelError> {
     self.token_repo.fetch_model_by_id(id).await
   }
+  async fn fetch_user_by_id(
+    &self,
+    id: UserRecordId,
+  ) -> Result<Option<User>, FetchModelError> {
+    self.user_repo.fetch_model_by_id(id).await
+  }
   async fn enumerate_caches(&self) -> Result<Vec<Cache>> {
     self.cache_repo.enumerate_models().await
   }
+  async fn enumerate_caches_by_org(
+    &self,
+    org: models::OrgRecordId,
+  ) -> Result<Vec<Cache>> {
+    Ok(
+      self
+        .cache_repo
+        .enumerate_models()
+        .await?
+        .into_iter()
+        .filter(|c| c.org == org)
+        .collect(),
+    )
+  }
   async fn enumerate_entries(&self) -> Result<Vec<Entry>> {
     self.entry_repo.enumerate_models().await
   }
@@ -187,6 +767,21 @@ where
   async fn enumerate_tokens(&self) -> Result<Vec<Token>> {
     self.token_repo.enumerate_models().await
   }
+  async fn enumerate_tokens_by_owner(
+    &self,
+    owner: UserRecordId,
+  ) -> Result<Vec<Token>> {
+    // tokens aren't indexed by owner, and there are few enough to scan
+    Ok(
+      self
+        .token_repo
+        .enumerate_models()
+        .await?
+        .into_iter()
+        .filter(|t| t.owner == owner)
+        .collect(),
+    )
+  }
 
   async fn find_cache_by_name(
     &self,
@@ -197,13 +792,83 @@ where
   async fn find_entry_by_id_and_path(
     &self,
     cache_id: CacheRecordId,
-    path: models::LaxSlug,
+    path: models::StorePath,
   ) -> Result<Option<Entry>, FetchModelByIndexError> {
     self
       .entry_repo
       .find_entry_by_id_and_path(cache_id, path)
       .await
   }
+  async fn find_entries_by_id_and_paths(
+    &self,
+    cache_id: CacheRecordId,
+    paths: Vec<models::StorePath>,
+  ) -> Result<Vec<Option<Entry>>, FetchModelByIndexError> {
+    self
+      .entry_repo
+      .find_entries_by_id_and_paths(cache_id, paths)
+      .await
+  }
+  async fn find_entry_by_id_and_path_hash(
+    &self,
+    cache_id: CacheRecordId,
+    path_hash: LaxSlug,
+  ) -> Result<Option<Entry>, FetchModelByIndexError> {
+    self
+      .entry_repo
+      .find_entry_by_id_and_path_hash(cache_id, path_hash)
+      .await
+  }
+  async fn find_signing_key_by_cache_id(
+    &self,
+    cache_id: CacheRecordId,
+  ) -> Result<Option<SigningKey>, FetchModelByIndexError> {
+    self.signing_key_repo.find_by_cache_id(cache_id).await
+  }
+  async fn find_debuginfo_by_id_and_build_id(
+    &self,
+    cache_id: CacheRecordId,
+    build_id: LaxSlug,
+  ) -> Result<Option<DebugInfo>, FetchModelByIndexError> {
+    self
+      .debuginfo_repo
+      .find_by_cache_id_and_build_id(cache_id, build_id)
+      .await
+  }
+  async fn find_realisation_by_id_and_drv_output(
+    &self,
+    cache_id: CacheRecordId,
+    drv_output: models::DrvOutput,
+  ) -> Result<Option<Realisation>, FetchModelByIndexError> {
+    self
+      .realisation_repo
+      .find_by_cache_id_and_drv_output(cache_id, drv_output)
+      .await
+  }
+  async fn find_paths_available_upstream(
+    &self,
+    cache: &Cache,
+    paths: Vec<models::StorePath>,
+  ) -> Vec<models::StorePath> {
+    if cache.upstreams.is_empty() {
+      return Vec::new();
+    }
+    self
+      .upstream_client
+      .find_trusted_paths(&cache.upstreams, paths)
+      .await
+  }
+  async fn find_narinfo_from_proxy(
+    &self,
+    cache: &Cache,
+    hash: LaxSlug,
+  ) -> Option<nasty::narinfo::NarInfo> {
+    let proxy = cache.proxy.as_ref()?;
+    self
+      .upstream_client
+      .find_trusted_narinfo(proxy, hash.as_ref())
+      .await
+  }
   async fn verify_token_id_and_secret(
     &self,
     id: TokenRecordId,
@@ -216,16 +881,168 @@ where
       .map_err(TokenVerifyError::FetchError)?
       .ok_or(TokenVerifyError::IdNotFound)?;
 
-    if token.secret != secret {
+    let verified = match (&token.secret_hash, &token.legacy_secret) {
+      (Some(hash), _) => self.token_hasher.verify(&secret, hash),
+      (None, Some(legacy_secret)) => {
+        self.token_hasher.verify_legacy(&secret, legacy_secret)
+      }
+      (None, None) => false,
+    };
+    if !verified {
       return Err(TokenVerifyError::SecretMismatch);
     }
-    Ok(token)
+
+    let now = chrono::Utc::now();
+    if token.revoked() {
+      return Err(TokenVerifyError::Revoked);
+    }
+    if token.expired(now) {
+      return Err(TokenVerifyError::Expired);
+    }
+
+    if token.legacy_secret.is_some() {
+      // the token is valid either way, so a failed migration can wait for the
+      // next use
+      match self.hash_legacy_token_secret(token.id, Some(now)).await {
+        // the refetched token may have been revoked in the meantime
+        Ok(token) if token.revoked() => return Err(TokenVerifyError::Revoked),
+        Ok(token) if token.expired(now) => {
+          return Err(TokenVerifyError::Expired)
+        }
+        Ok(token) => return Ok(token),
+        Err(e) => {
+          tracing::warn!(
+            token = %token.id,
+            "failed to hash legacy token secret: {e:?}"
+          );
+        }
+      }
+    }
+
+    Ok(self.record_token_use(token, now).await)
+  }
+  async fn verify_credentials(
+    &self,
+    credentials: models::Credentials,
+  ) -> Result<Principal, TokenVerifyError> {
+    let models::Credentials { id, secret } = credentials;
+
+    match self
+      .verify_token_id_and_secret(TokenRecordId::from_ulid(id), secret.clone())
+      .await
+    {
+      Err(TokenVerifyError::IdNotFound) => {
+        let (session, user) = self
+          .verify_session_id_and_secret(SessionRecordId::from_ulid(id), secret)
+          .await?;
+        Ok(Principal::Session { session, user })
+      }
+      result => result.map(Principal::Token),
+    }
+  }
+  async fn login(
+    &self,
+    username: models::EntityName,
+    password: models::Password,
+  ) -> Result<(Session, models::TokenSecret), LoginError> {
+    let user = self
+      .user_repo
+      .find_by_username(username)
+      .await
+      .map_err(LoginError::FetchError)?;
+
+    // hash the password even when there's nothing to check it against, so
+    // the response time doesn't reveal which usernames exist
+    let password_hash = user.as_ref().and_then(|u| u.password_hash.clone());
+    let verified = tokio::task::spawn_blocking(move || match password_hash {
+      Some(hash) => verify_password(&password, &hash),
+      None => {
+        hash_password(&password);
+        false
+      }
+    })
+    .await
+    .expect("password hashing panicked");
+
+    let Some(user) = user.filter(|_| verified) else {
+      return Err(LoginError::InvalidCredentials);
+    };
+
+    let secret = generate_token_secret();
+    let now = chrono::Utc::now();
+    let session = self
+      .session_repo
+      .create_model(SessionCreateRequest {
+        secret_hash: self.token_hasher.hash(&secret),
+        user:        user.id,
+        org:         user.org,
+        created_at:  now,
+        expires_at:  now + chrono::TimeDelta::seconds(SESSION_LIFETIME_SECS),
+      })
+      .await
+      .map_err(LoginError::CreateError)?;
+    tracing::info!(user = %user.id, session = %session.id, "user logged in");
+
+    Ok((session, secret))
+  }
+  async fn end_session(
+    &self,
+    session: Session,
+  ) -> Result<Session, UpdateModelError> {
+    self
+      .session_repo
+      .update_model(Session {
+        expires_at: chrono::Utc::now(),
+        ..session
+      })
+      .await
+  }
+  async fn migrate_legacy_token_secrets(&self) -> Result<usize> {
+    let legacy_tokens = self
+      .token_repo
+      .enumerate_models()
+      .await?
+      .into_iter()
+      .filter(|t| t.legacy_secret.is_some())
+      .collect::<Vec<_>>();
+
+    let count = legacy_tokens.len();
+    for token in legacy_tokens {
+      self.hash_legacy_token_secret(token.id, None).await?;
+    }
+
+    Ok(count)
+  }
+  async fn migrate_legacy_entries(&self) -> Result<usize> {
+    let legacy_entries = self
+      .entry_repo
+      .enumerate_models()
+      .await?
+      .into_iter()
+      .filter(Entry::is_legacy)
+      .collect::<Vec<_>>();
+
+    // one unreadable entry shouldn't hold up the rest
+    let mut count = 0;
+    for entry in legacy_entries {
+      let id = entry.id;
+      match self.migrate_legacy_entry(entry).await {
+        Ok(_) => count += 1,
+        Err(e) => {
+          tracing::warn!(entry = %id, "failed to migrate legacy entry: {e:?}");
+        }
+      }
+    }
+
+    Ok(count)
   }
 
   async fn create_entry(
     &self,
     owning_cache: CacheRecordId,
-    path: LaxSlug,
+    path: models::StorePath,
+    meta: Option<models::EntryMetadata>,
+    expected_nar_hash: Option<models::NixHash>,
     data: Belt,
   ) -> Result<Entry, CreateEntryError> {
     // check if the entry already exists
@@ -243,11 +1060,39 @@ where
       .map_err(CreateEntryError::FetchModelError)?
       .ok_or(CreateEntryError::CacheNotFound(owning_cache))?;
 
-    let c_status = self.write_to_store(cache.store, path.clone(), data).await?;
+    // refuse to store data that doesn't match what the uploader claimed
+    let written = match expected_nar_hash {
+      Some(expected) => {
+        let temp_path = self.stage_verified_nar(expected, data).await?;
+        let written = match self.temp_storage_repo.read(temp_path.clone()).await
+        {
+          Ok(data) => self
+            .write_to_store(cache.store, path.clone(), data)
+            .await
+            .map_err(CreateEntryError::from),
+          Err(e) => Err(CreateEntryError::StorageReadError(e)),
+        };
+        self.discard_temp_storage(temp_path).await;
+        written
+      }
+      None => self
+        .write_to_store(cache.store, path.clone(), data)
+        .await
+        .map_err(CreateEntryError::from),
+    };
+    let WrittenData {
+      c_status,
+      nar_hash,
+      file_hash,
+      listing,
+    } = written?;
 
     let entry_cr = EntryCreateRequest {
       path,
       c_status,
+      nar_hash,
+      file_hash,
+      meta,
       cache: owning_cache,
       org: cache.org,
     };
@@ -258,49 +1103,300 @@ where
       .await
       .map_err(CreateEntryError::CreateError)?;
 
+    if let (true, Some(listing)) = (cache.index_debuginfo, &listing) {
+      self.index_debuginfo(&entry, listing).await;
+    }
+
     Ok(entry)
   }
-  async fn read_from_entry(
+  async fn create_token(
     &self,
-    entry_id: EntryRecordId,
-  ) -> Result<Belt, ReadFromEntryError> {
-    let entry = self
-      .fetch_entry_by_id(entry_id)
+    nickname: models::EntityNickname,
+    perms: models::PermissionSet,
+    owner: models::UserRecordId,
+    org: models::OrgRecordId,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+  ) -> Result<(Token, models::TokenSecret), CreateModelError> {
+    let secret = generate_token_secret();
+
+    let token = self
+      .token_repo
+      .create_model(TokenCreateRequest {
+        nickname,
+        secret_hash: self.token_hasher.hash(&secret),
+        perms,
+        owner,
+        org,
+        expires_at,
+      })
+      .await?;
+
+    Ok((token, secret))
+  }
+  async fn issue_token(
+    &self,
+    issuer: &User,
+    nickname: models::EntityNickname,
+    perms: models::PermissionSet,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+  ) -> Result<(Token, models::TokenSecret), IssueTokenError> {
+    let excess = models::PermissionSet(
+      perms
+        .0
+        .iter()
+        .filter(|perm| !issuer.perms.contains(perm))
+        .cloned()
+        .collect(),
+    );
+    if !excess.0.is_empty() {
+      return Err(IssueTokenError::ExcessPermissions(excess));
+    }
+
+    let (token, secret) = self
+      .create_token(nickname, perms, issuer.id, issuer.org, expires_at)
       .await
-      .map_err(ReadFromEntryError::FetchModelError)?
-      .ok_or_else(|| ReadFromEntryError::EntryNotFound(entry_id))?;
+      .map_err(IssueTokenError::CreateError)?;
+    tracing::info!(token = %token.id, user = %issuer.id, "issued token");
+
+    Ok((token, secret))
+  }
+  async fn revoke_token(
+    &self,
+    owner: &User,
+    id: TokenRecordId,
+  ) -> Result<Token, RevokeTokenError> {
+    let token = self
+      .token_repo
+      .fetch_model_by_id(id)
+      .await
+      .map_err(RevokeTokenError::FetchError)?
+      // other users' tokens are reported as missing, so their IDs don't leak
+      .filter(|t| t.owner == owner.id)
+      .ok_or(RevokeTokenError::NotFound(id))?;
+    if token.revoked() {
+      return Ok(token);
+    }
+
+    let token = self
+      .token_repo
+      .update_model(Token {
+        revoked_at: Some(chrono::Utc::now()),
+        ..token
+      })
+      .await
+      .map_err(RevokeTokenError::UpdateError)?;
+    tracing::info!(token = %token.id, user = %owner.id, "revoked token");
+
+    Ok(token)
+  }
+  async fn create_cache(
+    &self,
+    creator: &User,
+    name: models::EntityName,
+    visibility: models::Visibility,
+    store: StoreRecordId,
+  ) -> Result<Cache, CreateCacheError> {
+    self
+      .fetch_store_for_cache(creator.org, store)
+      .await
+      .map_err(CreateCacheError::StoreError)?;
 
     let cache = self
-      .fetch_cache_by_id(entry.cache)
+      .cache_repo
+      .create_model(models::CacheCreateRequest {
+        name: name.clone(),
+        visibility,
+        store,
+        org: creator.org,
+        index_debuginfo: false,
+        upstreams: Vec::new(),
+        proxy: None,
+      })
       .await
-      .map_err(ReadFromEntryError::FetchModelError)?
-      .ok_or_else(|| {
-        ReadFromEntryError::DataIntegrityError(miette::miette!(
-          "entry references non-existent cache: {}",
-          entry.cache
-        ))
+      .map_err(|e| match e {
+        CreateModelError::IndexAlreadyExists { .. } => {
+          CreateCacheError::NameTaken(name)
+        }
+        e => CreateCacheError::CreateError(e),
       })?;
 
-    let store = self
-      .fetch_store_by_id(cache.store)
+    // a cache nobody can use or sign with is worse than no cache
+    if let Err(e) = self.set_up_cache(creator, &cache).await {
+      self.roll_back_cache(cache.id).await;
+      return Err(e);
+    }
+    tracing::info!(cache = %cache.id, user = %creator.id, "created cache");
+
+    Ok(cache)
+  }
+  async fn update_cache(
+    &self,
+    updater: &User,
+    id: CacheRecordId,
+    update: models::CacheUpdateRequest,
+  ) -> Result<Cache, UpdateCacheError> {
+    let (cache, writable) = self
+      .fetch_cache_for_writing(updater, id)
       .await
-      .map_err(ReadFromEntryError::FetchModelError)?
-      .ok_or_else(|| {
-        ReadFromEntryError::DataIntegrityError(miette::miette!(
-          "cache references non-existent store: {}",
-          cache.store
-        ))
+      .map_err(UpdateCacheError::FetchError)?
+      .ok_or(UpdateCacheError::NotFound(id))?;
+    if !writable {
+      return Err(UpdateCacheError::Unauthorized(id));
+    }
+
+    if let Some(store) = update.store.filter(|s| *s != cache.store) {
+      self
+        .fetch_store_for_cache(cache.org, store)
+        .await
+        .map_err(UpdateCacheError::StoreError)?;
+      // the entries are in the old store, and would be lost
+      if self
+        .cache_has_entries(id)
+        .await
+        .map_err(UpdateCacheError::FetchError)?
+      {
+        return Err(UpdateCacheError::NotEmpty(id));
+      }
+    }
+
+    let new_name = update.name.clone();
+    let cache = self
+      .cache_repo
+      .update_model(update.apply(cache))
+      .await
+      .map_err(|e| match (e, new_name) {
+        (UpdateModelError::IndexAlreadyExists { .. }, Some(name)) => {
+          UpdateCacheError::NameTaken(name)
+        }
+        (e, _) => UpdateCacheError::UpdateError(e),
       })?;
+    tracing::info!(cache = %cache.id, user = %updater.id, "updated cache");
 
-    // check what compression algorithm is configured in the store
-    let algorithm = entry.c_status.algorithm();
+    Ok(cache)
+  }
+  async fn delete_cache(
+    &self,
+    deleter: &User,
+    id: CacheRecordId,
+  ) -> Result<Cache, DeleteCacheError> {
+    let (_, writable) = self
+      .fetch_cache_for_writing(deleter, id)
+      .await
+      .map_err(DeleteCacheError::FetchError)?
+      .ok_or(DeleteCacheError::NotFound(id))?;
+    if !writable {
+      return Err(DeleteCacheError::Unauthorized(id));
+    }
+    if self
+      .cache_has_entries(id)
+      .await
+      .map_err(DeleteCacheError::FetchError)?
+    {
+      return Err(DeleteCacheError::NotEmpty(id));
+    }
 
-    // get the user storage client
-    let client = self
-      .user_storage_repo
-      .connect_to_user_storage(store.credentials.clone())
+    // everything hanging off the cache goes first, so a failure partway
+    // leaves the cache in place to retry the deletion on
+    let signing_key = self
+      .signing_key_repo
+      .find_by_cache_id(id)
       .await
-      .map_err(ReadFromEntryError::StorageConnectionError)?;
+      .map_err(|e| DeleteCacheError::FetchError(e.into()))?;
+    if let Some(signing_key) = signing_key {
+      self
+        .signing_key_repo
+        .delete_model(signing_key.id)
+        .await
+        .map_err(DeleteCacheError::DeleteError)?;
+    }
+
+    let realisations = self
+      .realisation_repo
+      .find_all_by_cache_id(id)
+      .await
+      .map_err(|e| DeleteCacheError::FetchError(e.into()))?;
+    for realisation in realisations {
+      self
+        .realisation_repo
+        .delete_model(realisation.id)
+        .await
+        .map_err(DeleteCacheError::DeleteError)?;
+    }
+    let debuginfos = self
+      .debuginfo_repo
+      .find_all_by_cache_id(id)
+      .await
+      .map_err(|e| DeleteCacheError::FetchError(e.into()))?;
+    for debuginfo in debuginfos {
+      self
+        .debuginfo_repo
+        .delete_model(debuginfo.id)
+        .await
+        .map_err(DeleteCacheError::DeleteError)?;
+    }
+
+    let cache = self
+      .cache_repo
+      .delete_model(id)
+      .await
+      .map_err(DeleteCacheError::DeleteError)?
+      .ok_or(DeleteCacheError::NotFound(id))?;
+    tracing::info!(cache = %cache.id, user = %deleter.id, "deleted cache");
+
+    // the grants go last, since the deleter needs theirs to retry a deletion
+    // that failed partway
+    self
+      .revoke_cache_grants(&cache)
+      .await
+      .map_err(DeleteCacheError::GrantError)?;
+
+    Ok(cache)
+  }
+  async fn create_realisation(
+    &self,
+    owning_cache: CacheRecordId,
+    realisation: nasty::realisation::Realisation,
+  ) -> Result<Realisation, CreateRealisationError> {
+    // check if the realisation already exists
+    let existing_realisation = self
+      .find_realisation_by_id_and_drv_output(
+        owning_cache,
+        realisation.id.clone(),
+      )
+      .await
+      .map_err(CreateRealisationError::FetchModelByIndexError)?;
+    if let Some(existing_realisation) = existing_realisation {
+      return Err(CreateRealisationError::RealisationAlreadyExists(Box::new(
+        existing_realisation,
+      )));
+    }
+
+    let cache = self
+      .fetch_cache_by_id(owning_cache)
+      .await
+      .map_err(CreateRealisationError::FetchModelError)?
+      .ok_or(CreateRealisationError::CacheNotFound(owning_cache))?;
+
+    let realisation_cr = RealisationCreateRequest {
+      drv_output:             realisation.id,
+      out_path:               realisation.out_path,
+      signatures:             realisation.signatures,
+      dependent_realisations: realisation.dependent_realisations,
+      cache:                  owning_cache,
+      org:                    cache.org,
+    };
+
+    self
+      .realisation_repo
+      .create_model(realisation_cr)
+      .await
+      .map_err(CreateRealisationError::CreateError)
+  }
+  async fn read_from_entry(
+    &self,
+    entry_id: EntryRecordId,
+  ) -> Result<Belt, ReadFromEntryError> {
+    let (entry, client) = self.connect_to_entry_storage(entry_id).await?;
 
     let path = PathBuf::from_str(entry.path.as_ref()).unwrap();
     let reader = client
@@ -308,10 +1404,42 @@ where
       .await
       .map_err(ReadFromEntryError::StorageReadError)?;
 
-    let reader = reader.set_declared_comp(dvf_comp_to_belt_comp(algorithm));
+    let reader = reader
+      .set_declared_comp(dvf_comp_to_belt_comp(entry.c_status.algorithm()));
 
     Ok(reader)
   }
+  async fn read_listing_from_entry(
+    &self,
+    entry_id: EntryRecordId,
+  ) -> Result<nasty::listing::Listing, ReadFromEntryError> {
+    let (entry, client) = self.connect_to_entry_storage(entry_id).await?;
+
+    // the listing is compressed like the NAR next to it
+    let path =
+      PathBuf::from_str(&crate::listing_storage_path(&entry.path)).unwrap();
+    let reader = client
+      .read(&path)
+      .await
+      .map_err(ReadFromEntryError::StorageReadError)?
+      .set_declared_comp(dvf_comp_to_belt_comp(entry.c_status.algorithm()))
+      .adapt_to_no_comp();
+
+    let mut json = Vec::new();
+    reader
+      .to_async_buf_read()
+      .read_to_end(&mut json)
+      .await
+      .map_err(|e| {
+        ReadFromEntryError::StorageReadError(StorageReadError::IoError(e))
+      })?;
+
+    nasty::listing::Listing::from_json(&json).map_err(|e| {
+      ReadFromEntryError::DataIntegrityError(miette::miette!(
+        "listing for entry {entry_id} is malformed: {e}"
+      ))
+    })
+  }
 
   async fn read_from_temp_storage(
     &self,
@@ -325,17 +1453,47 @@ where
   ) -> Result<models::TempStoragePath, StorageWriteError> {
     self.temp_storage_repo.store(data).await
   }
+  async fn write_to_temp_storage_at(
+    &self,
+    path: models::TempStoragePath,
+    data: Belt,
+  ) -> Result<models::TempStoragePath, StorageWriteError> {
+    self.temp_storage_repo.store_at(path, data).await
+  }
+  async fn delete_from_temp_storage(
+    &self,
+    path: models::TempStoragePath,
+  ) -> Result<(), StorageDeleteError> {
+    self.temp_storage_repo.delete(path).await
+  }
 }
 
 #[async_trait::async_trait]
-impl<CR, ER, SR, TR, TSR, USR> health::HealthReporter
-  for PrimeDomainServiceCanonical<CR, ER, SR, TR, TSR, USR>
+impl<CR, DIR, ER, RR, SER, SKR, SR, TR, TSR, UR, USR> health::HealthReporter
+  for PrimeDomainServiceCanonical<
+    CR,
+    DIR,
+    ER,
+    RR,
+    SER,
+    SKR,
+    SR,
+    TR,
+    TSR,
+    UR,
+    USR,
+  >
 where
   CR: CacheRepository,
+  DIR: DebugInfoRepository,
   ER: EntryRepository,
+  RR: RealisationRepository,
+  SER: SessionRepository,
+  SKR: SigningKeyRepository,
   SR: StoreRepository,
   TR: TokenRepository,
   TSR: TempStorageRepository,
+  UR: UserRepository,
   USR: repos::UserStorageRepository,
 {
   fn name(&self) -> &'static str { stringify!(PrimeDomainServiceCanonical) }
@@ -343,10 +1501,15 @@ where
   async fn health_check(&self) -> health::ComponentHealth {
     health::AdditiveComponentHealth::from_futures(vec![
       self.cache_repo.health_report(),
+      self.debuginfo_repo.health_report(),
       self.entry_repo.health_report(),
+      self.realisation_repo.health_report(),
+      self.session_repo.health_report(),
+      self.signing_key_repo.health_report(),
       self.store_repo.health_report(),
       self.token_repo.health_report(),
       self.temp_storage_repo.health_report(),
+      self.user_repo.health_report(),
       self.user_storage_repo.health_report(),
     ])
     .await
@@ -10,7 +10,10 @@
 //! the service.
 
 mod canonical;
+mod password;
 mod pointer;
+mod token_secret;
+mod upstream;
 
 use std::sync::Arc;
 
@@ -19,23 +22,34 @@ use hex::Hexagonal;
 use miette::Result;
 pub use models;
 use models::{
-  Cache, CacheRecordId, Entry, EntryRecordId, LaxSlug, Store, StoreRecordId,
-  StrictSlug, Token, TokenRecordId,
+  Cache, CacheRecordId, DebugInfo, Entry, EntryRecordId, LaxSlug, Realisation,
+  Session, SigningKey, Store, StoreRecordId, StrictSlug, Token, TokenRecordId,
+  User, UserRecordId,
 };
 pub use repos::{
-  self, StorageReadError, StorageWriteError, TempStorageCreds,
-  TempStorageCredsError,
+  self, StorageDeleteError, StorageReadError, StorageWriteError,
+  TempStorageCreds, TempStorageCredsError,
 };
 use repos::{
   belt::Belt,
-  db::{FetchModelByIndexError, FetchModelError},
+  db::{
+    CreateModelError, DeleteModelError, FetchModelByIndexError,
+    FetchModelError, UpdateModelError,
+  },
 };
 
-pub use self::canonical::*;
+pub use self::{canonical::*, password::*, token_secret::*};
 
 /// A dynamic [`PrimeDomainService`] trait object.
 pub type DynPrimeDomainService = Arc<Box<dyn PrimeDomainService>>;
 
+/// Returns where the `.ls` listing of a store path's NAR is kept in its store.
+///
+/// The listing is compressed the same way as the NAR next to it.
+pub fn listing_storage_path(path: &models::StorePath) -> String {
+  format!("{path}.ls")
+}
+
 /// The prime domain service trait.
 #[async_trait::async_trait]
 pub trait PrimeDomainService: Hexagonal {
@@ -59,14 +73,29 @@ pub trait PrimeDomainService: Hexagonal {
     &self,
     id: TokenRecordId,
   ) -> Result<Option<Token>, FetchModelError>;
+  /// Fetch a [`User`] by their ID.
+  async fn fetch_user_by_id(
+    &self,
+    id: UserRecordId,
+  ) -> Result<Option<User>, FetchModelError>;
   /// Produce a list of all [`Cache`]s.
   async fn enumerate_caches(&self) -> Result<Vec<Cache>>;
+  /// Produce a list of the [`Cache`]s in an [`Org`](models::Org).
+  async fn enumerate_caches_by_org(
+    &self,
+    org: models::OrgRecordId,
+  ) -> Result<Vec<Cache>>;
   /// Produce a list of all [`Entry`]s.
   async fn enumerate_entries(&self) -> Result<Vec<Entry>>;
   /// Produce a list of all [`Store`]s.
   async fn enumerate_stores(&self) -> Result<Vec<Store>>;
   /// Produce a list of all [`Token`]s.
   async fn enumerate_tokens(&self) -> Result<Vec<Token>>;
+  /// Produce a list of the [`Token`]s owned by a [`User`].
+  async fn enumerate_tokens_by_owner(
+    &self,
+    owner: UserRecordId,
+  ) -> Result<Vec<Token>>;
 
   /// Find a [`Cache`] by its name.
   async fn find_cache_by_name(
@@ -77,27 +106,194 @@ pub trait PrimeDomainService: Hexagonal {
   async fn find_entry_by_id_and_path(
     &self,
     cache_id: CacheRecordId,
-    path: models::LaxSlug,
+    path: models::StorePath,
+  ) -> Result<Option<Entry>, FetchModelByIndexError>;
+  /// Find many [`Entry`]s by their [`Cache`] ID and paths, in a single
+  /// transaction. Returns one result per path, in the same order.
+  async fn find_entries_by_id_and_paths(
+    &self,
+    cache_id: CacheRecordId,
+    paths: Vec<models::StorePath>,
+  ) -> Result<Vec<Option<Entry>>, FetchModelByIndexError>;
+  /// Find an [`Entry`] by its [`Cache`] ID and the hash part of its path.
+  async fn find_entry_by_id_and_path_hash(
+    &self,
+    cache_id: CacheRecordId,
+    path_hash: LaxSlug,
   ) -> Result<Option<Entry>, FetchModelByIndexError>;
+  /// Find the [`SigningKey`] belonging to a [`Cache`].
+  async fn find_signing_key_by_cache_id(
+    &self,
+    cache_id: CacheRecordId,
+  ) -> Result<Option<SigningKey>, FetchModelByIndexError>;
+  /// Find a [`DebugInfo`] by its [`Cache`] ID and build ID.
+  async fn find_debuginfo_by_id_and_build_id(
+    &self,
+    cache_id: CacheRecordId,
+    build_id: LaxSlug,
+  ) -> Result<Option<DebugInfo>, FetchModelByIndexError>;
+  /// Find a [`Realisation`] by its cache ID and derivation output.
+  async fn find_realisation_by_id_and_drv_output(
+    &self,
+    cache_id: CacheRecordId,
+    drv_output: models::DrvOutput,
+  ) -> Result<Option<Realisation>, FetchModelByIndexError>;
+  /// Find which of the given paths the [`Cache`]'s upstream caches have,
+  /// signed with their public keys.
+  ///
+  /// Upstream caches that can't be reached are treated as not having the
+  /// paths.
+  async fn find_paths_available_upstream(
+    &self,
+    cache: &Cache,
+    paths: Vec<models::StorePath>,
+  ) -> Vec<models::StorePath>;
+  /// Find the narinfo of the path with the given hash part in the [`Cache`]'s
+  /// proxy, if it has one signed with its public key.
+  ///
+  /// A proxy that can't be reached is treated as not having the path.
+  async fn find_narinfo_from_proxy(
+    &self,
+    cache: &Cache,
+    hash: LaxSlug,
+  ) -> Option<nasty::narinfo::NarInfo>;
   /// Verify a [`Token`] by its ID and secret.
+  ///
+  /// The secret is checked before the token's revocation and expiry, so those
+  /// are only revealed to its holder. A token whose secret is still stored in
+  /// plaintext has it hashed once it verifies.
   async fn verify_token_id_and_secret(
     &self,
     id: TokenRecordId,
     secret: models::TokenSecret,
   ) -> Result<Token, TokenVerifyError>;
+  /// Verify credentials that belong to either a [`Token`] or a [`Session`].
+  ///
+  /// Tokens are checked first, and sessions are only looked up if no token
+  /// has the ID. A session acts with the permissions of its [`User`].
+  async fn verify_credentials(
+    &self,
+    credentials: models::Credentials,
+  ) -> Result<Principal, TokenVerifyError>;
+  /// Logs a [`User`] in by their username and password, starting a
+  /// [`Session`].
+  ///
+  /// Only the session secret's hash is stored, so the returned secret can't be
+  /// recovered later.
+  async fn login(
+    &self,
+    username: models::EntityName,
+    password: models::Password,
+  ) -> Result<(Session, models::TokenSecret), LoginError>;
+  /// Ends a [`Session`] by expiring it now.
+  async fn end_session(
+    &self,
+    session: Session,
+  ) -> Result<Session, UpdateModelError>;
+  /// Hashes the secrets of all [`Token`]s that still store them in plaintext.
+  ///
+  /// Returns how many tokens were migrated.
+  async fn migrate_legacy_token_secrets(&self) -> Result<usize>;
+  /// Records the hashes of all [`Entry`]s written before they were, and adds
+  /// them to indices that were introduced after them.
+  ///
+  /// Returns how many entries were migrated. Entries that fail are logged and
+  /// left to be retried.
+  async fn migrate_legacy_entries(&self) -> Result<usize>;
 
   /// Creates an [`Entry`] in a given [`Cache`], with the given path and data.
+  ///
+  /// `meta` should only be left out if the uploader didn't report it, since
+  /// the entry can't be signed without knowing its references. If
+  /// `expected_nar_hash` is given, the data is staged in temp storage and only
+  /// written to the store if it hashes to it. If the cache indexes debug info,
+  /// the NAR's debug info files are recorded as [`DebugInfo`]s.
   async fn create_entry(
     &self,
     owning_cache: CacheRecordId,
-    path: LaxSlug,
+    path: models::StorePath,
+    meta: Option<models::EntryMetadata>,
+    expected_nar_hash: Option<models::NixHash>,
     data: Belt,
   ) -> Result<Entry, CreateEntryError>;
+  /// Creates a [`Token`] with a freshly generated secret.
+  ///
+  /// Only the secret's hash is stored, so the returned secret can't be
+  /// recovered later.
+  async fn create_token(
+    &self,
+    nickname: models::EntityNickname,
+    perms: models::PermissionSet,
+    owner: models::UserRecordId,
+    org: models::OrgRecordId,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+  ) -> Result<(Token, models::TokenSecret), CreateModelError>;
+  /// Issues a [`Token`] on behalf of a [`User`], who owns it.
+  ///
+  /// Users can only hand out permissions they hold themselves.
+  async fn issue_token(
+    &self,
+    issuer: &User,
+    nickname: models::EntityNickname,
+    perms: models::PermissionSet,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+  ) -> Result<(Token, models::TokenSecret), IssueTokenError>;
+  /// Revokes a [`Token`] owned by the given [`User`].
+  ///
+  /// Revoking a token that's already revoked leaves it as it was.
+  async fn revoke_token(
+    &self,
+    owner: &User,
+    id: TokenRecordId,
+  ) -> Result<Token, RevokeTokenError>;
+  /// Creates a [`Cache`] on behalf of a [`User`], in their org.
+  ///
+  /// The backing [`Store`] has to belong to the same org. The cache gets a
+  /// fresh [`SigningKey`], and the user is granted read and write access to
+  /// it. If either fails, the cache is removed again.
+  async fn create_cache(
+    &self,
+    creator: &User,
+    name: models::EntityName,
+    visibility: models::Visibility,
+    store: StoreRecordId,
+  ) -> Result<Cache, CreateCacheError>;
+  /// Changes a [`Cache`]'s settings on behalf of a [`User`] with write access
+  /// to it.
+  ///
+  /// The backing [`Store`] can only be changed while the cache has no entries,
+  /// since they're kept in the store.
+  async fn update_cache(
+    &self,
+    updater: &User,
+    id: CacheRecordId,
+    update: models::CacheUpdateRequest,
+  ) -> Result<Cache, UpdateCacheError>;
+  /// Deletes an empty [`Cache`] on behalf of a [`User`] with write access to
+  /// it, along with its [`SigningKey`], [`Realisation`]s and [`DebugInfo`]s.
+  ///
+  /// Any permissions on it are revoked from the users and tokens in its org.
+  async fn delete_cache(
+    &self,
+    deleter: &User,
+    id: CacheRecordId,
+  ) -> Result<Cache, DeleteCacheError>;
+  /// Creates a [`Realisation`] in a given [`Cache`].
+  async fn create_realisation(
+    &self,
+    owning_cache: CacheRecordId,
+    realisation: nasty::realisation::Realisation,
+  ) -> Result<Realisation, CreateRealisationError>;
   /// Reads data from an [`Entry`].
   async fn read_from_entry(
     &self,
     entry_id: EntryRecordId,
   ) -> Result<Belt, ReadFromEntryError>;
+  /// Reads the listing of an [`Entry`]'s NAR.
+  async fn read_listing_from_entry(
+    &self,
+    entry_id: EntryRecordId,
+  ) -> Result<nasty::listing::Listing, ReadFromEntryError>;
 
   /// Read data from the temp storage.
   async fn read_from_temp_storage(
@@ -109,9 +305,63 @@ pub trait PrimeDomainService: Hexagonal {
     &self,
     data: Belt,
   ) -> Result<models::TempStoragePath, StorageWriteError>;
+  /// Store data in the temp storage at the given path.
+  async fn write_to_temp_storage_at(
+    &self,
+    path: models::TempStoragePath,
+    data: Belt,
+  ) -> Result<models::TempStoragePath, StorageWriteError>;
+  /// Delete data from the temp storage, once it's no longer needed.
+  async fn delete_from_temp_storage(
+    &self,
+    path: models::TempStoragePath,
+  ) -> Result<(), StorageDeleteError>;
+}
+
+/// Whoever a request is made by, once their credentials are verified.
+#[derive(Clone, Debug)]
+pub enum Principal {
+  /// A [`Token`], acting with its own permissions.
+  Token(Token),
+  /// A [`Session`], acting with the permissions of its user.
+  Session {
+    /// The verified session.
+    session: Session,
+    /// The user the session belongs to.
+    user:    User,
+  },
+}
+
+impl Principal {
+  /// The ID of the token or session.
+  pub fn id(&self) -> models::Ulid {
+    match self {
+      Self::Token(token) => token.id.into(),
+      Self::Session { session, .. } => session.id.into(),
+    }
+  }
+
+  /// The ID of the [`User`] acting, or owning the token that is.
+  pub fn user_id(&self) -> UserRecordId {
+    match self {
+      Self::Token(token) => token.owner,
+      Self::Session { user, .. } => user.id,
+    }
+  }
+
+  /// Check if the principal has the given permissions.
+  pub fn authorized(&self, perms: &models::PermissionSet) -> bool {
+    match self {
+      Self::Token(token) => token.authorized(perms),
+      Self::Session { user, .. } => user.authorized(perms),
+    }
+  }
 }
 
 /// The error type for token verification.
+///
+/// Session verification reports its failures the same way, as sessions are
+/// tokens that a user got by logging in.
 #[derive(Debug, thiserror::Error, miette::Diagnostic)]
 pub enum TokenVerifyError {
   /// The token ID was not found.
@@ -120,10 +370,155 @@ pub enum TokenVerifyError {
   /// The token secret does not match the expected secret.
   #[error("token secret mismatch")]
   SecretMismatch,
+  /// The token has expired.
+  #[error("token has expired")]
+  Expired,
+  /// The token has been revoked.
+  #[error("token has been revoked")]
+  Revoked,
+  /// An error occurred while fetching the token.
+  #[error("error fetching token")]
+  #[diagnostic_source]
+  FetchError(FetchModelError),
+}
+
+/// The error type for logging in.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum LoginError {
+  /// The username or password is wrong, or the user can't log in.
+  #[error("invalid username or password")]
+  InvalidCredentials,
+  /// An error occurred while fetching the user.
+  #[error("error fetching user")]
+  #[diagnostic_source]
+  FetchError(FetchModelByIndexError),
+  /// Failed to create the session.
+  #[error("failed to create session")]
+  #[diagnostic_source]
+  CreateError(CreateModelError),
+}
+
+/// The error type for issuing a token.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum IssueTokenError {
+  /// The issuer doesn't hold some of the requested permissions.
+  #[error("issuer doesn't hold the requested permissions")]
+  ExcessPermissions(models::PermissionSet),
+  /// Failed to create the token.
+  #[error("failed to create token")]
+  #[diagnostic_source]
+  CreateError(CreateModelError),
+}
+
+/// The error type for revoking a token.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum RevokeTokenError {
+  /// The token doesn't exist, or belongs to someone else.
+  #[error("token not found")]
+  NotFound(TokenRecordId),
   /// An error occurred while fetching the token.
   #[error("error fetching token")]
   #[diagnostic_source]
   FetchError(FetchModelError),
+  /// Failed to write the revocation.
+  #[error("failed to revoke token")]
+  #[diagnostic_source]
+  UpdateError(UpdateModelError),
+}
+
+/// The error type for choosing the backing store of a cache.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum CacheStoreError {
+  /// The store doesn't exist.
+  #[error("store {0} not found")]
+  NotFound(StoreRecordId),
+  /// The store belongs to a different org than the cache.
+  #[error("store {0} belongs to another org")]
+  ForeignOrg(StoreRecordId),
+  /// An error occurred while fetching the store.
+  #[error("error fetching store")]
+  #[diagnostic_source]
+  FetchError(FetchModelError),
+}
+
+/// The error type for creating a cache.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum CreateCacheError {
+  /// Another cache already has the name.
+  #[error("cache name {0:?} is taken")]
+  NameTaken(models::EntityName),
+  /// The backing store can't be used.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  StoreError(CacheStoreError),
+  /// Failed to create the cache.
+  #[error("failed to create cache")]
+  #[diagnostic_source]
+  CreateError(CreateModelError),
+  /// Failed to create the cache's signing key.
+  #[error("failed to create the new cache's signing key")]
+  #[diagnostic_source]
+  SigningKeyError(CreateModelError),
+  /// Failed to grant the creator access to the cache.
+  #[error("failed to grant access to the new cache")]
+  #[diagnostic_source]
+  GrantError(miette::Report),
+}
+
+/// The error type for updating a cache.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum UpdateCacheError {
+  /// The cache doesn't exist, or belongs to another org.
+  #[error("cache not found")]
+  NotFound(CacheRecordId),
+  /// The user doesn't have write access to the cache.
+  #[error("user doesn't have write access to the cache")]
+  Unauthorized(CacheRecordId),
+  /// The store can't be changed because the cache has entries.
+  #[error("cache has entries")]
+  NotEmpty(CacheRecordId),
+  /// Another cache already has the name.
+  #[error("cache name {0:?} is taken")]
+  NameTaken(models::EntityName),
+  /// The new backing store can't be used.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  StoreError(CacheStoreError),
+  /// An error occurred while fetching the cache or its entries.
+  #[error("error fetching cache")]
+  #[diagnostic_source]
+  FetchError(miette::Report),
+  /// Failed to write the changes.
+  #[error("failed to update cache")]
+  #[diagnostic_source]
+  UpdateError(UpdateModelError),
+}
+
+/// The error type for deleting a cache.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum DeleteCacheError {
+  /// The cache doesn't exist, or belongs to another org.
+  #[error("cache not found")]
+  NotFound(CacheRecordId),
+  /// The user doesn't have write access to the cache.
+  #[error("user doesn't have write access to the cache")]
+  Unauthorized(CacheRecordId),
+  /// The cache still has entries.
+  #[error("cache has entries")]
+  NotEmpty(CacheRecordId),
+  /// An error occurred while fetching the cache, or what belongs to it.
+  #[error("error fetching cache")]
+  #[diagnostic_source]
+  FetchError(miette::Report),
+  /// Failed to delete the cache, or what belongs to it.
+  #[error("failed to delete cache")]
+  #[diagnostic_source]
+  DeleteError(DeleteModelError),
+  /// The cache was deleted, but revoking the permissions granted on it
+  /// failed.
+  #[error("failed to revoke access to the deleted cache")]
+  #[diagnostic_source]
+  GrantError(miette::Report),
 }
 
 /// The error type for writing to a store.
@@ -161,6 +556,9 @@ pub enum CreateEntryError {
   /// An error occurred while writing to the store.
   #[error("failed to write to store")]
   StorageWriteError(StorageWriteError),
+  /// An error occurred while reading back data staged in temp storage.
+  #[error("failed to read from temp storage")]
+  StorageReadError(StorageReadError),
   /// An error occurred while fetching a model.
   #[error("failed to fetch model")]
   FetchModelError(FetchModelError),
@@ -170,6 +568,14 @@ pub enum CreateEntryError {
   /// An error occurred due to data integrity failure.
   #[error("data integrity error")]
   DataIntegrityError(miette::Report),
+  /// The data didn't match the expected NAR hash.
+  #[error("NAR hash mismatch: expected {expected}, got {actual}")]
+  NarHashMismatch {
+    /// The expected NAR hash.
+    expected: models::NixHash,
+    /// The actual NAR hash of the data.
+    actual:   models::NixHash,
+  },
 }
 
 impl From<WriteToStoreError> for CreateEntryError {
@@ -191,6 +597,26 @@ impl From<WriteToStoreError> for CreateEntryError {
   }
 }
 
+/// The error type for creating a realisation.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum CreateRealisationError {
+  /// A realisation of the same derivation output already exists.
+  #[error("realisation already exists")]
+  RealisationAlreadyExists(Box<Realisation>),
+  /// The cache was not found.
+  #[error("cache not found")]
+  CacheNotFound(CacheRecordId),
+  /// Failed to create the realisation.
+  #[error("failed to create realisation")]
+  CreateError(repos::CreateModelError),
+  /// An error occurred while fetching a model.
+  #[error("failed to fetch model")]
+  FetchModelError(FetchModelError),
+  /// An error occurred while fetching a model by index.
+  #[error("failed to fetch model by index")]
+  FetchModelByIndexError(FetchModelByIndexError),
+}
+
 /// The error type for reading from a store.
 #[derive(Debug, thiserror::Error, miette::Diagnostic)]
 pub enum ReadFromEntryError {
@@ -0,0 +1,63 @@
+//! Password hashing for user credentials.
+
+use argon2::{
+  password_hash::{
+    PasswordHash as PhcString, PasswordHasher, PasswordVerifier, SaltString,
+  },
+  Argon2,
+};
+use models::{Password, PasswordHash};
+use rand::rngs::OsRng;
+
+/// Hashes a password with argon2id, with a fresh random salt.
+pub fn hash_password(password: &Password) -> PasswordHash {
+  let salt = SaltString::generate(&mut OsRng);
+  let hash = Argon2::default()
+    .hash_password(password_bytes(password), &salt)
+    .expect("argon2 accepts passwords and salts of these lengths");
+  PasswordHash::new(hash.to_string())
+}
+
+/// Checks a password against a stored hash.
+///
+/// The hash carries its own parameters, so hashes made with older parameters
+/// keep verifying. A hash that can't be parsed never matches.
+pub fn verify_password(password: &Password, hash: &PasswordHash) -> bool {
+  let Ok(hash) = PhcString::new(hash.as_ref()) else {
+    return false;
+  };
+  Argon2::default()
+    .verify_password(password_bytes(password), &hash)
+    .is_ok()
+}
+
+fn password_bytes(password: &Password) -> &[u8] {
+  let password: &str = password.as_ref();
+  password.as_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn password(s: &str) -> Password { Password::try_new(s.to_string()).unwrap() }
+
+  #[test]
+  fn test_hash_and_verify() {
+    let hash = hash_password(&password("correct horse"));
+    let phc: &str = hash.as_ref();
+    assert!(phc.starts_with("$argon2id$"));
+
+    assert!(verify_password(&password("correct horse"), &hash));
+    assert!(!verify_password(&password("battery staple"), &hash));
+
+    // the salt is random, so the same password hashes differently
+    assert_ne!(hash, hash_password(&password("correct horse")));
+  }
+
+  #[test]
+  fn test_verify_malformed_hash() {
+    let hash = PasswordHash::new("not a phc string".to_string());
+    assert!(!verify_password(&password("correct horse"), &hash));
+  }
+}
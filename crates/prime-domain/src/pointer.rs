@@ -1,16 +1,21 @@
 use miette::Result;
 use models::{
   CacheRecordId, EntryRecordId, LaxSlug, StoreRecordId, StrictSlug,
-  TokenRecordId,
+  TokenRecordId, UserRecordId,
 };
 use repos::{
   belt::Belt,
-  db::{FetchModelByIndexError, FetchModelError},
-  Cache, Entry, StorageReadError, StorageWriteError, Store, Token,
+  db::{
+    CreateModelError, FetchModelByIndexError, FetchModelError, UpdateModelError,
+  },
+  Cache, DebugInfo, Entry, Realisation, Session, SigningKey,
+  StorageDeleteError, StorageReadError, StorageWriteError, Store, Token, User,
 };
 
 use crate::{
-  CreateEntryError, PrimeDomainService, ReadFromEntryError, TokenVerifyError,
+  CreateCacheError, CreateEntryError, CreateRealisationError, DeleteCacheError,
+  IssueTokenError, LoginError, PrimeDomainService, Principal,
+  ReadFromEntryError, RevokeTokenError, TokenVerifyError, UpdateCacheError,
 };
 
 // impl for smart pointers
@@ -44,9 +49,21 @@ where
   ) -> Result<Option<Token>, FetchModelError> {
     self.deref().fetch_token_by_id(id).await
   }
+  async fn fetch_user_by_id(
+    &self,
+    id: UserRecordId,
+  ) -> Result<Option<User>, FetchModelError> {
+    self.deref().fetch_user_by_id(id).await
+  }
   async fn enumerate_caches(&self) -> Result<Vec<Cache>> {
     self.deref().enumerate_caches().await
   }
+  async fn enumerate_caches_by_org(
+    &self,
+    org: models::OrgRecordId,
+  ) -> Result<Vec<Cache>> {
+    self.deref().enumerate_caches_by_org(org).await
+  }
   async fn enumerate_entries(&self) -> Result<Vec<Entry>> {
     self.deref().enumerate_entries().await
   }
@@ -56,6 +73,12 @@ where
   async fn enumerate_tokens(&self) -> Result<Vec<Token>> {
     self.deref().enumerate_tokens().await
   }
+  async fn enumerate_tokens_by_owner(
+    &self,
+    owner: UserRecordId,
+  ) -> Result<Vec<Token>> {
+    self.deref().enumerate_tokens_by_owner(owner).await
+  }
 
   async fn find_cache_by_name(
     &self,
@@ -66,10 +89,73 @@ where
   async fn find_entry_by_id_and_path(
     &self,
     cache_id: CacheRecordId,
-    path: models::LaxSlug,
+    path: models::StorePath,
   ) -> Result<Option<Entry>, FetchModelByIndexError> {
     self.deref().find_entry_by_id_and_path(cache_id, path).await
   }
+  async fn find_entries_by_id_and_paths(
+    &self,
+    cache_id: CacheRecordId,
+    paths: Vec<models::StorePath>,
+  ) -> Result<Vec<Option<Entry>>, FetchModelByIndexError> {
+    self
+      .deref()
+      .find_entries_by_id_and_paths(cache_id, paths)
+      .await
+  }
+  async fn find_entry_by_id_and_path_hash(
+    &self,
+    cache_id: CacheRecordId,
+    path_hash: LaxSlug,
+  ) -> Result<Option<Entry>, FetchModelByIndexError> {
+    self
+      .deref()
+      .find_entry_by_id_and_path_hash(cache_id, path_hash)
+      .await
+  }
+  async fn find_signing_key_by_cache_id(
+    &self,
+    cache_id: CacheRecordId,
+  ) -> Result<Option<SigningKey>, FetchModelByIndexError> {
+    self.deref().find_signing_key_by_cache_id(cache_id).await
+  }
+  async fn find_debuginfo_by_id_and_build_id(
+    &self,
+    cache_id: CacheRecordId,
+    build_id: LaxSlug,
+  ) -> Result<Option<DebugInfo>, FetchModelByIndexError> {
+    self
+      .deref()
+      .find_debuginfo_by_id_and_build_id(cache_id, build_id)
+      .await
+  }
+  async fn find_realisation_by_id_and_drv_output(
+    &self,
+    cache_id: CacheRecordId,
+    drv_output: models::DrvOutput,
+  ) -> Result<Option<Realisation>, FetchModelByIndexError> {
+    self
+      .deref()
+      .find_realisation_by_id_and_drv_output(cache_id, drv_output)
+      .await
+  }
+  async fn find_paths_available_upstream(
+    &self,
+    cache: &Cache,
+    paths: Vec<models::StorePath>,
+  ) -> Vec<models::StorePath> {
+    self
+      .deref()
+      .find_paths_available_upstream(cache, paths)
+      .await
+  }
+  async fn find_narinfo_from_proxy(
+    &self,
+    cache: &Cache,
+    hash: LaxSlug,
+  ) -> Option<nasty::narinfo::NarInfo> {
+    self.deref().find_narinfo_from_proxy(cache, hash).await
+  }
   async fn verify_token_id_and_secret(
     &self,
     id: TokenRecordId,
@@ -77,14 +163,113 @@ where
   ) -> Result<Token, TokenVerifyError> {
     self.deref().verify_token_id_and_secret(id, secret).await
   }
+  async fn verify_credentials(
+    &self,
+    credentials: models::Credentials,
+  ) -> Result<Principal, TokenVerifyError> {
+    self.deref().verify_credentials(credentials).await
+  }
+  async fn login(
+    &self,
+    username: models::EntityName,
+    password: models::Password,
+  ) -> Result<(Session, models::TokenSecret), LoginError> {
+    self.deref().login(username, password).await
+  }
+  async fn end_session(
+    &self,
+    session: Session,
+  ) -> Result<Session, UpdateModelError> {
+    self.deref().end_session(session).await
+  }
+  async fn migrate_legacy_token_secrets(&self) -> Result<usize> {
+    self.deref().migrate_legacy_token_secrets().await
+  }
+  async fn migrate_legacy_entries(&self) -> Result<usize> {
+    self.deref().migrate_legacy_entries().await
+  }
 
   async fn create_entry(
     &self,
     owning_cache: CacheRecordId,
-    path: LaxSlug,
+    path: models::StorePath,
+    meta: Option<models::EntryMetadata>,
+    expected_nar_hash: Option<models::NixHash>,
     data: Belt,
   ) -> Result<Entry, CreateEntryError> {
-    self.deref().create_entry(owning_cache, path, data).await
+    self
+      .deref()
+      .create_entry(owning_cache, path, meta, expected_nar_hash, data)
+      .await
+  }
+  async fn create_token(
+    &self,
+    nickname: models::EntityNickname,
+    perms: models::PermissionSet,
+    owner: models::UserRecordId,
+    org: models::OrgRecordId,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+  ) -> Result<(Token, models::TokenSecret), CreateModelError> {
+    self
+      .deref()
+      .create_token(nickname, perms, owner, org, expires_at)
+      .await
+  }
+  async fn issue_token(
+    &self,
+    issuer: &User,
+    nickname: models::EntityNickname,
+    perms: models::PermissionSet,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+  ) -> Result<(Token, models::TokenSecret), IssueTokenError> {
+    self
+      .deref()
+      .issue_token(issuer, nickname, perms, expires_at)
+      .await
+  }
+  async fn revoke_token(
+    &self,
+    owner: &User,
+    id: TokenRecordId,
+  ) -> Result<Token, RevokeTokenError> {
+    self.deref().revoke_token(owner, id).await
+  }
+  async fn create_cache(
+    &self,
+    creator: &User,
+    name: models::EntityName,
+    visibility: models::Visibility,
+    store: StoreRecordId,
+  ) -> Result<Cache, CreateCacheError> {
+    self
+      .deref()
+      .create_cache(creator, name, visibility, store)
+      .await
+  }
+  async fn update_cache(
+    &self,
+    updater: &User,
+    id: CacheRecordId,
+    update: models::CacheUpdateRequest,
+  ) -> Result<Cache, UpdateCacheError> {
+    self.deref().update_cache(updater, id, update).await
+  }
+  async fn delete_cache(
+    &self,
+    deleter: &User,
+    id: CacheRecordId,
+  ) -> Result<Cache, DeleteCacheError> {
+    self.deref().delete_cache(deleter, id).await
+  }
+  async fn create_realisation(
+    &self,
+    owning_cache: CacheRecordId,
+    realisation: nasty::realisation::Realisation,
+  ) -> Result<Realisation, CreateRealisationError> {
+    self
+      .deref()
+      .create_realisation(owning_cache, realisation)
+      .await
   }
   async fn read_from_entry(
     &self,
@@ -92,6 +277,12 @@ where
   ) -> Result<Belt, ReadFromEntryError> {
     self.deref().read_from_entry(entry_id).await
   }
+  async fn read_listing_from_entry(
+    &self,
+    entry_id: EntryRecordId,
+  ) -> Result<nasty::listing::Listing, ReadFromEntryError> {
+    self.deref().read_listing_from_entry(entry_id).await
+  }
 
   async fn read_from_temp_storage(
     &self,
@@ -105,4 +296,17 @@ where
   ) -> Result<models::TempStoragePath, StorageWriteError> {
     self.deref().write_to_temp_storage(data).await
   }
+  async fn write_to_temp_storage_at(
+    &self,
+    path: models::TempStoragePath,
+    data: Belt,
+  ) -> Result<models::TempStoragePath, StorageWriteError> {
+    self.deref().write_to_temp_storage_at(path, data).await
+  }
+  async fn delete_from_temp_storage(
+    &self,
+    path: models::TempStoragePath,
+  ) -> Result<(), StorageDeleteError> {
+    self.deref().delete_from_temp_storage(path).await
+  }
 }
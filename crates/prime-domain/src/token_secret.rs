@@ -0,0 +1,154 @@
+//! Keyed hashing for token secrets.
+
+use hmac::{Hmac, Mac};
+use models::{StrictSlug, TokenSecret, TokenSecretHash};
+use rand::Rng;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+/// The length of generated token secrets.
+const TOKEN_SECRET_LENGTH: usize = 48;
+/// The characters generated token secrets are made of.
+const TOKEN_SECRET_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Error type for [`TokenSecretHasher::new_from_env()`].
+#[derive(thiserror::Error, Debug, miette::Diagnostic)]
+pub enum TokenSecretHasherError {
+  /// An environment variable is missing.
+  #[error("failed to read environment variable: {0:?}")]
+  MissingEnvVar(String),
+}
+
+/// Hashes token secrets with HMAC-SHA256, keyed by a server-side pepper.
+///
+/// Only the hash is stored, so the database alone isn't enough to use a
+/// token. The pepper must stay the same across restarts, or no stored token
+/// will verify.
+#[derive(Clone)]
+pub struct TokenSecretHasher {
+  pepper: Vec<u8>,
+}
+
+impl TokenSecretHasher {
+  /// Creates a new hasher with the given pepper.
+  pub fn new(pepper: impl Into<Vec<u8>>) -> Self {
+    Self {
+      pepper: pepper.into(),
+    }
+  }
+
+  /// Creates a new hasher with the pepper in `TOKEN_SECRET_PEPPER`.
+  pub fn new_from_env() -> Result<Self, TokenSecretHasherError> {
+    let pepper = std::env::var("TOKEN_SECRET_PEPPER")
+      .ok()
+      .filter(|p| !p.is_empty())
+      .ok_or_else(|| {
+        TokenSecretHasherError::MissingEnvVar("TOKEN_SECRET_PEPPER".into())
+      })?;
+    Ok(Self::new(pepper))
+  }
+
+  /// Hashes a token secret.
+  pub fn hash(&self, secret: &TokenSecret) -> TokenSecretHash {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&self.pepper)
+      .expect("HMAC accepts keys of any length");
+    mac.update(secret_bytes(secret));
+    let digest = mac.finalize().into_bytes();
+
+    TokenSecretHash::new(
+      digest
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>(),
+    )
+  }
+
+  /// Checks a token secret against a stored hash, in constant time.
+  pub fn verify(&self, secret: &TokenSecret, hash: &TokenSecretHash) -> bool {
+    let actual = self.hash(secret);
+    let actual: &str = actual.as_ref();
+    let expected: &str = hash.as_ref();
+    actual.as_bytes().ct_eq(expected.as_bytes()).into()
+  }
+
+  /// Checks a token secret against one stored in plaintext, in constant time.
+  pub fn verify_legacy(
+    &self,
+    secret: &TokenSecret,
+    legacy_secret: &TokenSecret,
+  ) -> bool {
+    secret_bytes(secret)
+      .ct_eq(secret_bytes(legacy_secret))
+      .into()
+  }
+}
+
+/// Generates a new random token secret.
+pub fn generate_token_secret() -> TokenSecret {
+  let mut rng = rand::rngs::OsRng;
+  let secret = (0..TOKEN_SECRET_LENGTH)
+    .map(|_| {
+      let index = rng.gen_range(0..TOKEN_SECRET_ALPHABET.len());
+      TOKEN_SECRET_ALPHABET[index] as char
+    })
+    .collect::<String>();
+  TokenSecret::new(StrictSlug::new(secret))
+}
+
+fn secret_bytes(secret: &TokenSecret) -> &[u8] {
+  let slug: &StrictSlug = secret.as_ref();
+  let slug: &str = slug.as_ref();
+  slug.as_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_hash_and_verify() {
+    let hasher = TokenSecretHasher::new("pepper");
+    let secret = generate_token_secret();
+
+    let hash = hasher.hash(&secret);
+    assert!(hasher.verify(&secret, &hash));
+    assert!(!hasher.verify(&generate_token_secret(), &hash));
+
+    // the pepper is part of the hash
+    let other_hasher = TokenSecretHasher::new("other-pepper");
+    assert!(!other_hasher.verify(&secret, &hash));
+  }
+
+  #[test]
+  fn test_hash_is_stable() {
+    // stored hashes must keep verifying, so the encoding can't drift
+    let hasher = TokenSecretHasher::new("pepper");
+    let secret = TokenSecret::new(StrictSlug::confident(
+      "zvka5d29dgvpujdyqa6ftnkei02i-qm1n-fjzuqfbyrq7avxbzi6ma8flxsuwe4l",
+    ));
+    assert_eq!(
+      hasher.hash(&secret),
+      TokenSecretHash::new(
+        "d753d846e1dc22dc4423f332f7609101f8ee422b485adfe24291657b4664e8e6"
+          .to_string()
+      )
+    );
+  }
+
+  #[test]
+  fn test_verify_legacy() {
+    let hasher = TokenSecretHasher::new("pepper");
+    let secret = generate_token_secret();
+
+    assert!(hasher.verify_legacy(&secret, &secret.clone()));
+    assert!(!hasher.verify_legacy(&secret, &generate_token_secret()));
+  }
+
+  #[test]
+  fn test_generate_token_secret() {
+    let secret = generate_token_secret();
+    let bytes = secret_bytes(&secret);
+    assert_eq!(bytes.len(), TOKEN_SECRET_LENGTH);
+    assert!(bytes.iter().all(|b| TOKEN_SECRET_ALPHABET.contains(b)));
+  }
+}
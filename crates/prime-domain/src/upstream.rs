@@ -0,0 +1,341 @@
+//! Looks up paths in a cache's upstream caches.
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use models::{StorePath, UpstreamCache};
+use nasty::{narinfo::NarInfo, signing::PublicKey};
+
+/// How long to wait for an upstream cache to answer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many paths to look up at once.
+const CONCURRENT_LOOKUPS: usize = 16;
+
+/// An error encountered while looking up a path upstream.
+#[derive(Debug, thiserror::Error)]
+enum UpstreamError {
+  /// The request failed.
+  #[error("request failed: {0}")]
+  Request(#[from] reqwest::Error),
+  /// The upstream cache responded with an unexpected status.
+  #[error("unexpected status {0}")]
+  Status(reqwest::StatusCode),
+  /// The narinfo was malformed.
+  #[error("malformed narinfo: {0}")]
+  MalformedNarInfo(String),
+}
+
+/// A client for checking upstream caches.
+#[derive(Clone, Debug)]
+pub(crate) struct UpstreamClient {
+  http: reqwest::Client,
+}
+
+impl UpstreamClient {
+  /// Creates a new upstream client.
+  pub(crate) fn new() -> Self {
+    let http = reqwest::Client::builder()
+      .timeout(REQUEST_TIMEOUT)
+      .build()
+      .expect("failed to build HTTP client");
+    Self { http }
+  }
+
+  /// Returns the paths that any of the upstream caches has, signed with its
+  /// public key.
+  ///
+  /// Upstream caches that fail are treated as not having the path.
+  pub(crate) async fn find_trusted_paths(
+    &self,
+    upstreams: &[UpstreamCache],
+    paths: Vec<StorePath>,
+  ) -> Vec<StorePath> {
+    let upstreams = upstreams
+      .iter()
+      .filter_map(|upstream| match upstream.public_key.parse::<PublicKey>() {
+        Ok(key) => Some((upstream.url.trim_end_matches('/'), key)),
+        Err(e) => {
+          tracing::warn!("skipping upstream {:?}: bad key: {e}", upstream.url);
+          None
+        }
+      })
+      .collect::<Vec<_>>();
+    if upstreams.is_empty() {
+      return Vec::new();
+    }
+
+    futures::stream::iter(paths)
+      .map(|path| {
+        let upstreams = &upstreams;
+        async move {
+          for (url, key) in upstreams {
+            match self.has_trusted_path(url, key, &path).await {
+              Ok(true) => return Some(path),
+              Ok(false) => (),
+              Err(e) => {
+                tracing::warn!("failed to look up {path} in {url:?}: {e}");
+              }
+            }
+          }
+          None
+        }
+      })
+      .buffered(CONCURRENT_LOOKUPS)
+      .filter_map(std::future::ready)
+      .collect()
+      .await
+  }
+
+  /// Returns the narinfo of the path with the given hash part from the
+  /// upstream cache, if it has one signed with its public key.
+  ///
+  /// Failures are treated as the upstream cache not having the path.
+  pub(crate) async fn find_trusted_narinfo(
+    &self,
+    upstream: &UpstreamCache,
+    hash: &str,
+  ) -> Option<NarInfo> {
+    let key = match upstream.public_key.parse::<PublicKey>() {
+      Ok(key) => key,
+      Err(e) => {
+        tracing::warn!("skipping upstream {:?}: bad key: {e}", upstream.url);
+        return None;
+      }
+    };
+    let url = upstream.url.trim_end_matches('/');
+
+    match self.fetch_narinfo(url, hash).await {
+      Ok(Some(info))
+        if info.store_path.hash_part() == hash && key.verify(&info) =>
+      {
+        Some(info)
+      }
+      Ok(Some(_)) => {
+        tracing::warn!("narinfo for {hash} in {url:?} is not trusted");
+        None
+      }
+      Ok(None) => None,
+      Err(e) => {
+        tracing::warn!("failed to look up {hash} in {url:?}: {e}");
+        None
+      }
+    }
+  }
+
+  /// Returns whether the upstream cache at `url` has `path`, signed with
+  /// `key`.
+  async fn has_trusted_path(
+    &self,
+    url: &str,
+    key: &PublicKey,
+    path: &StorePath,
+  ) -> Result<bool, UpstreamError> {
+    Ok(
+      self
+        .fetch_narinfo(url, path.hash_part())
+        .await?
+        .is_some_and(|info| info.store_path == *path && key.verify(&info)),
+    )
+  }
+
+  /// Fetches the narinfo of the path with the given hash part from the
+  /// upstream cache at `url`, or `None` if it doesn't have one.
+  async fn fetch_narinfo(
+    &self,
+    url: &str,
+    hash: &str,
+  ) -> Result<Option<NarInfo>, UpstreamError> {
+    let response = self
+      .http
+      .get(format!("{url}/{hash}.narinfo"))
+      .send()
+      .await?;
+    match response.status() {
+      reqwest::StatusCode::OK => (),
+      reqwest::StatusCode::NOT_FOUND => return Ok(None),
+      status => return Err(UpstreamError::Status(status)),
+    }
+
+    let info = NarInfo::parse_lenient(&response.text().await?)
+      .map_err(|e| UpstreamError::MalformedNarInfo(e.to_string()))?;
+    Ok(Some(info))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+  };
+
+  use nasty::{signing::SecretKey, NixHash};
+
+  use super::*;
+
+  const HELLO: &str = "kwmqk7ygvhypxadsdaai27gl6qfxv7za-hello-2.12.1";
+  const GLIBC: &str = "yaz7pyf0ah88g2v505l38n0f3wg2vzdj-glibc-2.37-8";
+  const MISSING: &str = "5g5nzkrq9bw1bcfbbiwdh8pdmdrxdpy7-missing";
+
+  fn path(p: &str) -> StorePath { StorePath::try_new(p).unwrap() }
+
+  fn narinfo(p: &str) -> NarInfo {
+    format!(
+      "StorePath: /nix/store/{p}\nURL: nar/{p}.nar\nCompression: \
+       none\nNarHash: {}\nNarSize: 4\nReferences: \n",
+      NixHash::from_sha256_digest([7; 32])
+    )
+    .parse()
+    .unwrap()
+  }
+
+  /// Starts a stand-in binary cache on a local port, serving the given files
+  /// and 404s for everything else. Returns its URL.
+  fn stand_in_cache(files: HashMap<String, String>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let mut stream = stream.unwrap();
+        let mut request_line = String::new();
+        let mut reader = BufReader::new(&stream);
+        reader.read_line(&mut request_line).unwrap();
+        // skip the headers
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+          line.clear();
+        }
+
+        let target = request_line.split(' ').nth(1).unwrap();
+        let response = match files.get(target.trim_start_matches('/')) {
+          Some(body) => format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: \
+             close\r\n\r\n{body}",
+            body.len()
+          ),
+          None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: \
+                   close\r\n\r\n"
+            .to_string(),
+        };
+        stream.write_all(response.as_bytes()).unwrap();
+      }
+    });
+
+    url
+  }
+
+  #[tokio::test]
+  async fn test_find_trusted_paths() {
+    let key = SecretKey::generate("upstream-1");
+    let other_key = SecretKey::generate("other-1");
+
+    let mut hello = narinfo(HELLO);
+    key.sign_in_place(&mut hello);
+    let mut glibc = narinfo(GLIBC);
+    other_key.sign_in_place(&mut glibc);
+    let url = stand_in_cache(HashMap::from([
+      (
+        format!("{}.narinfo", path(HELLO).hash_part()),
+        hello.to_string(),
+      ),
+      (
+        format!("{}.narinfo", path(GLIBC).hash_part()),
+        glibc.to_string(),
+      ),
+    ]));
+
+    let upstream = UpstreamCache {
+      url:        format!("{url}/"),
+      public_key: key.public_key().to_string(),
+    };
+    let found = UpstreamClient::new()
+      .find_trusted_paths(&[upstream], vec![
+        path(HELLO),
+        path(GLIBC),
+        path(MISSING),
+      ])
+      .await;
+
+    // glibc is there, but not signed with the upstream's key
+    assert_eq!(found, vec![path(HELLO)]);
+  }
+
+  #[tokio::test]
+  async fn test_find_trusted_paths_survives_bad_upstreams() {
+    let key = SecretKey::generate("upstream-1");
+    let mut hello = narinfo(HELLO);
+    key.sign_in_place(&mut hello);
+    let url = stand_in_cache(HashMap::from([(
+      format!("{}.narinfo", path(HELLO).hash_part()),
+      hello.to_string(),
+    )]));
+
+    let upstreams = [
+      UpstreamCache {
+        url:        "http://127.0.0.1:1".to_string(),
+        public_key: key.public_key().to_string(),
+      },
+      UpstreamCache {
+        url:        url.clone(),
+        public_key: "not a key".to_string(),
+      },
+      UpstreamCache {
+        url,
+        public_key: key.public_key().to_string(),
+      },
+    ];
+    let found = UpstreamClient::new()
+      .find_trusted_paths(&upstreams, vec![path(HELLO)])
+      .await;
+
+    assert_eq!(found, vec![path(HELLO)]);
+  }
+
+  #[tokio::test]
+  async fn test_find_trusted_narinfo() {
+    let key = SecretKey::generate("upstream-1");
+    let other_key = SecretKey::generate("other-1");
+
+    let mut hello = narinfo(HELLO);
+    key.sign_in_place(&mut hello);
+    let mut glibc = narinfo(GLIBC);
+    other_key.sign_in_place(&mut glibc);
+    let url = stand_in_cache(HashMap::from([
+      (
+        format!("{}.narinfo", path(HELLO).hash_part()),
+        hello.to_string(),
+      ),
+      (
+        format!("{}.narinfo", path(GLIBC).hash_part()),
+        glibc.to_string(),
+      ),
+    ]));
+
+    let upstream = UpstreamCache {
+      url,
+      public_key: key.public_key().to_string(),
+    };
+    let client = UpstreamClient::new();
+
+    assert_eq!(
+      client
+        .find_trusted_narinfo(&upstream, path(HELLO).hash_part())
+        .await,
+      Some(hello)
+    );
+    assert_eq!(
+      client
+        .find_trusted_narinfo(&upstream, path(GLIBC).hash_part())
+        .await,
+      None
+    );
+    assert_eq!(
+      client
+        .find_trusted_narinfo(&upstream, path(MISSING).hash_part())
+        .await,
+      None
+    );
+  }
+}
@@ -1,7 +1,10 @@
 use std::marker::PhantomData;
 
 pub use db::CreateModelError;
-pub(crate) use db::{DatabaseAdapter, FetchModelByIndexError, FetchModelError};
+pub(crate) use db::{
+  DatabaseAdapter, DeleteModelError, FetchModelByIndexError, FetchModelError,
+  UpdateModelError,
+};
 use hex::health;
 use miette::Result;
 use tracing::instrument;
@@ -93,8 +96,68 @@ impl<M: models::Model, DB: DatabaseAdapter> ModelRepository
       .await
   }
 
+  #[instrument(skip(self, index_values))]
+  async fn fetch_models_by_index(
+    &self,
+    index_name: String,
+    index_values: Vec<models::EitherSlug>,
+  ) -> Result<Vec<Option<Self::Model>>, FetchModelByIndexError> {
+    self
+      .db_adapter
+      .fetch_models_by_index(index_name, index_values)
+      .await
+  }
+
+  #[instrument(skip(self))]
+  async fn fetch_models_by_index_prefix(
+    &self,
+    index_name: String,
+    prefix: models::EitherSlug,
+  ) -> Result<Vec<Self::Model>, FetchModelByIndexError> {
+    self
+      .db_adapter
+      .fetch_models_by_index_prefix(index_name, prefix)
+      .await
+  }
+
   #[instrument(skip(self))]
   async fn enumerate_models(&self) -> Result<Vec<Self::Model>> {
     self.db_adapter.enumerate_models::<Self::Model>().await
   }
+
+  #[instrument(skip(self))]
+  async fn update_model(
+    &self,
+    model: Self::Model,
+  ) -> Result<Self::Model, UpdateModelError> {
+    self.db_adapter.update_model(model).await
+  }
+
+  #[instrument(skip(self))]
+  async fn delete_model(
+    &self,
+    id: models::RecordId<Self::Model>,
+  ) -> Result<Option<Self::Model>, DeleteModelError> {
+    self.db_adapter.delete_model(id).await
+  }
+
+  #[instrument(skip(self))]
+  async fn remove_retired_index_entry(
+    &self,
+    index_name: String,
+    index_value: models::EitherSlug,
+  ) -> Result<bool> {
+    self
+      .db_adapter
+      .remove_retired_index_entry::<Self::Model>(index_name, index_value)
+      .await
+  }
+
+  #[instrument(skip(self))]
+  async fn restore_index_entries(
+    &self,
+    id: models::RecordId<Self::Model>,
+  ) -> Result<usize> {
+    self.db_adapter.restore_index_entries(id).await
+  }
 }
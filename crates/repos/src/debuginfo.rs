@@ -0,0 +1,85 @@
+//! Provides a repository for the [`DebugInfo`] domain model.
+
+use db::{FetchModelByIndexError, FetchModelError};
+use hex::health::{self, HealthAware};
+use models::{CacheRecordId, LaxSlug};
+pub use models::{DebugInfo, DebugInfoCreateRequest};
+use tracing::instrument;
+
+use super::*;
+pub use crate::base::CreateModelError;
+use crate::base::{BaseRepository, DatabaseAdapter};
+
+/// Descriptor trait for repositories that handle [`DebugInfo`] domain model.
+#[async_trait::async_trait]
+pub trait DebugInfoRepository:
+  ModelRepository<
+  Model = DebugInfo,
+  ModelCreateRequest = DebugInfoCreateRequest,
+  CreateError = CreateModelError,
+>
+{
+  /// Find a [`DebugInfo`] by its cache ID and build ID.
+  #[instrument(skip(self))]
+  async fn find_by_cache_id_and_build_id(
+    &self,
+    cache_id: CacheRecordId,
+    build_id: LaxSlug,
+  ) -> Result<Option<DebugInfo>, FetchModelByIndexError> {
+    let index_value = LaxSlug::new(format!("{cache_id}-{build_id}"));
+    self
+      .fetch_model_by_index("cache-id-build-id".into(), index_value.into())
+      .await
+  }
+
+  /// Find all [`DebugInfo`]s in a cache.
+  #[instrument(skip(self))]
+  async fn find_all_by_cache_id(
+    &self,
+    cache_id: CacheRecordId,
+  ) -> Result<Vec<DebugInfo>, FetchModelByIndexError> {
+    let prefix = LaxSlug::new(cache_id.to_string());
+    self
+      .fetch_models_by_index_prefix("cache-id-build-id".into(), prefix.into())
+      .await
+  }
+}
+
+impl<T> DebugInfoRepository for T where
+  T: ModelRepository<
+    Model = DebugInfo,
+    ModelCreateRequest = DebugInfoCreateRequest,
+    CreateError = CreateModelError,
+  >
+{
+}
+
+/// The repository for the [`DebugInfo`] domain model.
+pub struct DebugInfoRepositoryCanonical<DB: DatabaseAdapter> {
+  base_repo: BaseRepository<DebugInfo, DB>,
+}
+
+impl<DB: DatabaseAdapter + Clone> Clone for DebugInfoRepositoryCanonical<DB> {
+  fn clone(&self) -> Self {
+    Self {
+      base_repo: self.base_repo.clone(),
+    }
+  }
+}
+
+impl<DB: DatabaseAdapter> DebugInfoRepositoryCanonical<DB> {
+  /// Create a new instance of the [`DebugInfo`] repository.
+  pub fn new(db_adapter: DB) -> Self {
+    tracing::info!("creating new `DebugInfoRepositoryCanonical` instance");
+    Self {
+      base_repo: BaseRepository::new(db_adapter),
+    }
+  }
+}
+
+crate::impl_repository_on_base!(
+  DebugInfoRepositoryCanonical,
+  DebugInfo,
+  DebugInfoCreateRequest,
+  CreateModelError
+);
@@ -2,7 +2,7 @@
 
 use db::{FetchModelByIndexError, FetchModelError};
 use hex::health::{self, HealthAware};
-use models::{CacheRecordId, LaxSlug};
+use models::{CacheRecordId, LaxSlug, StorePath};
 pub use models::{Entry, EntryCreateRequest};
 use tracing::instrument;
 
@@ -24,13 +24,57 @@ pub trait EntryRepository:
   async fn find_entry_by_id_and_path(
     &self,
     cache_id: CacheRecordId,
-    path: LaxSlug,
+    path: StorePath,
   ) -> Result<Option<Entry>, FetchModelByIndexError> {
     let index_value = LaxSlug::new(format!("{cache_id}-{path}"));
     self
       .fetch_model_by_index("cache-id-path".into(), index_value.into())
       .await
   }
+
+  /// Find an [`Entry`] by its cache ID and the hash part of its path.
+  #[instrument(skip(self))]
+  async fn find_entry_by_id_and_path_hash(
+    &self,
+    cache_id: CacheRecordId,
+    path_hash: LaxSlug,
+  ) -> Result<Option<Entry>, FetchModelByIndexError> {
+    let index_value = LaxSlug::new(format!("{cache_id}-{path_hash}"));
+    self
+      .fetch_model_by_index("cache-id-path-hash".into(), index_value.into())
+      .await
+  }
+
+  /// Find many [`Entry`]s by their cache ID and paths, in a single
+  /// transaction.
+  ///
+  /// Returns one result per path, in the same order.
+  #[instrument(skip(self, paths))]
+  async fn find_entries_by_id_and_paths(
+    &self,
+    cache_id: CacheRecordId,
+    paths: Vec<StorePath>,
+  ) -> Result<Vec<Option<Entry>>, FetchModelByIndexError> {
+    let index_values = paths
+      .into_iter()
+      .map(|path| LaxSlug::new(format!("{cache_id}-{path}")).into())
+      .collect();
+    self
+      .fetch_models_by_index("cache-id-path".into(), index_values)
+      .await
+  }
+
+  /// Find all [`Entry`]s in a cache.
+  #[instrument(skip(self))]
+  async fn find_entries_by_cache_id(
+    &self,
+    cache_id: CacheRecordId,
+  ) -> Result<Vec<Entry>, FetchModelByIndexError> {
+    let prefix = LaxSlug::new(cache_id.to_string());
+    self
+      .fetch_models_by_index_prefix("cache-id-path".into(), prefix.into())
+      .await
+  }
 }
 
 impl<T> EntryRepository for T where
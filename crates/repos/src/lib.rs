@@ -6,14 +6,21 @@
 
 mod base;
 mod cache;
+mod debuginfo;
 mod entry;
+mod realisation;
+mod session;
+mod signing_key;
 mod store;
 mod temp_storage;
 mod token;
+mod user;
 mod user_storage;
 
 pub use db;
-use db::{FetchModelByIndexError, FetchModelError};
+use db::{
+  DeleteModelError, FetchModelByIndexError, FetchModelError, UpdateModelError,
+};
 use hex::Hexagonal;
 use miette::Result;
 use models::EitherSlug;
@@ -23,7 +30,8 @@ pub use storage::{
 };
 
 pub use self::{
-  cache::*, entry::*, store::*, temp_storage::*, token::*, user_storage::*,
+  cache::*, debuginfo::*, entry::*, realisation::*, session::*, signing_key::*,
+  store::*, temp_storage::*, token::*, user::*, user_storage::*,
 };
 
 /// Defines a repository interface for models.
@@ -57,8 +65,54 @@ pub trait ModelRepository: Hexagonal {
     index_value: EitherSlug,
   ) -> Result<Option<Self::Model>, FetchModelByIndexError>;
 
+  /// Fetches models by many values of the same index, in a single
+  /// transaction.
+  ///
+  /// Returns one result per value, in the same order.
+  async fn fetch_models_by_index(
+    &self,
+    index_name: String,
+    index_values: Vec<EitherSlug>,
+  ) -> Result<Vec<Option<Self::Model>>, FetchModelByIndexError>;
+
   /// Produces a list of all model IDs.
   async fn enumerate_models(&self) -> Result<Vec<Self::Model>>;
+
+  /// Fetches every model whose value of an index starts with a prefix
+  /// followed by a dash, in a single transaction.
+  async fn fetch_models_by_index_prefix(
+    &self,
+    index_name: String,
+    prefix: EitherSlug,
+  ) -> Result<Vec<Self::Model>, FetchModelByIndexError>;
+
+  /// Replaces an existing model.
+  async fn update_model(
+    &self,
+    model: Self::Model,
+  ) -> Result<Self::Model, UpdateModelError>;
+
+  /// Deletes a model, returning it if it existed.
+  async fn delete_model(
+    &self,
+    id: models::RecordId<Self::Model>,
+  ) -> Result<Option<Self::Model>, DeleteModelError>;
+
+  /// Removes an entry from an index that the model no longer defines.
+  ///
+  /// Returns whether an entry was removed.
+  async fn remove_retired_index_entry(
+    &self,
+    index_name: String,
+    index_value: EitherSlug,
+  ) -> Result<bool>;
+  /// Writes any of a model's index entries that are missing.
+  ///
+  /// Returns how many entries were written.
+  async fn restore_index_entries(
+    &self,
+    id: models::RecordId<Self::Model>,
+  ) -> Result<usize>;
 }
 
 #[async_trait::async_trait]
@@ -90,9 +144,48 @@ where
   ) -> Result<Option<Self::Model>, FetchModelByIndexError> {
     I::fetch_model_by_index(self, index_name, index_value).await
   }
+  async fn fetch_models_by_index(
+    &self,
+    index_name: String,
+    index_values: Vec<EitherSlug>,
+  ) -> Result<Vec<Option<Self::Model>>, FetchModelByIndexError> {
+    I::fetch_models_by_index(self, index_name, index_values).await
+  }
+  async fn fetch_models_by_index_prefix(
+    &self,
+    index_name: String,
+    prefix: EitherSlug,
+  ) -> Result<Vec<Self::Model>, FetchModelByIndexError> {
+    I::fetch_models_by_index_prefix(self, index_name, prefix).await
+  }
   async fn enumerate_models(&self) -> Result<Vec<Self::Model>> {
     I::enumerate_models(self).await
   }
+  async fn update_model(
+    &self,
+    model: Self::Model,
+  ) -> Result<Self::Model, UpdateModelError> {
+    I::update_model(self, model).await
+  }
+  async fn delete_model(
+    &self,
+    id: models::RecordId<Self::Model>,
+  ) -> Result<Option<Self::Model>, DeleteModelError> {
+    I::delete_model(self, id).await
+  }
+  async fn remove_retired_index_entry(
+    &self,
+    index_name: String,
+    index_value: EitherSlug,
+  ) -> Result<bool> {
+    I::remove_retired_index_entry(self, index_name, index_value).await
+  }
+  async fn restore_index_entries(
+    &self,
+    id: models::RecordId<Self::Model>,
+  ) -> Result<usize> {
+    I::restore_index_entries(self, id).await
+  }
 }
 
 /// Defines a repository fetcher interface for models.
@@ -239,10 +332,70 @@ macro_rules! impl_repository_on_base {
           .await
       }
 
+      #[instrument(skip(self, index_values))]
+      async fn fetch_models_by_index(
+        &self,
+        index_name: String,
+        index_values: Vec<EitherSlug>,
+      ) -> Result<Vec<Option<Self::Model>>, FetchModelByIndexError> {
+        self
+          .base_repo
+          .fetch_models_by_index(index_name, index_values)
+          .await
+      }
+
+      #[instrument(skip(self))]
+      async fn fetch_models_by_index_prefix(
+        &self,
+        index_name: String,
+        prefix: EitherSlug,
+      ) -> Result<Vec<Self::Model>, FetchModelByIndexError> {
+        self
+          .base_repo
+          .fetch_models_by_index_prefix(index_name, prefix)
+          .await
+      }
+
       #[instrument(skip(self))]
       async fn enumerate_models(&self) -> Result<Vec<Self::Model>> {
         self.base_repo.enumerate_models().await
       }
+
+      #[instrument(skip(self))]
+      async fn update_model(
+        &self,
+        model: Self::Model,
+      ) -> Result<Self::Model, UpdateModelError> {
+        self.base_repo.update_model(model).await
+      }
+
+      #[instrument(skip(self))]
+      async fn delete_model(
+        &self,
+        id: models::RecordId<Self::Model>,
+      ) -> Result<Option<Self::Model>, DeleteModelError> {
+        self.base_repo.delete_model(id).await
+      }
+
+      #[instrument(skip(self))]
+      async fn remove_retired_index_entry(
+        &self,
+        index_name: String,
+        index_value: EitherSlug,
+      ) -> Result<bool> {
+        self
+          .base_repo
+          .remove_retired_index_entry(index_name, index_value)
+          .await
+      }
+
+      #[instrument(skip(self))]
+      async fn restore_index_entries(
+        &self,
+        id: models::RecordId<Self::Model>,
+      ) -> Result<usize> {
+        self.base_repo.restore_index_entries(id).await
+      }
     }
   };
 }
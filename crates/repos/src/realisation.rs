@@ -0,0 +1,85 @@
+//! Provides a repository for the [`Realisation`] domain model.
+
+use db::{FetchModelByIndexError, FetchModelError};
+use hex::health::{self, HealthAware};
+use models::{CacheRecordId, DrvOutput, LaxSlug};
+pub use models::{Realisation, RealisationCreateRequest};
+use tracing::instrument;
+
+use super::*;
+pub use crate::base::CreateModelError;
+use crate::base::{BaseRepository, DatabaseAdapter};
+
+/// Descriptor trait for repositories that handle [`Realisation`] domain model.
+#[async_trait::async_trait]
+pub trait RealisationRepository:
+  ModelRepository<
+  Model = Realisation,
+  ModelCreateRequest = RealisationCreateRequest,
+  CreateError = CreateModelError,
+>
+{
+  /// Find a [`Realisation`] by its cache ID and derivation output.
+  #[instrument(skip(self))]
+  async fn find_by_cache_id_and_drv_output(
+    &self,
+    cache_id: CacheRecordId,
+    drv_output: DrvOutput,
+  ) -> Result<Option<Realisation>, FetchModelByIndexError> {
+    let index_value = LaxSlug::new(format!("{cache_id}-{drv_output}"));
+    self
+      .fetch_model_by_index("cache-id-drv-output".into(), index_value.into())
+      .await
+  }
+
+  /// Find all [`Realisation`]s in a cache.
+  #[instrument(skip(self))]
+  async fn find_all_by_cache_id(
+    &self,
+    cache_id: CacheRecordId,
+  ) -> Result<Vec<Realisation>, FetchModelByIndexError> {
+    let prefix = LaxSlug::new(cache_id.to_string());
+    self
+      .fetch_models_by_index_prefix("cache-id-drv-output".into(), prefix.into())
+      .await
+  }
+}
+
+impl<T> RealisationRepository for T where
+  T: ModelRepository<
+    Model = Realisation,
+    ModelCreateRequest = RealisationCreateRequest,
+    CreateError = CreateModelError,
+  >
+{
+}
+
+/// The repository for the [`Realisation`] domain model.
+pub struct RealisationRepositoryCanonical<DB: DatabaseAdapter> {
+  base_repo: BaseRepository<Realisation, DB>,
+}
+
+impl<DB: DatabaseAdapter + Clone> Clone for RealisationRepositoryCanonical<DB> {
+  fn clone(&self) -> Self {
+    Self {
+      base_repo: self.base_repo.clone(),
+    }
+  }
+}
+
+impl<DB: DatabaseAdapter> RealisationRepositoryCanonical<DB> {
+  /// Create a new instance of the [`Realisation`] repository.
+  pub fn new(db_adapter: DB) -> Self {
+    tracing::info!("creating new `RealisationRepositoryCanonical` instance");
+    Self {
+      base_repo: BaseRepository::new(db_adapter),
+    }
+  }
+}
+
+crate::impl_repository_on_base!(
+  RealisationRepositoryCanonical,
+  Realisation,
+  RealisationCreateRequest,
+  CreateModelError
+);
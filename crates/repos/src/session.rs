@@ -0,0 +1,59 @@
+//! Provides a repository for the [`Session`] domain model.
+
+use hex::health::{self, HealthAware};
+pub use models::{Session, SessionCreateRequest};
+use tracing::instrument;
+
+use super::*;
+pub use crate::base::CreateModelError;
+use crate::base::{BaseRepository, DatabaseAdapter};
+
+/// Descriptor trait for repositories that handle [`Session`] domain model.
+#[async_trait::async_trait]
+pub trait SessionRepository:
+  ModelRepository<
+  Model = Session,
+  ModelCreateRequest = SessionCreateRequest,
+  CreateError = CreateModelError,
+>
+{
+}
+
+impl<T> SessionRepository for T where
+  T: ModelRepository<
+    Model = Session,
+    ModelCreateRequest = SessionCreateRequest,
+    CreateError = CreateModelError,
+  >
+{
+}
+
+/// The repository for the [`Session`] domain model.
+pub struct SessionRepositoryCanonical<DB: DatabaseAdapter> {
+  base_repo: BaseRepository<Session, DB>,
+}
+
+impl<DB: DatabaseAdapter + Clone> Clone for SessionRepositoryCanonical<DB> {
+  fn clone(&self) -> Self {
+    Self {
+      base_repo: self.base_repo.clone(),
+    }
+  }
+}
+
+impl<DB: DatabaseAdapter> SessionRepositoryCanonical<DB> {
+  /// Create a new instance of the [`Session`] repository.
+  pub fn new(db_adapter: DB) -> Self {
+    tracing::info!("creating new `SessionRepositoryCanonical` instance");
+    Self {
+      base_repo: BaseRepository::new(db_adapter),
+    }
+  }
+}
+
+crate::impl_repository_on_base!(
+  SessionRepositoryCanonical,
+  Session,
+  SessionCreateRequest,
+  CreateModelError
+);
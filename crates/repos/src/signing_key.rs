@@ -0,0 +1,72 @@
+//! Provides a repository for the [`SigningKey`] domain model.
+
+use db::{FetchModelByIndexError, FetchModelError};
+use hex::health::{self, HealthAware};
+use models::{CacheRecordId, LaxSlug};
+pub use models::{SigningKey, SigningKeyCreateRequest};
+use tracing::instrument;
+
+use super::*;
+pub use crate::base::CreateModelError;
+use crate::base::{BaseRepository, DatabaseAdapter};
+
+/// Descriptor trait for repositories that handle [`SigningKey`] domain model.
+#[async_trait::async_trait]
+pub trait SigningKeyRepository:
+  ModelRepository<
+  Model = SigningKey,
+  ModelCreateRequest = SigningKeyCreateRequest,
+  CreateError = CreateModelError,
+>
+{
+  /// Find the [`SigningKey`] belonging to a cache.
+  #[instrument(skip(self))]
+  async fn find_by_cache_id(
+    &self,
+    cache_id: CacheRecordId,
+  ) -> Result<Option<SigningKey>, FetchModelByIndexError> {
+    let index_value = LaxSlug::new(cache_id.to_string());
+    self
+      .fetch_model_by_index("cache".into(), index_value.into())
+      .await
+  }
+}
+
+impl<T> SigningKeyRepository for T where
+  T: ModelRepository<
+    Model = SigningKey,
+    ModelCreateRequest = SigningKeyCreateRequest,
+    CreateError = CreateModelError,
+  >
+{
+}
+
+/// The repository for the [`SigningKey`] domain model.
+pub struct SigningKeyRepositoryCanonical<DB: DatabaseAdapter> {
+  base_repo: BaseRepository<SigningKey, DB>,
+}
+
+impl<DB: DatabaseAdapter + Clone> Clone for SigningKeyRepositoryCanonical<DB> {
+  fn clone(&self) -> Self {
+    Self {
+      base_repo: self.base_repo.clone(),
+    }
+  }
+}
+
+impl<DB: DatabaseAdapter> SigningKeyRepositoryCanonical<DB> {
+  /// Create a new instance of the [`SigningKey`] repository.
+  pub fn new(db_adapter: DB) -> Self {
+    tracing::info!("creating new `SigningKeyRepositoryCanonical` instance");
+    Self {
+      base_repo: BaseRepository::new(db_adapter),
+    }
+  }
+}
+
+crate::impl_repository_on_base!(
+  SigningKeyRepositoryCanonical,
+  SigningKey,
+  SigningKeyCreateRequest,
+  CreateModelError
+);
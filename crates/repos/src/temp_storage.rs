@@ -7,8 +7,8 @@ use hex::{
 use models::TempStoragePath;
 use storage::{belt::Belt, temp::TempStorageCreds};
 pub use storage::{
-  ReadError as StorageReadError, StorageClientGenerator,
-  WriteError as StorageWriteError,
+  DeleteError as StorageDeleteError, ReadError as StorageReadError,
+  StorageClientGenerator, WriteError as StorageWriteError,
 };
 
 pub use self::mock::TempStorageRepositoryMock;
@@ -24,6 +24,17 @@ pub trait TempStorageRepository: Hexagonal {
     &self,
     data: Belt,
   ) -> Result<TempStoragePath, StorageWriteError>;
+  /// Store data in the storage at the given path, overwriting existing data.
+  async fn store_at(
+    &self,
+    path: TempStoragePath,
+    data: Belt,
+  ) -> Result<TempStoragePath, StorageWriteError>;
+  /// Delete data from the storage. Deleting missing data is not an error.
+  async fn delete(
+    &self,
+    path: TempStoragePath,
+  ) -> Result<(), StorageDeleteError>;
 }
 
 #[async_trait::async_trait]
@@ -44,6 +55,19 @@ where
   ) -> Result<TempStoragePath, StorageWriteError> {
     self.deref().store(data).await
   }
+  async fn store_at(
+    &self,
+    path: TempStoragePath,
+    data: Belt,
+  ) -> Result<TempStoragePath, StorageWriteError> {
+    self.deref().store_at(path, data).await
+  }
+  async fn delete(
+    &self,
+    path: TempStoragePath,
+  ) -> Result<(), StorageDeleteError> {
+    self.deref().delete(path).await
+  }
 }
 
 /// The repository for temp storage.
@@ -89,12 +113,30 @@ impl TempStorageRepository for TempStorageRepositoryCanonical {
     &self,
     data: Belt,
   ) -> Result<TempStoragePath, StorageWriteError> {
-    let mut path = TempStoragePath::new_random(models::FileSize::new(0));
+    self
+      .store_at(TempStoragePath::new_random(models::FileSize::new(0)), data)
+      .await
+  }
+
+  #[tracing::instrument(skip(self, data))]
+  async fn store_at(
+    &self,
+    mut path: TempStoragePath,
+    data: Belt,
+  ) -> Result<TempStoragePath, StorageWriteError> {
     let counter = data.counter();
     self.client.write(path.path(), data).await?;
     path.set_size(models::FileSize::new(counter.current()));
     Ok(path)
   }
+
+  #[tracing::instrument(skip(self))]
+  async fn delete(
+    &self,
+    path: TempStoragePath,
+  ) -> Result<(), StorageDeleteError> {
+    self.client.delete(path.path()).await
+  }
 }
 
 mod mock {
@@ -141,11 +183,21 @@ mod mock {
     async fn store(
       &self,
       data: Belt,
+    ) -> Result<TempStoragePath, StorageWriteError> {
+      self
+        .store_at(TempStoragePath::new_random(models::FileSize::new(0)), data)
+        .await
+    }
+
+    #[tracing::instrument(skip(self, data))]
+    async fn store_at(
+      &self,
+      mut path: TempStoragePath,
+      data: Belt,
     ) -> Result<TempStoragePath, StorageWriteError> {
       // create fs_root if it doesn't exist
       tokio::fs::create_dir_all(&self.fs_root).await?;
 
-      let mut path = TempStoragePath::new_random(models::FileSize::new(0));
       let real_path = self.fs_root.join(path.path());
 
       let counter = data.counter();
@@ -154,5 +206,16 @@ mod mock {
       path.set_size(models::FileSize::new(counter.current()));
       Ok(path)
     }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete(
+      &self,
+      path: TempStoragePath,
+    ) -> Result<(), StorageDeleteError> {
+      match tokio::fs::remove_file(self.fs_root.join(path.path())).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+      }
+    }
   }
 }
@@ -0,0 +1,74 @@
+//! Provides a repository for the [`User`] domain model.
+
+use db::{FetchModelByIndexError, FetchModelError};
+use hex::health::{self, HealthAware};
+use models::EntityName;
+pub use models::{User, UserCreateRequest};
+use tracing::instrument;
+
+use super::*;
+pub use crate::base::CreateModelError;
+use crate::base::{BaseRepository, DatabaseAdapter};
+
+/// Descriptor trait for repositories that handle [`User`] domain model.
+#[async_trait::async_trait]
+pub trait UserRepository:
+  ModelRepository<
+  Model = User,
+  ModelCreateRequest = UserCreateRequest,
+  CreateError = CreateModelError,
+>
+{
+  /// Find a [`User`] by their username.
+  #[instrument(skip(self))]
+  async fn find_by_username(
+    &self,
+    username: EntityName,
+  ) -> Result<Option<User>, FetchModelByIndexError> {
+    self
+      .fetch_model_by_index(
+        "username".to_string(),
+        username.into_inner().into(),
+      )
+      .await
+  }
+}
+
+impl<T> UserRepository for T where
+  T: ModelRepository<
+    Model = User,
+    ModelCreateRequest = UserCreateRequest,
+    CreateError = CreateModelError,
+  >
+{
+}
+
+/// The repository for the [`User`] domain model.
+pub struct UserRepositoryCanonical<DB: DatabaseAdapter> {
+  base_repo: BaseRepository<User, DB>,
+}
+
+impl<DB: DatabaseAdapter + Clone> Clone for UserRepositoryCanonical<DB> {
+  fn clone(&self) -> Self {
+    Self {
+      base_repo: self.base_repo.clone(),
+    }
+  }
+}
+
+impl<DB: DatabaseAdapter> UserRepositoryCanonical<DB> {
+  /// Create a new instance of the [`User`] repository.
+  pub fn new(db_adapter: DB) -> Self {
+    tracing::info!("creating new `UserRepositoryCanonical` instance");
+    Self {
+      base_repo: BaseRepository::new(db_adapter),
+    }
+  }
+}
+
+crate::impl_repository_on_base!(
+  UserRepositoryCanonical,
+  User,
+  UserCreateRequest,
+  CreateModelError
+);
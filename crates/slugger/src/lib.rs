@@ -29,6 +29,15 @@ impl StrictSlug {
     assert_eq!(slug, s, "provided string is not already a valid slug");
     StrictSlug::new(slug)
   }
+
+  /// Creates a new slug only if the string is already a valid, non-empty
+  /// slug.
+  ///
+  /// This is intended for user-supplied names, which should be rejected
+  /// rather than silently rewritten.
+  pub fn try_exact(s: String) -> Option<StrictSlug> {
+    (!s.is_empty() && strict_slugify(&s) == s).then(|| StrictSlug::new(s))
+  }
 }
 
 /// A lax slug, meant to accomodate Nix store paths.
@@ -65,17 +65,42 @@ pub enum WriteError {
   MultipartError(miette::Report),
 }
 
+/// An error type used when deleting from a `StorageClient`.
+#[derive(thiserror::Error, Debug, miette::Diagnostic)]
+pub enum DeleteError {
+  /// The path was invalid.
+  #[error("the supplied path was invalid: {0}")]
+  InvalidPath(String),
+  /// An IO error occurred.
+  #[error("a local filesystem error occurred: {0}")]
+  IoError(#[from] std::io::Error),
+  /// An error occurred in the object store.
+  #[error("an error occurred while deleting from the object store: {0}")]
+  ObjectStoreError(miette::Report),
+}
+
 /// The main storage trait. Allows reading to or writing from a stream of bytes.
 #[async_trait::async_trait]
 pub trait StorageClient: Hexagonal {
   /// Reads a file. Returns a [`Belt`].
   async fn read(&self, path: &Path) -> Result<Belt, ReadError>;
+  /// Reads `len` bytes of a file, starting at `offset`. Returns a [`Belt`].
+  ///
+  /// The range is clamped to the end of the file.
+  async fn read_range(
+    &self,
+    path: &Path,
+    offset: u64,
+    len: u64,
+  ) -> Result<Belt, ReadError>;
   /// Writes a file. Consumes a [`Belt`].
   async fn write(
     &self,
     path: &Path,
     data: Belt,
   ) -> Result<dvf::FileSize, WriteError>;
+  /// Deletes a file. Deleting a file that doesn't exist is not an error.
+  async fn delete(&self, path: &Path) -> Result<(), DeleteError>;
 }
 
 #[async_trait::async_trait]
@@ -87,6 +112,14 @@ where
   async fn read(&self, path: &Path) -> Result<Belt, ReadError> {
     self.deref().read(path).await
   }
+  async fn read_range(
+    &self,
+    path: &Path,
+    offset: u64,
+    len: u64,
+  ) -> Result<Belt, ReadError> {
+    self.deref().read_range(path, offset, len).await
+  }
   async fn write(
     &self,
     path: &Path,
@@ -94,4 +127,7 @@ where
   ) -> Result<dvf::FileSize, WriteError> {
     self.deref().write(path, data).await
   }
+  async fn delete(&self, path: &Path) -> Result<(), DeleteError> {
+    self.deref().delete(path).await
+  }
 }
@@ -1,13 +1,15 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 use belt::Belt;
 use dvf::LocalStorageCredentials;
 use hex::health;
 use miette::{Context, IntoDiagnostic};
-use tokio::io::{AsyncWriteExt, BufReader, BufWriter};
+use tokio::io::{
+  AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, BufWriter,
+};
 
 use super::{ReadError, StorageClient};
-use crate::WriteError;
+use crate::{DeleteError, WriteError};
 
 pub struct LocalStorageClient(PathBuf);
 
@@ -22,20 +24,12 @@ impl LocalStorageClient {
         .to_path_buf(),
     ))
   }
-}
-
-#[async_trait::async_trait]
-impl health::HealthReporter for LocalStorageClient {
-  fn name(&self) -> &'static str { stringify!(LocalStorageClient) }
-  async fn health_check(&self) -> health::ComponentHealth {
-    health::IntrensicallyUp.into()
-  }
-}
 
-#[async_trait::async_trait]
-impl StorageClient for LocalStorageClient {
-  #[tracing::instrument(skip(self))]
-  async fn read(&self, input_path: &Path) -> Result<Belt, ReadError> {
+  /// Opens a file, making sure it exists and doesn't escape the store path.
+  async fn open(
+    &self,
+    input_path: &Path,
+  ) -> Result<tokio::fs::File, ReadError> {
     let path = self.0.as_path().join(input_path);
 
     // make sure it exists
@@ -56,7 +50,23 @@ impl StorageClient for LocalStorageClient {
       ));
     }
 
-    let file = tokio::fs::File::open(&path).await?;
+    Ok(tokio::fs::File::open(&path).await?)
+  }
+}
+
+#[async_trait::async_trait]
+impl health::HealthReporter for LocalStorageClient {
+  fn name(&self) -> &'static str { stringify!(LocalStorageClient) }
+  async fn health_check(&self) -> health::ComponentHealth {
+    health::IntrensicallyUp.into()
+  }
+}
+
+#[async_trait::async_trait]
+impl StorageClient for LocalStorageClient {
+  #[tracing::instrument(skip(self))]
+  async fn read(&self, input_path: &Path) -> Result<Belt, ReadError> {
+    let file = self.open(input_path).await?;
 
     Ok(Belt::from_async_buf_read(
       BufReader::new(file),
@@ -64,6 +74,22 @@ impl StorageClient for LocalStorageClient {
     ))
   }
 
+  #[tracing::instrument(skip(self))]
+  async fn read_range(
+    &self,
+    input_path: &Path,
+    offset: u64,
+    len: u64,
+  ) -> Result<Belt, ReadError> {
+    let mut file = self.open(input_path).await?;
+    file.seek(std::io::SeekFrom::Start(offset)).await?;
+
+    Ok(Belt::from_async_buf_read(
+      BufReader::new(file.take(len)),
+      Some(belt::DEFAULT_CHUNK_SIZE),
+    ))
+  }
+
   #[tracing::instrument(skip(self))]
   async fn write(
     &self,
@@ -94,6 +120,24 @@ impl StorageClient for LocalStorageClient {
 
     Ok(file_size)
   }
+
+  #[tracing::instrument(skip(self))]
+  async fn delete(&self, input_path: &Path) -> Result<(), DeleteError> {
+    // only plain relative paths, so nothing outside the store is removed
+    if !input_path
+      .components()
+      .all(|c| matches!(c, Component::Normal(_)))
+    {
+      return Err(DeleteError::InvalidPath(
+        input_path.to_string_lossy().to_string(),
+      ));
+    }
+
+    match tokio::fs::remove_file(self.0.as_path().join(input_path)).await {
+      Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+      _ => Ok(()),
+    }
+  }
 }
 
 #[cfg(test)]
@@ -131,4 +175,61 @@ mod tests {
 
     assert_eq!(&result, "abc");
   }
+
+  #[tokio::test]
+  async fn read_range_works() {
+    let temp = TempDir::new().unwrap();
+
+    let f = temp.child("file1");
+    std::fs::write(&f, "abcdef").unwrap();
+
+    let client = LocalStorageClient::new(LocalStorageCredentials(
+      temp.path().to_path_buf(),
+    ))
+    .await
+    .unwrap();
+    let read_range = |offset, len| {
+      let client = &client;
+      async move {
+        let mut result = String::new();
+        client
+          .read_range(&PathBuf::from_str("file1").unwrap(), offset, len)
+          .await
+          .unwrap()
+          .to_async_buf_read()
+          .read_to_string(&mut result)
+          .await
+          .unwrap();
+        result
+      }
+    };
+
+    assert_eq!(read_range(1, 3).await, "bcd");
+    assert_eq!(read_range(4, 10).await, "ef");
+    assert_eq!(read_range(2, 0).await, "");
+  }
+
+  #[tokio::test]
+  async fn delete_works() {
+    let temp = TempDir::new().unwrap();
+
+    let f = temp.child("file1");
+    std::fs::write(&f, "abc").unwrap();
+
+    let client = LocalStorageClient::new(LocalStorageCredentials(
+      temp.path().to_path_buf(),
+    ))
+    .await
+    .unwrap();
+    let path = PathBuf::from_str("file1").unwrap();
+
+    client.delete(&path).await.unwrap();
+    assert!(!f.exists());
+    // deleting again is fine
+    client.delete(&path).await.unwrap();
+    assert!(client
+      .delete(&PathBuf::from_str("../file1").unwrap())
+      .await
+      .is_err());
+  }
 }
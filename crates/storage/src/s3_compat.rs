@@ -8,12 +8,12 @@ use hex::health;
 use miette::{Context, IntoDiagnostic, Report};
 use object_store::{
   aws::{AmazonS3, AmazonS3Builder},
-  Error as ObjectStoreError, ObjectStore, PutPayload,
+  Error as ObjectStoreError, GetOptions, GetRange, ObjectStore, PutPayload,
 };
 use tokio::sync::Mutex;
 
 use super::{ReadError, StorageClient};
-use crate::WriteError;
+use crate::{DeleteError, WriteError};
 
 pub struct S3CompatStorageClient {
   store: AmazonS3,
@@ -45,6 +45,36 @@ impl S3CompatStorageClient {
       }
     }
   }
+
+  /// Streams an object, or part of one, into a [`Belt`].
+  async fn get(
+    &self,
+    input_path: &Path,
+    options: GetOptions,
+  ) -> Result<Belt, ReadError> {
+    let input_path_string = input_path.to_str().unwrap().to_string();
+    let path = object_store::path::Path::parse(input_path_string.clone())
+      .map_err(|_| ReadError::InvalidPath(input_path_string))?;
+
+    let get_result =
+      self.store.get_opts(&path, options).await.map_err(|e| {
+        if let ObjectStoreError::NotFound { .. } = e {
+          ReadError::NotFound(input_path.to_path_buf())
+        } else {
+          Err(e).into_diagnostic().unwrap()
+        }
+      })?;
+
+    let data = Belt::from_stream(
+      get_result.into_stream().map_err(|e| {
+        tracing::error!("error while streaming from R2 store: {e:?}");
+        futures::io::Error::from(e)
+      }),
+      Some(belt::DEFAULT_CHUNK_SIZE),
+    );
+
+    Ok(data)
+  }
 }
 
 #[async_trait::async_trait]
@@ -72,27 +102,28 @@ impl health::HealthReporter for S3CompatStorageClient {
 impl StorageClient for S3CompatStorageClient {
   #[tracing::instrument(skip(self))]
   async fn read(&self, input_path: &Path) -> Result<Belt, ReadError> {
-    let input_path_string = input_path.to_str().unwrap().to_string();
-    let path = object_store::path::Path::parse(input_path_string.clone())
-      .map_err(|_| ReadError::InvalidPath(input_path_string))?;
-
-    let get_result = self.store.get(&path).await.map_err(|e| {
-      if let ObjectStoreError::NotFound { .. } = e {
-        ReadError::NotFound(input_path.to_path_buf())
-      } else {
-        Err(e).into_diagnostic().unwrap()
-      }
-    })?;
+    self.get(input_path, GetOptions::default()).await
+  }
 
-    let data = Belt::from_stream(
-      get_result.into_stream().map_err(|e| {
-        tracing::error!("error while streaming from R2 store: {e:?}");
-        futures::io::Error::from(e)
-      }),
-      Some(belt::DEFAULT_CHUNK_SIZE),
-    );
+  #[tracing::instrument(skip(self))]
+  async fn read_range(
+    &self,
+    input_path: &Path,
+    offset: u64,
+    len: u64,
+  ) -> Result<Belt, ReadError> {
+    // object stores reject empty ranges
+    if len == 0 {
+      return Ok(Belt::from_stream(futures::stream::empty(), None));
+    }
 
-    Ok(data)
+    let range = offset as usize..(offset + len) as usize;
+    self
+      .get(input_path, GetOptions {
+        range: Some(GetRange::Bounded(range)),
+        ..Default::default()
+      })
+      .await
   }
 
   #[tracing::instrument(skip(self))]
@@ -169,4 +200,18 @@ impl StorageClient for S3CompatStorageClient {
 
     Ok(file_size)
   }
+
+  #[tracing::instrument(skip(self))]
+  async fn delete(&self, input_path: &Path) -> Result<(), DeleteError> {
+    let input_path_string = input_path.to_str().unwrap().to_string();
+    let path = object_store::path::Path::parse(input_path_string.clone())
+      .map_err(|_| DeleteError::InvalidPath(input_path_string))?;
+
+    match self.store.delete(&path).await {
+      Ok(()) | Err(ObjectStoreError::NotFound { .. }) => Ok(()),
+      Err(e) => Err(DeleteError::ObjectStoreError(
+        Report::from_err(e).wrap_err("failed to delete object"),
+      )),
+    }
+  }
 }
@@ -0,0 +1,312 @@
+use mollusk::*;
+use nasty::{
+  narinfo::{NarCompression, NarInfo},
+  realisation::Realisation,
+};
+use prime_domain::{
+  models::{self, CacheRecordId, Credentials, LaxSlug, StrictSlug},
+  repos::belt,
+  CreateEntryError, CreateRealisationError, DynPrimeDomainService,
+  StorageReadError,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  discard_temp_storage, prepare_fetch_payload::authorize_cache_access,
+};
+
+/// The PrepareNarUpload task.
+///
+/// Authorizes a NAR upload and returns the temp storage path to write it to.
+/// The NAR sits there until its narinfo is uploaded with
+/// [`UploadNarInfoTask`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrepareNarUploadTask {
+  /// The name of the cache to upload to.
+  pub cache_name:  StrictSlug,
+  /// The credentials of the token or session being used to upload the file.
+  pub credentials: Option<Credentials>,
+  /// The file name of the NAR under `nar/`.
+  pub file:        LaxSlug,
+}
+
+#[async_trait::async_trait]
+impl rope::Task for PrepareNarUploadTask {
+  const NAME: &'static str = "PrepareNarUpload";
+
+  type Response = models::TempStoragePath;
+  type Error = BinaryCacheUploadError;
+  type State = DynPrimeDomainService;
+
+  async fn run(
+    self,
+    state: Self::State,
+  ) -> Result<Self::Response, Self::Error> {
+    let PrepareNarUploadTask {
+      cache_name,
+      credentials,
+      file,
+    } = self;
+
+    let cache =
+      fetch_cache_for_writing(&state, cache_name, credentials).await?;
+
+    Ok(nar_upload_path(cache.id, &file))
+  }
+}
+
+/// The UploadNarInfo task.
+///
+/// Creates an entry from an uploaded narinfo and the NAR it points to, which
+/// must already have been uploaded.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UploadNarInfoTask {
+  /// The name of the cache to upload to.
+  pub cache_name:  StrictSlug,
+  /// The credentials of the token or session being used to upload the file.
+  pub credentials: Option<Credentials>,
+  /// The hash part of the store path, from the narinfo's file name.
+  pub hash:        LaxSlug,
+  /// The contents of the narinfo.
+  pub narinfo:     String,
+}
+
+#[async_trait::async_trait]
+impl rope::Task for UploadNarInfoTask {
+  const NAME: &'static str = "UploadNarInfo";
+
+  type Response = ();
+  type Error = BinaryCacheUploadError;
+  type State = DynPrimeDomainService;
+
+  #[tracing::instrument(name = "UploadNarInfo", skip(self, state))]
+  async fn run(
+    self,
+    state: Self::State,
+  ) -> Result<Self::Response, Self::Error> {
+    let UploadNarInfoTask {
+      cache_name,
+      credentials,
+      hash,
+      narinfo,
+    } = self;
+
+    let prime_domain_service = state;
+
+    let cache =
+      fetch_cache_for_writing(&prime_domain_service, cache_name, credentials)
+        .await?;
+
+    let info =
+      NarInfo::parse_lenient(&narinfo).map_err(|e| MalformedNarInfoError {
+        reason: e.to_string(),
+      })?;
+    if info.store_path.hash_part() != hash.as_ref() {
+      Err(MalformedNarInfoError {
+        reason: format!(
+          "the store path {:?} doesn't match the file name {hash}.narinfo",
+          info.store_path.to_string()
+        ),
+      })?;
+    }
+    let Some(BinaryCacheUpload::Nar(file)) =
+      BinaryCacheUpload::from_request_path(&info.url)
+    else {
+      Err(MalformedNarInfoError {
+        reason: format!("the URL {:?} is not a NAR under `nar/`", info.url),
+      })?
+    };
+
+    // nix treats a missing `Compression` field as bzip2
+    let compression = match info.compression.unwrap_or(NarCompression::Bzip2) {
+      NarCompression::None => None,
+      NarCompression::Xz => Some(belt::CompressionAlgorithm::Xz),
+      NarCompression::Zstd => Some(belt::CompressionAlgorithm::Zstd),
+      other => Err(UnsupportedCompressionError {
+        compression: other.to_string(),
+      })?,
+    };
+
+    tracing::info!("reading uploaded NAR from temp storage");
+    let nar_path = nar_upload_path(cache.id, &file);
+    let data = prime_domain_service
+      .read_from_temp_storage(nar_path.clone())
+      .await
+      .map_err(|e| match e {
+        StorageReadError::NotFound(_) => {
+          BinaryCacheUploadError::from(MissingPathError {
+            path: info.url.clone(),
+          })
+        }
+        e => InternalError(format!("{e:?}")).into(),
+      })?;
+    let data = match compression {
+      Some(algorithm) => data.adapt_from_comp(algorithm),
+      None => data,
+    };
+
+    let meta = models::EntryMetadata {
+      references: info.references,
+      deriver:    info.deriver,
+      sigs:       info.sigs,
+      ca:         info.ca,
+    };
+
+    tracing::info!("creating entry");
+    let result = prime_domain_service
+      .create_entry(
+        cache.id,
+        info.store_path.clone(),
+        Some(meta),
+        Some(info.nar_hash),
+        data,
+      )
+      .await;
+
+    // the NAR is kept for a retry only if storing it failed for some other
+    // reason; otherwise it's stored or known to be bad
+    if matches!(
+      result,
+      Ok(_)
+        | Err(CreateEntryError::EntryAlreadyExists)
+        | Err(CreateEntryError::NarHashMismatch { .. })
+    ) {
+      discard_temp_storage(&prime_domain_service, nar_path).await;
+    }
+
+    match result {
+      // uploads are idempotent, so a second upload of a path is fine
+      Ok(_) | Err(CreateEntryError::EntryAlreadyExists) => Ok(()),
+      Err(CreateEntryError::NarHashMismatch { expected, actual }) => {
+        Err(NarHashMismatchError {
+          path:     info.store_path.to_string(),
+          expected: expected.to_string(),
+          actual:   actual.to_string(),
+        })?
+      }
+      Err(e) => Err(InternalError(format!("{e:?}")))?,
+    }
+  }
+}
+
+/// The UploadRealisation task.
+///
+/// Creates a realisation from an uploaded `.doi` file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UploadRealisationTask {
+  /// The name of the cache to upload to.
+  pub cache_name:  StrictSlug,
+  /// The credentials of the token or session being used to upload the file.
+  pub credentials: Option<Credentials>,
+  /// The derivation output, from the realisation's file name.
+  pub id:          models::DrvOutput,
+  /// The contents of the realisation.
+  pub realisation: String,
+}
+
+#[async_trait::async_trait]
+impl rope::Task for UploadRealisationTask {
+  const NAME: &'static str = "UploadRealisation";
+
+  type Response = ();
+  type Error = BinaryCacheUploadError;
+  type State = DynPrimeDomainService;
+
+  #[tracing::instrument(name = "UploadRealisation", skip(self, state))]
+  async fn run(
+    self,
+    state: Self::State,
+  ) -> Result<Self::Response, Self::Error> {
+    let UploadRealisationTask {
+      cache_name,
+      credentials,
+      id,
+      realisation,
+    } = self;
+
+    let prime_domain_service = state;
+
+    let cache =
+      fetch_cache_for_writing(&prime_domain_service, cache_name, credentials)
+        .await?;
+
+    let realisation = Realisation::from_json(&realisation).map_err(|e| {
+      MalformedRealisationError {
+        reason: e.to_string(),
+      }
+    })?;
+    if realisation.id != id {
+      Err(MalformedRealisationError {
+        reason: format!(
+          "the ID {:?} doesn't match the file name {id}.doi",
+          realisation.id.to_string()
+        ),
+      })?;
+    }
+
+    tracing::info!("creating realisation");
+    let out_path = realisation.out_path.clone();
+    let result = prime_domain_service
+      .create_realisation(cache.id, realisation)
+      .await;
+
+    match result {
+      Ok(_) => Ok(()),
+      // uploads are idempotent, as long as they agree on the output path
+      Err(CreateRealisationError::RealisationAlreadyExists(existing))
+        if existing.out_path == out_path =>
+      {
+        Ok(())
+      }
+      Err(CreateRealisationError::RealisationAlreadyExists(existing)) => {
+        Err(RealisationConflictError {
+          id:                id.to_string(),
+          existing_out_path: existing.out_path.to_string(),
+        })?
+      }
+      Err(e) => Err(InternalError(format!("{e:?}")))?,
+    }
+  }
+}
+
+/// Returns the temp storage path that an uploaded NAR is kept at until its
+/// narinfo arrives.
+///
+/// The path is derived from the cache and the NAR's file name, so that the
+/// narinfo can find it without any other bookkeeping.
+fn nar_upload_path(
+  cache_id: CacheRecordId,
+  file: &LaxSlug,
+) -> models::TempStoragePath {
+  models::TempStoragePath::new_named(
+    &format!("nar-upload-{cache_id}-{file}"),
+    models::FileSize::new(0),
+  )
+}
+
+/// Fetches a cache by name, and makes sure the credentials can write to it.
+///
+/// Unlike reading, writing always requires a token or session with the
+/// [`Write`](models::CachePermissionType::Write) permission, even for public
+/// caches.
+async fn fetch_cache_for_writing(
+  prime_domain_service: &DynPrimeDomainService,
+  cache_name: StrictSlug,
+  credentials: Option<Credentials>,
+) -> Result<models::Cache, BinaryCacheUploadError> {
+  let cache = prime_domain_service
+    .find_cache_by_name(cache_name.clone())
+    .await
+    .map_err(|e| InternalError(format!("{e:?}")))?
+    .ok_or(NonExistentCacheError(cache_name.to_string()))?;
+
+  authorize_cache_access::<BinaryCacheUploadError>(
+    prime_domain_service,
+    &cache,
+    credentials,
+    models::CachePermissionType::Write,
+  )
+  .await?;
+
+  Ok(cache)
+}
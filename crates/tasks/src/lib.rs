@@ -1,8 +1,30 @@
 //! Provides types and business logic for all platform tasks used with [`rope`].
 
+mod binary_cache_upload;
 mod naive_upload;
+mod prepare_binary_cache_payload;
 mod prepare_fetch_payload;
+mod query_missing_paths;
+mod store_proxied_nar;
 
 pub use rope::Task;
 
-pub use self::{naive_upload::*, prepare_fetch_payload::*};
+pub use self::{
+  binary_cache_upload::*, naive_upload::*, prepare_binary_cache_payload::*,
+  prepare_fetch_payload::*, query_missing_paths::*, store_proxied_nar::*,
+};
+
+/// Deletes an upload from temp storage once a task is done with it.
+///
+/// Failures are only logged, since they don't change the task's outcome.
+async fn discard_temp_storage(
+  prime_domain_service: &prime_domain::DynPrimeDomainService,
+  path: prime_domain::models::TempStoragePath,
+) {
+  if let Err(e) = prime_domain_service
+    .delete_from_temp_storage(path.clone())
+    .await
+  {
+    tracing::warn!("failed to delete {path:?} from temp storage: {e}");
+  }
+}
@@ -1,16 +1,22 @@
+use mollusk::InternalError;
 use prime_domain::{
-  models::{self, LaxSlug, StrictSlug},
-  DynPrimeDomainService,
+  models::{self, CacheRecordId, StorePath},
+  CreateEntryError, DynPrimeDomainService,
 };
 use serde::{Deserialize, Serialize};
 
-/// The health check task.
+use crate::discard_temp_storage;
+
+/// The NaiveUpload task.
+///
+/// Creates an entry from an uploaded NAR, without any store path metadata. The
+/// uploader must already have been authorized to write to the cache.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NaiveUploadTask {
-  /// The target store name.
-  pub cache_name:        StrictSlug,
+  /// The target cache.
+  pub cache_id:          CacheRecordId,
   /// The target path.
-  pub path:              LaxSlug,
+  pub path:              StorePath,
   /// The temporary storage path where the payload is currently stored.
   pub temp_storage_path: models::TempStoragePath,
 }
@@ -20,7 +26,7 @@ impl rope::Task for NaiveUploadTask {
   const NAME: &'static str = "NaiveUpload";
 
   type Response = ();
-  type Error = ();
+  type Error = InternalError;
   type State = DynPrimeDomainService;
 
   #[tracing::instrument(name = "NaiveUpload", skip(self, state))]
@@ -30,25 +36,35 @@ impl rope::Task for NaiveUploadTask {
   ) -> Result<Self::Response, Self::Error> {
     let prime_domain_service = state;
 
-    tracing::info!("fetching cache");
-    let cache = prime_domain_service
-      .find_cache_by_name(self.cache_name.clone())
-      .await
-      .expect("failed to fetch cache")
-      .expect("cache not found");
-
-    prime_domain_service
-      .create_entry(
-        cache.id,
-        self.path,
-        prime_domain_service
-          .read_from_temp_storage(self.temp_storage_path)
-          .await
-          .expect("failed to read from temp storage"),
-      )
+    // the upload is only ever read once, whatever comes of it
+    let result = self.create_entry(&prime_domain_service).await;
+    discard_temp_storage(&prime_domain_service, self.temp_storage_path).await;
+    result
+  }
+}
+
+impl NaiveUploadTask {
+  /// Creates the entry from the upload in temp storage.
+  async fn create_entry(
+    &self,
+    prime_domain_service: &DynPrimeDomainService,
+  ) -> Result<(), InternalError> {
+    let data = prime_domain_service
+      .read_from_temp_storage(self.temp_storage_path.clone())
       .await
-      .expect("failed to create entry");
+      .map_err(|e| InternalError(format!("{e:?}")))?;
+
+    // naive uploads don't say what the path references, so the entry is left
+    // unsigned
+    tracing::info!("creating entry");
+    let result = prime_domain_service
+      .create_entry(self.cache_id, self.path.clone(), None, None, data)
+      .await;
 
-    Ok(())
+    match result {
+      // uploads are idempotent, so a second upload of a path is fine
+      Ok(_) | Err(CreateEntryError::EntryAlreadyExists) => Ok(()),
+      Err(e) => Err(InternalError(format!("{e:?}"))),
+    }
   }
 }
@@ -0,0 +1,394 @@
+use mollusk::*;
+use nasty::{
+  debuginfo::DebugInfoLink,
+  nar_index::NarIndex,
+  narinfo::{NarCompression, NarInfo},
+  realisation::Realisation,
+  signing::SecretKey,
+};
+use prime_domain::{
+  models::{self, Credentials, StrictSlug},
+  DynPrimeDomainService, ReadFromEntryError, StorageReadError,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::prepare_fetch_payload::fetch_cache_for_reading;
+
+/// The contents of the `nix-cache-info` file, served for every cache.
+const NIX_CACHE_INFO: &str =
+  "StoreDir: /nix/store\nWantMassQuery: 1\nPriority: 40\n";
+
+/// The PrepareBinaryCachePayload task.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrepareBinaryCachePayloadTask {
+  /// The name of the cache to fetch from.
+  pub cache_name:  StrictSlug,
+  /// The credentials of the token or session being used to fetch the file.
+  pub credentials: Option<Credentials>,
+  /// The binary cache file to fetch.
+  pub file:        BinaryCacheFile,
+}
+
+#[async_trait::async_trait]
+impl rope::Task for PrepareBinaryCachePayloadTask {
+  const NAME: &'static str = "PrepareBinaryCachePayload";
+
+  type Response = BinaryCachePayload;
+  type Error = PrepareFetchPayloadError;
+  type State = DynPrimeDomainService;
+
+  async fn run(
+    self,
+    state: Self::State,
+  ) -> Result<Self::Response, Self::Error> {
+    let PrepareBinaryCachePayloadTask {
+      cache_name,
+      credentials,
+      file,
+    } = self;
+
+    let prime_domain_service = state;
+
+    let cache =
+      fetch_cache_for_reading(&prime_domain_service, cache_name, credentials)
+        .await?;
+
+    let (key, requested_compression) = match file {
+      BinaryCacheFile::CacheInfo => {
+        return Ok(BinaryCachePayload::Text(NIX_CACHE_INFO.to_string()));
+      }
+      BinaryCacheFile::NarInfo(hash) => (hash, None),
+      BinaryCacheFile::Listing(hash) => {
+        return listing_payload(&prime_domain_service, &cache, hash).await;
+      }
+      BinaryCacheFile::NarMember { hash, member } => {
+        return nar_member_payload(&prime_domain_service, &cache, hash, member)
+          .await;
+      }
+      BinaryCacheFile::Nar { key, compression } => (key, Some(compression)),
+      BinaryCacheFile::Realisation(id) => {
+        return realisation_payload(&prime_domain_service, &cache, id).await;
+      }
+      BinaryCacheFile::DebugInfo(build_id) => {
+        return debuginfo_payload(&prime_domain_service, &cache, build_id)
+          .await;
+      }
+    };
+
+    let entry = prime_domain_service
+      .find_entry_by_id_and_path_hash(cache.id, key.clone())
+      .await
+      .map_err(|e| InternalError(format!("{e:?}")))?;
+    let Some(entry) = entry else {
+      return proxy_payload(
+        &prime_domain_service,
+        &cache,
+        key,
+        requested_compression,
+      )
+      .await;
+    };
+
+    let Some(requested_compression) = requested_compression else {
+      // entries from before hashes were recorded can't be described until
+      // they're migrated
+      let Some(mut info) = narinfo_for_entry(&entry) else {
+        Err(MissingPathError {
+          path: format!("{key}.narinfo"),
+        })?
+      };
+      // a signature would vouch for references we don't know
+      if entry.meta.is_some() {
+        sign_narinfo(&prime_domain_service, &cache, &mut info).await?;
+      }
+      return Ok(BinaryCachePayload::Text(info.to_string()));
+    };
+
+    // the NAR is served as stored, so the extension has to match
+    if requested_compression != entry.c_status.algorithm() {
+      Err(MissingPathError {
+        path: BinaryCacheFile::nar_url(key.as_ref(), requested_compression),
+      })?;
+    }
+
+    let store = prime_domain_service
+      .fetch_store_by_id(cache.store)
+      .await
+      .map_err(|e| InternalError(format!("{e:?}")))?
+      .ok_or(InternalError(format!("store not found: {:?}", cache.store)))?;
+
+    Ok(BinaryCachePayload::Nar {
+      path:        entry.path,
+      credentials: store.credentials,
+    })
+  }
+}
+
+/// Prepares the payload for a narinfo or NAR that's missing from the cache, by
+/// pulling it through from the cache's proxy.
+///
+/// The narinfo is rewritten to point at an uncompressed NAR under our own
+/// `nar/`, which the fetcher decompresses on the way through and stores as an
+/// entry. Its signatures stay valid, since they don't cover the NAR's URL or
+/// compression.
+async fn proxy_payload(
+  prime_domain_service: &DynPrimeDomainService,
+  cache: &models::Cache,
+  hash: models::LaxSlug,
+  requested_compression: Option<Option<models::CompressionAlgorithm>>,
+) -> Result<BinaryCachePayload, PrepareFetchPayloadError> {
+  let missing = || MissingPathError {
+    path: match requested_compression {
+      None => format!("{hash}.narinfo"),
+      Some(compression) => BinaryCacheFile::nar_url(hash.as_ref(), compression),
+    },
+  };
+
+  let Some(proxy) = &cache.proxy else {
+    Err(missing())?
+  };
+  let mut info = prime_domain_service
+    .find_narinfo_from_proxy(cache, hash.clone())
+    .await
+    .ok_or_else(missing)?;
+
+  // the URL comes from upstream, so it must stay a NAR under the proxy's own
+  // `nar/` rather than point the fetcher anywhere else
+  let Some(BinaryCacheUpload::Nar(nar_file)) =
+    BinaryCacheUpload::from_request_path(&info.url)
+  else {
+    tracing::warn!(
+      "can't pull through {hash}: the URL {:?} is not a NAR under `nar/`",
+      info.url
+    );
+    Err(missing())?
+  };
+
+  // nix treats a missing `Compression` field as bzip2
+  let compression = match info.compression.unwrap_or(NarCompression::Bzip2) {
+    NarCompression::None => None,
+    NarCompression::Xz => Some(ProxiedCompression::Xz),
+    NarCompression::Zstd => Some(ProxiedCompression::Zstd),
+    other => {
+      tracing::warn!(
+        "can't pull through {hash}: unsupported compression {other}"
+      );
+      Err(missing())?
+    }
+  };
+
+  match requested_compression {
+    None => {
+      info.url = BinaryCacheFile::nar_url(hash.as_ref(), None);
+      info.compression = Some(NarCompression::None);
+      info.file_hash = None;
+      info.file_size = None;
+      sign_narinfo(prime_domain_service, cache, &mut info).await?;
+      Ok(BinaryCachePayload::Text(info.to_string()))
+    }
+    Some(None) => Ok(BinaryCachePayload::ProxiedNar {
+      hash,
+      url: format!("{}/nar/{nar_file}", proxy.url.trim_end_matches('/')),
+      compression,
+    }),
+    // the rewritten narinfo only points at uncompressed NARs
+    Some(Some(_)) => Err(missing())?,
+  }
+}
+
+/// Signs a narinfo with the cache's signing key, if it has one.
+async fn sign_narinfo(
+  prime_domain_service: &DynPrimeDomainService,
+  cache: &models::Cache,
+  info: &mut NarInfo,
+) -> Result<(), PrepareFetchPayloadError> {
+  let signing_key = prime_domain_service
+    .find_signing_key_by_cache_id(cache.id)
+    .await
+    .map_err(|e| InternalError(format!("{e:?}")))?;
+  if let Some(signing_key) = signing_key {
+    let secret_key =
+      signing_key
+        .secret
+        .as_ref()
+        .parse::<SecretKey>()
+        .map_err(|e| {
+          InternalError(format!(
+            "signing key {} is malformed: {e}",
+            signing_key.id
+          ))
+        })?;
+    secret_key.sign_in_place(info);
+  }
+  Ok(())
+}
+
+/// Prepares the payload for the `.ls` listing of the entry with the given
+/// store path hash.
+///
+/// Entries created before listings were generated don't have one, in which case
+/// the fetcher won't find the file and responds with a 404.
+async fn listing_payload(
+  prime_domain_service: &DynPrimeDomainService,
+  cache: &models::Cache,
+  hash: models::LaxSlug,
+) -> Result<BinaryCachePayload, PrepareFetchPayloadError> {
+  let entry = prime_domain_service
+    .find_entry_by_id_and_path_hash(cache.id, hash.clone())
+    .await
+    .map_err(|e| InternalError(format!("{e:?}")))?
+    .ok_or(MissingPathError {
+      path: format!("{hash}.ls"),
+    })?;
+
+  let store = prime_domain_service
+    .fetch_store_by_id(cache.store)
+    .await
+    .map_err(|e| InternalError(format!("{e:?}")))?
+    .ok_or(InternalError(format!("store not found: {:?}", cache.store)))?;
+
+  Ok(BinaryCachePayload::Listing {
+    path:        prime_domain::listing_storage_path(&entry.path),
+    credentials: store.credentials,
+    compression: entry.c_status.algorithm(),
+  })
+}
+
+/// Prepares the payload for a single file out of the NAR of the entry with the
+/// given store path hash, using the NAR's listing to find it.
+async fn nar_member_payload(
+  prime_domain_service: &DynPrimeDomainService,
+  cache: &models::Cache,
+  hash: models::LaxSlug,
+  member: String,
+) -> Result<BinaryCachePayload, PrepareFetchPayloadError> {
+  let missing = || MissingPathError {
+    path: format!("file/{hash}/{member}"),
+  };
+
+  let entry = prime_domain_service
+    .find_entry_by_id_and_path_hash(cache.id, hash.clone())
+    .await
+    .map_err(|e| InternalError(format!("{e:?}")))?
+    .ok_or_else(missing)?;
+
+  let listing = prime_domain_service
+    .read_listing_from_entry(entry.id)
+    .await
+    .map_err(|e| match e {
+      ReadFromEntryError::StorageReadError(StorageReadError::NotFound(_)) => {
+        PrepareFetchPayloadError::from(missing())
+      }
+      e => InternalError(format!("{e:?}")).into(),
+    })?;
+  let range = NarIndex::from_listing(listing)
+    .file(&member)
+    .map_err(|_| missing())?;
+
+  let store = prime_domain_service
+    .fetch_store_by_id(cache.store)
+    .await
+    .map_err(|e| InternalError(format!("{e:?}")))?
+    .ok_or(InternalError(format!("store not found: {:?}", cache.store)))?;
+
+  Ok(BinaryCachePayload::NarMember {
+    path:        entry.path,
+    credentials: store.credentials,
+    compression: entry.c_status.algorithm(),
+    offset:      range.offset,
+    size:        range.size,
+  })
+}
+
+/// Prepares the `.doi` file for the realisation of the given derivation
+/// output.
+async fn realisation_payload(
+  prime_domain_service: &DynPrimeDomainService,
+  cache: &models::Cache,
+  id: models::DrvOutput,
+) -> Result<BinaryCachePayload, PrepareFetchPayloadError> {
+  let realisation = prime_domain_service
+    .find_realisation_by_id_and_drv_output(cache.id, id.clone())
+    .await
+    .map_err(|e| InternalError(format!("{e:?}")))?
+    .ok_or(MissingPathError {
+      path: format!("realisations/{id}.doi"),
+    })?;
+
+  let realisation = Realisation {
+    id:                     realisation.drv_output,
+    out_path:               realisation.out_path,
+    signatures:             realisation.signatures,
+    dependent_realisations: realisation.dependent_realisations,
+  };
+  Ok(BinaryCachePayload::Text(realisation.to_json()))
+}
+
+/// Prepares the `debuginfo/<build-id>` link to the NAR member holding the
+/// debug info with the given build ID.
+async fn debuginfo_payload(
+  prime_domain_service: &DynPrimeDomainService,
+  cache: &models::Cache,
+  build_id: models::LaxSlug,
+) -> Result<BinaryCachePayload, PrepareFetchPayloadError> {
+  let debuginfo = prime_domain_service
+    .find_debuginfo_by_id_and_build_id(cache.id, build_id.clone())
+    .await
+    .map_err(|e| InternalError(format!("{e:?}")))?
+    .ok_or(MissingPathError {
+      path: format!("debuginfo/{build_id}"),
+    })?;
+
+  let entry = prime_domain_service
+    .fetch_entry_by_id(debuginfo.entry)
+    .await
+    .map_err(|e| InternalError(format!("{e:?}")))?
+    .ok_or(InternalError(format!(
+      "entry not found: {:?}",
+      debuginfo.entry
+    )))?;
+
+  let nar_url =
+    BinaryCacheFile::nar_url(entry.path_hash(), entry.c_status.algorithm());
+  let link = DebugInfoLink::new(&nar_url, debuginfo.member);
+  Ok(BinaryCachePayload::Text(link.to_json()))
+}
+
+/// Builds the `.narinfo` file for an [`Entry`](models::Entry).
+///
+/// Signatures recorded at upload time are kept, since they only cover the
+/// NAR's contents and references, not how we store it. Entries without
+/// metadata are described with no references. Returns `None` if the entry's
+/// NAR hash isn't recorded yet.
+fn narinfo_for_entry(entry: &models::Entry) -> Option<NarInfo> {
+  let algorithm = entry.c_status.algorithm();
+  let (file_size, nar_size) = match &entry.c_status {
+    models::CompressionStatus::Compressed {
+      compressed_size,
+      uncompressed_size,
+      ..
+    } => (*compressed_size.as_ref(), *uncompressed_size.as_ref()),
+    models::CompressionStatus::Uncompressed { size } => {
+      (*size.as_ref(), *size.as_ref())
+    }
+  };
+  let compression = match algorithm {
+    Some(models::CompressionAlgorithm::Zstd) => NarCompression::Zstd,
+    None => NarCompression::None,
+  };
+  let meta = entry.meta.clone().unwrap_or_default();
+
+  Some(NarInfo {
+    store_path: entry.path.clone(),
+    url: BinaryCacheFile::nar_url(entry.path_hash(), algorithm),
+    compression: Some(compression),
+    file_hash: entry.file_hash,
+    file_size: Some(file_size),
+    nar_hash: entry.nar_hash?,
+    nar_size,
+    references: meta.references,
+    deriver: meta.deriver,
+    sigs: meta.sigs,
+    ca: meta.ca,
+  })
+}
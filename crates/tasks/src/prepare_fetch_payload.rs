@@ -1,7 +1,7 @@
 use mollusk::*;
 use prime_domain::{
-  models::{self, LaxSlug, StrictSlug, TokenRecordId, TokenSecret},
-  DynPrimeDomainService,
+  models::{self, Credentials, StorePath, StrictSlug},
+  DynPrimeDomainService, TokenVerifyError,
 };
 use serde::{Deserialize, Serialize};
 
@@ -9,13 +9,11 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PrepareFetchPayloadTask {
   /// The name of the cache to fetch from.
-  pub cache_name:   StrictSlug,
-  /// The token being used to fetch the path.
-  pub token_id:     Option<TokenRecordId>,
-  /// The secret of the token being used to fetch the path.
-  pub token_secret: Option<TokenSecret>,
+  pub cache_name:  StrictSlug,
+  /// The credentials of the token or session being used to fetch the path.
+  pub credentials: Option<Credentials>,
   /// The path to fetch from the cache.
-  pub path:         LaxSlug,
+  pub path:        StorePath,
 }
 
 #[async_trait::async_trait]
@@ -32,20 +30,15 @@ impl rope::Task for PrepareFetchPayloadTask {
   ) -> Result<Self::Response, Self::Error> {
     let PrepareFetchPayloadTask {
       cache_name,
-      token_id,
-      token_secret,
+      credentials,
       path,
     } = self;
 
     let prime_domain_service = state;
 
-    let cache = prime_domain_service
-      .find_cache_by_name(cache_name.clone())
-      .await
-      .map_err(|e| {
-        PrepareFetchPayloadError::InternalError(InternalError(format!("{e:?}")))
-      })?
-      .ok_or(NonExistentCacheError(cache_name.to_string()))?;
+    let cache =
+      fetch_cache_for_reading(&prime_domain_service, cache_name, credentials)
+        .await?;
 
     let store = prime_domain_service
       .fetch_store_by_id(cache.store)
@@ -53,39 +46,6 @@ impl rope::Task for PrepareFetchPayloadTask {
       .map_err(|e| InternalError(format!("{e:?}")))?
       .ok_or(InternalError(format!("store not found: {:?}", cache.store)))?;
 
-    // run through authentication
-    if matches!(cache.visibility, models::Visibility::Private) {
-      // if the store is not public, we must have a token
-      let token_id = token_id
-        .ok_or(UnauthenticatedStoreAccessError(cache_name.to_string()))?;
-      let token_secret = token_secret
-        .ok_or(UnauthenticatedStoreAccessError(cache_name.to_string()))?;
-
-      let required_permission = models::Permission::CachePermission {
-        cache_id:   cache.id,
-        permission: models::CachePermissionType::Read,
-      };
-      let required_permission_set =
-        models::PermissionSet::from_iter(vec![required_permission.clone()]);
-
-      let token = prime_domain_service
-        .verify_token_id_and_secret(token_id, token_secret.clone())
-        .await
-        .map_err(|e| {
-          PrepareFetchPayloadError::InternalError(InternalError(format!(
-            "{e:?}"
-          )))
-        })?;
-      let authorized = token.authorized(&required_permission_set);
-
-      if !authorized {
-        Err(UnauthorizedCacheAccessError {
-          cache_name: cache.name.clone().into_inner().into_inner(),
-          permission: models::CachePermissionType::Read,
-        })?;
-      }
-    }
-
     let _entry = prime_domain_service
       .find_entry_by_id_and_path(cache.id, path.clone())
       .await
@@ -94,3 +54,98 @@ impl rope::Task for PrepareFetchPayloadTask {
     Ok(store.credentials)
   }
 }
+
+/// Fetches a cache by name, and runs through authentication if it's private.
+///
+/// Public caches can be read without credentials. Private caches require a
+/// token or session with the [`Read`](models::CachePermissionType::Read)
+/// permission.
+pub(crate) async fn fetch_cache_for_reading(
+  prime_domain_service: &DynPrimeDomainService,
+  cache_name: StrictSlug,
+  credentials: Option<Credentials>,
+) -> Result<models::Cache, PrepareFetchPayloadError> {
+  let cache = prime_domain_service
+    .find_cache_by_name(cache_name.clone())
+    .await
+    .map_err(|e| {
+      PrepareFetchPayloadError::InternalError(InternalError(format!("{e:?}")))
+    })?
+    .ok_or(NonExistentCacheError(cache_name.to_string()))?;
+
+  // run through authentication
+  if matches!(cache.visibility, models::Visibility::Private) {
+    authorize_cache_access::<PrepareFetchPayloadError>(
+      prime_domain_service,
+      &cache,
+      credentials,
+      models::CachePermissionType::Read,
+    )
+    .await?;
+  }
+
+  Ok(cache)
+}
+
+/// Makes sure credentials are present, valid, and have the given permission on
+/// a cache.
+///
+/// The credentials can belong to a token or a session.
+pub(crate) async fn authorize_cache_access<E>(
+  prime_domain_service: &DynPrimeDomainService,
+  cache: &models::Cache,
+  credentials: Option<Credentials>,
+  permission: models::CachePermissionType,
+) -> Result<(), E>
+where
+  E: From<UnauthenticatedStoreAccessError>
+    + From<UnauthorizedCacheAccessError>
+    + From<NonExistentTokenError>
+    + From<ExpiredTokenError>
+    + From<RevokedTokenError>
+    + From<InternalError>,
+{
+  let cache_name = cache.name.clone().into_inner().into_inner();
+
+  let credentials =
+    credentials.ok_or(UnauthenticatedStoreAccessError(cache_name.clone()))?;
+  let id = credentials.id;
+
+  let required_permission = models::Permission::CachePermission {
+    cache_id:   cache.id,
+    permission: permission.clone(),
+  };
+  let required_permission_set =
+    models::PermissionSet::from_iter(vec![required_permission]);
+
+  let principal = prime_domain_service
+    .verify_credentials(credentials)
+    .await
+    .map_err(|e| match e {
+      // don't tell the client which half was wrong
+      TokenVerifyError::IdNotFound | TokenVerifyError::SecretMismatch => {
+        E::from(NonExistentTokenError {
+          token: id.to_string(),
+        })
+      }
+      TokenVerifyError::Expired => E::from(ExpiredTokenError {
+        token: id.to_string(),
+      }),
+      TokenVerifyError::Revoked => E::from(RevokedTokenError {
+        token: id.to_string(),
+      }),
+      TokenVerifyError::FetchError(e) => {
+        E::from(InternalError(format!("{e:?}")))
+      }
+    })?;
+  let authorized = principal.authorized(&required_permission_set);
+
+  if !authorized {
+    Err(UnauthorizedCacheAccessError {
+      cache_name,
+      permission,
+    })?;
+  }
+
+  Ok(())
+}
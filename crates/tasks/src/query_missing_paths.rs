@@ -0,0 +1,83 @@
+use mollusk::*;
+use prime_domain::{
+  models::{Credentials, StorePath, StrictSlug},
+  DynPrimeDomainService,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::prepare_fetch_payload::fetch_cache_for_reading;
+
+/// The paths that a cache doesn't have, as found by
+/// [`QueryMissingPathsTask`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MissingPaths {
+  /// The paths that need to be pushed.
+  pub missing:            Vec<StorePath>,
+  /// The paths that aren't in the cache, but are in one of its upstream
+  /// caches with a trusted signature, so don't need to be pushed.
+  pub available_upstream: Vec<StorePath>,
+}
+
+/// The QueryMissingPaths task.
+///
+/// Finds which of a list of paths are missing from a cache, so that clients
+/// only push what isn't there yet. Paths that the cache's upstream caches
+/// have are reported separately.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueryMissingPathsTask {
+  /// The name of the cache to check.
+  pub cache_name:  StrictSlug,
+  /// The credentials of the token or session being used to query the cache.
+  pub credentials: Option<Credentials>,
+  /// The paths to check for.
+  pub paths:       Vec<StorePath>,
+}
+
+#[async_trait::async_trait]
+impl rope::Task for QueryMissingPathsTask {
+  const NAME: &'static str = "QueryMissingPaths";
+
+  type Response = MissingPaths;
+  type Error = PrepareFetchPayloadError;
+  type State = DynPrimeDomainService;
+
+  #[tracing::instrument(name = "QueryMissingPaths", skip(self, state))]
+  async fn run(
+    self,
+    state: Self::State,
+  ) -> Result<Self::Response, Self::Error> {
+    let QueryMissingPathsTask {
+      cache_name,
+      credentials,
+      paths,
+    } = self;
+
+    let prime_domain_service = state;
+
+    // knowing what's in a cache is the same as being able to read it
+    let cache =
+      fetch_cache_for_reading(&prime_domain_service, cache_name, credentials)
+        .await?;
+
+    let entries = prime_domain_service
+      .find_entries_by_id_and_paths(cache.id, paths.clone())
+      .await
+      .map_err(|e| InternalError(format!("{e:?}")))?;
+
+    let mut missing = paths
+      .into_iter()
+      .zip(entries)
+      .filter_map(|(path, entry)| entry.is_none().then_some(path))
+      .collect::<Vec<_>>();
+
+    let available_upstream = prime_domain_service
+      .find_paths_available_upstream(&cache, missing.clone())
+      .await;
+    missing.retain(|path| !available_upstream.contains(path));
+
+    Ok(MissingPaths {
+      missing,
+      available_upstream,
+    })
+  }
+}
@@ -0,0 +1,126 @@
+use mollusk::*;
+use prime_domain::{
+  models::{self, Credentials, LaxSlug, StrictSlug},
+  CreateEntryError, DynPrimeDomainService,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  discard_temp_storage, prepare_fetch_payload::authorize_cache_access,
+};
+
+/// The StoreProxiedNar task.
+///
+/// Creates an entry from a NAR that the fetcher pulled through from the
+/// upstream of a proxy cache, using the upstream's narinfo for its metadata.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoreProxiedNarTask {
+  /// The name of the proxy cache.
+  pub cache_name:        StrictSlug,
+  /// The credentials of the token or session being used to fetch the NAR.
+  pub credentials:       Option<Credentials>,
+  /// The hash part of the NAR's store path.
+  pub hash:              LaxSlug,
+  /// The temporary storage path where the uncompressed NAR is stored.
+  pub temp_storage_path: models::TempStoragePath,
+}
+
+#[async_trait::async_trait]
+impl rope::Task for StoreProxiedNarTask {
+  const NAME: &'static str = "StoreProxiedNar";
+
+  type Response = ();
+  type Error = BinaryCacheUploadError;
+  type State = DynPrimeDomainService;
+
+  #[tracing::instrument(name = "StoreProxiedNar", skip(self, state))]
+  async fn run(
+    self,
+    state: Self::State,
+  ) -> Result<Self::Response, Self::Error> {
+    let prime_domain_service = state;
+
+    // the fetcher won't send the NAR again, so it's discarded either way
+    let result = self.create_entry(&prime_domain_service).await;
+    discard_temp_storage(&prime_domain_service, self.temp_storage_path).await;
+    result
+  }
+}
+
+impl StoreProxiedNarTask {
+  /// Creates the entry from the pulled-through NAR in temp storage.
+  async fn create_entry(
+    &self,
+    prime_domain_service: &DynPrimeDomainService,
+  ) -> Result<(), BinaryCacheUploadError> {
+    let StoreProxiedNarTask {
+      cache_name,
+      credentials,
+      hash,
+      temp_storage_path,
+    } = self.clone();
+
+    let cache = prime_domain_service
+      .find_cache_by_name(cache_name.clone())
+      .await
+      .map_err(|e| InternalError(format!("{e:?}")))?
+      .ok_or(NonExistentCacheError(cache_name.to_string()))?;
+
+    // pulling a path through is part of reading it
+    if matches!(cache.visibility, models::Visibility::Private) {
+      authorize_cache_access::<BinaryCacheUploadError>(
+        prime_domain_service,
+        &cache,
+        credentials,
+        models::CachePermissionType::Read,
+      )
+      .await?;
+    }
+
+    // the metadata comes from upstream rather than the fetcher, so that only
+    // what the proxy signed is recorded
+    let info = prime_domain_service
+      .find_narinfo_from_proxy(&cache, hash.clone())
+      .await
+      .ok_or(MissingPathError {
+        path: format!("{hash}.narinfo"),
+      })?;
+
+    tracing::info!("reading proxied NAR from temp storage");
+    let data = prime_domain_service
+      .read_from_temp_storage(temp_storage_path)
+      .await
+      .map_err(|e| InternalError(format!("{e:?}")))?;
+
+    let meta = models::EntryMetadata {
+      references: info.references,
+      deriver:    info.deriver,
+      sigs:       info.sigs,
+      ca:         info.ca,
+    };
+
+    tracing::info!("creating entry");
+    let result = prime_domain_service
+      .create_entry(
+        cache.id,
+        info.store_path.clone(),
+        Some(meta),
+        Some(info.nar_hash),
+        data,
+      )
+      .await;
+
+    match result {
+      // concurrent fetches of the same path may both try to store it
+      Ok(_) | Err(CreateEntryError::EntryAlreadyExists) => Ok(()),
+      Err(CreateEntryError::NarHashMismatch { expected, actual }) => {
+        Err(NarHashMismatchError {
+          path:     info.store_path.to_string(),
+          expected: expected.to_string(),
+          actual:   actual.to_string(),
+        })?
+      }
+      Err(e) => Err(InternalError(format!("{e:?}")))?,
+    }
+  }
+}
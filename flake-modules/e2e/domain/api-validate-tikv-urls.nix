@@ -5,6 +5,8 @@
       enable = true;
       mockTempStorage = true;
       tikvUrls = tikv-urls;
+      tokenSecretPepperFile = "${pkgs.writeText "token-secret-pepper" "e2e-pepper"}";
+      fetcherSecretFile = "${pkgs.writeText "fetcher-secret" "e2e-fetcher-secret"}";
     };
     environment.systemPackages = with pkgs; [ curl jq ];
   };
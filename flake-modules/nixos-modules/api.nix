@@ -31,6 +31,18 @@ in {
         description = "The port to listen on for API requests.";
       };
 
+      tokenSecretPepperFile = lib.mkOption {
+        type = lib.types.nullOr lib.types.str;
+        default = null;
+        description = "Path to a file containing the pepper used to hash token secrets. Required. It must stay the same across restarts, or existing tokens will stop verifying.";
+      };
+
+      fetcherSecretFile = lib.mkOption {
+        type = lib.types.nullOr lib.types.str;
+        default = null;
+        description = "Path to a file containing the secret shared with the fetcher, which authenticates the routes only it may call. Required. The fetcher must be given the same secret in `FETCHER_SECRET`.";
+      };
+
       mockTempStorage = lib.mkOption {
         type = lib.types.bool;
         default = false;
@@ -51,6 +63,10 @@ in {
     optional-flag = module-lib.optional-flag cfg;
 
     command = pkgs.writeShellScript "api" ''
+      TOKEN_SECRET_PEPPER="$(cat ${lib.escapeShellArg cfg.tokenSecretPepperFile})"
+      export TOKEN_SECRET_PEPPER
+      FETCHER_SECRET="$(cat ${lib.escapeShellArg cfg.fetcherSecretFile})"
+      export FETCHER_SECRET
       ${cfg.package}/bin/api \
         ${pkgs.lib.optionalString cfg.mockTempStorage "--mock-temp-storage"} \
         start \
@@ -59,6 +75,17 @@ in {
     '';
     actualPort = if cfg.port != null then cfg.port else 3000;
   in lib.mkIf cfg.enable {
+    assertions = [
+      {
+        assertion = cfg.tokenSecretPepperFile != null;
+        message = "services.api.tokenSecretPepperFile must be set to a file containing the pepper used to hash token secrets.";
+      }
+      {
+        assertion = cfg.fetcherSecretFile != null;
+        message = "services.api.fetcherSecretFile must be set to a file containing the secret shared with the fetcher.";
+      }
+    ];
+
     networking.firewall.allowedTCPPorts = [ actualPort ];
     systemd.services.api = {
       description = "API Server";